@@ -0,0 +1,164 @@
+use std::ops::{Range, RangeInclusive};
+
+use crate::position::{LineIndex, display_columns};
+
+/// Rectangular selection of the [`CodeEditor`](crate::CodeEditor), selected with `Alt`+drag or `Shift+Alt`+arrows.
+///
+/// The corners are lines and display columns, the columns of the tabs go to the next tab stop and the wide chars
+/// take 2 columns, like the layout of the editor. The lines shorter than a column are selected up to their end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Block {
+    /// Line and column where the selection started.
+    pub(crate) anchor: (usize, usize),
+    /// Line and column of the caret.
+    pub(crate) head: (usize, usize),
+}
+
+/// Edit typed into every line of a [`Block`].
+pub(crate) enum BlockEdit<'a> {
+    /// Replaces the selected columns with the text.
+    Insert(&'a str),
+    /// Deletes the selected columns, or the char before them without columns.
+    Backspace,
+    /// Deletes the selected columns, or the char after them without columns.
+    Delete,
+}
+
+impl Block {
+    pub(crate) fn new(anchor: (usize, usize), head: (usize, usize)) -> Self {
+        Self { anchor, head }
+    }
+
+    pub(crate) fn lines(&self) -> RangeInclusive<usize> {
+        self.anchor.0.min(self.head.0)..=self.anchor.0.max(self.head.0)
+    }
+
+    pub(crate) fn columns(&self) -> Range<usize> {
+        self.anchor.1.min(self.head.1)..self.anchor.1.max(self.head.1)
+    }
+
+    /// Byte ranges of the selected chars of every line of `code`, the lines after the code have none.
+    pub(crate) fn ranges(&self, code: &str, tab_size: usize) -> Vec<Range<usize>> {
+        let index = LineIndex::new(code);
+        let columns = self.columns();
+        self.lines()
+            .filter(|&line| line < index.line_count())
+            .map(|line| {
+                let start = index.line_range(line).start;
+                let text = index.line(line);
+                start + column_offset(text, columns.start, tab_size)..start + column_offset(text, columns.end, tab_size)
+            })
+            .collect()
+    }
+
+    /// Selected text, a line per line of the block.
+    pub(crate) fn text(&self, code: &str, tab_size: usize) -> String {
+        let lines: Vec<&str> = self.ranges(code, tab_size).into_iter().map(|range| &code[range]).collect();
+        lines.join("\n")
+    }
+
+    /// Applies `edit` to every line of the block in `code`, returns the block of the carets after it.
+    ///
+    /// A text of as many lines as the block is inserted a line per line, like a copied block.
+    pub(crate) fn edit(&self, code: &mut String, edit: BlockEdit, tab_size: usize) -> Block {
+        let ranges = self.ranges(code, tab_size);
+        // without the line breaks, so a `\r\n` is not split by a deleted char
+        let index = LineIndex::new(code);
+        let lines: Vec<Range<usize>> = self.lines().take(ranges.len()).map(|line| index.line_range(line)).collect();
+        let column = self.columns().start;
+        let pasted: Vec<&str> = match edit {
+            BlockEdit::Insert(text) if text.lines().count() == ranges.len() => text.lines().collect(),
+            BlockEdit::Insert(text) => vec![text; ranges.len()],
+            _ => vec![""; ranges.len()],
+        };
+        // the column after the edit of the lines reaching the block
+        let mut caret = column;
+        for ((range, text), line) in ranges.into_iter().zip(pasted).zip(lines).rev() {
+            let (line_start, line_end) = (line.start, line.end);
+            let reaches = column_of(&code[line_start..], range.start - line_start, tab_size) == column;
+            let range = match edit {
+                BlockEdit::Backspace if range.is_empty() => {
+                    let previous = code[line_start..range.start].chars().next_back().filter(|_| reaches);
+                    range.start - previous.map_or(0, char::len_utf8)..range.end
+                }
+                BlockEdit::Delete if range.is_empty() => {
+                    let next = code[range.end..line_end].chars().next();
+                    range.start..range.end + next.map_or(0, char::len_utf8)
+                }
+                _ => range,
+            };
+            code.replace_range(range.clone(), text);
+            if reaches {
+                caret = column_of(&code[line_start..], range.start - line_start + text.len(), tab_size);
+            }
+        }
+        let lines = self.lines();
+        Block::new((*lines.start(), caret), (*lines.end(), caret))
+    }
+}
+
+/// Byte offset of the char at the display `column` of `line`, or of the end of the line when it is shorter.
+pub(crate) fn column_offset(line: &str, column: usize, tab_size: usize) -> usize {
+    let mut current = 0;
+    for (offset, c) in line.char_indices() {
+        if current >= column || c == '\n' {
+            return offset;
+        }
+        current = display_columns(&line[offset..offset + c.len_utf8()], current, tab_size);
+    }
+    line.len()
+}
+
+/// Display column of the byte `offset` of `line`.
+pub(crate) fn column_of(line: &str, offset: usize, tab_size: usize) -> usize {
+    display_columns(&line[..offset], 0, tab_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_the_columns_of_each_line() {
+        let code = "abc\nd\n\tgh";
+        let block = Block::new((2, 3), (0, 1));
+        assert_eq!(block.ranges(code, 4), vec![1..3, 5..5, 7..7]);
+        assert_eq!(block.text(code, 4), "bc\n\n");
+        assert_eq!(column_offset("\tgh", 5, 4), 2);
+        assert_eq!(column_of("\tgh", 2, 4), 5);
+    }
+
+    #[test]
+    fn inserts_into_every_line() {
+        let mut code = String::from("abc\nde\nfgh");
+        let block = Block::new((0, 1), (2, 1)).edit(&mut code, BlockEdit::Insert("X"), 4);
+        assert_eq!(code, "aXbc\ndXe\nfXgh");
+        assert_eq!(block, Block::new((0, 2), (2, 2)));
+    }
+
+    #[test]
+    fn pastes_a_line_per_line() {
+        let mut code = String::from("abc\nde\nfgh");
+        Block::new((0, 1), (2, 2)).edit(&mut code, BlockEdit::Insert("1\n2\n3"), 4);
+        assert_eq!(code, "a1c\nd2\nf3h");
+    }
+
+    #[test]
+    fn deletes_around_an_empty_block() {
+        let mut code = String::from("abc\nde\nfgh");
+        let block = Block::new((0, 1), (2, 1)).edit(&mut code, BlockEdit::Backspace, 4);
+        assert_eq!(code, "bc\ne\ngh");
+        assert_eq!(block, Block::new((0, 0), (2, 0)));
+
+        let mut code = String::from("abc\nde\nfgh");
+        Block::new((0, 1), (2, 1)).edit(&mut code, BlockEdit::Delete, 4);
+        assert_eq!(code, "ac\nd\nfh");
+    }
+
+    #[test]
+    fn keeps_the_crlf_of_the_short_lines() {
+        let mut code = String::from("abc\r\nd\r\nfgh");
+        Block::new((0, 1), (2, 1)).edit(&mut code, BlockEdit::Delete, 4);
+        assert_eq!(code, "ac\r\nd\r\nfh");
+    }
+}
@@ -0,0 +1,72 @@
+use std::ops::Range;
+
+use crate::language::LanguageConfig;
+
+/// Byte ranges of the bracket next to the `caret` (the one after it first) and of the bracket matching it.
+///
+/// `brackets` are the byte offsets of the brackets of every line of `code` outside the strings and the comments,
+/// as found by the highlighting, so `'('` or `"{"` are not matched. Only the brackets of `config` are matched,
+/// and `None` is returned when the bracket at the caret is unmatched.
+pub(crate) fn matching_pair<'a>(
+    code: &str,
+    brackets: impl Iterator<Item = &'a [usize]>,
+    caret: usize,
+    config: &LanguageConfig,
+) -> Option<(Range<usize>, Range<usize>)> {
+    let mut offsets = Vec::new();
+    let mut line_start = 0;
+    for (line, brackets) in code.split_inclusive('\n').zip(brackets) {
+        // the brackets of a text highlighted before an edit may be out of date
+        offsets.extend(
+            brackets
+                .iter()
+                .map(|offset| line_start + offset)
+                .filter(|&offset| bracket_at(code, offset, config).is_some()),
+        );
+        line_start += line.len();
+    }
+
+    let after = offsets.binary_search(&caret).ok();
+    // the brackets are ASCII, one byte long
+    let before = offsets.partition_point(|&offset| offset < caret).checked_sub(1).filter(|&i| offsets[i] + 1 == caret);
+    let at = after.or(before)?;
+    let (c, open) = bracket_at(code, offsets[at], config)?;
+    let partner = if open {
+        config.closing_bracket(c)?
+    } else {
+        config.opening_bracket(c)?
+    };
+
+    let mut depth = 0usize;
+    let mut scan = |i: usize| {
+        let (other, _) = bracket_at(code, offsets[i], config)?;
+        if other == c {
+            depth += 1;
+        } else if other == partner {
+            if depth == 0 {
+                return Some(i);
+            }
+            depth -= 1;
+        }
+        None
+    };
+    let found = if open {
+        (at + 1..offsets.len()).find_map(&mut scan)
+    } else {
+        (0..at).rev().find_map(&mut scan)
+    }?;
+    let range = |i: usize| offsets[i]..offsets[i] + 1;
+    Some((range(at), range(found)))
+}
+
+/// Bracket of `config` starting at the byte `offset` of `code`, and whether it opens.
+fn bracket_at(code: &str, offset: usize, config: &LanguageConfig) -> Option<(char, bool)> {
+    let c = code.get(offset..)?.chars().next()?;
+    if config.closing_bracket(c).is_some() {
+        Some((c, true))
+    } else if config.opening_bracket(c).is_some() {
+        Some((c, false))
+    } else {
+        None
+    }
+}
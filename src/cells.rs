@@ -0,0 +1,130 @@
+use std::ops::Range;
+
+use egui::{Galley, Rangef, Ui};
+
+/// Cell of a script split like a notebook, by the `# %%` comments (the percent format of Jupytext, Spyder and
+/// VS Code) or the `In [ ]:` prompts of IPython.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CodeCell {
+    /// Lines of the cell (0-based), starting with its marker line.
+    pub lines: Range<usize>,
+    /// Byte range of the code of the cell, without the marker line (or the `In [ ]:` prompt).
+    pub code: Range<usize>,
+    /// Text after the marker, like `Load the data` in `# %% Load the data`, empty for the prompts and
+    /// the code before the first marker.
+    pub title: String,
+}
+
+/// Cells of `code`, none without a marker. The code before the first marker is a cell too, if not blank.
+///
+/// The markers are `%%` after the `line_comment` (`#` by default) at the start of a line, and the `In [ ]:`
+/// or `In [12]:` prompts, the code of their cell starts after them on the same line.
+pub fn cells(code: &str, line_comment: Option<&str>) -> Vec<CodeCell> {
+    let comment = line_comment.unwrap_or("#");
+    let mut cells: Vec<CodeCell> = Vec::new();
+    let mut offset = 0;
+    for (line, text) in code.split_inclusive('\n').enumerate() {
+        if let Some((title, code_start)) = marker(text, comment) {
+            match cells.last_mut() {
+                Some(cell) => cell.code.end = offset,
+                None if !code[..offset].trim().is_empty() => {
+                    cells.push(CodeCell { lines: 0..line, code: 0..offset, title: String::new() });
+                }
+                None => {}
+            }
+            if let Some(cell) = cells.last_mut() {
+                cell.lines.end = line;
+            }
+            let start = offset + code_start;
+            cells.push(CodeCell { lines: line..line + 1, code: start..start, title: title.to_owned() });
+        }
+        offset += text.len();
+    }
+    if let Some(cell) = cells.last_mut() {
+        cell.code.end = code.len();
+        cell.lines.end = code.split('\n').count();
+    }
+    cells
+}
+
+/// Title of the cell started by the `line` and the offset of its code in the line, `None` if it is not a marker.
+fn marker<'a>(line: &'a str, comment: &str) -> Option<(&'a str, usize)> {
+    let trimmed = line.trim_start();
+    if let Some(rest) = trimmed.strip_prefix(comment).map(str::trim_start)
+        && let Some(title) = rest.strip_prefix("%%")
+    {
+        return Some((title.trim(), line.len()));
+    }
+    let (count, code) = trimmed.strip_prefix("In [")?.split_once("]:")?;
+    let code = code.strip_prefix(' ').unwrap_or(code);
+    count.chars().all(|c| c.is_ascii_digit() || c == ' ').then_some(("", line.len() - code.len()))
+}
+
+/// Paints a separator above the marker lines of the `cells` with a run button on their right,
+/// returns the cell whose button was clicked.
+pub(crate) fn cells_ui(
+    ui: &Ui,
+    id: egui::Id,
+    cells: &[CodeCell],
+    spans: &[Rangef],
+    galley: &Galley,
+    galley_pos: egui::Pos2,
+    x_range: Rangef,
+) -> Option<CodeCell> {
+    let row_height = galley.rows.first().map_or(0.0, |row| row.height());
+    let stroke = egui::Stroke::new(1.0, ui.visuals().weak_text_color().gamma_multiply(0.5));
+    let right = x_range.max.min(ui.clip_rect().right());
+    let mut clicked = None;
+    // the code before the first marker has no marker line
+    for cell in cells.iter().filter(|cell| cell.code.start > 0) {
+        let Some(span) = spans.get(cell.lines.start).filter(|span| span.span() > 0.0) else {
+            continue;
+        };
+        let top = galley_pos.y + span.min;
+        if cell.lines.start > 0 {
+            ui.painter().hline(x_range, top, stroke);
+        }
+        let rect = egui::Rect::from_min_max(egui::pos2(right - row_height, top), egui::pos2(right, top + row_height));
+        let button = ui.interact(rect, id.with(("run_cell", cell.lines.start)), egui::Sense::click());
+        let visuals = ui.style().interact(&button);
+        let font = egui::FontId::proportional(row_height * 0.7);
+        ui.painter().text(rect.center(), egui::Align2::CENTER_CENTER, "▶", font, visuals.text_color());
+        if button.on_hover_text("Run cell").on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
+            clicked = Some(cell.clone());
+        }
+    }
+    clicked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(lines: Range<usize>, code: Range<usize>, title: &str) -> CodeCell {
+        CodeCell { lines, code, title: title.to_owned() }
+    }
+
+    #[test]
+    fn splits_at_the_percent_markers() {
+        let code = "import a\n# %% Load\nx = 1\n#%%\ny\n";
+        let expected = [cell(0..1, 0..9, ""), cell(1..3, 19..25, "Load"), cell(3..6, 29..31, "")];
+        assert_eq!(cells(code, None), expected);
+        assert_eq!(cells("// %% a\nb", Some("//")), [cell(0..2, 8..9, "a")]);
+        // a blank start is no cell
+        assert_eq!(cells("\n# %%\nx", None), [cell(1..3, 6..7, "")]);
+    }
+
+    #[test]
+    fn splits_at_the_prompts() {
+        let code = "In [1]: x = 1\ny\nIn [ ]: z";
+        assert_eq!(cells(code, None), [cell(0..2, 8..16, ""), cell(2..3, 24..25, "")]);
+    }
+
+    #[test]
+    fn has_no_cells_without_markers() {
+        assert_eq!(cells("", None), []);
+        assert_eq!(cells("x = 1\n# %\n%%", None), []);
+        // prompts without their closing bracket or with a label
+        assert_eq!(cells("In [1 x\nIn [a]: y", None), []);
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+
+/// Default capacity of the [`ClipboardRing`].
+pub const DEFAULT_CAPACITY: usize = 20;
+
+/// Recent cuts and copies of an editor, newest first, kept apart from the OS clipboard.
+///
+/// Copying a text already in the ring moves it to the front, the oldest entries are dropped beyond the capacity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClipboardRing {
+    entries: VecDeque<String>,
+    capacity: usize,
+}
+
+impl Default for ClipboardRing {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl ClipboardRing {
+    /// Empty ring keeping up to `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes the capacity, dropping the oldest entries beyond it.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.entries.truncate(capacity);
+    }
+
+    /// Adds `text` as the newest entry, the empty texts are ignored.
+    pub fn push(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        if text.is_empty() || self.capacity == 0 {
+            return;
+        }
+        self.entries.retain(|entry| *entry != text);
+        self.entries.push_front(text);
+        self.entries.truncate(self.capacity);
+    }
+
+    /// Entry `index`, 0 is the newest one.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    /// The entries, newest first.
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// First non-blank line of `text` shortened to `max_chars`, with the number of the other lines,
+/// shown in the history popup.
+pub(crate) fn preview(text: &str, max_chars: usize) -> String {
+    let first = text.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default();
+    let mut preview: String = first.chars().take(max_chars).collect();
+    if first.chars().count() > max_chars {
+        preview.push('…');
+    }
+    let more = text.lines().count().saturating_sub(1);
+    if more > 0 {
+        preview.push_str(&format!("  (+{more} lines)"));
+    }
+    preview
+}
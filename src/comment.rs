@@ -0,0 +1,153 @@
+use std::ops::Range;
+
+use crate::indent::indentation;
+use crate::position::LineIndex;
+
+/// Edits commenting out the `lines` of `code` with the line comment `prefix`, or uncommenting them
+/// when all their non-blank lines are comments already. The blank lines are left as they are.
+///
+/// The prefix and a space are inserted at the smallest indentation of the lines, so the comments are aligned,
+/// and removed with the space after them. The edits are sorted, with byte ranges of `code`.
+pub(crate) fn toggle_line_comment(code: &str, lines: Range<usize>, prefix: &str) -> Vec<(Range<usize>, String)> {
+    let index = LineIndex::new(code);
+    let lines: Vec<(usize, &str)> = lines
+        .filter(|&line| line < index.line_count())
+        .map(|line| (index.line_range(line).start, index.line(line)))
+        .filter(|(_, text)| !text.trim().is_empty())
+        .collect();
+
+    let commented = !lines.is_empty() && lines.iter().all(|(_, text)| text.trim_start().starts_with(prefix));
+    if commented {
+        lines
+            .iter()
+            .map(|&(start, text)| {
+                let at = start + indentation(text).len();
+                let rest = &text[indentation(text).len() + prefix.len()..];
+                let len = prefix.len() + usize::from(rest.starts_with(' '));
+                (at..at + len, String::new())
+            })
+            .collect()
+    } else {
+        let column = lines.iter().map(|(_, text)| indentation(text).len()).min().unwrap_or(0);
+        lines
+            .iter()
+            .map(|&(start, _)| (start + column..start + column, format!("{prefix} ")))
+            .collect()
+    }
+}
+
+/// Like [`toggle_line_comment`] for the languages without a line comment, wraps each non-blank line
+/// in the block comment `open` and `close` (like `/* a */`), or unwraps them when they are all wrapped.
+pub(crate) fn toggle_block_comment(
+    code: &str,
+    lines: Range<usize>,
+    open: &str,
+    close: &str,
+) -> Vec<(Range<usize>, String)> {
+    let index = LineIndex::new(code);
+    let lines: Vec<(usize, &str)> = lines
+        .filter(|&line| line < index.line_count())
+        .map(|line| (index.line_range(line).start, index.line(line)))
+        .filter(|(_, text)| !text.trim().is_empty())
+        .collect();
+
+    let wrapped = |text: &str| {
+        let text = text.trim();
+        text.len() >= open.len() + close.len() && text.starts_with(open) && text.ends_with(close)
+    };
+    let commented = !lines.is_empty() && lines.iter().all(|(_, text)| wrapped(text));
+    let mut edits = Vec::new();
+    for &(start, text) in &lines {
+        let at = start + indentation(text).len();
+        let end = start + text.trim_end().len();
+        if commented {
+            let inner = &code[at + open.len()..end - close.len()];
+            let before = usize::from(inner.starts_with(' '));
+            let after = usize::from(inner.len() > before && inner.ends_with(' '));
+            edits.push((at..at + open.len() + before, String::new()));
+            edits.push((end - close.len() - after..end, String::new()));
+        } else {
+            edits.push((at..at, format!("{open} ")));
+            edits.push((end..end, format!(" {close}")));
+        }
+    }
+    edits
+}
+
+/// `offset` of the text before the sorted `edits`, moved to the same place in the text after them.
+pub(crate) fn shift_offset(offset: usize, edits: &[(Range<usize>, String)]) -> usize {
+    let mut shifted = offset;
+    for (range, text) in edits {
+        if range.start >= offset {
+            break;
+        }
+        shifted = shifted - (offset.min(range.end) - range.start) + text.len();
+    }
+    shifted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(code: &str, edits: &[(Range<usize>, String)]) -> String {
+        let mut code = code.to_owned();
+        for (range, text) in edits.iter().rev() {
+            code.replace_range(range.clone(), text);
+        }
+        code
+    }
+
+    #[test]
+    fn comments_out_at_the_smallest_indentation() {
+        let code = "fn f() {\n    a();\n\n        b();\n}";
+        let edits = toggle_line_comment(code, 1..4, "//");
+        assert_eq!(apply(code, &edits), "fn f() {\n    // a();\n\n    //     b();\n}");
+    }
+
+    #[test]
+    fn uncomments_when_all_lines_are_comments() {
+        let code = "    // a();\n\n    //b();";
+        let edits = toggle_line_comment(code, 0..3, "//");
+        assert_eq!(apply(code, &edits), "    a();\n\n    b();");
+        assert_eq!(toggle_line_comment("\n  \n", 0..3, "//"), Vec::new());
+    }
+
+    #[test]
+    fn comments_out_a_mixed_selection() {
+        let code = "# a\nb\n# c";
+        let edits = toggle_line_comment(code, 0..3, "#");
+        assert_eq!(apply(code, &edits), "# # a\n# b\n# # c");
+        // the lines past the end are ignored
+        assert_eq!(apply("a", &toggle_line_comment("a", 0..5, "#")), "# a");
+    }
+
+    #[test]
+    fn falls_back_to_block_comments() {
+        let code = "a {\n  color: red;  \n\n}";
+        let edits = toggle_block_comment(code, 0..4, "/*", "*/");
+        let commented = apply(code, &edits);
+        assert_eq!(commented, "/* a { */\n  /* color: red; */  \n\n/* } */");
+
+        let edits = toggle_block_comment(&commented, 0..4, "/*", "*/");
+        assert_eq!(apply(&commented, &edits), "a {\n  color: red;  \n\n}");
+        // a line wrapped without spaces and an empty comment
+        assert_eq!(apply("/*a*/\n/**/", &toggle_block_comment("/*a*/\n/**/", 0..2, "/*", "*/")), "a\n");
+    }
+
+    #[test]
+    fn block_comments_a_mixed_selection() {
+        let code = "/* a */\nb";
+        let edits = toggle_block_comment(code, 0..2, "/*", "*/");
+        assert_eq!(apply(code, &edits), "/* /* a */ */\n/* b */");
+    }
+
+    #[test]
+    fn shifts_the_offsets_past_the_edits() {
+        let edits = vec![(0..0, "// ".to_owned()), (4..7, String::new())];
+        assert_eq!(shift_offset(0, &edits), 0);
+        assert_eq!(shift_offset(2, &edits), 5);
+        assert_eq!(shift_offset(5, &edits), 7);
+        assert_eq!(shift_offset(9, &edits), 9);
+    }
+}
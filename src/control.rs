@@ -0,0 +1,133 @@
+use egui::text::LayoutJob;
+use egui::{Align2, Color32, FontId, Galley, Pos2, Ui};
+
+use crate::geometry;
+
+/// Names of the C0 control chars, `\t` and `\n` are laid out by egui.
+const C0: [&str; 32] = [
+    "NUL", "SOH", "STX", "ETX", "EOT", "ENQ", "ACK", "BEL", "BS", "", "", "VT", "FF", "CR", "SO", "SI", "DLE", "DC1",
+    "DC2", "DC3", "DC4", "NAK", "SYN", "ETB", "CAN", "EM", "SUB", "ESC", "FS", "GS", "RS", "US",
+];
+
+/// Mnemonic of the control char at the byte `offset` of `text`, `None` for the other chars.
+///
+/// A `\r` before a `\n` is a Windows line break and is not shown.
+pub(crate) fn mnemonic(text: &str, offset: usize, c: char) -> Option<&'static str> {
+    match c {
+        '\r' if text[offset + 1..].starts_with('\n') => None,
+        '\u{7f}' => Some("DEL"),
+        c if (c as u32) < 0x20 => Some(C0[c as usize]).filter(|name| !name.is_empty()),
+        _ => None,
+    }
+}
+
+/// Whether `text` has a control char to show, to skip the work for the usual code.
+pub(crate) fn has_control(text: &str) -> bool {
+    text.char_indices().any(|(i, c)| mnemonic(text, i, c).is_some())
+}
+
+/// Makes the glyphs of the control chars transparent, their boxes are painted over them by [`paint`].
+pub(crate) fn conceal(job: &mut LayoutJob) {
+    if !has_control(&job.text) {
+        return;
+    }
+    let text = &job.text;
+    let mut sections = Vec::with_capacity(job.sections.len());
+    for section in job.sections.drain(..) {
+        let range = section.byte_range.clone();
+        let mut start = range.start;
+        // the leading space goes before the first piece of the section only
+        let mut leading = section.leading_space;
+        let mut push = |byte_range: std::ops::Range<usize>, color: Option<Color32>| {
+            let mut piece = section.clone();
+            piece.byte_range = byte_range;
+            piece.leading_space = std::mem::take(&mut leading);
+            if let Some(color) = color {
+                piece.format.color = color;
+            }
+            sections.push(piece);
+        };
+        for (i, c) in text[range.clone()].char_indices() {
+            let offset = range.start + i;
+            if mnemonic(text, offset, c).is_none() {
+                continue;
+            }
+            if offset > start {
+                push(start..offset, None);
+            }
+            start = offset + c.len_utf8();
+            push(offset..start, Some(Color32::TRANSPARENT));
+        }
+        if start < range.end {
+            push(start..range.end, None);
+        }
+    }
+    job.sections = sections;
+}
+
+/// Paints the visible control chars of the galley of `text` as boxes with their mnemonic, in reversed colors.
+pub(crate) fn paint(ui: &Ui, galley: &Galley, galley_pos: Pos2, font: &FontId, fill: Color32, text_color: Color32) {
+    let text = galley.text();
+    if !has_control(text) {
+        return;
+    }
+    let clip = ui.clip_rect();
+    let first = galley.cursor_from_pos(clip.min - galley_pos).index;
+    let last = galley.cursor_from_pos(clip.max - galley_pos).index;
+    let font = FontId::new(font.size * 0.7, font.family.clone());
+    let painter = ui.painter();
+    for (index, (offset, c)) in text.char_indices().enumerate() {
+        if index > last {
+            break;
+        }
+        let Some(name) = mnemonic(text, offset, c).filter(|_| index >= first) else {
+            continue;
+        };
+        for rect in geometry::char_range_rects(galley, galley_pos, index..index + 1) {
+            let rect = rect.shrink2(egui::vec2(1.0, 2.0));
+            painter.rect_filled(rect, 2.0, fill);
+            painter.text(rect.center(), Align2::CENTER_CENTER, name, font.clone(), text_color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_the_control_chars() {
+        assert_eq!(mnemonic("\0", 0, '\0'), Some("NUL"));
+        assert_eq!(mnemonic("\u{1b}[0m", 0, '\u{1b}'), Some("ESC"));
+        assert_eq!(mnemonic("\u{7f}", 0, '\u{7f}'), Some("DEL"));
+        assert_eq!(mnemonic("a\r", 1, '\r'), Some("CR"));
+        assert_eq!(mnemonic("\t", 0, '\t'), None);
+        assert_eq!(mnemonic("\n", 0, '\n'), None);
+        assert_eq!(mnemonic("a", 0, 'a'), None);
+    }
+
+    #[test]
+    fn skips_the_windows_line_breaks() {
+        assert_eq!(mnemonic("a\r\nb", 1, '\r'), None);
+        assert!(!has_control("a\r\nb\tc"));
+        assert!(has_control("a\rb"));
+        assert!(!has_control(""));
+    }
+
+    #[test]
+    fn conceals_only_the_control_chars() {
+        let mut job = LayoutJob::default();
+        job.append("a\u{1b}b", 2.0, egui::TextFormat::default());
+        conceal(&mut job);
+        let ranges: Vec<_> = job.sections.iter().map(|section| section.byte_range.clone()).collect();
+        assert_eq!(ranges, [0..1, 1..2, 2..3]);
+        assert_eq!(job.sections[1].format.color, Color32::TRANSPARENT);
+        assert_ne!(job.sections[2].format.color, Color32::TRANSPARENT);
+        let leading: Vec<f32> = job.sections.iter().map(|section| section.leading_space).collect();
+        assert_eq!(leading, [2.0, 0.0, 0.0]);
+
+        let mut job = LayoutJob::default();
+        conceal(&mut job);
+        assert!(job.sections.is_empty());
+    }
+}
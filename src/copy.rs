@@ -0,0 +1,122 @@
+use std::ops::Range;
+use std::path::Path;
+
+use syntect::parsing::SyntaxReference;
+
+use crate::position::LineIndex;
+
+/// Variant of the copied text, offered by the context menu of the editor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CopyMode {
+    /// The selected lines prefixed with their numbers, see [`with_line_numbers`].
+    WithLineNumbers,
+    /// The selection in a Markdown code fence tagged with the language, see [`markdown_fence`].
+    MarkdownFence,
+    /// The path of the file with the selected lines, like `src/main.rs:12`, see [`path_line`].
+    PathLine,
+}
+
+impl CopyMode {
+    pub const ALL: &[CopyMode] = &[CopyMode::WithLineNumbers, CopyMode::MarkdownFence, CopyMode::PathLine];
+
+    /// Label in the context menu.
+    pub fn label(self) -> &'static str {
+        match self {
+            CopyMode::WithLineNumbers => "Copy with line numbers",
+            CopyMode::MarkdownFence => "Copy as Markdown code fence",
+            CopyMode::PathLine => "Copy file path:line",
+        }
+    }
+}
+
+/// The `lines` (0-based) of `code`, prefixed with their 1-based numbers aligned to the right.
+pub fn with_line_numbers(code: &str, lines: Range<usize>) -> String {
+    let index = LineIndex::new(code);
+    let lines = lines.start..lines.end.min(index.line_count());
+    let width = lines.end.to_string().len();
+    let numbered: Vec<String> = lines
+        .map(|line| match index.line(line) {
+            "" => format!("{:>width$} |", line + 1),
+            text => format!("{:>width$} | {text}", line + 1),
+        })
+        .collect();
+    numbered.join("\n")
+}
+
+/// `text` in a Markdown code fence with the info string `tag`, the fence is longer than the backtick runs of `text`.
+pub fn markdown_fence(text: &str, tag: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let fence = "`".repeat((longest + 1).max(3));
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    format!("{fence}{tag}\n{text}\n{fence}")
+}
+
+/// `path` with the 1-based numbers of the `lines` (0-based), like `src/main.rs:12` or `src/main.rs:12-15`.
+pub fn path_line(path: &Path, lines: Range<usize>) -> String {
+    let first = lines.start + 1;
+    let last = lines.end.max(first);
+    if last > first {
+        format!("{}:{first}-{last}", path.display())
+    } else {
+        format!("{}:{first}", path.display())
+    }
+}
+
+/// Link to the `lines` (0-based) of the file at `url` on GitHub, like `https://github.com/o/r/blob/v1/src/lib.rs#L12-L15`,
+/// for [`CodeViewer::permalink`](crate::CodeViewer::permalink).
+pub fn github_permalink(url: &str, lines: Range<usize>) -> String {
+    let first = lines.start + 1;
+    let last = lines.end.max(first);
+    if last > first {
+        format!("{url}#L{first}-L{last}")
+    } else {
+        format!("{url}#L{first}")
+    }
+}
+
+/// Info string of the code fences of `syntax`: its lowercase name when it is one word, like `rust`,
+/// otherwise its first file extension, like `cpp`, nothing for the plain text.
+pub(crate) fn fence_tag(syntax: &SyntaxReference) -> String {
+    if syntax.name == "Plain Text" {
+        return String::new();
+    }
+    if syntax.name.chars().all(char::is_alphanumeric) {
+        return syntax.name.to_lowercase();
+    }
+    syntax.file_extensions.first().cloned().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_the_lines_aligned_to_the_right() {
+        let code = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj";
+        assert_eq!(with_line_numbers(code, 7..10), " 8 | h\n 9 | i\n10 | j");
+        assert_eq!(with_line_numbers("a\n\nc", 0..5), "1 | a\n2 |\n3 | c");
+        assert_eq!(with_line_numbers("a", 3..5), "");
+    }
+
+    #[test]
+    fn fences_longer_than_the_backtick_runs() {
+        assert_eq!(markdown_fence("let a = `b`;\n", "rust"), "```rust\nlet a = `b`;\n```");
+        assert_eq!(markdown_fence("```x```", "md"), "````md\n```x```\n````");
+        assert_eq!(markdown_fence("", ""), "```\n\n```");
+    }
+
+    #[test]
+    fn formats_the_paths_and_the_permalinks_of_the_lines() {
+        let path = Path::new("src/main.rs");
+        assert_eq!(path_line(path, 11..12), "src/main.rs:12");
+        assert_eq!(path_line(path, 11..15), "src/main.rs:12-15");
+        assert_eq!(path_line(path, 11..11), "src/main.rs:12");
+        assert_eq!(github_permalink("https://github.com/o/r/blob/v1/a.rs", 0..1), "https://github.com/o/r/blob/v1/a.rs#L1");
+        assert_eq!(github_permalink("u", 2..5), "u#L3-L5");
+    }
+}
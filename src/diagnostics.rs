@@ -0,0 +1,88 @@
+use std::ops::Range;
+
+use egui::{Color32, Painter, Pos2, Rect, Shape, Stroke};
+
+use crate::position::Position;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Hint,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn color(self) -> Color32 {
+        match self {
+            Severity::Hint => Color32::from_rgb(120, 120, 120),
+            Severity::Info => Color32::from_rgb(78, 140, 214),
+            Severity::Warning => Color32::from_rgb(220, 170, 50),
+            Severity::Error => Color32::from_rgb(214, 84, 84),
+        }
+    }
+}
+
+/// Problem in the code, shown with a squiggle under its range and a mark in the gutter.
+///
+/// The columns of the positions use the `position_encoding` of the editor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub range: Range<Position>,
+    pub severity: Severity,
+    pub message: String,
+    /// Quick fixes, offered by the lightbulb on the line of the diagnostic.
+    pub actions: Vec<CodeAction>,
+}
+
+impl Diagnostic {
+    pub fn new(range: Range<Position>, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            range,
+            severity,
+            message: message.into(),
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn with_action(mut self, action: CodeAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+}
+
+/// Quick fix of a [`Diagnostic`].
+///
+/// The `edits` are applied by the editor when the action is selected,
+/// actions without edits are only returned in `EditorOutput::code_action` for the host to run them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeAction {
+    pub title: String,
+    pub edits: Vec<(Range<Position>, String)>,
+}
+
+impl CodeAction {
+    pub fn new(title: impl Into<String>, edits: Vec<(Range<Position>, String)>) -> Self {
+        Self {
+            title: title.into(),
+            edits,
+        }
+    }
+}
+
+/// Paints a wavy line under `rect`.
+pub(crate) fn paint_squiggle(painter: &Painter, rect: Rect, color: Color32) {
+    const STEP: f32 = 2.0;
+    let y = rect.bottom() - 1.0;
+    let mut points = Vec::new();
+    let mut x = rect.left();
+    let mut up = true;
+    while x <= rect.right() {
+        points.push(Pos2::new(x, if up { y - STEP / 2.0 } else { y + STEP / 2.0 }));
+        x += STEP;
+        up = !up;
+    }
+    if points.len() > 1 {
+        painter.add(Shape::line(points, Stroke::new(1.0, color)));
+    }
+}
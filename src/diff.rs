@@ -0,0 +1,218 @@
+use std::ops::Range;
+
+/// Changed region between two versions of a text, as ranges of 0-based line indices.
+///
+/// `old` is empty for inserted lines and `new` is empty for deleted ones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+impl Hunk {
+    pub fn kind(&self) -> HunkKind {
+        if self.old.is_empty() {
+            HunkKind::Added
+        } else if self.new.is_empty() {
+            HunkKind::Deleted
+        } else {
+            HunkKind::Modified
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HunkKind {
+    Added,
+    Deleted,
+    Modified,
+}
+
+/// Returns `text` with the lines of `hunk` restored from `original`.
+pub fn revert_hunk(text: &str, original: &str, hunk: &Hunk) -> String {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let original_lines: Vec<&str> = original.split_inclusive('\n').collect();
+    let new = hunk.new.start.min(lines.len())..hunk.new.end.min(lines.len());
+    let old = hunk.old.start.min(original_lines.len())..hunk.old.end.min(original_lines.len());
+
+    let mut result: String = lines[..new.start].concat();
+    // the previous line might be the last one, without the line break
+    if !result.is_empty() && !result.ends_with('\n') && !old.is_empty() {
+        result.push('\n');
+    }
+    result.push_str(&original_lines[old].concat());
+    if new.end < lines.len() && !result.is_empty() && !result.ends_with('\n') {
+        result.push('\n');
+    }
+    result.push_str(&lines[new.end..].concat());
+    result
+}
+
+/// Line of the new text corresponding to the 0-based `line` of the old one.
+///
+/// The lines of a changed region keep their offset in it, as far as the new region is long enough.
+pub fn map_line(hunks: &[Hunk], line: usize) -> usize {
+    let mut shift = 0isize;
+    for hunk in hunks {
+        if line < hunk.old.start {
+            break;
+        }
+        if line < hunk.old.end {
+            let offset = (line - hunk.old.start).min(hunk.new.len().saturating_sub(1));
+            return hunk.new.start + offset;
+        }
+        shift += hunk.new.len() as isize - hunk.old.len() as isize;
+    }
+    line.saturating_add_signed(shift)
+}
+
+/// Line based diff of two texts.
+pub fn diff(old: &str, new: &str) -> Vec<Hunk> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    diff_lines(&old, &new)
+}
+
+/// Diff of two line lists (Myers algorithm).
+pub fn diff_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut hunks = Vec::new();
+    let (mut last_old, mut last_new) = (0, 0);
+    let Some(matches) = matching_lines(old_mid, new_mid) else {
+        // too different to be worth matching
+        return vec![Hunk {
+            old: prefix..old.len() - suffix,
+            new: prefix..new.len() - suffix,
+        }];
+    };
+    for (x, y) in matches.into_iter().chain([(old_mid.len(), new_mid.len())]) {
+        if x > last_old || y > last_new {
+            hunks.push(Hunk {
+                old: prefix + last_old..prefix + x,
+                new: prefix + last_new..prefix + y,
+            });
+        }
+        last_old = x + 1;
+        last_new = y + 1;
+    }
+    hunks
+}
+
+/// Most added and deleted lines handled by [`matching_lines`], its memory grows with their square.
+const MAX_EDIT_DISTANCE: isize = 1000;
+
+/// Pairs of equal line indices of the longest common subsequence, in order,
+/// `None` past [`MAX_EDIT_DISTANCE`] changed lines.
+fn matching_lines<T: PartialEq>(old: &[T], new: &[T]) -> Option<Vec<(usize, usize)>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = n + m;
+    if n == 0 || m == 0 {
+        return Some(Vec::new());
+    }
+
+    let offset = max as usize;
+    let mut v = vec![0isize; 2 * offset + 2];
+    // the diagonals reached before each step, from the first one of the vector
+    let mut trace = Vec::new();
+
+    'search: for d in 0..=max {
+        if d > MAX_EDIT_DISTANCE {
+            return None;
+        }
+        let low = (max - d - 1).max(0) as usize;
+        trace.push((low, v[low..=(max + d + 1) as usize].to_vec()));
+        for k in (-d..=d).step_by(2) {
+            let i = (k + max) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // walk the trace back from the end, collecting the diagonal moves
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, (low, v)) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + max) as usize - low];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+        if d > 0 {
+            x = prev_x;
+            y = prev_y;
+        }
+    }
+    matches.reverse();
+    Some(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hunks_of_each_kind() {
+        let hunks = diff("a\nb\nc\nd\n", "a\nB\nc\nd\ne\n");
+        assert_eq!(hunks, vec![Hunk { old: 1..2, new: 1..2 }, Hunk { old: 4..4, new: 4..5 }]);
+        assert_eq!(hunks[0].kind(), HunkKind::Modified);
+        assert_eq!(hunks[1].kind(), HunkKind::Added);
+        assert_eq!(diff("a\nb\n", "b\n")[0].kind(), HunkKind::Deleted);
+        assert!(diff("a\nb\n", "a\nb\n").is_empty());
+    }
+
+    #[test]
+    fn reverts_a_hunk() {
+        let original = "a\nb\nc\nd\n";
+        let text = "a\nB\nc\nd\ne\n";
+        let hunks = diff(original, text);
+        assert_eq!(revert_hunk(text, original, &hunks[0]), "a\nb\nc\nd\ne\n");
+        assert_eq!(revert_hunk(text, original, &hunks[1]), "a\nB\nc\nd\n");
+    }
+
+    #[test]
+    fn maps_lines_across_hunks() {
+        let hunks = diff("a\nb\nc\nd\n", "x\ny\na\nd\n");
+        assert_eq!(map_line(&hunks, 0), 2);
+        assert_eq!(map_line(&hunks, 3), 3);
+        // a deleted line goes to the start of its region
+        assert_eq!(map_line(&hunks, 1), 3);
+    }
+
+    #[test]
+    fn gives_up_on_unrelated_texts() {
+        let old: Vec<String> = (0..5000).map(|i| format!("old {i}")).collect();
+        let new: Vec<String> = (0..5000).map(|i| format!("new {i}")).collect();
+        assert_eq!(diff_lines(&old, &new), vec![Hunk { old: 0..5000, new: 0..5000 }]);
+        // the inserted lines are matched without any step
+        assert_eq!(diff_lines(&[], &new), vec![Hunk { old: 0..0, new: 0..5000 }]);
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Lines of `code` appearing more than once, in groups of identical lines (0-based line numbers, in order).
+///
+/// The lines are compared without their trailing whitespace, the lines shorter than `min_len` chars
+/// without their indentation are skipped, like the blank lines and the lone closing brackets.
+pub fn duplicate_lines(code: &str, min_len: usize) -> Vec<Vec<usize>> {
+    let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (line, text) in code.lines().enumerate() {
+        let text = text.trim_end();
+        if text.trim_start().chars().count() >= min_len.max(1) {
+            groups.entry(text).or_default().push(line);
+        }
+    }
+    let mut groups: Vec<Vec<usize>> = groups.into_values().filter(|lines| lines.len() > 1).collect();
+    groups.sort_by_key(|lines| lines[0]);
+    groups
+}
+
+/// The duplicated lines of the `groups` merged into blocks of consecutive lines, sorted.
+pub(crate) fn blocks(groups: &[Vec<usize>]) -> Vec<Range<usize>> {
+    let mut lines: Vec<usize> = groups.iter().flatten().copied().collect();
+    lines.sort_unstable();
+    let mut blocks: Vec<Range<usize>> = Vec::new();
+    for line in lines {
+        match blocks.last_mut() {
+            Some(block) if block.end == line => block.end += 1,
+            _ => blocks.push(line..line + 1),
+        }
+    }
+    blocks
+}
+
+/// Line to jump to from the `caret` line: the next (or previous) line of its group when it is duplicated,
+/// otherwise the next (or previous) duplicated line, wrapping around the code.
+pub(crate) fn target(groups: &[Vec<usize>], caret: usize, forward: bool) -> Option<usize> {
+    if let Some(group) = groups.iter().find(|group| group.contains(&caret)) {
+        let i = group.iter().position(|line| *line == caret)?;
+        let next = if forward { (i + 1) % group.len() } else { (i + group.len() - 1) % group.len() };
+        return Some(group[next]);
+    }
+    let mut lines: Vec<usize> = groups.iter().flatten().copied().collect();
+    lines.sort_unstable();
+    if forward {
+        lines.iter().find(|line| **line > caret).or(lines.first()).copied()
+    } else {
+        lines.iter().rev().find(|line| **line < caret).or(lines.last()).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_the_identical_lines() {
+        let code = "let a = 1;\nx();\nlet a = 1;  \nx();\nlet a = 1;";
+        assert_eq!(duplicate_lines(code, 3), vec![vec![0, 2, 4], vec![1, 3]]);
+    }
+
+    #[test]
+    fn keeps_the_near_misses_apart() {
+        assert_eq!(duplicate_lines("let a = 1;\nlet a = 2;", 3), Vec::<Vec<usize>>::new());
+        // the indentation is compared
+        assert_eq!(duplicate_lines("    foo();\nfoo();", 3), Vec::<Vec<usize>>::new());
+    }
+
+    #[test]
+    fn skips_the_lines_shorter_than_the_minimum() {
+        let code = "    }\n}\nabc\nabc\n\n";
+        assert_eq!(duplicate_lines(code, 3), vec![vec![2, 3]]);
+        assert_eq!(duplicate_lines(code, 4), Vec::<Vec<usize>>::new());
+        assert_eq!(duplicate_lines("}\n}\n\n", 0), vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn merges_the_consecutive_lines_into_blocks() {
+        assert_eq!(blocks(&[vec![0, 4], vec![1, 5], vec![8, 9]]), vec![0..2, 4..6, 8..10]);
+    }
+
+    #[test]
+    fn jumps_through_the_group_or_to_the_next_duplicate() {
+        let groups = [vec![1, 5, 9], vec![3, 7]];
+        assert_eq!(target(&groups, 5, true), Some(9));
+        assert_eq!(target(&groups, 9, true), Some(1));
+        assert_eq!(target(&groups, 1, false), Some(9));
+        assert_eq!(target(&groups, 4, true), Some(5));
+        assert_eq!(target(&groups, 10, true), Some(1));
+        assert_eq!(target(&groups, 0, false), Some(9));
+        assert_eq!(target(&[], 0, true), None);
+    }
+}
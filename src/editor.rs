@@ -1,27 +1,303 @@
-use egui::{Color32, FontId, Galley, TextEdit, TextFormat, Ui};
-use egui::text::LayoutJob;
+use egui::{FontId, Galley, TextEdit, Ui};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::fmt;
+use std::ops::Range;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use syntect::easy::HighlightLines;
-use syntect::highlighting::{Theme, ThemeSet, Style};
+use syntect::highlighting::Theme;
 use syntect::parsing::{SyntaxSet, SyntaxReference};
 
+use crate::CodeViewer;
+use crate::block::{self, Block, BlockEdit};
+use crate::brackets;
+use crate::cancel::CancelToken;
+use crate::cells::{self, CodeCell};
+use crate::comment;
+use crate::control;
+use crate::diagnostics::{self, CodeAction, Diagnostic, Severity};
+use crate::diff::{self, Hunk, HunkKind};
+use crate::duplicates;
+use crate::events::{CodeChange, EditorEvent, EventArgs, EventRegistry, TrackedCode, merge_change};
+use crate::folding::{self, FoldMethod};
+use crate::fonts;
+use crate::gutter::{self, LineNumbers};
+use crate::edits::{self, TextEditError};
+use crate::clipboard::{self, ClipboardRing};
+use crate::copy::{self, CopyMode};
+use crate::error::LitecodeError;
+use crate::pairs::AutoPairs;
+use crate::paste::{self, PasteMode};
+use crate::patch::{self, PatchError, PatchReport};
+use crate::geometry;
+use crate::image_preview::{self, ImagePreviewProvider};
+use crate::indent;
+use crate::keymap::{Command, Keymap};
+use crate::settings::{self, EditorSettings};
+use crate::language::{Injection, LanguageConfig, LanguageRegistry};
+use crate::layout;
+use crate::long_lines::{HorizontalWindow, WindowedCode};
+use crate::syntaxes;
+use crate::theme;
+use crate::toggle;
+use crate::linked::{self, LinkedEditingProvider};
+use crate::lint;
+use crate::literate;
+use crate::peek::{Peek, PeekAction};
+use crate::popup;
+use crate::rename::{self, RenameProvider, RenameState};
+use crate::scrub::{self, Scrub};
+use crate::search::{SearchField, SearchState};
+use crate::spans::Span;
+use crate::signature::{SignatureHelp, SignatureHelpProvider};
+use crate::print::{self, PrintStyle};
+use crate::position::{LineIndex, Position, PositionEncoding};
+use crate::highlight::{self, GalleyCache, HighlightWorker, LineCache};
+use crate::history::{CheckpointId, History};
+use crate::watermark;
+use crate::whitespace;
+
+/// Width in chars of the centered column of the zen mode.
+const ZEN_COLUMNS: usize = 100;
+
+/// Width in chars of the `↪` starting the continuation rows of the wrapped lines, with its space.
+const WRAP_MARKER_COLUMNS: f32 = 2.0;
+
 /// Basical code editor widget for [egui](https://crates.io/crates/egui), supporting syntax highlighting and themes.
 /// 
 /// # Implement
 /// 
 /// Use `CodeEditor::new(syntax_ext, color_theme)` to create a new instance.\
-/// Then use call `ui` method to integrate it into your egui application.
+/// Then use call `ui` method, or `ui.add(&mut mycodeeditor)`, to integrate it into your egui application.
 /// 
 /// # Usage
 /// 
-/// Use `mycodeeditor.code` to access the code.\
+/// Use `mycodeeditor.code` to access the code.
 pub struct CodeEditor {
     pub code: String,
-    syntax_set: SyntaxSet,
+    syntax_set: Arc<SyntaxSet>,
     theme: Arc<Theme>,
-    syntax: &'static SyntaxReference,
-    highlighter: Option<HighlightLines<'static>>,
+    theme_name: String,
+    /// Index of the syntax in `syntax_set`, see [`syntaxes::index_of`].
+    syntax_index: usize,
+    /// Extension given to `new` without a syntax, looked up again in the syntax sets given later.
+    missing_extension: Option<String>,
+    cancel: CancelToken,
+    events: EventRegistry,
+    last_language: Option<String>,
+    /// Change of the code since the previous frame, merged from the edits.
+    pending_change: Option<CodeChange>,
+    /// Change of the code in the last frame, see [`EditorOutput::change`].
+    change: Option<CodeChange>,
+    id: Option<egui::Id>,
+    path: Option<PathBuf>,
+    save_on_request: bool,
+    read_only: bool,
+    watermark: Option<String>,
+    escape_behavior: EscapeBehavior,
+    auto_pairs: AutoPairs,
+    /// Close chars inserted by the auto pairs, as their byte distance from the end of the code,
+    /// which typing between the pair does not change.
+    auto_closed: Vec<usize>,
+    keymap: Keymap,
+    /// Commands given to `send_command`, run on the next frame.
+    sent_commands: Vec<Command>,
+    shortcut_help: bool,
+    font_size: f32,
+    wrap: bool,
+    wrap_mode: WrapMode,
+    wrap_column: Option<usize>,
+    wrap_indicators: bool,
+    tab_size: usize,
+    indent_style: IndentStyle,
+    auto_indent: bool,
+    control_chars: bool,
+    show_whitespace: Whitespace,
+    scroll_past_end: bool,
+    smooth_scrolling: bool,
+    theme_background: bool,
+    current_line: bool,
+    current_line_color: Option<egui::Color32>,
+    match_brackets: bool,
+    typewriter: bool,
+    zen: bool,
+    highlight_line_limit: usize,
+    injections: Vec<Injection>,
+    background_lines: Option<usize>,
+    line_numbers: bool,
+    line_numbers_style: LineNumbers,
+    folding: bool,
+    fold_method: FoldMethod,
+    /// Folded line ranges, relative to `folded_code`.
+    folds: Vec<Range<usize>>,
+    folded_code: String,
+    fold_regions: Vec<Range<usize>>,
+    /// Hash of the code and the method the regions were found with.
+    fold_regions_key: Option<(u64, FoldMethod)>,
+    diagnostics_gutter: bool,
+    change_markers: bool,
+    caret_width: f32,
+    caret_blink: bool,
+    /// Options set on this editor, kept when the global defaults change.
+    overridden: settings::Overridden,
+    /// Generation of the global defaults last taken by the editor.
+    defaults_generation: u64,
+    interactive: bool,
+    baseline: Option<String>,
+    hunks: Vec<Hunk>,
+    hunks_hash: Option<u64>,
+    hunk_popup: Option<usize>,
+    position_encoding: PositionEncoding,
+    caret_rect: Option<egui::Rect>,
+    signature_provider: Option<Box<dyn SignatureHelpProvider>>,
+    signature_help: Option<SignatureHelp>,
+    cursor_range: Option<egui::text::CCursorRange>,
+    rename_provider: Option<Box<dyn RenameProvider>>,
+    image_provider: Option<Box<dyn ImagePreviewProvider>>,
+    /// Last hovered image source and its image, so the provider is called once per hovered string.
+    image_preview: Option<(String, Option<egui::ImageSource<'static>>)>,
+    rename: Option<RenameState>,
+    linked_provider: Option<Box<dyn LinkedEditingProvider>>,
+    linked_ranges: Vec<Range<usize>>,
+    peek: Option<Peek>,
+    diagnostics: Vec<Diagnostic>,
+    actions_menu: bool,
+    /// Pending caret move, and whether to focus and scroll to it.
+    go_to: Option<(Position, bool)>,
+    /// Pending selection of a restored history state.
+    restore_cursor: Option<egui::text::CCursorRange>,
+    /// Scrolls to the pending `restore_cursor`.
+    reveal_cursor: bool,
+    /// Rectangular selection of `Alt`+drag and `Shift+Alt`+arrows, typed into every line.
+    block: Option<Block>,
+    /// Part of the long lines laid out this frame, without the wrap.
+    window: Option<HorizontalWindow>,
+    /// Offset of the text from the left of the editor in the last frame, for the visible columns of the long lines.
+    text_offset: f32,
+    search: SearchState,
+    search_bar: bool,
+    search_open: bool,
+    search_focus: Option<SearchField>,
+    highlight_error_reported: bool,
+    galley_cache: GalleyCache,
+    line_cache: LineCache,
+    highlight_worker: HighlightWorker,
+    language: LanguageConfig,
+    languages: LanguageRegistry,
+    lint_indentation: bool,
+    max_line_length: Option<usize>,
+    line_length_severity: Severity,
+    long_lines: usize,
+    lint: Vec<Diagnostic>,
+    lint_hash: Option<u64>,
+    duplicates_min_len: Option<usize>,
+    cell_markers: bool,
+    literate_comments: bool,
+    /// Pairs of words of [`CodeEditor::toggle_literal`].
+    toggle_pairs: Vec<(String, String)>,
+    number_scrubbing: bool,
+    scrub_step: f64,
+    /// Number dragged with `Alt`, see [`CodeEditor::number_scrubbing`].
+    scrub: Option<Scrub>,
+    /// Scroll with `Ctrl` not yet stepped, see [`scrub::WHEEL_POINTS`].
+    scrub_wheel: f32,
+    /// Groups of identical lines, see [`duplicates::duplicate_lines`].
+    duplicates: Vec<Vec<usize>>,
+    /// Hash of the code and the minimum length the duplicates were found with.
+    duplicates_hash: Option<u64>,
+    /// Recent cuts and copies, pasted back from the popup of [`Command::PasteFromHistory`].
+    clipboard_ring: ClipboardRing,
+    /// Selected entry of the open clipboard history popup.
+    clipboard_popup: Option<usize>,
+    /// Paste special chosen in the context menu, waiting for the clipboard text requested on the given pass.
+    pending_paste: Option<(PasteMode, u64)>,
+    insert_final_newline: bool,
+    history: History,
+    /// The undo of the `TextEdit` keeps only the last state, the editor has its own history.
+    undoer_limited: bool,
+}
+
+/// Output of [`CodeEditor::show`].
+#[derive(Debug)]
+pub struct EditorOutput {
+    pub response: egui::Response,
+    /// `Ctrl+S` was pressed while the editor has focus.
+    pub save_requested: bool,
+    /// Result of the automatic save, see [`CodeEditor::save_on_request`].
+    pub save_result: Option<io::Result<()>>,
+    /// The "Open" button of the peek panel was clicked, contains the peeked location.
+    pub open_peek: Option<String>,
+    /// Quick fix selected in the lightbulb menu, its edits are already applied.
+    pub code_action: Option<CodeAction>,
+    /// The edits of the quick fix selected in the lightbulb menu were rejected, the code is unchanged.
+    pub code_action_error: Option<TextEditError>,
+    /// The syntax failed to highlight some lines, they are shown as plain text.
+    ///
+    /// Reported only once per editor.
+    pub highlight_error: Option<String>,
+    /// The run button of a cell was clicked, or [`Command::RunCell`] was triggered in it, see [`CodeEditor::cell_markers`].
+    pub run_cell: Option<CodeCell>,
+    /// The code changed in this frame, by typing, a command or the API since the previous frame.
+    pub changed: bool,
+    /// Range of the change of the code, see [`CodeChange`], `None` when the code did not change.
+    ///
+    /// Not reported for the first frame, the initial code is not a change. The writes to the
+    /// [`code`](CodeEditor::code) field are not tracked, use [`set_code`](CodeEditor::set_code) to report them.
+    pub change: Option<CodeChange>,
+    /// Caret position, see [`CodeEditor::cursor`].
+    pub cursor: Position,
+    /// Byte range of the selection, see [`CodeEditor::selection`].
+    pub selection: Option<Range<usize>>,
+}
+
+/// What pressing `Esc` in the editor does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EscapeBehavior {
+    /// Every press does the first step that applies: clear the selection, close the open popups, release the focus.
+    #[default]
+    Staged,
+    /// Like `Staged`, but the focus is never released.
+    KeepFocus,
+    /// Releases the focus at once, like the egui `TextEdit` (the popups are closed too).
+    ReleaseFocus,
+}
+
+/// Which spaces and tabs are marked with a `·` and a `→`, see [`CodeEditor::show_whitespace`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Whitespace {
+    #[default]
+    None,
+    /// The whitespace at the end of the lines, usually a leftover.
+    Trailing,
+    All,
+}
+
+/// What the `Tab` key and the automatic indentation insert, see [`CodeEditor::indent_style`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IndentStyle {
+    /// Like the code: tabs if its first indented line starts with one, spaces to the next tab stop otherwise.
+    #[default]
+    Detect,
+    /// A tab char, shown up to the next tab stop.
+    Tabs,
+    /// Spaces up to the next multiple of the count, 4 for `Spaces(4)`.
+    Spaces(usize),
+}
+
+/// Where the long lines break when they are wrapped, see [`CodeEditor::wrap_mode`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WrapMode {
+    /// At the spaces and the punctuation, the words longer than a row are broken anywhere.
+    #[default]
+    Word,
+    /// After the last char fitting in the row, like a terminal, the rows are filled but the words are cut.
+    Anywhere,
 }
 
 impl Clone for CodeEditor {
@@ -30,8 +306,117 @@ impl Clone for CodeEditor {
             code: self.code.clone(),
             syntax_set: self.syntax_set.clone(),
             theme: self.theme.clone(),
-            syntax: self.syntax, // static reference, just copy
-            highlighter: None,   // do not clone highlighter
+            theme_name: self.theme_name.clone(),
+            syntax_index: self.syntax_index,
+            missing_extension: self.missing_extension.clone(),
+            cancel: CancelToken::new(),
+            events: EventRegistry::default(), // callbacks are not cloned
+            last_language: None,
+            pending_change: None,
+            change: None,
+            id: None,
+            path: self.path.clone(),
+            save_on_request: self.save_on_request,
+            read_only: self.read_only,
+            watermark: self.watermark.clone(),
+            escape_behavior: self.escape_behavior,
+            auto_pairs: self.auto_pairs.clone(),
+            auto_closed: Vec::new(),
+            keymap: self.keymap.clone(),
+            sent_commands: Vec::new(),
+            shortcut_help: false,
+            font_size: self.font_size,
+            wrap: self.wrap,
+            wrap_mode: self.wrap_mode,
+            wrap_column: self.wrap_column,
+            wrap_indicators: self.wrap_indicators,
+            tab_size: self.tab_size,
+            indent_style: self.indent_style,
+            auto_indent: self.auto_indent,
+            control_chars: self.control_chars,
+            show_whitespace: self.show_whitespace,
+            scroll_past_end: self.scroll_past_end,
+            smooth_scrolling: self.smooth_scrolling,
+            theme_background: self.theme_background,
+            current_line: self.current_line,
+            current_line_color: self.current_line_color,
+            match_brackets: self.match_brackets,
+            typewriter: self.typewriter,
+            zen: self.zen,
+            highlight_line_limit: self.highlight_line_limit,
+            injections: self.injections.clone(),
+            background_lines: self.background_lines,
+            line_numbers: self.line_numbers,
+            line_numbers_style: self.line_numbers_style.clone(),
+            folding: self.folding,
+            fold_method: self.fold_method,
+            folds: self.folds.clone(),
+            folded_code: self.folded_code.clone(),
+            fold_regions: self.fold_regions.clone(),
+            fold_regions_key: self.fold_regions_key,
+            diagnostics_gutter: self.diagnostics_gutter,
+            change_markers: self.change_markers,
+            caret_width: self.caret_width,
+            caret_blink: self.caret_blink,
+            overridden: self.overridden,
+            defaults_generation: self.defaults_generation,
+            interactive: self.interactive,
+            baseline: self.baseline.clone(),
+            hunks: self.hunks.clone(),
+            hunks_hash: self.hunks_hash,
+            hunk_popup: None,
+            position_encoding: self.position_encoding,
+            caret_rect: None,
+            signature_provider: None, // provider is not cloned
+            signature_help: None,
+            cursor_range: None,
+            rename_provider: None, // provider is not cloned
+            image_provider: None, // provider is not cloned
+            image_preview: None,
+            rename: None,
+            linked_provider: None, // provider is not cloned
+            linked_ranges: self.linked_ranges.clone(),
+            peek: None,
+            diagnostics: self.diagnostics.clone(),
+            actions_menu: false,
+            go_to: None,
+            restore_cursor: None,
+            block: None,
+            window: None,
+            text_offset: 0.0,
+            reveal_cursor: false,
+            search: self.search.clone(),
+            search_bar: self.search_bar,
+            search_open: false,
+            search_focus: None,
+            highlight_error_reported: false,
+            galley_cache: GalleyCache::default(),
+            line_cache: LineCache::default(),
+            highlight_worker: HighlightWorker::default(),
+            language: self.language.clone(),
+            languages: self.languages.clone(),
+            lint_indentation: self.lint_indentation,
+            max_line_length: self.max_line_length,
+            line_length_severity: self.line_length_severity,
+            long_lines: self.long_lines,
+            lint: self.lint.clone(),
+            lint_hash: self.lint_hash,
+            duplicates_min_len: self.duplicates_min_len,
+            cell_markers: self.cell_markers,
+            literate_comments: self.literate_comments,
+            toggle_pairs: self.toggle_pairs.clone(),
+            number_scrubbing: self.number_scrubbing,
+            scrub_step: self.scrub_step,
+            scrub: None,
+            scrub_wheel: 0.0,
+            duplicates: self.duplicates.clone(),
+            duplicates_hash: self.duplicates_hash,
+            clipboard_ring: self.clipboard_ring.clone(),
+            clipboard_popup: None,
+            pending_paste: None,
+            insert_final_newline: self.insert_final_newline,
+            history: self.history.clone(),
+            undoer_limited: false,
         }
     }
 }
@@ -41,9 +426,50 @@ impl fmt::Debug for CodeEditor {
         f.debug_struct("CodeEditor")
             .field("code", &self.code)
             .field("syntax_set", &"...")
-            .field("theme", &"...")
-            .field("syntax", &self.syntax.name)
-            .field("highlighter", &self.highlighter.is_some())
+            .field("theme", &self.theme_name)
+            .field("syntax", &self.syntax().name)
+            .field("cancel", &self.cancel.is_cancelled())
+            .field("events", &self.events.len())
+            .field("path", &self.path)
+            .field("save_on_request", &self.save_on_request)
+            .field("insert_final_newline", &self.insert_final_newline)
+            .field("read_only", &self.read_only)
+            .field("watermark", &self.watermark)
+            .field("escape_behavior", &self.escape_behavior)
+            .field("auto_pairs", &self.auto_pairs)
+            .field("keymap", &self.keymap)
+            .field("font_size", &self.font_size)
+            .field("indent_style", &self.indent_style)
+            .field("wrap", &self.wrap)
+            .field("wrap_mode", &self.wrap_mode)
+            .field("wrap_column", &self.wrap_column)
+            .field("wrap_indicators", &self.wrap_indicators)
+            .field("injections", &self.injections)
+            .field("interactive", &self.interactive)
+            .field("cell_markers", &self.cell_markers)
+            .field("literate_comments", &self.literate_comments)
+            .field("toggle_pairs", &self.toggle_pairs)
+            .field("number_scrubbing", &self.number_scrubbing)
+            .field("scrub_step", &self.scrub_step)
+            .field("baseline", &self.baseline.is_some())
+            .field("hunks", &self.hunks)
+            .field("position_encoding", &self.position_encoding)
+            .field("caret_rect", &self.caret_rect)
+            .field("signature_provider", &self.signature_provider.is_some())
+            .field("signature_help", &self.signature_help)
+            .field("cursor_range", &self.cursor_range)
+            .field("rename_provider", &self.rename_provider.is_some())
+            .field("image_provider", &self.image_provider.is_some())
+            .field("rename", &self.rename)
+            .field("linked_provider", &self.linked_provider.is_some())
+            .field("linked_ranges", &self.linked_ranges)
+            .field("peek", &self.peek)
+            .field("diagnostics", &self.diagnostics)
+            .field("language", &self.language)
+            .field("languages", &self.languages)
+            .field("lint_indentation", &self.lint_indentation)
+            .field("max_line_length", &self.max_line_length)
+            .field("clipboard_ring", &self.clipboard_ring)
             .finish()
     }
 }
@@ -52,99 +478,4120 @@ impl PartialEq for CodeEditor {
     fn eq(&self, other: &Self) -> bool {
         self.code == other.code
             && self.theme == other.theme
-            && std::ptr::eq(self.syntax, other.syntax)
+            && Arc::ptr_eq(&self.syntax_set, &other.syntax_set)
+            && self.syntax_index == other.syntax_index
     }
 }
 
 impl CodeEditor {
+    /// Like [`try_new`](Self::try_new), but the unknown extensions are highlighted as Rust.
+    ///
+    /// # Panics
+    ///
+    /// If there is no built-in theme `color_theme`.
     pub fn new(syntax_ext: &str, color_theme: &str) -> Self {
-        let ps = SyntaxSet::load_defaults_newlines();
-        let ts = ThemeSet::load_defaults();
-        let theme = Arc::new(ts.themes[color_theme].clone());
-        let syntax = ps.find_syntax_by_extension(syntax_ext).unwrap_or(ps.find_syntax_by_extension("rs").unwrap());
+        Self::create(syntax_ext, color_theme, true).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates an editor for the files with the extension `syntax_ext`, in the built-in theme `color_theme`.
+    pub fn try_new(syntax_ext: &str, color_theme: &str) -> Result<Self, LitecodeError> {
+        Self::create(syntax_ext, color_theme, false)
+    }
 
-        Self {
+    fn create(syntax_ext: &str, color_theme: &str, fallback_syntax: bool) -> Result<Self, LitecodeError> {
+        let ps = syntaxes::defaults();
+        let theme = theme::built_in(color_theme).ok_or_else(|| LitecodeError::UnknownTheme(color_theme.to_owned()))?;
+        let missing_extension = ps.find_syntax_by_extension(syntax_ext).is_none().then(|| syntax_ext.to_owned());
+        let syntax = match ps.find_syntax_by_extension(syntax_ext) {
+            Some(syntax) => syntax,
+            None if fallback_syntax => ps.find_syntax_by_extension("rs").expect("built-in Rust syntax"),
+            None => return Err(LitecodeError::UnknownSyntax(syntax_ext.to_owned())),
+        };
+
+        let syntax_index = syntaxes::index_of(&ps, syntax);
+
+        let mut editor = Self {
             code: "".into(),
             syntax_set: ps.clone(),
             theme,
-            syntax: Box::leak(Box::new(syntax.clone())), // static lifetime workaround
-            highlighter: None,
+            theme_name: color_theme.to_owned(),
+            syntax_index,
+            missing_extension,
+            cancel: CancelToken::new(),
+            events: EventRegistry::default(),
+            last_language: None,
+            pending_change: None,
+            change: None,
+            id: None,
+            path: None,
+            save_on_request: false,
+            read_only: false,
+            watermark: None,
+            escape_behavior: EscapeBehavior::default(),
+            auto_pairs: AutoPairs::disabled(),
+            auto_closed: Vec::new(),
+            keymap: Keymap::default(),
+            sent_commands: Vec::new(),
+            shortcut_help: false,
+            font_size: 14.0,
+            wrap: true,
+            wrap_mode: WrapMode::default(),
+            wrap_column: None,
+            wrap_indicators: true,
+            tab_size: 4,
+            indent_style: IndentStyle::default(),
+            auto_indent: true,
+            control_chars: true,
+            show_whitespace: Whitespace::None,
+            scroll_past_end: false,
+            smooth_scrolling: true,
+            theme_background: true,
+            current_line: false,
+            current_line_color: None,
+            match_brackets: true,
+            typewriter: false,
+            zen: false,
+            highlight_line_limit: highlight::LINE_LIMIT,
+            injections: Vec::new(),
+            background_lines: None,
+            line_numbers: false,
+            line_numbers_style: LineNumbers::default(),
+            folding: false,
+            fold_method: FoldMethod::default(),
+            folds: Vec::new(),
+            folded_code: String::new(),
+            fold_regions: Vec::new(),
+            fold_regions_key: None,
+            diagnostics_gutter: true,
+            change_markers: true,
+            caret_width: 2.0,
+            caret_blink: true,
+            overridden: settings::Overridden { theme: true, ..Default::default() },
+            defaults_generation: 0,
+            interactive: true,
+            baseline: None,
+            hunks: Vec::new(),
+            hunks_hash: None,
+            hunk_popup: None,
+            position_encoding: PositionEncoding::default(),
+            caret_rect: None,
+            signature_provider: None,
+            signature_help: None,
+            cursor_range: None,
+            rename_provider: None,
+            image_provider: None,
+            image_preview: None,
+            rename: None,
+            linked_provider: None,
+            linked_ranges: Vec::new(),
+            peek: None,
+            diagnostics: Vec::new(),
+            actions_menu: false,
+            go_to: None,
+            restore_cursor: None,
+            block: None,
+            window: None,
+            text_offset: 0.0,
+            reveal_cursor: false,
+            search: SearchState::new(),
+            search_bar: true,
+            search_open: false,
+            search_focus: None,
+            highlight_error_reported: false,
+            galley_cache: GalleyCache::default(),
+            line_cache: LineCache::default(),
+            highlight_worker: HighlightWorker::default(),
+            language: LanguageConfig::for_syntax(&syntax.name),
+            languages: LanguageRegistry::default(),
+            lint_indentation: false,
+            max_line_length: None,
+            line_length_severity: Severity::Info,
+            long_lines: 0,
+            lint: Vec::new(),
+            lint_hash: None,
+            duplicates_min_len: None,
+            cell_markers: false,
+            literate_comments: false,
+            toggle_pairs: toggle::DEFAULT_PAIRS.iter().map(|(a, b)| (a.to_string(), b.to_string())).collect(),
+            number_scrubbing: false,
+            scrub_step: 1.0,
+            scrub: None,
+            scrub_wheel: 0.0,
+            duplicates: Vec::new(),
+            duplicates_hash: None,
+            clipboard_ring: ClipboardRing::default(),
+            clipboard_popup: None,
+            pending_paste: None,
+            insert_final_newline: false,
+            history: History::default(),
+            undoer_limited: false,
+        };
+        editor.inherit_global_defaults();
+        Ok(editor)
+    }
+
+    /// Cancels the full re-highlight, the code is shown without highlighting
+    /// until `rehighlight` is called or the code changes.
+    ///
+    /// `Esc` in the editor cancels only the highlighting running on the worker thread,
+    /// see [`background_highlighting`](Self::background_highlighting): the highlighting on the UI thread
+    /// is done within the frame, before the key is read.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Enables the highlighting again after `cancel`.
+    pub fn rehighlight(&self) {
+        self.cancel.reset();
+    }
+
+    /// Highlights and lays out the whole code again on the next frame.
+    ///
+    /// The cached layout follows the changes of `code`, the wrap width, the font and the zoom,
+    /// use this when the highlighting depends on something else.
+    pub fn invalidate_highlight(&mut self) {
+        self.galley_cache.clear();
+        self.line_cache = LineCache::default();
+        self.highlight_worker = HighlightWorker::default();
+    }
+
+    /// Token to cancel the long operations from other places (for example from another thread).
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// In read only mode the code can be selected and copied, but not edited.
+    ///
+    /// The methods editing the code (like [`insert_text`](Self::insert_text),
+    /// [`apply_text_edits`](Self::apply_text_edits) or [`undo`](Self::undo)) refuse too,
+    /// only [`set_code`](Self::set_code) and the `code` field replace it.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.overridden.read_only = true;
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.overridden.read_only = true;
+        self.read_only = read_only;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// What pressing `Esc` does, [`EscapeBehavior::Staged`] by default.
+    pub fn escape_behavior(mut self, behavior: EscapeBehavior) -> Self {
+        self.overridden.escape_behavior = true;
+        self.escape_behavior = behavior;
+        self
+    }
+
+    /// What `Tab` inserts, and the level of the automatic indentation, [`IndentStyle::Detect`] by default.
+    ///
+    /// With spaces, `Tab` inserts the spaces up to the next multiple of their count, replacing the selection.
+    /// The tab chars are shown up to the stops of [`tab_size`](Self::tab_size) in any style.
+    pub fn indent_style(mut self, style: IndentStyle) -> Self {
+        self.overridden.indent_style = true;
+        self.indent_style = style;
+        self
+    }
+
+    pub fn set_indent_style(&mut self, style: IndentStyle) {
+        self.overridden.indent_style = true;
+        self.indent_style = style;
+    }
+
+    /// Starts the line of `Enter` at the indentation of the previous one, one level more after an
+    /// [`indent_after`](LanguageConfig::indent_after) ending of the language, on by default.
+    ///
+    /// A level follows the [`indent_style`](Self::indent_style).
+    pub fn auto_indent(mut self, auto_indent: bool) -> Self {
+        self.overridden.auto_indent = true;
+        self.auto_indent = auto_indent;
+        self
+    }
+
+    pub fn set_auto_indent(&mut self, auto_indent: bool) {
+        self.overridden.auto_indent = true;
+        self.auto_indent = auto_indent;
+    }
+
+    /// Brackets and quotes typed in pairs, [`AutoPairs::disabled`] by default.
+    pub fn auto_pairs(mut self, auto_pairs: AutoPairs) -> Self {
+        self.auto_pairs = auto_pairs;
+        self
+    }
+
+    pub fn set_auto_pairs(&mut self, auto_pairs: AutoPairs) {
+        self.auto_pairs = auto_pairs;
+        self.auto_closed.clear();
+    }
+
+    /// Width of the tab stops in columns, a tab moves the text after it to the next stop, 4 by default.
+    pub fn tab_size(mut self, tab_size: usize) -> Self {
+        self.overridden.tab_size = true;
+        self.tab_size = tab_size;
+        self
+    }
+
+    pub fn set_tab_size(&mut self, tab_size: usize) {
+        self.overridden.tab_size = true;
+        self.tab_size = tab_size;
+    }
+
+    /// Shows the control chars (like `\x00` or `\x1b`) as boxes with their mnemonic (`NUL`, `ESC`), on by default.
+    pub fn control_chars(mut self, control_chars: bool) -> Self {
+        self.overridden.control_chars = true;
+        self.control_chars = control_chars;
+        self
+    }
+
+    pub fn set_control_chars(&mut self, control_chars: bool) {
+        self.overridden.control_chars = true;
+        self.control_chars = control_chars;
+    }
+
+    /// Wraps the lines longer than the editor width, on by default.
+    ///
+    /// Turn it off for the minified files and the logs, only the visible columns of the lines of megabytes are laid out.
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.overridden.wrap = true;
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.overridden.wrap = true;
+        self.wrap = wrap;
+    }
+
+    /// Where the long lines break, [`WrapMode::Word`] by default.
+    ///
+    /// The words suit the code, breaking anywhere fills the rows of long strings, data or comments.
+    pub fn wrap_mode(mut self, mode: WrapMode) -> Self {
+        self.overridden.wrap_mode = true;
+        self.wrap_mode = mode;
+        self
+    }
+
+    pub fn set_wrap_mode(&mut self, mode: WrapMode) {
+        self.overridden.wrap_mode = true;
+        self.wrap_mode = mode;
+    }
+
+    /// Wraps the lines at `column` chars whatever the editor width, like a print margin, `None` (the width) by default.
+    ///
+    /// The lines are not wrapped at all when the [`wrap`](Self::wrap) is off.
+    pub fn wrap_column(mut self, column: Option<usize>) -> Self {
+        self.overridden.wrap_column = true;
+        self.wrap_column = column;
+        self
+    }
+
+    pub fn set_wrap_column(&mut self, column: Option<usize>) {
+        self.overridden.wrap_column = true;
+        self.wrap_column = column;
+    }
+
+    /// Starts the continuation rows of the wrapped lines with a `↪` and indents them like their line
+    /// (a hanging indent), so the wrapped code keeps its shape, on by default.
+    pub fn wrap_indicators(mut self, wrap_indicators: bool) -> Self {
+        self.overridden.wrap_indicators = true;
+        self.wrap_indicators = wrap_indicators;
+        self
+    }
+
+    pub fn set_wrap_indicators(&mut self, wrap_indicators: bool) {
+        self.overridden.wrap_indicators = true;
+        self.wrap_indicators = wrap_indicators;
+    }
+
+    /// Marks the spaces with a `·` and the tabs with a `→`, all of them or only the trailing ones, none by default.
+    pub fn show_whitespace(mut self, whitespace: Whitespace) -> Self {
+        self.overridden.show_whitespace = true;
+        self.show_whitespace = whitespace;
+        self
+    }
+
+    pub fn set_show_whitespace(&mut self, whitespace: Whitespace) {
+        self.overridden.show_whitespace = true;
+        self.show_whitespace = whitespace;
+    }
+
+    /// Adds the height of the editor minus a line below the code, so the last line can be scrolled to the top,
+    /// off by default.
+    pub fn scroll_past_end(mut self, scroll_past_end: bool) -> Self {
+        self.overridden.scroll_past_end = true;
+        self.scroll_past_end = scroll_past_end;
+        self
+    }
+
+    pub fn set_scroll_past_end(&mut self, scroll_past_end: bool) {
+        self.overridden.scroll_past_end = true;
+        self.scroll_past_end = scroll_past_end;
+    }
+
+    /// Animates the scrolling to the caret and to the jump targets, on by default.
+    /// The animations disabled with [`set_animations`](crate::set_animations) or the reduced motion turn it off too.
+    pub fn smooth_scrolling(mut self, smooth_scrolling: bool) -> Self {
+        self.overridden.smooth_scrolling = true;
+        self.smooth_scrolling = smooth_scrolling;
+        self
+    }
+
+    pub fn set_smooth_scrolling(&mut self, smooth_scrolling: bool) {
+        self.overridden.smooth_scrolling = true;
+        self.smooth_scrolling = smooth_scrolling;
+    }
+
+    fn scroll_animation(&self, ui: &Ui) -> egui::style::ScrollAnimation {
+        if self.smooth_scrolling { settings::scroll_animation(ui) } else { egui::style::ScrollAnimation::none() }
+    }
+
+    /// Paints the background of the theme behind the code and the gutter, on by default.
+    ///
+    /// Without it the code is on the background of the egui text edits, like the rest of the app.
+    pub fn theme_background(mut self, theme_background: bool) -> Self {
+        self.overridden.theme_background = true;
+        self.theme_background = theme_background;
+        self
+    }
+
+    pub fn set_theme_background(&mut self, theme_background: bool) {
+        self.overridden.theme_background = true;
+        self.theme_background = theme_background;
+    }
+
+    /// Tints the background of the line of the caret, in the `line_highlight` color of the theme
+    /// or the one of [`current_line_color`](Self::current_line_color).
+    pub fn current_line(mut self, current_line: bool) -> Self {
+        self.overridden.current_line = true;
+        self.current_line = current_line;
+        self
+    }
+
+    pub fn set_current_line(&mut self, current_line: bool) {
+        self.overridden.current_line = true;
+        self.current_line = current_line;
+    }
+
+    /// Color of the current line, instead of the one of the theme (a faint foreground without it).
+    pub fn current_line_color(mut self, color: egui::Color32) -> Self {
+        self.current_line_color = Some(color);
+        self
+    }
+
+    /// Outlines the bracket next to the caret and the one matching it, on by default.
+    ///
+    /// The brackets are the ones of the [`language_config`](Self::language_config), those in the strings and
+    /// the comments of the syntax are skipped.
+    pub fn match_brackets(mut self, match_brackets: bool) -> Self {
+        self.overridden.match_brackets = true;
+        self.match_brackets = match_brackets;
+        self
+    }
+
+    pub fn set_match_brackets(&mut self, match_brackets: bool) {
+        self.overridden.match_brackets = true;
+        self.match_brackets = match_brackets;
+    }
+
+    /// Keeps the caret vertically centered in the scroll area of the editor while typing and moving it,
+    /// like a typewriter. Half a screen of space is added below the code, so the last lines get centered too.
+    pub fn typewriter(mut self, typewriter: bool) -> Self {
+        self.overridden.typewriter = true;
+        self.typewriter = typewriter;
+        self
+    }
+
+    pub fn set_typewriter(&mut self, typewriter: bool) {
+        self.overridden.typewriter = true;
+        self.typewriter = typewriter;
+    }
+
+    /// Distraction-free mode: hides the gutter (line numbers, diagnostics strip, change and fold markers)
+    /// and the decorations (current line, matching brackets, duplicates), and shows the code in a centered column
+    /// with wide margins. The options are kept, so leaving the mode shows them again.
+    ///
+    /// [`Command::ToggleZenMode`] switches the mode from the keyboard.
+    pub fn zen(mut self, zen: bool) -> Self {
+        self.overridden.zen = true;
+        self.zen = zen;
+        self
+    }
+
+    pub fn set_zen(&mut self, zen: bool) {
+        self.overridden.zen = true;
+        self.zen = zen;
+    }
+
+    pub fn is_zen(&self) -> bool {
+        self.zen
+    }
+
+    /// Highlights only the first `chars` chars of every line, 10 000 by default.
+    ///
+    /// The rest of the longer lines (minified code, base64 blobs) is plain with a faint underline,
+    /// so a single huge line does not slow down every frame.
+    pub fn highlight_line_limit(mut self, chars: usize) -> Self {
+        self.highlight_line_limit = chars;
+        self
+    }
+
+    pub fn set_highlight_line_limit(&mut self, chars: usize) {
+        self.highlight_line_limit = chars;
+    }
+
+    /// Highlights the languages embedded in the regions of the code with their syntaxes, like SQL in the strings,
+    /// see [`Injection`].
+    pub fn injections(mut self, injections: Vec<Injection>) -> Self {
+        self.injections = injections;
+        self
+    }
+
+    pub fn set_injections(&mut self, injections: Vec<Injection>) {
+        self.injections = injections;
+    }
+
+    /// Highlights the code of `min_lines` lines or more on a worker thread.
+    ///
+    /// The edits are highlighted again in the background: until the worker is done (the short edits are done in time)
+    /// the last highlighting is shown shifted by the edits, so pasting a huge log does not freeze the UI.
+    /// Not available on the web (wasm32), where the code is always highlighted on the UI thread.
+    pub fn background_highlighting(mut self, min_lines: usize) -> Self {
+        self.background_lines = Some(min_lines);
+        self
+    }
+
+    /// Like [`background_highlighting`](Self::background_highlighting), `None` highlights on the UI thread.
+    pub fn set_background_highlighting(&mut self, min_lines: Option<usize>) {
+        self.background_lines = min_lines;
+    }
+
+    /// Shows the line numbers to the left of the code.
+    pub fn line_numbers(mut self, line_numbers: bool) -> Self {
+        self.overridden.line_numbers = true;
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    pub fn set_line_numbers(&mut self, line_numbers: bool) {
+        self.overridden.line_numbers = true;
+        self.line_numbers = line_numbers;
+    }
+
+    /// Width, alignment and colors of the line numbers.
+    pub fn line_numbers_style(mut self, style: LineNumbers) -> Self {
+        self.line_numbers_style = style;
+        self
+    }
+
+    /// Shows the fold markers of the foldable regions in the gutter, a click on a marker folds its region.
+    pub fn folding(mut self, folding: bool) -> Self {
+        self.overridden.folding = true;
+        self.folding = folding;
+        self
+    }
+
+    pub fn set_folding(&mut self, folding: bool) {
+        self.overridden.folding = true;
+        self.folding = folding;
+    }
+
+    /// How the foldable regions are found, [`FoldMethod::Indentation`] by default.
+    pub fn fold_method(mut self, method: FoldMethod) -> Self {
+        self.overridden.fold_method = true;
+        self.fold_method = method;
+        self
+    }
+
+    pub fn set_fold_method(&mut self, method: FoldMethod) {
+        self.overridden.fold_method = true;
+        self.fold_method = method;
+    }
+
+    /// Folds the `lines` (0-based, end excluded), the first one stays visible with a placeholder for the others.
+    ///
+    /// The folds follow the edits around them, moving the caret into a fold unfolds it.
+    pub fn fold_range(&mut self, lines: Range<usize>) {
+        self.sync_folds();
+        if lines.end > lines.start + 1 && !self.folds.contains(&lines) {
+            self.folds.push(lines);
+            self.folds.sort_by_key(|fold| fold.start);
+            self.folded_code.clone_from(&self.code);
         }
     }
 
-    pub fn ui(&mut self, ui: &mut Ui) -> egui::Response {
-        let font = FontId::monospace(14.0);
-        let syntax_set = self.syntax_set.clone();
-        let theme = self.theme.clone();
-        let syntax = self.syntax;
+    /// Unfolds the folds intersecting the `lines`.
+    pub fn unfold_range(&mut self, lines: Range<usize>) {
+        self.sync_folds();
+        self.folds.retain(|fold| fold.end <= lines.start || fold.start >= lines.end);
+    }
 
-        let mut layouter = {
-            let font = font.clone();
-            Box::new(move |ui: &Ui, text_buffer: &dyn egui::TextBuffer, wrap_width: f32| {
-                let mut job = LayoutJob::default();
-                let mut highlighter = HighlightLines::new(syntax, &theme);
-                let text = text_buffer.as_str();
+    pub fn unfold_all(&mut self) {
+        self.folds.clear();
+        self.folded_code.clear();
+    }
 
-                for (i, line) in text.lines().enumerate() {
-                    if let Ok(ranges) = highlighter.highlight_line(line, &syntax_set) {
-                        for (style, text) in ranges {
-                            let color = Color32::from_rgb(
-                                style.foreground.r,
-                                style.foreground.g,
-                                style.foreground.b,
-                            );
-                            job.append(
-                                text,
-                                0.0,
-                                TextFormat {
-                                    font_id: font.clone(),
-                                    color,
-                                    ..Default::default()
-                                },
-                            );
-                        }
-                    }
+    /// Folded line ranges, sorted by their first line.
+    pub fn folded_ranges(&self) -> &[Range<usize>] {
+        &self.folds
+    }
 
-                    if i + 1 < text.lines().count() {
-                        job.append(
-                            "\n",
-                            0.0,
-                            TextFormat {
-                                font_id: font.clone(),
-                                color: Color32::WHITE,
-                                ..Default::default()
-                            },
-                        );
-                    }
-                }
+    /// Moves the folds to the lines of the current code.
+    fn sync_folds(&mut self) {
+        if self.folds.is_empty() {
+            self.folded_code.clear();
+        } else if self.folded_code != self.code {
+            self.folds = folding::shift_folds(&self.folds, &self.folded_code, &self.code);
+            self.folded_code.clone_from(&self.code);
+        }
+    }
+
+    fn update_fold_regions(&mut self) {
+        let mut hasher = DefaultHasher::new();
+        self.code.hash(&mut hasher);
+        // the blocks of the worker arrive later than the code
+        self.highlight_worker.revision().hash(&mut hasher);
+        let key = (hasher.finish(), self.fold_method);
+        if self.fold_regions_key != Some(key) {
+            self.fold_regions = match (self.fold_method, self.highlight_worker.blocks()) {
+                (FoldMethod::Indentation, _) => folding::indent_regions(&self.code),
+                (FoldMethod::Scopes, Some(blocks)) => folding::scope_regions(blocks),
+                (FoldMethod::Scopes, None) => folding::scope_regions(self.line_cache.blocks()),
+            };
+            self.fold_regions_key = Some(key);
+        }
+    }
+
+    /// Paints the fold markers and the placeholders of the folds, a click on them folds or unfolds.
+    ///
+    /// The markers of the unfolded regions are shown while the pointer is over the gutter.
+    fn folding_ui(&mut self, ui: &Ui, gutter_rect: egui::Rect, id: egui::Id, galley: &Galley, galley_pos: egui::Pos2) {
+        self.update_fold_regions();
+        let spans = gutter::line_spans(galley);
+        let response = ui.interact(gutter_rect, id.with("fold_markers"), egui::Sense::click());
+        let hovered_line = response.hover_pos().and_then(|pos| gutter::line_at(&spans, galley_pos, pos.y));
+        let row_height = galley.rows.first().map_or(0.0, |row| row.height());
+
+        let mut toggled = None;
+        for region in &self.fold_regions {
+            let Some(span) = spans.get(region.start).filter(|span| !gutter::is_hidden(span)) else {
+                continue;
+            };
+            let folded = self.folds.iter().any(|fold| fold.start == region.start);
+            let hovered = hovered_line == Some(region.start);
+            if folded || response.hovered() {
+                let top = galley_pos.y + span.min;
+                let rect = egui::Rect::from_x_y_ranges(gutter_rect.x_range(), top..=top + row_height);
+                folding::paint_marker(ui, rect, folded, hovered);
+            }
+            if hovered && response.clicked() {
+                toggled = Some((region.clone(), folded));
+            }
+        }
 
-                job.wrap.max_width = wrap_width;
-                ui.fonts(|f| f.layout_job(job))
-            }) as Box<dyn FnMut(&Ui, &dyn egui::TextBuffer, f32) -> Arc<Galley>>
+        let index = LineIndex::new(&self.code);
+        let mut unfolded = None;
+        for fold in &self.folds {
+            let end = index.to_encoded(index.line_range(fold.start).end, PositionEncoding::Char);
+            let pos = galley.pos_from_cursor(egui::text::CCursor::new(end)).translate(galley_pos.to_vec2());
+            let rect = folding::placeholder_rect(pos.right_top(), pos.height());
+            let placeholder = ui.interact(rect, id.with(("fold", fold.start)), egui::Sense::click());
+            let visuals = ui.style().interact(&placeholder);
+            ui.painter().rect_filled(rect, 3.0, visuals.weak_bg_fill);
+            ui.painter().text(rect.center(), egui::Align2::CENTER_CENTER, "…", FontId::proportional(rect.height() * 0.8), visuals.text_color());
+            if placeholder.on_hover_cursor(egui::CursorIcon::PointingHand).clicked() {
+                unfolded = Some(fold.start);
+            }
+        }
+
+        if toggled.is_some() || unfolded.is_some() {
+            ui.ctx().request_repaint();
+        }
+        match toggled {
+            Some((region, true)) => self.folds.retain(|fold| fold.start != region.start),
+            Some((region, false)) => self.fold_range(region),
+            None => {}
+        }
+        if let Some(start) = unfolded {
+            self.folds.retain(|fold| fold.start != start);
+        }
+    }
+
+    /// Shortcuts of the editor commands, [`Keymap::default`] by default, `F1` lists them in the editor.
+    pub fn with_keymap(mut self, keymap: Keymap) -> Self {
+        self.overridden.keymap = true;
+        self.keymap = keymap;
+        self
+    }
+
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.overridden.keymap = true;
+        self.keymap = keymap;
+    }
+
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    /// Runs `command` on the next frame like its shortcut, also when the editor is not focused.
+    ///
+    /// Drives the editor from a menu, an integration test or a macro replay without simulating the keys.
+    pub fn send_command(&mut self, command: Command) {
+        self.sent_commands.push(command);
+    }
+
+    /// Id of the text edit of the editor, `None` before the first frame.
+    ///
+    /// Focus the editor from a test with `ctx.memory_mut(|m| m.request_focus(id))`.
+    pub fn id(&self) -> Option<egui::Id> {
+        self.id
+    }
+
+    /// Opens or closes the list of the keyboard shortcuts, like [`Command::ShortcutHelp`] (`F1`).
+    pub fn toggle_shortcut_help(&mut self) {
+        self.shortcut_help = !self.shortcut_help;
+    }
+
+    /// Switches to the built-in syntect theme `name`, returns `false` if there is no such theme.
+    pub fn set_theme(&mut self, name: &str) -> bool {
+        self.overridden.theme = true;
+        self.load_theme(name)
+    }
+
+    fn load_theme(&mut self, name: &str) -> bool {
+        if name == self.theme_name {
+            return true;
+        }
+        let Some(theme) = theme::built_in(name) else {
+            return false;
         };
+        self.theme = theme;
+        self.theme_name = name.to_owned();
+        self.invalidate_highlight();
+        true
+    }
 
-        ui.add(
-            TextEdit::multiline(&mut self.code)
-                .font(font)
-                .desired_width(f32::INFINITY)
-                .code_editor()
-                .layouter(&mut layouter),
-        )
+    /// Highlights the code with a color theme of the application, like one read by [`theme_from_bytes`](crate::theme_from_bytes).
+    ///
+    /// The [`theme_name`](Self::theme_name) is the name in the theme, or `"Custom"` without one.
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.set_custom_theme(theme);
+        self
     }
-}
 
-impl Default for CodeEditor {
-    fn default() -> Self {
-        let ps = SyntaxSet::load_defaults_newlines();
-        let ts = ThemeSet::load_defaults();
-        let theme = Arc::new(ts.themes["base16-ocean.dark"].clone());
-        let syntax= ps.find_syntax_by_extension("rs").unwrap(); // force unwrap safe here
-        Self {
-            code: "".into(),
-            syntax_set: ps.clone(),
-            theme,
-            syntax: Box::leak(Box::new(syntax.clone())), // static lifetime workaround
-            highlighter: None,
+    pub fn set_custom_theme(&mut self, theme: Theme) {
+        self.overridden.theme = true;
+        self.theme_name = theme.name.clone().unwrap_or_else(|| "Custom".to_owned());
+        self.theme = Arc::new(theme);
+        self.invalidate_highlight();
+    }
+
+    pub fn theme_name(&self) -> &str {
+        &self.theme_name
+    }
+
+    /// Name of the syntax highlighting the code, like `"Rust"`.
+    pub fn syntax_name(&self) -> &str {
+        &self.syntax().name
+    }
+
+    fn syntax(&self) -> &SyntaxReference {
+        &self.syntax_set.syntaxes()[self.syntax_index]
+    }
+
+    pub(crate) fn syntaxes(&self) -> &[SyntaxReference] {
+        self.syntax_set.syntaxes()
+    }
+
+    /// Highlights the code with the syntax named `name` (like `"TOML"`), for example when another file is opened.
+    ///
+    /// The code, the selection, the scroll and the undo history are kept,
+    /// the [`LanguageConfig`] is replaced by the one of the syntax in the [`language_registry`](Self::with_language_registry).
+    pub fn set_syntax_by_name(&mut self, name: &str) -> Result<(), LitecodeError> {
+        let syntax = self.syntax_set.find_syntax_by_name(name).ok_or_else(|| LitecodeError::UnknownSyntax(name.to_owned()))?;
+        self.missing_extension = None;
+        let index = syntaxes::index_of(&self.syntax_set, syntax);
+        self.replace_syntax(self.syntax_set.clone(), index);
+        Ok(())
+    }
+
+    /// Highlights the code with the syntax of the files with the extension `ext` (like `"toml"`), for example when
+    /// another file is opened in the same editor, see [`set_syntax_by_name`](Self::set_syntax_by_name).
+    pub fn set_syntax_by_extension(&mut self, ext: &str) -> Result<(), LitecodeError> {
+        let syntax = self.syntax_set.find_syntax_by_extension(ext).ok_or_else(|| LitecodeError::UnknownSyntax(ext.to_owned()))?;
+        self.missing_extension = None;
+        let index = syntaxes::index_of(&self.syntax_set, syntax);
+        self.replace_syntax(self.syntax_set.clone(), index);
+        Ok(())
+    }
+
+    /// Highlights the code with the syntaxes of `syntax_set` instead of the syntect defaults, for example with GLSL or Zig.
+    ///
+    /// The set must be built for the lines with their newline, like `SyntaxSet::load_defaults_newlines()`,
+    /// an `Arc<SyntaxSet>` can be shared by many widgets. The syntax is looked up again in the new set: the extension given to `new` if it had no syntax,
+    /// then the name of the current syntax, then the plain text.
+    pub fn with_syntax_set(mut self, syntax_set: impl Into<Arc<SyntaxSet>>) -> Self {
+        self.set_syntax_set(syntax_set);
+        self
+    }
+
+    pub fn set_syntax_set(&mut self, syntax_set: impl Into<Arc<SyntaxSet>>) {
+        let syntax_set = syntaxes::with_plain_text(syntax_set.into());
+        let index = syntaxes::find(&syntax_set, self.missing_extension.as_deref(), &self.syntax().name);
+        if self.missing_extension.as_deref().is_some_and(|ext| syntax_set.find_syntax_by_extension(ext).is_some()) {
+            self.missing_extension = None;
+        }
+        self.replace_syntax(syntax_set, index);
+    }
+
+    /// Adds the `.sublime-syntax` definitions of `folder` and its subfolders to the syntaxes of the editor,
+    /// see [`set_syntax_set`](Self::set_syntax_set).
+    pub fn add_syntax_from_folder(&mut self, folder: impl AsRef<Path>) -> Result<(), LitecodeError> {
+        let syntax_set = syntaxes::add_folder(&self.syntax_set, folder.as_ref())?;
+        self.set_syntax_set(syntax_set);
+        Ok(())
+    }
+
+    /// Highlights the code with the syntax `index` of `syntax_set`, the [`LanguageConfig`] is replaced by the one of
+    /// the registry when the language changes.
+    fn replace_syntax(&mut self, syntax_set: Arc<SyntaxSet>, index: usize) {
+        let name = &syntax_set.syntaxes()[index].name;
+        if *name != self.syntax().name {
+            self.language = self.languages.get(name);
+        }
+        self.syntax_set = syntax_set;
+        self.syntax_index = index;
+        self.lint_hash = None;
+        self.fold_regions_key = None;
+        self.invalidate_highlight();
+    }
+
+    /// Changes all the options at once, see [`EditorSettings`].
+    pub fn with_settings(mut self, settings: &EditorSettings) -> Self {
+        self.apply_settings(settings);
+        self
+    }
+
+    /// Changes all the options at once, for example after editing them in a settings UI.
+    ///
+    /// The options changed by `settings` are kept when the [global defaults](crate::set_global_defaults) change.
+    pub fn apply_settings(&mut self, settings: &EditorSettings) {
+        self.overridden.mark_changes(&self.settings(), settings);
+        self.set_options(settings);
+    }
+
+    fn set_options(&mut self, settings: &EditorSettings) {
+        // an unknown theme keeps the current one
+        self.load_theme(&settings.theme);
+        self.font_size = settings.font_size;
+        self.wrap = settings.wrap;
+        self.wrap_mode = settings.wrap_mode;
+        self.wrap_column = settings.wrap_column;
+        self.wrap_indicators = settings.wrap_indicators;
+        self.tab_size = settings.tab_size;
+        self.indent_style = settings.indent_style;
+        self.auto_indent = settings.auto_indent;
+        self.control_chars = settings.control_chars;
+        self.show_whitespace = settings.show_whitespace;
+        self.scroll_past_end = settings.scroll_past_end;
+        self.smooth_scrolling = settings.smooth_scrolling;
+        self.theme_background = settings.theme_background;
+        self.current_line = settings.current_line;
+        self.match_brackets = settings.match_brackets;
+        self.typewriter = settings.typewriter;
+        self.zen = settings.zen;
+        self.line_numbers = settings.line_numbers;
+        self.folding = settings.folding;
+        self.fold_method = settings.fold_method;
+        self.read_only = settings.read_only;
+        self.diagnostics_gutter = settings.diagnostics_gutter;
+        self.change_markers = settings.change_markers;
+        self.caret_width = settings.caret_width;
+        self.caret_blink = settings.caret_blink;
+        self.lint_indentation = settings.lint_indentation;
+        self.max_line_length = settings.max_line_length;
+        self.insert_final_newline = settings.insert_final_newline;
+        self.save_on_request = settings.save_on_request;
+        self.history.set_max_entries(settings.max_undo_entries);
+        self.history.set_max_bytes(settings.max_undo_bytes);
+        self.escape_behavior = settings.escape_behavior;
+        self.keymap = settings.keymap.clone();
+    }
+
+    /// Takes the global defaults changed since the last call, keeping the options changed on this editor.
+    fn inherit_global_defaults(&mut self) {
+        let Some((generation, defaults)) = settings::global_defaults_since(self.defaults_generation) else {
+            return;
+        };
+        let settings = settings::inherit(&self.settings(), &self.overridden, &defaults);
+        self.set_options(&settings);
+        self.defaults_generation = generation;
+    }
+
+    /// Current options, to edit and give back to [`apply_settings`](Self::apply_settings).
+    pub fn settings(&self) -> EditorSettings {
+        EditorSettings {
+            theme: self.theme_name.clone(),
+            font_size: self.font_size,
+            wrap: self.wrap,
+            wrap_mode: self.wrap_mode,
+            wrap_column: self.wrap_column,
+            wrap_indicators: self.wrap_indicators,
+            tab_size: self.tab_size,
+            indent_style: self.indent_style,
+            auto_indent: self.auto_indent,
+            control_chars: self.control_chars,
+            show_whitespace: self.show_whitespace,
+            scroll_past_end: self.scroll_past_end,
+            smooth_scrolling: self.smooth_scrolling,
+            theme_background: self.theme_background,
+            current_line: self.current_line,
+            match_brackets: self.match_brackets,
+            typewriter: self.typewriter,
+            zen: self.zen,
+            line_numbers: self.line_numbers,
+            folding: self.folding,
+            fold_method: self.fold_method,
+            read_only: self.read_only,
+            diagnostics_gutter: self.diagnostics_gutter,
+            change_markers: self.change_markers,
+            caret_width: self.caret_width,
+            caret_blink: self.caret_blink,
+            lint_indentation: self.lint_indentation,
+            max_line_length: self.max_line_length,
+            insert_final_newline: self.insert_final_newline,
+            save_on_request: self.save_on_request,
+            max_undo_entries: self.history.max_entries(),
+            max_undo_bytes: self.history.max_bytes(),
+            escape_behavior: self.escape_behavior,
+            keymap: self.keymap.clone(),
+        }
+    }
+
+    /// Stamps `text` dimmed across the editor while it is read only, like "GENERATED — DO NOT EDIT".
+    pub fn watermark(mut self, text: impl Into<String>) -> Self {
+        self.watermark = Some(text.into());
+        self
+    }
+
+    pub fn set_watermark(&mut self, text: Option<String>) {
+        self.watermark = text;
+    }
+
+    /// Non interactive editor can not be focused, selected or edited.
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    /// Turns on the change tracking, the lines differing from `baseline` are marked in the gutter.
+    pub fn with_baseline(mut self, baseline: impl Into<String>) -> Self {
+        self.set_baseline(baseline);
+        self
+    }
+
+    /// Like [`with_baseline`](Self::with_baseline), clicking a change marker allows to revert the change.
+    pub fn set_baseline(&mut self, baseline: impl Into<String>) {
+        self.baseline = Some(baseline.into());
+        self.hunks_hash = None;
+    }
+
+    /// Turns off the change tracking.
+    pub fn clear_baseline(&mut self) {
+        self.baseline = None;
+        self.hunks.clear();
+        self.hunks_hash = None;
+        self.hunk_popup = None;
+    }
+
+    pub fn baseline(&self) -> Option<&str> {
+        self.baseline.as_deref()
+    }
+
+    /// Changes against the baseline, as of the last frame.
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+
+    /// Restores the baseline content of the hunk with `index`, as its own step of the undo history.
+    /// Returns `false` if there is no such hunk, or in read only mode.
+    pub fn revert_hunk(&mut self, index: usize) -> bool {
+        let (Some(baseline), Some(hunk)) = (&self.baseline, self.hunks.get(index)) else {
+            return false;
+        };
+        if self.read_only {
+            return false;
+        }
+        let code = diff::revert_hunk(&self.code, baseline, hunk);
+        self.history.commit(&self.code, self.cursor_range);
+        self.replace_code(code);
+        self.history.commit(&self.code, self.cursor_range);
+        self.update_hunks();
+        true
+    }
+
+    fn update_hunks(&mut self) {
+        let Some(baseline) = &self.baseline else {
+            return;
+        };
+
+        let mut hasher = DefaultHasher::new();
+        self.code.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.hunks_hash != Some(hash) {
+            self.hunks = diff::diff(baseline, &self.code);
+            self.hunks_hash = Some(hash);
+            self.hunk_popup = None;
+        }
+    }
+
+    /// Applies the unified diff `patch` to the code as a single change.
+    ///
+    /// The hunks whose context is not found are listed in the report and skipped.
+    /// The change is its own step of the undo history. Fails with [`PatchError::ReadOnly`] in read only mode.
+    pub fn apply_unified_diff(&mut self, patch: &str) -> Result<PatchReport, PatchError> {
+        if self.read_only {
+            return Err(PatchError::ReadOnly);
         }
+        let (code, report) = patch::apply_unified_diff(&self.code, patch)?;
+        self.history.commit(&self.code, self.cursor_range);
+        self.replace_code(code);
+        self.history.commit(&self.code, self.cursor_range);
+        Ok(report)
+    }
+
+    /// Unit of the columns of the [`Position`]s passed to the editor, UTF-16 by default (like in LSP).
+    pub fn position_encoding(mut self, encoding: PositionEncoding) -> Self {
+        self.position_encoding = encoding;
+        self
+    }
+
+    pub fn set_position_encoding(&mut self, encoding: PositionEncoding) {
+        self.position_encoding = encoding;
     }
-}
\ No newline at end of file
+
+    /// Line index of the code, to convert between byte offsets, UTF-16 offsets and positions.
+    pub fn line_index(&self) -> LineIndex<'_> {
+        LineIndex::new(&self.code)
+    }
+
+    /// Applies the edits (for example LSP `TextEdit[]` of a workspace edit) as a single change.
+    ///
+    /// All ranges refer to the code before the edits, the columns use the `position_encoding`.
+    /// The change is its own step of the undo history. Fails with [`TextEditError::ReadOnly`] in read only mode.
+    pub fn apply_text_edits(&mut self, edits: &[(Range<Position>, String)]) -> Result<(), TextEditError> {
+        if self.read_only {
+            return Err(TextEditError::ReadOnly);
+        }
+        let code = edits::apply_text_edits(&self.code, edits, self.position_encoding)?;
+        self.history.commit(&self.code, self.cursor_range);
+        self.replace_code(code);
+        self.history.commit(&self.code, self.cursor_range);
+        Ok(())
+    }
+
+    /// Screen rectangle of the caret in the last frame, `None` when the editor was never focused.
+    pub fn caret_screen_rect(&self) -> Option<egui::Rect> {
+        self.caret_rect
+    }
+
+    /// Shows a popup anchored at the caret, flipped above it near the bottom of the screen.
+    ///
+    /// Returns `None` when the editor was never focused.
+    pub fn caret_popup<R>(
+        &self,
+        ctx: &egui::Context,
+        id: egui::Id,
+        add_contents: impl FnOnce(&mut Ui) -> R,
+    ) -> Option<egui::InnerResponse<R>> {
+        let caret_rect = self.caret_rect?;
+        Some(popup::caret_popup(ctx, id, caret_rect, add_contents))
+    }
+
+    /// Sets the provider of the signature help, shown above the caret after typing `(` or `,`.
+    pub fn signature_help_provider(mut self, provider: impl SignatureHelpProvider + 'static) -> Self {
+        self.signature_provider = Some(Box::new(provider));
+        self
+    }
+
+    /// Currently shown signature help.
+    pub fn signature_help(&self) -> Option<&SignatureHelp> {
+        self.signature_help.as_ref()
+    }
+
+    fn signature_help_ui(&mut self, ui: &Ui, response: &egui::Response, cursor: Option<usize>) {
+        let Some(provider) = &mut self.signature_provider else {
+            return;
+        };
+
+        let typed: String = ui.input(|i| {
+            i.events
+                .iter()
+                .filter_map(|event| match event {
+                    egui::Event::Text(text) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect()
+        });
+        if response.lost_focus() || typed.contains(')') || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.signature_help = None;
+        } else if response.changed()
+            && (typed.contains('(') || typed.contains(','))
+            && let Some(char_index) = cursor
+        {
+            let offset = LineIndex::new(&self.code).from_encoded(char_index, PositionEncoding::Char);
+            self.signature_help = provider.signature_help(&self.code, offset);
+        }
+
+        let (Some(help), Some(caret_rect)) = (&self.signature_help, self.caret_rect) else {
+            return;
+        };
+        let font = fonts::code_font(ui, 13.0);
+        let job = help.layout_job(&font, ui.visuals().text_color(), ui.visuals().strong_text_color());
+        popup::caret_popup_above(ui.ctx(), response.id.with("signature_help"), caret_rect, |ui| {
+            ui.label(job);
+            if let Some(documentation) = &help.documentation {
+                ui.separator();
+                ui.label(documentation);
+            }
+        });
+    }
+
+    /// Sets the provider of the images previewed when hovering a string with an image path or a data URI.
+    pub fn image_preview_provider(mut self, provider: impl ImagePreviewProvider + 'static) -> Self {
+        self.image_provider = Some(Box::new(provider));
+        self
+    }
+
+    /// Shows the preview of the image source in the string under the pointer, see [`ImagePreviewProvider`].
+    fn image_preview_ui(&mut self, ui: &Ui, response: &egui::Response, galley: &Galley, galley_pos: egui::Pos2) {
+        let Some(pos) = response.hover_pos().filter(|_| self.image_provider.is_some()) else {
+            return;
+        };
+        let index = LineIndex::new(&self.code);
+        let char = self.code_char(galley.cursor_from_pos(pos - galley_pos).index);
+        let offset = index.from_encoded(char, PositionEncoding::Char);
+        let line = index.line_range(index.line_of(offset));
+        let Some(range) = image_preview::image_source_at(&self.code[line.clone()], offset - line.start)
+            .map(|range| line.start + range.start..line.start + range.end)
+        else {
+            return;
+        };
+        let chars = index.to_encoded(range.start, PositionEncoding::Char)..index.to_encoded(range.end, PositionEncoding::Char);
+        let rects = geometry::char_range_rects(galley, galley_pos, self.galley_chars(chars));
+        if !rects.iter().any(|rect| rect.contains(pos)) {
+            return;
+        }
+        let source = &self.code[range];
+        if self.image_preview.as_ref().is_none_or(|(last, _)| last != source)
+            && let Some(provider) = &mut self.image_provider
+        {
+            self.image_preview = Some((source.to_owned(), provider.image(source)));
+        }
+        let Some((_, Some(image))) = &self.image_preview else {
+            return;
+        };
+        let id = response.id.with("image_preview");
+        egui::Tooltip::always_open(ui.ctx().clone(), ui.layer_id(), id, egui::PopupAnchor::Pointer).show(|ui| {
+            ui.add(egui::Image::new(image.clone()).max_size(egui::Vec2::splat(image_preview::MAX_SIZE)));
+        });
+    }
+
+    /// Overrides the built-in [`LanguageConfig`] of the syntax (comments, brackets, indentation and words).
+    pub fn with_language_config(mut self, config: LanguageConfig) -> Self {
+        self.language = config;
+        self
+    }
+
+    pub fn set_language_config(&mut self, config: LanguageConfig) {
+        self.language = config;
+    }
+
+    pub fn language_config(&self) -> &LanguageConfig {
+        &self.language
+    }
+
+    /// Looks up the [`LanguageConfig`] of the syntaxes in `registry`, now and when the syntax changes,
+    /// instead of the built-in configs: the comments, the brackets and the indentation of the languages
+    /// are kept across [`set_syntax_by_extension`](Self::set_syntax_by_extension).
+    pub fn with_language_registry(mut self, registry: LanguageRegistry) -> Self {
+        self.set_language_registry(registry);
+        self
+    }
+
+    /// Replaces the registry of the language configs, the config of the current syntax is looked up again.
+    pub fn set_language_registry(&mut self, registry: LanguageRegistry) {
+        self.language = registry.get(&self.syntax().name);
+        self.languages = registry;
+    }
+
+    pub fn language_registry(&self) -> &LanguageRegistry {
+        &self.languages
+    }
+
+    /// Sets the provider of the occurrences renamed with `F2`, instead of the whole word matches.
+    pub fn rename_provider(mut self, provider: impl RenameProvider + 'static) -> Self {
+        self.rename_provider = Some(Box::new(provider));
+        self
+    }
+
+    /// Starts renaming the symbol under the caret, like pressing `F2`.
+    pub fn start_rename(&mut self) {
+        let Some(range) = self.cursor_range else {
+            return;
+        };
+        let offset = LineIndex::new(&self.code).from_encoded(range.primary.index, PositionEncoding::Char);
+        let is_word = |c| self.language.is_word_char(c);
+        let Some(word) = rename::word_at(&self.code, offset, is_word) else {
+            return;
+        };
+
+        let occurrences = match &mut self.rename_provider {
+            Some(provider) => {
+                let mut occurrences = provider.occurrences(&self.code, offset).unwrap_or_default();
+                // a range of the provider out of the code, reversed or inside a char is left out
+                occurrences.retain(|range| self.code.get(range.clone()).is_some());
+                occurrences
+            }
+            None => rename::word_occurrences(&self.code, &self.code[word.clone()], is_word),
+        };
+        if !occurrences.is_empty() {
+            self.rename = Some(RenameState {
+                occurrences,
+                new_name: self.code[word].to_owned(),
+                focus_requested: false,
+            });
+        }
+    }
+
+    fn rename_ui(&mut self, ui: &Ui, editor_id: egui::Id, galley: &Galley, galley_pos: egui::Pos2) {
+        let (Some(state), Some(caret_rect)) = (&mut self.rename, self.caret_rect) else {
+            return;
+        };
+
+        let index = LineIndex::new(&self.code);
+        let stroke = ui.visuals().selection.stroke;
+        let corner_radius = ui.visuals().widgets.noninteractive.corner_radius;
+        for range in &state.occurrences {
+            let chars = index.to_encoded(range.start, PositionEncoding::Char)
+                ..index.to_encoded(range.end, PositionEncoding::Char);
+            let chars = self.window.as_ref().map_or(chars.clone(), |window| window.to_window_chars(chars));
+            for rect in geometry::char_range_rects(galley, galley_pos, chars) {
+                ui.painter().rect_stroke(rect, corner_radius, stroke, egui::StrokeKind::Outside);
+            }
+        }
+
+        let id = editor_id.with("rename");
+        let name = popup::caret_popup(ui.ctx(), id.with("popup"), caret_rect, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("Rename {} occurrences", state.occurrences.len()));
+                ui.add(egui::TextEdit::singleline(&mut state.new_name).id(id).code_editor())
+            })
+            .inner
+        })
+        .inner;
+        if !state.focus_requested {
+            name.request_focus();
+            state.focus_requested = true;
+        }
+
+        if name.lost_focus() {
+            if ui.input(|i| i.key_pressed(egui::Key::Enter)) && !self.read_only {
+                let code = rename::replace_all(&self.code, &state.occurrences, &state.new_name);
+                self.history.commit(&self.code, self.cursor_range);
+                self.replace_code(code);
+                self.history.commit(&self.code, self.cursor_range);
+            }
+            self.rename = None;
+            ui.memory_mut(|m| m.request_focus(editor_id));
+        }
+    }
+
+    /// Sets the provider of the linked editing ranges, instead of the HTML/XML tag pairs.
+    pub fn linked_editing_provider(mut self, provider: impl LinkedEditingProvider + 'static) -> Self {
+        self.linked_provider = Some(Box::new(provider));
+        self
+    }
+
+    /// Links the byte `ranges`, typing in one of them is mirrored into the others.
+    ///
+    /// Returns `false` (and does nothing) when the ranges do not have identical content.
+    pub fn set_linked_ranges(&mut self, ranges: Vec<Range<usize>>) -> bool {
+        let identical = linked::are_identical(&self.code, &ranges);
+        if identical {
+            self.linked_ranges = ranges;
+        }
+        identical
+    }
+
+    /// Currently linked byte ranges.
+    pub fn linked_ranges(&self) -> &[Range<usize>] {
+        &self.linked_ranges
+    }
+
+    /// Mirrors the edit of this frame into the linked ranges.
+    fn mirror_linked_edit(&mut self, ui: &Ui, id: egui::Id, before: &str) {
+        match linked::mirror_edit(before, &self.code, &self.linked_ranges) {
+            Some(edit) => {
+                self.replace_code(edit.code);
+                self.linked_ranges = edit.ranges;
+                shift_caret(ui, id, edit.caret_shift);
+            }
+            None => self.linked_ranges.clear(),
+        }
+    }
+
+    /// Closes, skips over or wraps the `selected` text (its byte offset and text before the edit) in the pair of the
+    /// char just typed before the caret (in chars).
+    fn auto_pair(&mut self, ui: &Ui, cursor: usize, selected: Option<(usize, String)>) {
+        let offset = LineIndex::new(&self.code).from_encoded(cursor, PositionEncoding::Char);
+        let Some(typed) = self.code[..offset].chars().next_back() else {
+            return;
+        };
+        let typed_now = ui.input(|i| {
+            i.events.iter().any(|event| matches!(event, egui::Event::Text(text) if text.chars().eq([typed])))
+        });
+        if !typed_now {
+            return;
+        }
+
+        let len = self.code.len();
+        self.auto_closed.retain(|&distance| {
+            distance <= len && self.code[len - distance..].chars().next().is_some_and(|c| self.auto_pairs.is_closing(c))
+        });
+        if let Some((start, text)) = selected
+            && start + typed.len_utf8() == offset
+            && let Some(close) = self.auto_pairs.closing(typed).filter(|_| self.auto_pairs.wrap_selection)
+        {
+            self.edit_code(offset..offset, &format!("{text}{close}"));
+            self.select_bytes(offset..offset + text.len());
+        } else if self.auto_pairs.skip_over
+            && self.code[offset..].starts_with(typed)
+            && let Some(i) = self.auto_closed.iter().position(|&distance| distance == len - offset)
+        {
+            self.auto_closed.remove(i);
+            self.edit_code(offset..offset + typed.len_utf8(), "");
+        } else if let Some(close) = self.auto_pairs.auto_close(&self.code, offset, typed) {
+            self.edit_code(offset..offset, close.encode_utf8(&mut [0; 4]));
+            self.auto_closed.push(self.code.len() - offset);
+        }
+    }
+
+    /// Deletes the close char inserted by the auto pairs after the caret when `Backspace` deletes its open char,
+    /// before the `TextEdit` handles the key.
+    fn backspace_pair(&mut self, ui: &Ui) {
+        let backspace = ui.input(|i| {
+            i.events.iter().any(|event| {
+                matches!(
+                    event,
+                    egui::Event::Key { key: egui::Key::Backspace, pressed: true, modifiers, .. } if modifiers.is_none()
+                )
+            })
+        });
+        let Some(caret) = self.selection_bytes().filter(|range| backspace && range.is_empty()) else {
+            return;
+        };
+        let Some(close) = self.auto_pairs.empty_pair(&self.code, caret.start) else {
+            return;
+        };
+        let distance = self.code.len() - close.start;
+        if let Some(i) = self.auto_closed.iter().position(|&d| d == distance) {
+            self.auto_closed.remove(i);
+            self.edit_code(close, "");
+        }
+    }
+
+    /// Line and display column of the caret.
+    fn caret_cell(&self) -> Option<(usize, usize)> {
+        let index = LineIndex::new(&self.code);
+        let offset = index.from_encoded(self.cursor_range?.primary.index, PositionEncoding::Char);
+        let line = index.line_of(offset);
+        let start = index.line_range(line).start;
+        Some((line, block::column_of(index.line(line), offset - start, self.tab_size)))
+    }
+
+    /// Moves the caret of the `TextEdit` to the head of the block, without a selection.
+    fn set_block_caret(&mut self) {
+        let Some(block) = self.block else {
+            return;
+        };
+        let index = LineIndex::new(&self.code);
+        let (line, column) = block.head;
+        let offset = index.line_range(line).start + block::column_offset(index.line(line), column, self.tab_size);
+        let cursor = egui::text::CCursor::new(index.to_encoded(offset, PositionEncoding::Char));
+        self.restore_cursor = Some(egui::text::CCursorRange::one(cursor));
+        self.cursor_range = self.restore_cursor;
+    }
+
+    /// Extends the block with `Shift+Alt`+arrows, and types, deletes, copies and pastes in all its lines,
+    /// before the `TextEdit` handles the keys. The moves of the caret and `Enter` end the block.
+    fn block_keys(&mut self, ui: &Ui) {
+        let arrows = [
+            (egui::Key::ArrowUp, (-1, 0)),
+            (egui::Key::ArrowDown, (1, 0)),
+            (egui::Key::ArrowLeft, (0, -1)),
+            (egui::Key::ArrowRight, (0, 1)),
+        ];
+        let last_line = LineIndex::new(&self.code).line_count() - 1;
+        for (key, (lines, columns)) in arrows {
+            let count = ui.input_mut(|i| i.count_and_consume_key(egui::Modifiers::SHIFT | egui::Modifiers::ALT, key));
+            if count == 0 {
+                continue;
+            }
+            let (lines, columns) = (lines * count as isize, columns * count as isize);
+            let Some(caret) = self.block.map(|block| block.head).or_else(|| self.caret_cell()) else {
+                continue;
+            };
+            let anchor = self.block.map_or(caret, |block| block.anchor);
+            let head = (
+                caret.0.saturating_add_signed(lines).min(last_line),
+                caret.1.saturating_add_signed(columns),
+            );
+            self.block = Some(Block::new(anchor, head));
+            self.set_block_caret();
+        }
+        let Some(block) = self.block else {
+            return;
+        };
+        if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
+            self.block = None;
+            return;
+        }
+
+        let read_only = self.read_only;
+        let lines = block.lines().count();
+        let events = ui.input_mut(|i| {
+            let (taken, kept) = std::mem::take(&mut i.events).into_iter().partition(|event| match event {
+                egui::Event::Copy => true,
+                egui::Event::Text(_) | egui::Event::Cut => !read_only,
+                // a text of other lines than the block is pasted at the caret
+                egui::Event::Paste(text) => !read_only && (!text.contains('\n') || text.lines().count() == lines),
+                egui::Event::Key { key: egui::Key::Backspace | egui::Key::Delete, .. } => !read_only,
+                _ => false,
+            });
+            i.events = kept;
+            taken
+        });
+        let ended = ui.input(|i| {
+            i.events.iter().any(|event| match event {
+                egui::Event::Key { key, pressed: true, .. } => matches!(
+                    key,
+                    egui::Key::ArrowUp
+                        | egui::Key::ArrowDown
+                        | egui::Key::ArrowLeft
+                        | egui::Key::ArrowRight
+                        | egui::Key::Home
+                        | egui::Key::End
+                        | egui::Key::PageUp
+                        | egui::Key::PageDown
+                        | egui::Key::Enter
+                        | egui::Key::Tab
+                ),
+                egui::Event::Paste(_) => true,
+                _ => false,
+            })
+        });
+
+        let mut edited = None;
+        for event in &events {
+            let block = edited.unwrap_or(block);
+            let edit = match event {
+                egui::Event::Copy => {
+                    ui.ctx().copy_text(block.text(&self.code, self.tab_size));
+                    continue;
+                }
+                egui::Event::Cut => {
+                    ui.ctx().copy_text(block.text(&self.code, self.tab_size));
+                    if block.columns().is_empty() {
+                        continue;
+                    }
+                    BlockEdit::Delete
+                }
+                egui::Event::Text(text) | egui::Event::Paste(text) => BlockEdit::Insert(text),
+                egui::Event::Key { key: egui::Key::Backspace, pressed: true, .. } => BlockEdit::Backspace,
+                egui::Event::Key { key: egui::Key::Delete, pressed: true, .. } => BlockEdit::Delete,
+                _ => continue,
+            };
+            // the edit stays in the lines of the block
+            let index = LineIndex::new(&self.code);
+            let lines = index.line_range(*block.lines().start()).start..index.line_range(*block.lines().end()).end;
+            let len = self.code.len();
+            edited = Some(block.edit(&mut self.code, edit, self.tab_size));
+            self.note_edit(lines, len);
+        }
+        if ended {
+            self.block = None;
+        } else if edited.is_some() {
+            self.block = edited;
+            self.set_block_caret();
+        }
+    }
+
+    /// Copies and cuts the selection from the code, before the `TextEdit` copies only the laid out part of
+    /// the long lines.
+    fn window_clipboard(&mut self, ui: &Ui) {
+        let Some(range) = self.selection_bytes().filter(|range| !range.is_empty() && self.window.is_some()) else {
+            return;
+        };
+        let (copy, cut) = ui.input_mut(|i| {
+            let copy = i.events.iter().any(|event| matches!(event, egui::Event::Copy | egui::Event::Cut));
+            let cut = i.events.contains(&egui::Event::Cut);
+            i.events.retain(|event| !matches!(event, egui::Event::Copy | egui::Event::Cut));
+            (copy, cut)
+        });
+        if copy {
+            ui.ctx().copy_text(self.code[range.clone()].to_owned());
+        }
+        if cut && !self.read_only {
+            self.edit_code(range.clone(), "");
+            self.select_bytes(range.start..range.start);
+        }
+    }
+
+    /// Selects a block with `Alt`+drag, a press without `Alt` ends it.
+    fn block_drag(
+        &mut self,
+        ui: &Ui,
+        response: &egui::Response,
+        galley: &Galley,
+        galley_pos: egui::Pos2,
+        font: &egui::FontId,
+    ) {
+        let alt = ui.input(|i| i.modifiers.alt);
+        if response.is_pointer_button_down_on() && !alt {
+            self.block = None;
+        }
+        let char_width = ui.fonts(|f| layout::column_width(f, font));
+        let index = LineIndex::new(&self.code);
+        let cell = |pos: egui::Pos2| {
+            let cursor = galley.cursor_from_pos(pos - galley_pos);
+            let line = index.line_of(index.from_encoded(self.code_char(cursor.index), PositionEncoding::Char));
+            (line, ((pos.x - galley_pos.x) / char_width).round().max(0.0) as usize)
+        };
+        if response.dragged()
+            && alt
+            && self.scrub.is_none()
+            && let Some(origin) = ui.input(|i| i.pointer.press_origin())
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            self.block = Some(Block::new(cell(origin), cell(pos)));
+        }
+        if response.drag_stopped() {
+            self.set_block_caret();
+        }
+    }
+
+    /// Rectangles of the lines of the block, a caret for the lines without columns selected.
+    fn block_shape(
+        &self,
+        ui: &Ui,
+        block: &Block,
+        galley: &Galley,
+        galley_pos: egui::Pos2,
+        font: &egui::FontId,
+        color: egui::Color32,
+    ) -> egui::Shape {
+        let char_width = ui.fonts(|f| layout::column_width(f, font));
+        let columns = block.columns();
+        let left = galley_pos.x + columns.start as f32 * char_width;
+        let right = (galley_pos.x + columns.end as f32 * char_width).max(left + self.caret_width);
+        let spans = gutter::line_spans(galley);
+        let rects = block.lines().filter_map(|line| spans.get(line)).map(|span| {
+            let y = egui::Rangef::new(galley_pos.y + span.min, galley_pos.y + span.max);
+            egui::Shape::rect_filled(egui::Rect::from_x_y_ranges(left..=right, y), 0.0, color)
+        });
+        egui::Shape::Vec(rects.collect())
+    }
+
+    /// Inserts the spaces of the indent style on `Tab`, before the `TextEdit` inserts a tab char.
+    ///
+    /// With a selection over several lines, `Tab` indents the lines instead of replacing the selection, and
+    /// `Shift+Tab` dedents the lines of the selection or of the caret.
+    fn tab_key(&mut self, ui: &Ui) {
+        let Some(range) = self.selection_bytes() else {
+            return;
+        };
+        let index = LineIndex::new(&self.code);
+        let lines = selection_lines(&index, &range);
+        let width = indent::indent_width(&self.code, self.indent_style, self.tab_size);
+        if ui.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, egui::Key::Tab)) {
+            let edits = indent::dedent_lines(&self.code, lines, width.unwrap_or(self.tab_size));
+            self.apply_line_edits(&edits);
+            return;
+        }
+        if lines.len() > 1 {
+            if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab)) {
+                let unit = indent::indent_unit(&self.code, self.indent_style, self.tab_size);
+                let edits = indent::indent_lines(&self.code, lines, &unit);
+                self.apply_line_edits(&edits);
+            }
+            return;
+        }
+        let Some(width) = width else {
+            return;
+        };
+        if !ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab)) {
+            return;
+        }
+        let line_start = self.code[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        let tab_size = self.tab_size.max(1);
+        let column = self.code[line_start..range.start]
+            .chars()
+            .fold(0, |column, c| if c == '\t' { column + tab_size - column % tab_size } else { column + 1 });
+        self.replace_selection(&" ".repeat(width - column % width));
+    }
+
+    /// Indents the line started by `Enter` before the caret (in chars).
+    fn indent_new_line(&mut self, ui: &Ui, id: egui::Id, cursor: usize) {
+        let entered = ui.input(|i| {
+            i.events.iter().any(|event| matches!(event, egui::Event::Key { key: egui::Key::Enter, pressed: true, .. }))
+        });
+        if !entered {
+            return;
+        }
+        let offset = LineIndex::new(&self.code).from_encoded(cursor, PositionEncoding::Char);
+        let unit = indent::indent_unit(&self.code, self.indent_style, self.tab_size);
+        if let Some((text, caret)) = indent::newline_indent(&self.code, offset, &self.language, &unit) {
+            self.edit_code(offset..offset, &text);
+            shift_caret(ui, id, text[..caret].chars().count() as isize);
+        }
+    }
+
+    /// Re-indents the caret line when an electric char of the language was typed.
+    fn electric_indent(&mut self, ui: &Ui, id: egui::Id, cursor: usize) {
+        let index = LineIndex::new(&self.code);
+        let offset = index.from_encoded(cursor, PositionEncoding::Char);
+        let Some(typed) = self.code[..offset].chars().next_back() else {
+            return;
+        };
+        let typed_now = ui.input(|i| {
+            i.events.iter().any(|event| matches!(event, egui::Event::Text(text) if text.chars().eq([typed])))
+        });
+        if !typed_now {
+            return;
+        }
+
+        if let Some((range, indentation)) = indent::electric_indent(&self.code, offset, typed, &self.language) {
+            let delta = indentation.chars().count() as isize - self.code[range.clone()].chars().count() as isize;
+            self.edit_code(range, &indentation);
+            shift_caret(ui, id, delta);
+        }
+    }
+
+    fn update_linked_ranges(&mut self, cursor: usize) {
+        let offset = LineIndex::new(&self.code).from_encoded(cursor, PositionEncoding::Char);
+        if self.linked_ranges.iter().any(|range| (range.start..=range.end).contains(&offset)) {
+            return;
+        }
+
+        let markup = ["HTML", "XML"].contains(&self.syntax().name.as_str());
+        let ranges = match &mut self.linked_provider {
+            Some(provider) => provider.linked_ranges(&self.code, offset),
+            None if markup => linked::tag_pair(&self.code, offset),
+            None => None,
+        };
+        self.linked_ranges = ranges
+            .filter(|ranges| linked::are_identical(&self.code, ranges))
+            .unwrap_or_default();
+    }
+
+    /// Chars of the galley for the `chars` of the code, they differ when only a window of the long lines is laid out.
+    fn galley_chars(&self, chars: Range<usize>) -> Range<usize> {
+        self.window.as_ref().map_or(chars.clone(), |window| window.to_window_chars(chars))
+    }
+
+    /// Char of the code at the `char` of the galley.
+    fn code_char(&self, char: usize) -> usize {
+        self.window.as_ref().map_or(char, |window| window.to_code(char))
+    }
+
+    fn paint_linked_ranges(&self, ui: &Ui, galley: &Galley, galley_pos: egui::Pos2) {
+        let index = LineIndex::new(&self.code);
+        let stroke = egui::Stroke::new(1.0, ui.visuals().weak_text_color());
+        let corner_radius = ui.visuals().widgets.noninteractive.corner_radius;
+        for range in &self.linked_ranges {
+            let chars = index.to_encoded(range.start, PositionEncoding::Char)
+                ..index.to_encoded(range.end, PositionEncoding::Char);
+            for rect in geometry::char_range_rects(galley, galley_pos, self.galley_chars(chars)) {
+                ui.painter().rect_stroke(rect, corner_radius, stroke, egui::StrokeKind::Outside);
+            }
+        }
+    }
+
+    /// Shows the code of another location (for example a definition) in a panel below the current line.
+    ///
+    /// The `location` is shown in the title and returned in `EditorOutput::open_peek`
+    /// when the user wants to open it.
+    pub fn peek(&mut self, location: impl Into<String>, viewer: CodeViewer) {
+        self.peek = Some(Peek {
+            location: location.into(),
+            viewer,
+        });
+    }
+
+    pub fn close_peek(&mut self) {
+        self.peek = None;
+    }
+
+    pub fn is_peeking(&self) -> bool {
+        self.peek.is_some()
+    }
+
+    fn peek_ui(&mut self, ui: &Ui, id: egui::Id, editor_rect: egui::Rect) -> Option<String> {
+        let line_rect = self.caret_rect.unwrap_or(editor_rect);
+        let peek = self.peek.as_mut()?;
+        match peek.ui(ui, id.with("peek"), editor_rect, line_rect)? {
+            PeekAction::Open => self.peek.take().map(|peek| peek.location),
+            PeekAction::Close => {
+                self.peek = None;
+                None
+            }
+        }
+    }
+
+    /// Replaces the diagnostics underlined in the editor, their quick fixes are offered by the lightbulb (`Ctrl+.`).
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics = diagnostics;
+        self.actions_menu = false;
+    }
+
+    /// The diagnostics set with `set_diagnostics`, followed by the ones of the indentation and line length linters.
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().chain(&self.lint)
+    }
+
+    /// Flags mixed tabs and spaces and the dedents to no outer level as diagnostics,
+    /// in the languages with significant indentation (like Python and YAML).
+    pub fn lint_indentation(mut self, lint: bool) -> Self {
+        self.overridden.lint_indentation = true;
+        self.lint_indentation = lint;
+        self
+    }
+
+    /// Flags the lines longer than `max` columns as diagnostics of `severity` (info by default),
+    /// their message counts the long lines of the code.
+    ///
+    /// The tabs count up to their stop and the wide chars (CJK, emoji) as 2 columns.
+    pub fn lint_line_length(mut self, max: usize, severity: Severity) -> Self {
+        self.overridden.max_line_length = true;
+        self.max_line_length = Some(max);
+        self.line_length_severity = severity;
+        self
+    }
+
+    /// Changes the limit of the line length linter, `None` disables it.
+    pub fn set_max_line_length(&mut self, max: Option<usize>) {
+        self.overridden.max_line_length = true;
+        self.max_line_length = max;
+    }
+
+    /// Number of lines longer than the limit of [`lint_line_length`](Self::lint_line_length), as of the last frame.
+    pub fn long_line_count(&self) -> usize {
+        self.long_lines
+    }
+
+    fn update_lint(&mut self) {
+        let indentation = self.lint_indentation && self.language.significant_indentation;
+        if !indentation && self.max_line_length.is_none() {
+            self.lint.clear();
+            self.long_lines = 0;
+            self.lint_hash = None;
+            return;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        self.code.hash(&mut hasher);
+        (indentation, self.max_line_length, self.line_length_severity, self.tab_size, self.position_encoding).hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.lint_hash != Some(hash) {
+            self.lint = if indentation {
+                lint::indentation_diagnostics(&self.code, &self.language)
+            } else {
+                Vec::new()
+            };
+            if let Some(max) = self.max_line_length {
+                let long = lint::line_length_diagnostics(
+                    &self.code,
+                    max,
+                    self.line_length_severity,
+                    self.tab_size,
+                    self.position_encoding,
+                );
+                self.long_lines = long.len();
+                self.lint.extend(long);
+            } else {
+                self.long_lines = 0;
+            }
+            self.lint_hash = Some(hash);
+        }
+    }
+
+    /// Marks the lines appearing several times in the code, the lines shorter than `min_len` chars
+    /// (without their indentation) are ignored.
+    ///
+    /// [`Command::NextDuplicate`] (`Alt+F3`) and [`Command::PreviousDuplicate`] (`Alt+Shift+F3`) go through the copies
+    /// of the caret line, or to the next duplicated line.
+    pub fn highlight_duplicates(mut self, min_len: usize) -> Self {
+        self.duplicates_min_len = Some(min_len);
+        self
+    }
+
+    /// Changes the minimum length of the duplicated lines to mark, `None` stops marking them.
+    pub fn set_highlight_duplicates(&mut self, min_len: Option<usize>) {
+        self.duplicates_min_len = min_len;
+    }
+
+    /// Groups of identical lines (0-based line numbers) found by [`highlight_duplicates`](Self::highlight_duplicates),
+    /// as of the last frame.
+    pub fn duplicate_lines(&self) -> &[Vec<usize>] {
+        &self.duplicates
+    }
+
+    /// Selects the next copy of the caret line, or the next duplicated line, returns `false` if there is none.
+    pub fn next_duplicate(&mut self) -> bool {
+        self.jump_to_duplicate(true)
+    }
+
+    /// Selects the previous copy of the caret line, or the previous duplicated line, returns `false` if there is none.
+    pub fn previous_duplicate(&mut self) -> bool {
+        self.jump_to_duplicate(false)
+    }
+
+    /// Separates the cells of the scripts split by `# %%` comments or `In [ ]:` prompts, with a run button
+    /// on their marker line, for the notebook-like frontends. Off by default.
+    ///
+    /// The clicked cell is in [`EditorOutput::run_cell`], run it with the code of [`CodeCell::code`].
+    pub fn cell_markers(mut self, cell_markers: bool) -> Self {
+        self.cell_markers = cell_markers;
+        self
+    }
+
+    pub fn set_cell_markers(&mut self, cell_markers: bool) {
+        self.cell_markers = cell_markers;
+    }
+
+    /// Renders the doc comments (`///`, `//!`, `/** */` and roxygen `#'`) as formatted prose in the proportional font
+    /// between the code, for reading documented code. Off by default.
+    ///
+    /// The comment markers and the Markdown markup are hidden, the headings, the emphasis, the `code` spans and
+    /// the links are formatted. The text is unchanged, the hidden chars are still edited like the others.
+    pub fn literate_comments(mut self, literate_comments: bool) -> Self {
+        self.literate_comments = literate_comments;
+        self
+    }
+
+    pub fn set_literate_comments(&mut self, literate_comments: bool) {
+        self.literate_comments = literate_comments;
+    }
+
+    /// Changes the numbers live: `Alt` + drag a number left or right, or scroll with `Ctrl` over the editor to change
+    /// the number at the caret, like tweaking the parameters of a shader in a live coding host. Off by default,
+    /// `Alt` + drag selects a block otherwise.
+    ///
+    /// Every step changes the code, reported by [`EditorOutput::change`] and [`EditorEvent::TextChanged`] in its frame.
+    /// A drag is a single undo step.
+    pub fn number_scrubbing(mut self, number_scrubbing: bool) -> Self {
+        self.number_scrubbing = number_scrubbing;
+        self
+    }
+
+    pub fn set_number_scrubbing(&mut self, number_scrubbing: bool) {
+        self.number_scrubbing = number_scrubbing;
+    }
+
+    /// Step of the [`number_scrubbing`](Self::number_scrubbing) in units of the last digit of the number,
+    /// 1 by default: with a step of 5, `10` goes to `15` and `0.25` to `0.30`.
+    pub fn scrub_step(mut self, step: f64) -> Self {
+        self.scrub_step = step;
+        self
+    }
+
+    pub fn set_scrub_step(&mut self, step: f64) {
+        self.scrub_step = step;
+    }
+
+    /// Steps the number dragged with `Alt` or scrolled with `Ctrl`, see [`number_scrubbing`](Self::number_scrubbing).
+    fn scrub_numbers(&mut self, ui: &Ui, response: &egui::Response, galley: &Galley, galley_pos: egui::Pos2) {
+        if !self.number_scrubbing || self.read_only || !self.interactive {
+            self.scrub = None;
+            return;
+        }
+        if let Some(scrub) = &mut self.scrub {
+            let (down, pos) = ui.input(|i| (i.pointer.primary_down(), i.pointer.latest_pos()));
+            if !down {
+                self.scrub = None;
+                self.history.commit(&self.code, self.cursor_range);
+                return;
+            }
+            ui.ctx().set_cursor_icon(egui::CursorIcon::ResizeHorizontal);
+            let steps = pos.map_or(scrub.steps, |pos| ((pos.x - scrub.origin) / scrub::DRAG_POINTS) as i64);
+            if steps != scrub.steps
+                && let Some(text) = scrub::step_number(&scrub.start, steps, self.scrub_step)
+            {
+                scrub.steps = steps;
+                let range = scrub.range.clone();
+                let end = range.start + text.len();
+                scrub.range = range.start..end;
+                self.edit_code(range, &text);
+                self.select_bytes(end..end);
+            }
+            return;
+        }
+
+        if response.drag_started()
+            && ui.input(|i| i.modifiers.alt)
+            && let Some(origin) = ui.input(|i| i.pointer.press_origin())
+        {
+            let char = self.code_char(galley.cursor_from_pos(origin - galley_pos).index);
+            let offset = LineIndex::new(&self.code).from_encoded(char, PositionEncoding::Char);
+            if let Some(range) = scrub::number_at(&self.code, offset) {
+                self.history.commit(&self.code, self.cursor_range);
+                self.block = None;
+                let start = self.code[range.clone()].to_owned();
+                self.scrub = Some(Scrub { range, start, origin: origin.x, steps: 0 });
+            }
+            return;
+        }
+
+        // the caret of an unfocused editor is stale
+        if !response.hovered() || !response.has_focus() {
+            self.scrub_wheel = 0.0;
+            return;
+        }
+        let mut steps = 0;
+        // consumed, so the scroll area around does not scroll too
+        ui.input_mut(|i| {
+            i.events.retain(|event| match event {
+                egui::Event::MouseWheel { unit, delta, modifiers } if modifiers.command => {
+                    match unit {
+                        egui::MouseWheelUnit::Point => self.scrub_wheel += delta.y,
+                        _ => steps += delta.y.signum() as i64,
+                    }
+                    false
+                }
+                _ => true,
+            });
+        });
+        steps += (self.scrub_wheel / scrub::WHEEL_POINTS) as i64;
+        self.scrub_wheel %= scrub::WHEEL_POINTS;
+        if steps != 0
+            && let Some(caret) = self.selection_bytes().filter(|range| range.is_empty())
+            && let Some(range) = scrub::number_at(&self.code, caret.start)
+            && let Some(text) = scrub::step_number(&self.code[range.clone()], steps, self.scrub_step)
+        {
+            let end = range.start + text.len();
+            self.edit_code(range, &text);
+            self.select_bytes(end..end);
+        }
+    }
+
+    /// Cells of the code, see [`cells::cells`], with the line comment of the language.
+    pub fn cells(&self) -> Vec<CodeCell> {
+        cells::cells(&self.code, self.language.line_comment.as_deref())
+    }
+
+    /// Keeps up to `capacity` recent cuts and copies in the clipboard ring (20 by default).
+    pub fn clipboard_history(mut self, capacity: usize) -> Self {
+        self.clipboard_ring.set_capacity(capacity);
+        self
+    }
+
+    /// Recent cuts and copies of the editor, newest first, pasted from a list with `Ctrl+Shift+V`.
+    pub fn clipboard_ring(&self) -> &ClipboardRing {
+        &self.clipboard_ring
+    }
+
+    /// Allows to fill the clipboard ring from the application, or to clear it.
+    pub fn clipboard_ring_mut(&mut self) -> &mut ClipboardRing {
+        &mut self.clipboard_ring
+    }
+
+    /// Replaces the selection with the entry `index` of the clipboard ring (0 is the newest),
+    /// returns `false` if there is no such entry or the editor is read only.
+    ///
+    /// The entry moves to the front of the ring, like when it is copied again.
+    pub fn paste_from_history(&mut self, index: usize) -> bool {
+        if self.read_only {
+            return false;
+        }
+        let Some(text) = self.clipboard_ring.get(index).map(str::to_owned) else {
+            return false;
+        };
+        self.insert_text(&text);
+        self.clipboard_ring.push(text);
+        true
+    }
+
+    /// Replaces the selection with `text` transformed by `mode`, returns `false` (and does nothing) if the editor
+    /// is read only or the language has no comments for [`PasteMode::Comment`].
+    ///
+    /// The context menu uses the OS clipboard, use this to paste special from another source.
+    pub fn paste_special(&mut self, text: &str, mode: PasteMode) -> bool {
+        if self.read_only {
+            return false;
+        }
+        let line_break = if self.code.contains("\r\n") { "\r\n" } else { "\n" };
+        let transformed = match mode {
+            PasteMode::StringLiteral => paste::string_literal(text, &self.syntax().name),
+            PasteMode::Comment => match paste::comment(text, &self.language, line_break) {
+                Some(comment) => comment,
+                None => return false,
+            },
+            PasteMode::NormalizedLineEndings => paste::normalize_line_endings(text, line_break),
+        };
+        self.insert_text(&transformed);
+        true
+    }
+
+    /// The selection (or the whole code) as copied by the `mode`, `None` for [`CopyMode::PathLine`] without a path.
+    ///
+    /// The lines of the selection are numbered whole, [`CopyMode::PathLine`] gives the caret line without a selection.
+    pub fn copy_special(&self, mode: CopyMode) -> Option<String> {
+        let index = LineIndex::new(&self.code);
+        let caret = self.selection_bytes();
+        let selection = caret.clone().filter(|range| !range.is_empty());
+        let lines = match (&selection, &caret) {
+            (Some(range), _) => selection_lines(&index, range),
+            (None, Some(caret)) if mode == CopyMode::PathLine => {
+                let line = index.line_of(caret.start);
+                line..line + 1
+            }
+            _ => 0..index.line_count() - usize::from(self.code.ends_with('\n')),
+        };
+        match mode {
+            CopyMode::WithLineNumbers => Some(copy::with_line_numbers(&self.code, lines)),
+            CopyMode::MarkdownFence => {
+                let text = selection.map_or(self.code.as_str(), |range| &self.code[range]);
+                Some(copy::markdown_fence(text, &copy::fence_tag(self.syntax())))
+            }
+            CopyMode::PathLine => self.path.as_deref().map(|path| copy::path_line(path, lines)),
+        }
+    }
+
+    /// Puts the text of [`copy_special`](Self::copy_special) on the clipboard and in the clipboard ring.
+    fn copy_special_to_clipboard(&mut self, ctx: &egui::Context, mode: CopyMode) {
+        if let Some(text) = self.copy_special(mode) {
+            ctx.copy_text(text.clone());
+            self.clipboard_ring.push(text);
+        }
+    }
+
+    /// Comments out the `lines` (0-based, end excluded) with the line comment of the language, or uncomments them
+    /// when they are all comments, like [`Command::ToggleComment`] (`Ctrl+/`) does with the lines of the selection.
+    ///
+    /// The languages without a line comment wrap each line in their block comment instead.
+    /// Returns `false` in read only mode and for the languages without comments, see [`LanguageConfig::line_comment`].
+    pub fn toggle_comment(&mut self, lines: Range<usize>) -> bool {
+        if self.read_only {
+            return false;
+        }
+        let edits = match (&self.language.line_comment, &self.language.block_comment) {
+            (Some(prefix), _) => comment::toggle_line_comment(&self.code, lines, prefix),
+            (None, Some((open, close))) => comment::toggle_block_comment(&self.code, lines, open, close),
+            (None, None) => return false,
+        };
+        self.apply_line_edits(&edits);
+        true
+    }
+
+    /// Applies the sorted `edits` of whole lines as a single step of the undo history,
+    /// the selection stays on the same text.
+    fn apply_line_edits(&mut self, edits: &[(Range<usize>, String)]) {
+        self.history.commit(&self.code, self.cursor_range);
+        let selection = self.selection_bytes();
+        for (range, text) in edits.iter().rev() {
+            self.edit_code(range.clone(), text);
+        }
+        if let Some(range) = selection {
+            let (start, end) = (comment::shift_offset(range.start, edits), comment::shift_offset(range.end, edits));
+            // the selection keeps its direction
+            let reversed = self.cursor_range.is_some_and(|r| r.primary.index < r.secondary.index);
+            let index = LineIndex::new(&self.code);
+            let cursor = |offset| egui::text::CCursor::new(index.to_encoded(offset, PositionEncoding::Char));
+            let (primary, secondary) = if reversed { (start, end) } else { (end, start) };
+            self.restore_cursor = Some(egui::text::CCursorRange::two(cursor(secondary), cursor(primary)));
+            self.cursor_range = self.restore_cursor;
+        }
+        self.history.commit(&self.code, self.cursor_range);
+    }
+
+    /// Toggles the comments of the lines of the selection, or of the caret line.
+    fn toggle_selection_comment(&mut self) {
+        if let Some(range) = self.selection_bytes() {
+            let lines = selection_lines(&LineIndex::new(&self.code), &range);
+            self.toggle_comment(lines);
+        }
+    }
+
+    /// Replaces the word at the caret with the other word of its pair, like `true` with `false` or `Some` with `None`,
+    /// as a single step of the undo history. Returns `false` if the word is in no pair, or in read only mode.
+    ///
+    /// The pairs are set by [`toggle_pair`](Self::toggle_pair) and [`set_toggle_pairs`](Self::set_toggle_pairs).
+    pub fn toggle_literal(&mut self) -> bool {
+        let Some(caret) = self.selection_bytes().filter(|_| !self.read_only) else {
+            return false;
+        };
+        let language = &self.language;
+        let Some((range, other)) =
+            toggle::toggled_word(&self.code, caret.start, &self.toggle_pairs, |c| language.is_word_char(c))
+        else {
+            return false;
+        };
+        let other = other.to_owned();
+        let caret = range.start + (caret.start - range.start).min(other.len());
+        self.history.commit(&self.code, self.cursor_range);
+        self.edit_code(range, &other);
+        self.select_bytes(caret..caret);
+        self.history.commit(&self.code, self.cursor_range);
+        true
+    }
+
+    /// Adds a pair of words toggled by [`toggle_literal`](Self::toggle_literal), like `("enabled", "disabled")`.
+    ///
+    /// The built-in pairs are `true`/`false`, `Some`/`None`, `on`/`off` and `yes`/`no`, in their usual cases.
+    pub fn toggle_pair(mut self, a: impl Into<String>, b: impl Into<String>) -> Self {
+        self.toggle_pairs.push((a.into(), b.into()));
+        self
+    }
+
+    /// Replaces the pairs of words of [`toggle_literal`](Self::toggle_literal), the built-in ones included.
+    pub fn set_toggle_pairs(&mut self, pairs: Vec<(String, String)>) {
+        self.toggle_pairs = pairs;
+    }
+
+    pub fn toggle_pairs(&self) -> &[(String, String)] {
+        &self.toggle_pairs
+    }
+
+    /// Replaces the selection (or inserts at the end without a caret) with `text`, the caret goes after it.
+    ///
+    /// The insertion is its own step of the undo history, [`undo`](Self::undo) removes it.
+    /// Returns `false` (and does nothing) in read only mode.
+    pub fn insert_text(&mut self, text: &str) -> bool {
+        if self.read_only {
+            return false;
+        }
+        self.history.commit(&self.code, self.cursor_range);
+        self.replace_selection(text);
+        self.history.commit(&self.code, self.cursor_range);
+        true
+    }
+
+    /// Replaces the bytes of `range` of the code with `text`.
+    pub(crate) fn edit_code(&mut self, range: Range<usize>, text: &str) {
+        let len = self.code.len();
+        self.code.replace_range(range.clone(), text);
+        self.note_edit(range, len);
+    }
+
+    /// Replaces the whole code with `code`, only its changed part is reported.
+    fn replace_code(&mut self, code: String) {
+        if let Some(change) = CodeChange::between(&self.code, &code) {
+            merge_change(&mut self.pending_change, change);
+        }
+        self.code = code;
+    }
+
+    /// Records the edit of the bytes of `range` of the code (`len` bytes long before it), for [`EditorOutput::change`].
+    fn note_edit(&mut self, range: Range<usize>, len: usize) {
+        let inserted = range.len() + self.code.len() - len;
+        merge_change(&mut self.pending_change, CodeChange::replacing(range, inserted));
+    }
+
+    /// Replaces the selection (or inserts at the end without a caret) with `text`, the caret goes after it.
+    fn replace_selection(&mut self, text: &str) {
+        let range = self.selection_bytes().unwrap_or(self.code.len()..self.code.len());
+        self.edit_code(range.clone(), text);
+        let end = range.start + text.len();
+        self.select_bytes(end..end);
+    }
+
+    /// Context menu of the code, with the copy variants and the paste special transforms.
+    fn context_menu_ui(&mut self, ui: &Ui, response: &egui::Response) {
+        let mut requested = None;
+        let mut copied = None;
+        let has_comments = self.language.line_comment.is_some() || self.language.block_comment.is_some();
+        let has_path = self.path.is_some();
+        response.context_menu(|ui| {
+            ui.menu_button("Copy special", |ui| {
+                for mode in CopyMode::ALL {
+                    let enabled = *mode != CopyMode::PathLine || has_path;
+                    if ui.add_enabled(enabled, egui::Button::new(mode.label())).clicked() {
+                        copied = Some(*mode);
+                        ui.close();
+                    }
+                }
+            });
+            ui.add_enabled_ui(!self.read_only, |ui| {
+                ui.menu_button("Paste special", |ui| {
+                    for mode in PasteMode::ALL {
+                        let enabled = *mode != PasteMode::Comment || has_comments;
+                        if ui.add_enabled(enabled, egui::Button::new(mode.label())).clicked() {
+                            requested = Some(*mode);
+                            ui.close();
+                        }
+                    }
+                });
+            });
+        });
+        if let Some(mode) = copied {
+            self.copy_special_to_clipboard(ui.ctx(), mode);
+        }
+        if let Some(mode) = requested {
+            // the clipboard text comes with a paste event on the next frames
+            self.pending_paste = Some((mode, ui.ctx().cumulative_pass_nr()));
+            ui.ctx().send_viewport_cmd(egui::ViewportCommand::RequestPaste);
+            ui.memory_mut(|m| m.request_focus(response.id));
+        }
+    }
+
+    /// Takes the clipboard text requested by the context menu from the paste event, before the `TextEdit` pastes it.
+    fn pending_paste_ui(&mut self, ui: &Ui) {
+        let Some((mode, pass)) = self.pending_paste else {
+            return;
+        };
+        let text = ui.input_mut(|i| {
+            let text = i.events.iter().find_map(|event| match event {
+                egui::Event::Paste(text) => Some(text.clone()),
+                _ => None,
+            });
+            if text.is_some() {
+                i.events.retain(|event| !matches!(event, egui::Event::Paste(_)));
+            }
+            text
+        });
+        if let Some(text) = text {
+            self.pending_paste = None;
+            self.paste_special(&text, mode);
+        } else if ui.ctx().cumulative_pass_nr() > pass + 2 {
+            // the integration does not support the paste requests
+            self.pending_paste = None;
+        }
+    }
+
+    /// Opens the popup listing the clipboard ring at the caret, does nothing when the ring is empty.
+    pub fn open_clipboard_history(&mut self) {
+        if !self.read_only && !self.clipboard_ring.is_empty() {
+            self.clipboard_popup = Some(0);
+        }
+    }
+
+    /// Moves the selection of the clipboard history popup with the arrows and pastes the entry on `Enter`,
+    /// before the `TextEdit` handles the keys.
+    fn clipboard_history_keys(&mut self, ui: &Ui) {
+        let Some(selected) = self.clipboard_popup else {
+            return;
+        };
+        let (up, down, enter) = ui.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+            )
+        });
+        let last = self.clipboard_ring.len().saturating_sub(1);
+        if up {
+            self.clipboard_popup = Some(selected.saturating_sub(1));
+        } else if down {
+            self.clipboard_popup = Some((selected + 1).min(last));
+        } else if enter {
+            self.clipboard_popup = None;
+            self.paste_from_history(selected);
+        }
+    }
+
+    /// Popup of [`Command::PasteFromHistory`] at the caret, an entry is pasted by clicking it.
+    fn clipboard_history_ui(&mut self, ui: &Ui, editor_id: egui::Id) {
+        let (Some(selected), Some(caret_rect)) = (self.clipboard_popup, self.caret_rect) else {
+            return;
+        };
+        let mut clicked = None;
+        let popup = popup::caret_popup(ui.ctx(), editor_id.with("clipboard_history"), caret_rect, |ui| {
+            ui.weak("Clipboard history");
+            for (i, entry) in self.clipboard_ring.entries().enumerate() {
+                let label = egui::RichText::new(clipboard::preview(entry, 60)).monospace();
+                let response = ui.selectable_label(i == selected, label);
+                if i == selected {
+                    response.scroll_to_me_animation(None, settings::scroll_animation(ui));
+                }
+                if response.clicked() {
+                    clicked = Some(i);
+                }
+            }
+        });
+        if let Some(index) = clicked {
+            self.clipboard_popup = None;
+            self.paste_from_history(index);
+            ui.memory_mut(|m| m.request_focus(editor_id));
+            ui.ctx().request_repaint();
+        } else if popup.response.clicked_elsewhere() {
+            self.clipboard_popup = None;
+        }
+    }
+
+    /// Pushes the texts copied or cut by the `TextEdit` (the output commands after `before`) to the clipboard ring.
+    fn record_copies(&mut self, ui: &Ui, before: usize) {
+        let copied: Vec<String> = ui.output(|o| {
+            o.commands
+                .iter()
+                .skip(before)
+                .filter_map(|command| match command {
+                    egui::OutputCommand::CopyText(text) => Some(text.clone()),
+                    _ => None,
+                })
+                .collect()
+        });
+        for text in copied {
+            self.clipboard_ring.push(text);
+        }
+    }
+
+    fn jump_to_duplicate(&mut self, forward: bool) -> bool {
+        self.update_duplicates();
+        let index = LineIndex::new(&self.code);
+        let caret = self.selection_bytes().map_or(0, |range| index.line_of(range.start));
+        let Some(line) = duplicates::target(&self.duplicates, caret, forward) else {
+            return false;
+        };
+        let range = index.line_range(line);
+        let start = range.start + indent::indentation(&self.code[range.clone()]).len();
+        self.select_bytes(start..range.end);
+        true
+    }
+
+    fn update_duplicates(&mut self) {
+        let Some(min_len) = self.duplicates_min_len else {
+            self.duplicates.clear();
+            self.duplicates_hash = None;
+            return;
+        };
+        let mut hasher = DefaultHasher::new();
+        (&self.code, min_len).hash(&mut hasher);
+        let hash = hasher.finish();
+        if self.duplicates_hash != Some(hash) {
+            self.duplicates = duplicates::duplicate_lines(&self.code, min_len);
+            self.duplicates_hash = Some(hash);
+        }
+    }
+
+    /// Tints the blocks of duplicated lines, with a bar on their left.
+    fn paint_duplicates(&self, ui: &Ui, galley: &Galley, rect: egui::Rect, galley_pos: egui::Pos2) {
+        if self.duplicates.is_empty() {
+            return;
+        }
+        let spans = gutter::line_spans(galley);
+        let clip = ui.clip_rect();
+        let fill = ui.visuals().text_color().gamma_multiply(0.05);
+        let bar = ui.visuals().weak_text_color().gamma_multiply(0.6);
+        for block in duplicates::blocks(&self.duplicates) {
+            let (Some(first), Some(last)) = (spans.get(block.start), spans.get(block.end - 1)) else {
+                continue;
+            };
+            let y = galley_pos.y + first.min..=galley_pos.y + last.max;
+            let block_rect = egui::Rect::from_x_y_ranges(rect.x_range(), y);
+            if !block_rect.intersects(clip) {
+                continue;
+            }
+            ui.painter().rect_filled(block_rect, 0.0, fill);
+            let bar_rect = egui::Rect::from_x_y_ranges(rect.left()..=rect.left() + 2.0, block_rect.y_range());
+            ui.painter().rect_filled(bar_rect, 0.0, bar);
+        }
+    }
+
+    pub fn clear_diagnostics(&mut self) {
+        self.set_diagnostics(Vec::new());
+    }
+
+    /// Moves the caret to `position`, focuses the editor and scrolls to the caret on the next frame.
+    pub fn go_to(&mut self, position: Position) {
+        self.go_to = Some((position, true));
+    }
+
+    /// Like [`go_to`](Self::go_to) with the byte `offset` of the code.
+    pub fn go_to_offset(&mut self, offset: usize) {
+        self.go_to(LineIndex::new(&self.code).position(offset, self.position_encoding));
+    }
+
+    /// Position of the caret, with the columns in the [`position_encoding`](Self::position_encoding),
+    /// the start of the code before the caret was placed.
+    ///
+    /// Both are 0-based, add 1 for a status bar like "Ln 12, Col 4".
+    pub fn cursor(&self) -> Position {
+        let Some(range) = self.restore_cursor.or(self.cursor_range) else {
+            return Position::default();
+        };
+        let index = LineIndex::new(&self.code);
+        index.position(index.from_encoded(range.primary.index, PositionEncoding::Char), self.position_encoding)
+    }
+
+    /// Moves the caret to `position` (clamped to the code) and scrolls to it, without focusing the editor,
+    /// like when reopening a file. Set before the first frame, it is placed as soon as the editor is shown.
+    pub fn set_cursor(&mut self, position: Position) {
+        let offset = LineIndex::new(&self.code).offset(position, self.position_encoding);
+        self.select_bytes(offset..offset);
+    }
+
+    /// Byte range of the selection, empty at the caret, `None` when the editor has no caret yet.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        self.selection_bytes()
+    }
+
+    /// Like [`selection`](Self::selection) in chars, the unit of the egui cursors.
+    pub fn selection_chars(&self) -> Option<Range<usize>> {
+        Some(self.cursor_range?.as_sorted_char_range())
+    }
+
+    /// Selected text, `None` without a selection, like for a "Run selection" action.
+    pub fn selected_text(&self) -> Option<&str> {
+        self.selection_bytes().filter(|range| !range.is_empty()).map(|range| &self.code[range])
+    }
+
+    /// Selects the byte `range` (clamped to the code and its chars) and scrolls to it, the caret at its end.
+    ///
+    /// Ends the block of columns selected with `Alt`+drag or `Shift+Alt`+arrows, whose lines are all edited by the typing
+    /// and the paste.
+    pub fn select_range(&mut self, range: Range<usize>) {
+        self.block = None;
+        let end = range.end.min(self.code.len());
+        self.select_bytes(range.start.min(end)..end);
+    }
+
+    /// Selects the whole code, without scrolling.
+    pub fn select_all(&mut self) {
+        self.block = None;
+        let end = egui::text::CCursor::new(self.code.chars().count());
+        self.restore_cursor = Some(egui::text::CCursorRange::two(egui::text::CCursor::new(0), end));
+        self.cursor_range = self.restore_cursor;
+    }
+
+    /// Replaces the whole code, for example with the output of a formatter.
+    ///
+    /// The caret stays on the same line of the code when the line is unchanged (or moves to the changed region),
+    /// and only the changed lines are highlighted again.
+    pub fn set_code(&mut self, code: impl Into<String>) {
+        let code = code.into();
+        if let Some(range) = self.cursor_range {
+            let index = LineIndex::new(&self.code);
+            let offset = index.from_encoded(range.primary.index, PositionEncoding::Char);
+            let position = index.position(offset, self.position_encoding);
+            let line = diff::map_line(&diff::diff(&self.code, &code), position.line);
+            self.go_to = Some((Position::new(line, position.column), false));
+        }
+        self.replace_code(code);
+    }
+
+    /// Applies the edits of the quick fix.
+    pub fn apply_code_action(&mut self, action: &CodeAction) -> Result<(), TextEditError> {
+        self.apply_text_edits(&action.edits)
+    }
+
+    /// Byte range of the diagnostic, at least one char long so it stays visible.
+    fn diagnostic_range(&self, index: &LineIndex, diagnostic: &Diagnostic) -> Range<usize> {
+        let start = index.offset(diagnostic.range.start, self.position_encoding);
+        let mut end = index.offset(diagnostic.range.end, self.position_encoding).max(start);
+        if end == start {
+            end = self.code[start..].chars().next().map_or(start, |c| start + c.len_utf8());
+        }
+        start..end
+    }
+
+    fn diagnostics_ui(
+        &mut self,
+        ui: &mut Ui,
+        gutter_rect: egui::Rect,
+        response: &egui::Response,
+        galley: &Galley,
+        galley_pos: egui::Pos2,
+        toggle_menu: bool,
+    ) -> Option<Result<CodeAction, TextEditError>> {
+        let index = LineIndex::new(&self.code);
+        let spans = gutter::line_spans(galley);
+        let hover = response.hover_pos();
+        let mut hovered = Vec::new();
+
+        let mut line_severity = vec![None; spans.len()];
+        for diagnostic in self.diagnostics() {
+            let range = self.diagnostic_range(&index, diagnostic);
+            let chars = index.to_encoded(range.start, PositionEncoding::Char)
+                ..index.to_encoded(range.end, PositionEncoding::Char);
+            for rect in geometry::char_range_rects(galley, galley_pos, self.galley_chars(chars)) {
+                diagnostics::paint_squiggle(ui.painter(), rect, diagnostic.severity.color());
+                if hover.is_some_and(|pos| rect.contains(pos)) {
+                    hovered.push(diagnostic.message.clone());
+                }
+            }
+            if let Some(severity) = line_severity.get_mut(index.line_of(range.start)) {
+                *severity = (*severity).max(Some(diagnostic.severity));
+            }
+        }
+        if !hovered.is_empty() {
+            let id = response.id.with("diagnostic");
+            egui::Tooltip::always_open(ui.ctx().clone(), ui.layer_id(), id, egui::PopupAnchor::Pointer).show(|ui| {
+                for message in hovered {
+                    ui.label(message);
+                }
+            });
+        }
+
+        // the quick fixes of the diagnostics on the caret line
+        let caret_line = self
+            .cursor_range
+            .map(|range| index.line_of(index.from_encoded(range.primary.index, PositionEncoding::Char)));
+        let actions: Vec<CodeAction> = self
+            .diagnostics()
+            .filter(|d| caret_line.is_some_and(|line| (d.range.start.line..=d.range.end.line).contains(&line)))
+            .flat_map(|d| d.actions.iter().cloned())
+            .collect();
+
+        // without the gutter only the squiggles and the menu opened by the shortcut are shown
+        let gutter = gutter_rect.width() > 0.0;
+        for (line, severity) in line_severity.iter().enumerate() {
+            let Some(severity) = severity.filter(|_| gutter && !gutter::is_hidden(&spans[line])) else {
+                continue;
+            };
+            if Some(line) == caret_line && !actions.is_empty() {
+                continue;
+            }
+            let center = egui::pos2(gutter_rect.center().x, galley_pos.y + spans[line].center());
+            ui.painter().circle_filled(center, 3.0, severity.color());
+        }
+
+        let caret_line = caret_line.filter(|_| !actions.is_empty())?;
+        let span = spans.get(caret_line)?;
+        let bulb_rect = match self.caret_rect {
+            Some(caret) if !gutter => caret,
+            _ => egui::Rect::from_x_y_ranges(
+                gutter_rect.x_range(),
+                galley_pos.y + span.min..=galley_pos.y + span.min + gutter_rect.width(),
+            ),
+        };
+        let hint = match self.keymap.shortcuts(Command::QuickFix).next() {
+            Some(shortcut) => format!("{} ({})", Command::QuickFix.name(), ui.ctx().format_shortcut(shortcut)),
+            None => Command::QuickFix.name().to_owned(),
+        };
+        let bulb = gutter.then(|| ui.put(bulb_rect, egui::Button::new("💡").frame(false).small()).on_hover_text(hint));
+        let bulb_clicked = bulb.as_ref().is_some_and(egui::Response::clicked);
+        if bulb_clicked || toggle_menu {
+            self.actions_menu = !self.actions_menu;
+        }
+        if !self.actions_menu {
+            return None;
+        }
+
+        let mut selected = None;
+        let read_only = self.read_only;
+        let menu = popup::caret_popup(ui.ctx(), response.id.with("actions_menu"), bulb_rect, |ui| {
+            for action in &actions {
+                if ui.add_enabled(!read_only, egui::Button::new(&action.title)).clicked() {
+                    selected = Some(action.clone());
+                }
+            }
+        });
+        if selected.is_some()
+            || ui.input(|i| i.key_pressed(egui::Key::Escape))
+            || (menu.response.clicked_elsewhere() && !bulb_clicked)
+        {
+            self.actions_menu = false;
+        }
+
+        let action = selected?;
+        Some(self.apply_code_action(&action).map(|()| action))
+    }
+
+    /// Associates the editor with a file, used by `save`.
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn set_path(&mut self, path: Option<PathBuf>) {
+        self.path = path;
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Writes the code to the associated path, does nothing when there is no path.
+    pub fn save(&self) -> io::Result<()> {
+        match &self.path {
+            Some(path) => std::fs::write(path, self.export().as_bytes()),
+            None => Ok(()),
+        }
+    }
+
+    /// Maximum number of undo steps, the oldest ones are dropped first (100 by default).
+    pub fn max_undo_entries(mut self, max_entries: usize) -> Self {
+        self.overridden.max_undo_entries = true;
+        self.history.set_max_entries(max_entries);
+        self
+    }
+
+    pub fn set_max_undo_entries(&mut self, max_entries: usize) {
+        self.overridden.max_undo_entries = true;
+        self.history.set_max_entries(max_entries);
+    }
+
+    /// Maximum total size in bytes of the code kept for undo, the oldest steps are dropped first (16 MiB by default).
+    pub fn max_undo_bytes(mut self, max_bytes: usize) -> Self {
+        self.overridden.max_undo_bytes = true;
+        self.history.set_max_bytes(max_bytes);
+        self
+    }
+
+    pub fn set_max_undo_bytes(&mut self, max_bytes: usize) {
+        self.overridden.max_undo_bytes = true;
+        self.history.set_max_bytes(max_bytes);
+    }
+
+    /// Opens the search bar on `Ctrl+F` and `Ctrl+H`, enabled by default.
+    ///
+    /// Without it the search is still available with `search_mut`, `find_next` and `replace`.
+    pub fn search_bar(mut self, search_bar: bool) -> Self {
+        self.search_bar = search_bar;
+        self
+    }
+
+    /// Opens the search bar above the code, the selected text (on a single line) becomes the query.
+    pub fn open_search(&mut self) {
+        self.open_search_field(SearchField::Query);
+    }
+
+    fn open_search_field(&mut self, field: SearchField) {
+        if let Some(range) = self.cursor_range.filter(|range| !range.is_empty()) {
+            let chars = range.as_sorted_char_range();
+            let selected: String = self.code.chars().skip(chars.start).take(chars.len()).collect();
+            if !selected.contains('\n') {
+                self.search.query = selected;
+            }
+        }
+        self.search_open = true;
+        self.search_focus = Some(field);
+    }
+
+    pub fn close_search(&mut self) {
+        self.search_open = false;
+    }
+
+    pub fn is_search_open(&self) -> bool {
+        self.search_open
+    }
+
+    /// Query, options and matches of the search, see [`SearchState`].
+    pub fn search(&self) -> &SearchState {
+        &self.search
+    }
+
+    /// Like [`search`](Self::search), to search from the application with [`find_next`](Self::find_next)
+    /// without the find bar (`Ctrl+F`, and `Ctrl+H` to replace).
+    pub fn search_mut(&mut self) -> &mut SearchState {
+        &mut self.search
+    }
+
+    /// Selects the next match after the selection, returns `false` if there is none.
+    pub fn find_next(&mut self) -> bool {
+        let offset = self.selection_bytes().map_or(0, |range| range.end);
+        let found = self.search.next(&self.code, offset);
+        found.map(|range| self.select_bytes(range)).is_some()
+    }
+
+    /// Selects the previous match before the selection, returns `false` if there is none.
+    pub fn find_previous(&mut self) -> bool {
+        let offset = self.selection_bytes().map_or(self.code.len(), |range| range.start);
+        let found = self.search.previous(&self.code, offset);
+        found.map(|range| self.select_bytes(range)).is_some()
+    }
+
+    /// Replaces the selected match and selects the next one, returns `true` if a match was replaced.
+    ///
+    /// When the selection is not a match, only the next match is selected.
+    pub fn replace(&mut self) -> bool {
+        if self.read_only {
+            return false;
+        }
+        self.search.update(&self.code);
+        let current = self.search.current().and_then(|i| self.search.matches().get(i)).cloned();
+        if current.is_none() || current != self.selection_bytes() {
+            self.find_next();
+            return false;
+        }
+        self.history.commit(&self.code, self.cursor_range);
+        let len = self.code.len();
+        let replaced = self.search.replace(&mut self.code);
+        if let Some(range) = &replaced {
+            self.note_edit(range.start..range.start + len + range.len() - self.code.len(), len);
+            let found = self.search.next(&self.code, range.end);
+            let range = found.unwrap_or(range.end..range.end);
+            self.select_bytes(range);
+        }
+        self.history.commit(&self.code, self.cursor_range);
+        replaced.is_some()
+    }
+
+    /// Replaces every match, returns the number of replaced matches.
+    ///
+    /// The change is its own step of the undo history.
+    pub fn replace_all(&mut self) -> usize {
+        if self.read_only {
+            return 0;
+        }
+        self.history.commit(&self.code, self.cursor_range);
+        self.search.update(&self.code);
+        let matches = self.search.matches();
+        let edited = matches.first().zip(matches.last()).map(|(first, last)| first.start..last.end);
+        let len = self.code.len();
+        let replaced = self.search.replace_all(&mut self.code);
+        if let Some(range) = edited {
+            self.note_edit(range, len);
+        }
+        self.history.commit(&self.code, self.cursor_range);
+        replaced
+    }
+
+    /// Byte range of the selection (or of the caret).
+    fn selection_bytes(&self) -> Option<Range<usize>> {
+        let chars = self.cursor_range?.as_sorted_char_range();
+        let index = LineIndex::new(&self.code);
+        Some(index.from_encoded(chars.start, PositionEncoding::Char)..index.from_encoded(chars.end, PositionEncoding::Char))
+    }
+
+    /// Selects the byte `range` and scrolls to it, unfolding the lines hiding it.
+    fn select_bytes(&mut self, range: Range<usize>) {
+        let index = LineIndex::new(&self.code);
+        let line = index.line_of(range.start);
+        let start = egui::text::CCursor::new(index.to_encoded(range.start, PositionEncoding::Char));
+        let end = egui::text::CCursor::new(index.to_encoded(range.end, PositionEncoding::Char));
+        self.restore_cursor = Some(egui::text::CCursorRange::two(start, end));
+        self.cursor_range = self.restore_cursor;
+        self.reveal_cursor = true;
+        self.folds.retain(|fold| !(fold.start < line && line < fold.end));
+    }
+
+    /// Search bar docked above the code, `Enter` selects the next match and `Shift+Enter` the previous one.
+    fn search_bar_ui(&mut self, ui: &mut Ui) {
+        let query_id = ui.make_persistent_id("search_query");
+        let replacement_id = ui.make_persistent_id("search_replacement");
+        let focused = ui.memory(|m| m.focused());
+        let (next, previous) = if focused == Some(query_id) || focused == Some(replacement_id) {
+            ui.input_mut(|i| {
+                (
+                    i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+                    i.consume_key(egui::Modifiers::SHIFT, egui::Key::Enter),
+                )
+            })
+        } else {
+            (false, false)
+        };
+        let replace = next && focused == Some(replacement_id);
+
+        let (query, close) = ui
+            .horizontal(|ui| {
+                let query = ui.add(
+                    TextEdit::singleline(&mut self.search.query)
+                        .id(query_id)
+                        .hint_text("Find")
+                        .desired_width(200.0),
+                );
+                ui.toggle_value(&mut self.search.case_sensitive, "Aa").on_hover_text("Match case");
+                ui.toggle_value(&mut self.search.whole_word, "W").on_hover_text("Match whole word");
+                self.search.update(&self.code);
+                let count = self.search.matches().len();
+                if self.search.query.is_empty() {
+                    ui.weak("");
+                } else if count == 0 {
+                    ui.weak("No results");
+                } else {
+                    let current = self.search.current().map_or("?".to_owned(), |i| (i + 1).to_string());
+                    ui.weak(format!("{current} of {count}"));
+                }
+                if ui.button("Previous").on_hover_text("Shift+Enter").clicked() {
+                    self.find_previous();
+                }
+                if ui.button("Next").on_hover_text("Enter").clicked() {
+                    self.find_next();
+                }
+                (query, ui.button("✖").on_hover_text("Close (Esc)").clicked())
+            })
+            .inner;
+        let (replacement, replace_all) = ui
+            .horizontal(|ui| {
+                let replacement = ui.add(
+                    TextEdit::singleline(&mut self.search.replacement)
+                        .id(replacement_id)
+                        .hint_text("Replace")
+                        .desired_width(200.0),
+                );
+                if ui.add_enabled(!self.read_only, egui::Button::new("Replace")).clicked() {
+                    self.replace();
+                }
+                let replace_all = ui.add_enabled(!self.read_only, egui::Button::new("Replace all")).clicked();
+                (replacement, replace_all)
+            })
+            .inner;
+
+        if let Some(field) = self.search_focus.take() {
+            match field {
+                SearchField::Query => query.request_focus(),
+                SearchField::Replacement => replacement.request_focus(),
+            }
+        }
+        if query.changed() {
+            // the first match from the selection start, as the query is typed
+            let offset = self.selection_bytes().map_or(0, |range| range.start);
+            if let Some(range) = self.search.next(&self.code, offset) {
+                self.select_bytes(range);
+            }
+        }
+        if replace {
+            self.replace();
+        } else if next {
+            self.find_next();
+        }
+        if previous {
+            self.find_previous();
+        }
+        if replace_all {
+            self.replace_all();
+        }
+        let escaped = (query.lost_focus() || replacement.lost_focus()) && ui.input(|i| i.key_pressed(egui::Key::Escape));
+        if close || escaped {
+            self.search_open = false;
+            if let Some(id) = self.id {
+                ui.memory_mut(|m| m.request_focus(id));
+            }
+        }
+    }
+
+    /// Paints the visible control chars as boxes with their mnemonic, in the colors of the theme reversed.
+    fn paint_control_chars(&self, ui: &Ui, galley: &Galley, galley_pos: egui::Pos2, font: &egui::FontId) {
+        let fill = highlight::foreground(&self.theme, ui.visuals().text_color());
+        let text = highlight::background(&self.theme, ui.visuals().extreme_bg_color);
+        control::paint(ui, galley, galley_pos, font, fill, text);
+    }
+
+    /// Paints a `↪` before the visible continuation rows of the wrapped lines, in their hanging indent.
+    fn paint_wrap_indicators(&self, ui: &Ui, galley: &Galley, galley_pos: egui::Pos2, font: &egui::FontId) {
+        let clip = ui.clip_rect();
+        let color = highlight::foreground(&self.theme, ui.visuals().text_color()).gamma_multiply(0.4);
+        let small = egui::FontId::new(font.size * 0.8, font.family.clone());
+        let marker = ui.fonts(|f| f.glyph_width(font, ' ')) * WRAP_MARKER_COLUMNS;
+        for pair in galley.rows.windows(2) {
+            let rect = pair[1].rect().translate(galley_pos.to_vec2());
+            if pair[0].ends_with_newline || rect.bottom() < clip.top() {
+                continue;
+            }
+            if rect.top() > clip.bottom() {
+                break;
+            }
+            let pos = egui::pos2(rect.left() - marker, rect.center().y);
+            ui.painter().text(pos, egui::Align2::LEFT_CENTER, "↪", small.clone(), color);
+        }
+    }
+
+    /// Highlights the visible matches of the search, the current one framed.
+    fn paint_search_matches(&self, ui: &Ui, galley: &Galley, galley_pos: egui::Pos2) {
+        let clip = ui.clip_rect();
+        let first = self.code_char(galley.cursor_from_pos(clip.min - galley_pos).index);
+        let last = self.code_char(galley.cursor_from_pos(clip.max - galley_pos).index);
+        let fill = self.theme.settings.find_highlight.map_or(ui.visuals().selection.bg_fill, |c| {
+            egui::Color32::from_rgb(c.r, c.g, c.b)
+        });
+        let fill = fill.gamma_multiply(0.4);
+        let stroke = egui::Stroke::new(1.0, ui.visuals().strong_text_color());
+        let index = LineIndex::new(&self.code);
+        for (i, range) in self.search.matches().iter().enumerate() {
+            let chars = index.to_encoded(range.start, PositionEncoding::Char)..index.to_encoded(range.end, PositionEncoding::Char);
+            if chars.end < first || chars.start > last {
+                continue;
+            }
+            for rect in geometry::char_range_rects(galley, galley_pos, self.galley_chars(chars)) {
+                ui.painter().rect_filled(rect, 2.0, fill);
+                if self.search.current() == Some(i) {
+                    ui.painter().rect_stroke(rect, 2.0, stroke, egui::StrokeKind::Outside);
+                }
+            }
+        }
+    }
+
+    /// Outlines the bracket next to the `caret` (in chars) and the one matching it.
+    fn paint_matching_brackets(&self, ui: &Ui, galley: &Galley, galley_pos: egui::Pos2, caret: usize) {
+        let index = LineIndex::new(&self.code);
+        let caret = index.from_encoded(caret, PositionEncoding::Char);
+        let pair = match self.highlight_worker.brackets() {
+            Some(brackets) => brackets::matching_pair(&self.code, brackets, caret, &self.language),
+            None => brackets::matching_pair(&self.code, self.line_cache.brackets(), caret, &self.language),
+        };
+        let Some(pair) = pair else {
+            return;
+        };
+        let color = match self.theme.settings.brackets_foreground {
+            Some(c) => egui::Color32::from_rgb(c.r, c.g, c.b),
+            None => highlight::foreground(&self.theme, ui.visuals().text_color()).gamma_multiply(0.5),
+        };
+        for range in [pair.0, pair.1] {
+            let chars = index.to_encoded(range.start, PositionEncoding::Char)..index.to_encoded(range.end, PositionEncoding::Char);
+            for rect in geometry::char_range_rects(galley, galley_pos, self.galley_chars(chars)) {
+                ui.painter().rect_filled(rect, 2.0, color.gamma_multiply(0.2));
+                ui.painter().rect_stroke(rect, 2.0, egui::Stroke::new(1.0, color), egui::StrokeKind::Inside);
+            }
+        }
+    }
+
+    /// Marks the current code in the undo history with `label`, like "before running script".
+    ///
+    /// The checkpoints are kept regardless of the undo limits, use [`restore`](Self::restore) to return to one.
+    pub fn checkpoint(&mut self, label: impl Into<String>) -> CheckpointId {
+        CheckpointId(self.history.checkpoint(&self.code, self.cursor_range, label.into()))
+    }
+
+    /// Restores the code and the selection of the checkpoint, the current code stays in the history.
+    /// Returns `false` if the checkpoint is gone, or in read only mode.
+    pub fn restore(&mut self, checkpoint: CheckpointId) -> bool {
+        self.jump_to_state(checkpoint.0)
+    }
+
+    /// Ids and labels of the checkpoints, oldest first.
+    pub fn checkpoints(&self) -> impl Iterator<Item = (CheckpointId, &str)> {
+        self.history.checkpoints().map(|(id, label)| (CheckpointId(id), label))
+    }
+
+    /// Undoes the last step of the own history of the editor (not the one of the `TextEdit`),
+    /// like `Ctrl+Z`, returns `false` if there is none.
+    ///
+    /// The code changed since the last frame (like by [`set_code`](Self::set_code)) is a step of its own.
+    /// Does nothing in a read-only editor.
+    pub fn undo(&mut self) -> bool {
+        self.step_history(false)
+    }
+
+    /// Redoes the last undone step, like `Ctrl+Y`, returns `false` if there is none.
+    pub fn redo(&mut self) -> bool {
+        self.step_history(true)
+    }
+
+    /// Whether [`undo`](Self::undo) would change the code, for the enabled state of an Edit menu.
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo() || self.history.current_code().is_some_and(|code| code != self.code)
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo() && self.history.current_code() == Some(self.code.as_str())
+    }
+
+    /// Forgets the undo history and the checkpoints, like after opening another file in the editor.
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    pub(crate) fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Restores the state `id` of the undo history, returns `false` if it was dropped.
+    pub(crate) fn jump_to_state(&mut self, id: usize) -> bool {
+        if self.read_only {
+            return false;
+        }
+        self.history.commit(&self.code, self.cursor_range);
+        let Some(snapshot) = self.history.jump(id).cloned() else {
+            return false;
+        };
+        self.replace_code(snapshot.code);
+        self.restore_cursor = snapshot.cursor;
+        true
+    }
+
+    /// Whether a popup or a bar of the editor is open: the search bar, the quick fixes, the peek, the shortcuts...
+    pub fn has_open_popup(&self) -> bool {
+        self.signature_help.is_some()
+            || self.actions_menu
+            || self.peek.is_some()
+            || self.hunk_popup.is_some()
+            || self.shortcut_help
+            || self.search_open
+            || self.clipboard_popup.is_some()
+    }
+
+    fn close_popups(&mut self) {
+        self.shortcut_help = false;
+        self.search_open = false;
+        self.signature_help = None;
+        self.actions_menu = false;
+        self.peek = None;
+        self.hunk_popup = None;
+        self.clipboard_popup = None;
+    }
+
+    /// Handles `Esc` pressed in the focused editor `id`.
+    ///
+    /// egui releases the focus on `Esc` before the widgets run, unless the focus lock filter of the editor
+    /// keeps it (see `show`), so the staged behaviors release it here themselves.
+    /// Returns whether `Esc` cleared the selection or closed a popup, rather than only leaving the editor.
+    fn escape_ui(&mut self, ui: &Ui, id: egui::Id) -> bool {
+        let selection = self.cursor_range.filter(|range| !range.is_empty());
+        let had_popup = self.has_open_popup();
+        if self.escape_behavior == EscapeBehavior::ReleaseFocus {
+            self.close_popups();
+            return had_popup;
+        }
+        if let Some(range) = selection {
+            self.restore_cursor = Some(egui::text::CCursorRange::one(range.primary));
+        } else if had_popup {
+            self.close_popups();
+        } else if self.escape_behavior == EscapeBehavior::Staged {
+            ui.memory_mut(|m| m.surrender_focus(id));
+        }
+        ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape));
+        selection.is_some() || had_popup
+    }
+
+    fn shortcut_help_ui(&mut self, ui: &Ui, editor_id: egui::Id) {
+        if !self.shortcut_help {
+            return;
+        }
+        let modal = egui::Modal::new(editor_id.with("shortcut_help")).show(ui.ctx(), |ui| {
+            ui.heading("Keyboard shortcuts");
+            ui.separator();
+            self.keymap.help_ui(ui);
+        });
+        if modal.should_close() {
+            self.shortcut_help = false;
+        }
+    }
+
+    /// Reduces the undo of the `TextEdit` to the last state, the shortcuts use the own history of the editor.
+    fn limit_text_edit_undo(&mut self, ui: &Ui, id: egui::Id) {
+        if !self.undoer_limited {
+            let mut state = TextEdit::load_state(ui.ctx(), id).unwrap_or_default();
+            let settings = egui::util::undoer::Settings {
+                max_undos: 1,
+                ..Default::default()
+            };
+            state.set_undoer(egui::util::undoer::Undoer::with_settings(settings));
+            TextEdit::store_state(ui.ctx(), id, state);
+            self.undoer_limited = true;
+        }
+    }
+
+    /// Undoes (or redoes) one step of the history, returns `false` if there is none.
+    ///
+    /// The code changed since the last frame is committed first, so it is the step undone.
+    pub(crate) fn step_history(&mut self, redo: bool) -> bool {
+        if self.read_only {
+            return false;
+        }
+        self.history.commit(&self.code, self.cursor_range);
+        let snapshot = if redo { self.history.redo() } else { self.history.undo() };
+        let Some(snapshot) = snapshot.cloned() else {
+            return false;
+        };
+        self.replace_code(snapshot.code);
+        self.restore_cursor = snapshot.cursor;
+        true
+    }
+
+    /// Ends the saved and exported code with a line break when it is missing (in the style of the other line breaks).
+    pub fn insert_final_newline(mut self, insert: bool) -> Self {
+        self.overridden.insert_final_newline = true;
+        self.insert_final_newline = insert;
+        self
+    }
+
+    pub fn set_insert_final_newline(&mut self, insert: bool) {
+        self.overridden.insert_final_newline = true;
+        self.insert_final_newline = insert;
+    }
+
+    /// The code as written by `save`.
+    pub fn export(&self) -> Cow<'_, str> {
+        if !self.insert_final_newline || self.code.is_empty() || self.code.ends_with('\n') {
+            return Cow::Borrowed(&self.code);
+        }
+        let line_break = if self.code.contains("\r\n") { "\r\n" } else { "\n" };
+        Cow::Owned(format!("{}{line_break}", self.code))
+    }
+
+    /// Highlighted spans of the code, with the syntaxes, the theme and the line limit of the editor,
+    /// see [`spans::highlight`](crate::spans::highlight).
+    pub fn highlight_spans(&self) -> Vec<Span> {
+        highlight::spans(&self.code, self.syntax(), &self.syntax_set, &self.theme, self.highlight_line_limit, &self.injections).0
+    }
+
+    /// Textual description of the highlighted spans of the code, for the snapshot tests of the highlighting
+    /// and the theme of an application, in CI without a GPU.
+    ///
+    /// Every span is on a line like `3:5-3:9 #b48ead bold italic "self"`, with its 1-based position in chars,
+    /// its color (`fg` for the default foreground of the theme) and its styles. The description depends only on
+    /// the code, the syntax, the theme and the [`highlight_line_limit`](Self::highlight_line_limit).
+    pub fn debug_layout_dump(&self) -> String {
+        highlight::debug_dump(&self.code, self.syntax(), &self.syntax_set, &self.theme, self.highlight_line_limit, &self.injections)
+    }
+
+    /// Shows the code for printing or exporting, in the light theme and the font size of `style`,
+    /// without the caret, the selection, the folds and the gutter markers, and without changing the editor.
+    ///
+    /// The code is highlighted again on every call, call it for the frames of the export only.
+    pub fn print_ui(&self, ui: &mut Ui, style: &PrintStyle) -> egui::Response {
+        print::print_ui(ui, &self.code, self.syntax(), &self.syntax_set, self.tab_size, self.control_chars, style)
+    }
+
+    /// Calls `save` when `Ctrl+S` is pressed and a path is associated.
+    pub fn save_on_request(mut self, save_on_request: bool) -> Self {
+        self.overridden.save_on_request = true;
+        self.save_on_request = save_on_request;
+        self
+    }
+
+    /// Registers `callback` to be called each time `event` happens.
+    ///
+    /// The callbacks are called from `ui`, after the text edit was shown.
+    pub fn on_event(&mut self, event: EditorEvent, callback: impl FnMut(&EventArgs) + Send + 'static) {
+        self.events.add(event, Box::new(callback));
+    }
+
+    /// Registers `callback` to be called with the new code and its change each time the code changes,
+    /// like `on_event(EditorEvent::TextChanged, ..)`, for linting or compiling only the changed code.
+    pub fn on_change(&mut self, mut callback: impl FnMut(&str, &CodeChange) + Send + 'static) {
+        self.on_event(EditorEvent::TextChanged, move |args| {
+            if let Some(change) = args.change {
+                callback(args.code, change);
+            }
+        });
+    }
+
+    fn emit(&mut self, event: EditorEvent, dropped_file: Option<&egui::DroppedFile>) {
+        let args = EventArgs {
+            event,
+            code: &self.code,
+            language: &self.syntax_set.syntaxes()[self.syntax_index].name,
+            dropped_file,
+            change: self.change.as_ref(),
+        };
+        self.events.emit(&args);
+    }
+
+    fn emit_events(&mut self, ui: &Ui, response: &egui::Response) {
+        if response.gained_focus() {
+            self.emit(EditorEvent::FocusGained, None);
+        }
+        if response.lost_focus() {
+            self.emit(EditorEvent::FocusLost, None);
+        }
+        if self.change.is_some() {
+            self.emit(EditorEvent::TextChanged, None);
+        }
+
+        let language = &self.syntax().name;
+        if self.last_language.as_ref().is_some_and(|last| last != language) {
+            self.emit(EditorEvent::LanguageChanged, None);
+        }
+        self.last_language = Some(self.syntax().name.clone());
+
+        if response.contains_pointer() {
+            for file in ui.input(|i| i.raw.dropped_files.clone()) {
+                self.emit(EditorEvent::FileDropped, Some(&file));
+            }
+        }
+    }
+
+    /// Shows the editor, use [`show`](Self::show) to get the whole [`EditorOutput`] of the frame.
+    pub fn ui(&mut self, ui: &mut Ui) -> egui::Response {
+        self.show(ui).response
+    }
+
+    fn change_markers_ui(
+        &mut self,
+        ui: &Ui,
+        gutter_rect: egui::Rect,
+        id: egui::Id,
+        galley: &Galley,
+        galley_pos: egui::Pos2,
+        font: &FontId,
+    ) {
+        let spans = gutter::line_spans(galley);
+        gutter::paint_change_markers(ui.painter(), &spans, &self.hunks, galley_pos, gutter_rect.x_range());
+
+        let response = ui.interact(gutter_rect, id.with("change_markers"), egui::Sense::click());
+        if response.clicked()
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            self.hunk_popup = gutter::hunk_at(&spans, &self.hunks, galley_pos, pos.y);
+        }
+
+        let Some(index) = self.hunk_popup else {
+            return;
+        };
+        let (Some(hunk), Some(baseline)) = (self.hunks.get(index), &self.baseline) else {
+            self.hunk_popup = None;
+            return;
+        };
+        let top = gutter::hunk_span(&spans, hunk).map_or(galley_pos.y, |span| span.min + galley_pos.y);
+
+        let original: Vec<&str> = baseline.lines().skip(hunk.old.start).take(hunk.old.len()).collect();
+        let (job, _) = highlight::layout_job(
+            &original.join("\n"),
+            self.syntax(),
+            &self.syntax_set,
+            &self.theme,
+            font,
+            ui.visuals().text_color(),
+            &CancelToken::new(),
+        );
+        let kind = hunk.kind();
+        let read_only = self.read_only;
+
+        let mut revert = false;
+        let mut close = false;
+        let popup = egui::Area::new(id.with("hunk_popup"))
+            .order(egui::Order::Foreground)
+            .fade_in(settings::fade_in(ui.ctx()))
+            .fixed_pos(egui::pos2(gutter_rect.right(), top))
+            .show(ui.ctx(), |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    if kind == HunkKind::Added {
+                        ui.weak("Added lines");
+                    } else {
+                        ui.label(job);
+                    }
+                    ui.horizontal(|ui| {
+                        revert = ui.add_enabled(!read_only, egui::Button::new("Revert hunk")).clicked();
+                        close = ui.button("Close").clicked();
+                    });
+                });
+            });
+
+        if revert {
+            self.revert_hunk(index);
+        }
+        if revert || close || (popup.response.clicked_elsewhere() && !response.clicked()) {
+            self.hunk_popup = None;
+        }
+    }
+
+    /// Shows the editor, like `ui`, but returns the whole [`EditorOutput`].
+    pub fn show(&mut self, ui: &mut Ui) -> EditorOutput {
+        self.inherit_global_defaults();
+
+        // consume the shortcuts before anybody else, so the host app shortcuts do not swallow them
+        let has_focus = self.id.is_some_and(|id| ui.memory(|m| m.has_focus(id)));
+        let mut commands = if has_focus { self.keymap.consume(ui) } else { Vec::new() };
+        commands.append(&mut self.sent_commands);
+        let mut run_cell = None;
+        for command in &commands {
+            match command {
+                Command::Undo | Command::Redo => {
+                    self.step_history(*command == Command::Redo);
+                }
+                Command::Rename => self.start_rename(),
+                Command::Find if self.search_bar => self.open_search_field(SearchField::Query),
+                Command::Replace if self.search_bar && !self.read_only => {
+                    self.open_search_field(SearchField::Replacement);
+                }
+                Command::FindNext => {
+                    self.find_next();
+                }
+                Command::FindPrevious => {
+                    self.find_previous();
+                }
+                Command::NextDuplicate => {
+                    self.next_duplicate();
+                }
+                Command::PreviousDuplicate => {
+                    self.previous_duplicate();
+                }
+                Command::PasteFromHistory => {
+                    // the OS paste is triggered by the same keys
+                    ui.input_mut(|i| i.events.retain(|event| !matches!(event, egui::Event::Paste(_))));
+                    self.open_clipboard_history();
+                }
+                Command::CopyWithLineNumbers => self.copy_special_to_clipboard(ui.ctx(), CopyMode::WithLineNumbers),
+                Command::CopyAsMarkdown => self.copy_special_to_clipboard(ui.ctx(), CopyMode::MarkdownFence),
+                Command::CopyPathLine => self.copy_special_to_clipboard(ui.ctx(), CopyMode::PathLine),
+                Command::ShortcutHelp => self.shortcut_help = !self.shortcut_help,
+                Command::ToggleZenMode => self.set_zen(!self.zen),
+                Command::ToggleComment => self.toggle_selection_comment(),
+                Command::ToggleLiteral => {
+                    self.toggle_literal();
+                }
+                Command::RunCell => {
+                    let line = self.cursor().line;
+                    run_cell = self.cells().into_iter().find(|cell| cell.lines.contains(&line));
+                }
+                _ => {}
+            }
+        }
+        let save_requested = commands.contains(&Command::Save);
+        if has_focus {
+            self.clipboard_history_keys(ui);
+            self.block_keys(ui);
+            self.window_clipboard(ui);
+        }
+        if has_focus && !self.read_only {
+            self.tab_key(ui);
+            self.backspace_pair(ui);
+        }
+        self.pending_paste_ui(ui);
+        if let Some(id) = self.id {
+            self.limit_text_edit_undo(ui, id);
+        }
+        // checked before `escape_ui` consumes the key
+        let escape_pressed = ui.input(|i| i.key_pressed(egui::Key::Escape));
+        // an `Esc` closing something else does not cancel the highlighting
+        let mut escape_cancels = escape_pressed;
+        if let Some(id) = self.id.filter(|_| has_focus && escape_pressed) {
+            escape_cancels = !self.escape_ui(ui, id);
+        }
+
+        if self.search_open {
+            self.search_bar_ui(ui);
+        }
+        self.update_hunks();
+        self.update_lint();
+        self.update_duplicates();
+        self.sync_folds();
+
+        let font = fonts::code_font(ui, self.font_size);
+        let syntax_set = self.syntax_set.clone();
+        let theme = self.theme.clone();
+        let syntax_index = self.syntax_index;
+        let cancel = self.cancel.clone();
+        let wrap = self.wrap;
+        let wrap_indicators = self.wrap && self.wrap_indicators;
+        let wrap_column = self.wrap_column;
+        let break_anywhere = self.wrap_mode == WrapMode::Anywhere;
+        let tab_size = self.tab_size;
+        let control_chars = self.control_chars;
+        let doc_markers = self.literate_comments.then(|| literate::DocMarkers::new(&self.language));
+        let line_limit = self.highlight_line_limit;
+        self.line_cache.set_line_limit(line_limit);
+        self.line_cache.set_injections(&self.injections);
+        let injections = self.injections.clone();
+        let background_lines = self.background_lines.filter(|_| !cfg!(target_arch = "wasm32"));
+
+        // the selection typed over, for the auto pairs wrapping it
+        let wrapped_selection = self
+            .selection_bytes()
+            .filter(|range| self.auto_pairs.wrap_selection && !range.is_empty() && has_focus)
+            .map(|range| (range.start, self.code[range].to_owned()));
+
+        let highlight_error = Cell::new(None);
+        let folds = (!self.folds.is_empty()).then_some((&self.folds, &self.folded_code));
+        // columns of the long lines hidden from the layout, moved by the edits of the frame
+        let gaps = RefCell::new(Vec::new());
+        let mut layouter = {
+            let font = font.clone();
+            let highlight_error = &highlight_error;
+            let gaps = &gaps;
+            let cache = &mut self.galley_cache;
+            let line_cache = &mut self.line_cache;
+            let worker = &mut self.highlight_worker;
+            Box::new(move |ui: &Ui, text_buffer: &dyn egui::TextBuffer, wrap_width: f32| {
+                let text = text_buffer.as_str();
+                let gaps = gaps.borrow();
+                let plain = ui.visuals().text_color();
+                let wrap_width = match wrap_column {
+                    Some(column) if wrap => column as f32 * ui.fonts(|f| layout::column_width(f, &font)),
+                    _ if wrap => wrap_width,
+                    _ => f32::INFINITY,
+                };
+                worker.poll();
+                let key = (
+                    (text, &gaps[..]),
+                    (wrap_width.to_bits(), break_anywhere, wrap_indicators),
+                    &font,
+                    ui.ctx().pixels_per_point().to_bits(),
+                    plain,
+                    Arc::as_ptr(&theme),
+                    cancel.is_cancelled(),
+                    folds,
+                    tab_size,
+                    (control_chars, &doc_markers),
+                    (line_limit, &injections),
+                    worker.revision(),
+                );
+                let (galley, error) = cache.get_or_layout(key, || {
+                    let (mut job, error) = if background_lines.is_some_and(|min| text.lines().count() >= min) {
+                        let ctx = ui.ctx();
+                        worker.layout_job(text, &syntax_set, syntax_index, &theme, &font, plain, (line_limit, &injections), &cancel, ctx)
+                    } else {
+                        worker.set_unused();
+                        let syntax = &syntax_set.syntaxes()[syntax_index];
+                        line_cache.layout_job(text, syntax, &syntax_set, &theme, &font, plain, &cancel)
+                    };
+                    if control_chars {
+                        control::conceal(&mut job);
+                    }
+                    ui.fonts(|f| layout::align_columns(&mut job, tab_size, control_chars, &gaps, f, &font));
+                    if let Some(markers) = &doc_markers {
+                        literate::render(&mut job, markers, &font, ui.visuals());
+                    }
+                    if let Some((folds, folded_code)) = folds {
+                        // the text differs from the folded code after an edit in this frame
+                        let folds = folding::shift_folds(folds, folded_code, text);
+                        folding::hide(&mut job, &folding::hidden_bytes(text, &folds));
+                    }
+                    job.wrap.max_width = wrap_width;
+                    job.wrap.break_anywhere = break_anywhere;
+                    let mut galley = ui.fonts(|f| f.layout_job(job));
+                    if wrap_indicators && layout::is_wrapped(&galley) {
+                        let marker = ui.fonts(|f| f.glyph_width(&font, ' ')) * WRAP_MARKER_COLUMNS;
+                        layout::hang_wrapped_rows(Arc::make_mut(&mut galley), marker, wrap_width);
+                    }
+                    (galley, error)
+                });
+                if error.is_some() {
+                    highlight_error.set(error);
+                }
+                galley
+            }) as Box<dyn FnMut(&Ui, &dyn egui::TextBuffer, f32) -> Arc<Galley> + '_>
+        };
+
+        let before_edit = (!self.linked_ranges.is_empty()).then(|| self.code.clone());
+
+        // the id is known since the first frame, the jump waits for it
+        let jumped = self.id.is_some() && self.go_to.is_some_and(|(_, reveal)| reveal);
+        if let Some(id) = self.id
+            && let Some((position, reveal)) = self.go_to.take()
+        {
+            let index = LineIndex::new(&self.code);
+            let offset = index.offset(position, self.position_encoding);
+            let cursor = egui::text::CCursor::new(index.to_encoded(offset, PositionEncoding::Char));
+            let mut state = TextEdit::load_state(ui.ctx(), id).unwrap_or_default();
+            state.cursor.set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+            TextEdit::store_state(ui.ctx(), id, state);
+            if reveal {
+                ui.memory_mut(|m| m.request_focus(id));
+            }
+        }
+        if let Some(id) = self.id
+            && let Some(range) = self.restore_cursor.take()
+        {
+            let mut state = TextEdit::load_state(ui.ctx(), id).unwrap_or_default();
+            state.cursor.set_char_range(Some(range));
+            TextEdit::store_state(ui.ctx(), id, state);
+        }
+
+        // a line of megabytes is laid out around the view only
+        let window = if self.wrap || !self.folds.is_empty() {
+            None
+        } else {
+            let char_width = ui.fonts(|f| layout::column_width(f, &font));
+            let left = ui.cursor().left() + self.text_offset;
+            let clip = ui.clip_rect();
+            let visible = ((clip.left() - left) / char_width).max(0.0) as usize..((clip.right() - left) / char_width) as usize + 1;
+            // a screen on each side, for the scrolling before the next frame
+            let columns = visible.start.saturating_sub(visible.len())..visible.end + visible.len();
+            let cursor = self.id.and_then(|id| TextEdit::load_state(ui.ctx(), id)).and_then(|state| state.cursor.char_range());
+            let carets: Vec<usize> = cursor.iter().flat_map(|range| [range.primary.index, range.secondary.index]).collect();
+            HorizontalWindow::new(&self.code, columns, &carets, self.tab_size)
+        };
+        if let Some((window, window_gaps)) = &window {
+            *gaps.borrow_mut() = window_gaps.clone();
+            // the state of the `TextEdit` is in the chars of the code between the frames
+            if let Some(id) = self.id
+                && let Some(mut state) = TextEdit::load_state(ui.ctx(), id)
+                && let Some(range) = state.cursor.char_range()
+            {
+                state.cursor.set_char_range(Some(window.to_window_range(range)));
+                TextEdit::store_state(ui.ctx(), id, state);
+            }
+        }
+
+        // the zen mode hides the gutter and the decorations without changing their options
+        let zen = self.zen;
+        let line_numbers = self.line_numbers && !zen;
+        let numbers_width = if line_numbers {
+            gutter::width(ui, &font, self.code.lines().count(), &self.line_numbers_style)
+        } else {
+            0.0
+        };
+        // immutable buffer keeps the selection and copy working in read only mode
+        let mut read_only_code;
+        let mut windowed = None;
+        let mut tracked = None;
+        let text: &mut dyn egui::TextBuffer = match window {
+            Some((window, _)) => windowed.insert(WindowedCode {
+                code: &mut self.code,
+                window,
+                gaps: &gaps,
+                mutable: !self.read_only,
+                change: None,
+            }),
+            None if self.read_only => {
+                read_only_code = self.code.as_str();
+                &mut read_only_code
+            }
+            None => tracked.insert(TrackedCode { code: &mut self.code, change: None }),
+        };
+
+        let mut text_edit = TextEdit::multiline(text)
+            .font(font.clone())
+            .desired_width(f32::INFINITY)
+            .interactive(self.interactive && self.scrub.is_none())
+            .code_editor()
+            .layouter(&mut layouter);
+        let mut background = ui.visuals().text_edit_bg_color();
+        if self.theme_background {
+            background = highlight::background(&self.theme, background);
+        }
+        // painted below the text with the current line
+        text_edit = text_edit.background_color(egui::Color32::TRANSPARENT);
+        // fills the height given by `ui.add_sized` and the justified layouts
+        if ui.layout().vertical_justify() && ui.available_height().is_finite() {
+            text_edit = text_edit.min_size(egui::vec2(0.0, ui.available_height()));
+        }
+
+        let has_diagnostics = !self.diagnostics.is_empty() || !self.lint.is_empty();
+        let diagnostics_width = if !has_diagnostics || !self.diagnostics_gutter || zen {
+            0.0
+        } else {
+            gutter::DIAGNOSTICS_WIDTH
+        };
+        let markers_width = if self.baseline.is_some() && self.change_markers && !zen {
+            gutter::CHANGE_MARKERS_WIDTH
+        } else {
+            0.0
+        };
+        let folding_width = if self.folding && !zen { folding::WIDTH } else { 0.0 };
+        let mut gutter_width = numbers_width + diagnostics_width + markers_width + folding_width;
+        let full_width = ui.available_width();
+        if zen {
+            // a centered column with wide margins
+            let char_width = ui.fonts(|f| f.glyph_width(&font, ' '));
+            // at least 2 chars of margin on each side, none in an editor narrower than that
+            let column = (ZEN_COLUMNS as f32 * char_width).min(full_width - 4.0 * char_width).max(0.0);
+            gutter_width = ((full_width - column) / 2.0).max(0.0);
+            text_edit = text_edit.desired_width(column);
+        }
+        let copies_before = ui.output(|o| o.commands.len());
+        // painted below the gutter and the code once their size is known
+        let background_shape = ui.painter().add(egui::Shape::Noop);
+        let current_line_shape = ui.painter().add(egui::Shape::Noop);
+        let block_shape = ui.painter().add(egui::Shape::Noop);
+        // the block is painted with the selection color of the theme, instead of the selection of the `TextEdit`
+        let block_color = self.block.map(|_| {
+            let mut visuals = ui.visuals().clone();
+            highlight::apply_theme_visuals(&mut visuals, &self.theme);
+            visuals.selection.bg_fill
+        });
+        let (gutter_left, output) = ui
+            .horizontal_top(|ui| {
+                ui.spacing_mut().item_spacing.x = 0.0;
+                highlight::apply_theme_visuals(ui.visuals_mut(), &self.theme);
+                ui.visuals_mut().text_cursor.stroke.width = self.caret_width;
+                ui.visuals_mut().text_cursor.blink = self.caret_blink && settings::animations();
+                if block_color.is_some() {
+                    ui.visuals_mut().selection.bg_fill = egui::Color32::TRANSPARENT;
+                }
+                // the text edit follows the caret with the animation of the style
+                let animation = if self.smooth_scrolling { settings::scroll_animation(ui) } else { egui::style::ScrollAnimation::none() };
+                ui.style_mut().scroll_animation = animation;
+                let gutter_left = ui.cursor().left();
+                ui.add_space(gutter_width);
+                (gutter_left, text_edit.show(ui))
+            })
+            .inner;
+        drop(layouter);
+        let edited = windowed.as_mut().and_then(|windowed| windowed.change.take());
+        let edited = edited.or(tracked.and_then(|tracked| tracked.change));
+        self.window = windowed.map(|windowed| windowed.window);
+        if let Some(change) = edited {
+            merge_change(&mut self.pending_change, change);
+        }
+        self.text_offset = output.galley_pos.x - gutter_left;
+        let response = output.response;
+        // room for centering the last lines, or for scrolling the last line to the top
+        let mut padding = if self.typewriter { ui.clip_rect().height() / 2.0 } else { 0.0 };
+        if self.scroll_past_end {
+            padding = padding.max(ui.clip_rect().height() - ui.fonts(|f| f.row_height(&font)));
+        }
+        ui.add_space(padding);
+        // the gutter is on the background of the app without the theme background
+        let left = if self.theme_background { gutter_left } else { response.rect.left() };
+        let right = if zen { gutter_left + full_width } else { response.rect.right() };
+        let rect = egui::Rect::from_x_y_ranges(left..=right, response.rect.top()..=response.rect.bottom() + padding);
+        ui.painter().set(background_shape, egui::Shape::rect_filled(rect, 0.0, background));
+        if response.has_focus() {
+            self.record_copies(ui, copies_before);
+        }
+        if response.has_focus() && self.escape_behavior != EscapeBehavior::ReleaseFocus {
+            // the filter set by the `TextEdit`, plus `Esc` handled by `escape_ui`
+            let filter = egui::EventFilter {
+                tab: true,
+                horizontal_arrows: true,
+                vertical_arrows: true,
+                escape: true,
+            };
+            ui.memory_mut(|m| m.set_focus_lock_filter(response.id, filter));
+        }
+        let highlight_error = highlight_error.take().filter(|_| !self.highlight_error_reported);
+        self.highlight_error_reported |= highlight_error.is_some();
+        // the state keeps the cursor also when the editor is not focused
+        let galley_cursor_range = output.cursor_range.or(output.state.cursor.char_range());
+        self.caret_rect = galley_cursor_range.map(|range| {
+            output.galley.pos_from_cursor(range.primary).translate(output.galley_pos.to_vec2())
+        });
+        let mut cursor_range = galley_cursor_range;
+        if let Some(window) = &self.window {
+            cursor_range = cursor_range.map(|range| window.to_code_range(range));
+            let mut state = output.state.clone();
+            state.cursor.set_char_range(cursor_range);
+            TextEdit::store_state(ui.ctx(), response.id, state);
+        }
+
+        if self.read_only
+            && let Some(text) = &self.watermark
+        {
+            watermark::paint_watermark(ui, response.rect, text);
+        }
+
+        let typed = self.typewriter && response.has_focus() && (response.changed() || cursor_range != self.cursor_range);
+        // a caret restored before the id is known is revealed once it is placed
+        let reveal = (self.restore_cursor.is_none() && std::mem::take(&mut self.reveal_cursor)) || jumped || typed;
+        if reveal
+            && let Some(rect) = self.caret_rect
+        {
+            ui.scroll_to_rect_animation(rect, Some(egui::Align::Center), self.scroll_animation(ui));
+        }
+
+        if response.changed() {
+            self.update_hunks();
+            self.update_lint();
+        }
+        self.sync_folds();
+        let needs_caret_line = line_numbers || self.current_line || !self.folds.is_empty();
+        let caret_line = cursor_range.filter(|_| needs_caret_line).map(|range| {
+            let index = LineIndex::new(&self.code);
+            index.line_of(index.from_encoded(range.primary.index, PositionEncoding::Char))
+        });
+        if let Some(line) = caret_line {
+            let folds = self.folds.len();
+            self.folds.retain(|fold| !(fold.start < line && line < fold.end));
+            if self.folds.len() != folds {
+                ui.ctx().request_repaint();
+            }
+        }
+        if self.current_line
+            && !zen
+            && let Some(line) = caret_line
+            && let Some(span) = gutter::line_spans(&output.galley).get(line)
+        {
+            let color = self.current_line_color.unwrap_or_else(|| match self.theme.settings.line_highlight {
+                Some(c) => egui::Color32::from_rgba_unmultiplied(c.r, c.g, c.b, c.a),
+                None => highlight::foreground(&self.theme, ui.visuals().text_color()).gamma_multiply(0.08),
+            });
+            let y = egui::Rangef::new(output.galley_pos.y + span.min, output.galley_pos.y + span.max);
+            let rect = egui::Rect::from_x_y_ranges(response.rect.x_range(), y);
+            ui.painter().set(current_line_shape, egui::Shape::rect_filled(rect, 0.0, color));
+        }
+        if line_numbers {
+            gutter::paint_numbers(
+                ui,
+                0,
+                &gutter::line_spans(&output.galley),
+                output.galley_pos,
+                egui::Rangef::new(gutter_left, gutter_left + numbers_width),
+                &font,
+                &self.line_numbers_style,
+                caret_line,
+            );
+        }
+        let mut code_action = None;
+        let mut code_action_error = None;
+        if has_diagnostics {
+            let diagnostics_left = gutter_left + numbers_width;
+            let diagnostics_rect = egui::Rect::from_x_y_ranges(
+                diagnostics_left..=diagnostics_left + diagnostics_width,
+                response.rect.y_range(),
+            );
+            let toggle_menu = commands.contains(&Command::QuickFix);
+            match self.diagnostics_ui(ui, diagnostics_rect, &response, &output.galley, output.galley_pos, toggle_menu) {
+                Some(Ok(action)) => code_action = Some(action),
+                Some(Err(error)) => code_action_error = Some(error),
+                None => {}
+            }
+        }
+        if markers_width > 0.0 {
+            let markers_left = gutter_left + numbers_width + diagnostics_width;
+            let markers_rect = egui::Rect::from_x_y_ranges(
+                markers_left..=markers_left + markers_width,
+                response.rect.y_range(),
+            );
+            self.change_markers_ui(ui, markers_rect, response.id, &output.galley, output.galley_pos, &font);
+        }
+        if folding_width > 0.0 {
+            let folding_left = gutter_left + numbers_width + diagnostics_width + markers_width;
+            let folding_rect = egui::Rect::from_x_y_ranges(
+                folding_left..=folding_left + folding_width,
+                response.rect.y_range(),
+            );
+            self.folding_ui(ui, folding_rect, response.id, &output.galley, output.galley_pos);
+        }
+
+        // TextEdit may lose focus on Esc, so check the key on the frame it was pressed
+        if (response.has_focus() || response.lost_focus()) && escape_cancels && self.highlight_worker.is_busy() {
+            self.cancel();
+        }
+
+        if response.changed()
+            && let Some(before) = &before_edit
+        {
+            self.mirror_linked_edit(ui, response.id, before);
+        }
+        if response.changed()
+            && let Some(range) = cursor_range
+            && range.is_empty()
+        {
+            self.auto_pair(ui, range.primary.index, wrapped_selection);
+            if self.auto_indent {
+                self.indent_new_line(ui, response.id, range.primary.index);
+            }
+            self.electric_indent(ui, response.id, range.primary.index);
+        }
+        if response.has_focus()
+            && let Some(range) = cursor_range
+            && self.cursor_range != Some(range)
+        {
+            self.update_linked_ranges(range.primary.index);
+        }
+        if !zen {
+            self.paint_duplicates(ui, &output.galley, response.rect, output.galley_pos);
+        }
+        if self.cell_markers && !zen {
+            let spans = gutter::line_spans(&output.galley);
+            let x_range = response.rect.x_range();
+            let clicked = cells::cells_ui(ui, response.id, &self.cells(), &spans, &output.galley, output.galley_pos, x_range);
+            run_cell = run_cell.or(clicked);
+        }
+        if self.control_chars {
+            self.paint_control_chars(ui, &output.galley, output.galley_pos, &font);
+        }
+        let whitespace_color = highlight::foreground(&self.theme, ui.visuals().text_color()).gamma_multiply(0.4);
+        whitespace::paint(ui, &output.galley, output.galley_pos, &font, self.show_whitespace, whitespace_color);
+        if self.wrap && self.wrap_indicators {
+            self.paint_wrap_indicators(ui, &output.galley, output.galley_pos, &font);
+        }
+        self.scrub_numbers(ui, &response, &output.galley, output.galley_pos);
+        self.block_drag(ui, &response, &output.galley, output.galley_pos, &font);
+        if let Some(block) = self.block {
+            let color = block_color.unwrap_or(ui.visuals().selection.bg_fill);
+            let shape = self.block_shape(ui, &block, &output.galley, output.galley_pos, &font, color);
+            ui.painter().set(block_shape, shape);
+        }
+        self.paint_linked_ranges(ui, &output.galley, output.galley_pos);
+        if self.search_open {
+            self.paint_search_matches(ui, &output.galley, output.galley_pos);
+        }
+        // the brackets of the long lines laid out in a window are not found
+        if self.match_brackets
+            && !zen
+            && self.window.is_none()
+            && let Some(range) = cursor_range
+        {
+            self.paint_matching_brackets(ui, &output.galley, output.galley_pos, range.primary.index);
+        }
+
+        self.cursor_range = cursor_range;
+        // the drag of a number is committed once done, as a single undo step
+        if self.scrub.is_none() {
+            self.history.record(&self.code, cursor_range, ui.input(|i| i.time));
+        }
+        let cursor = cursor_range.map(|range| range.primary.index);
+        self.signature_help_ui(ui, &response, cursor);
+        self.rename_ui(ui, response.id, &output.galley, output.galley_pos);
+        self.image_preview_ui(ui, &response, &output.galley, output.galley_pos);
+        let open_peek = self.peek_ui(ui, response.id, response.rect);
+        self.shortcut_help_ui(ui, response.id);
+        self.clipboard_history_ui(ui, response.id);
+        self.context_menu_ui(ui, &response);
+
+        // the initial code is not a change
+        self.change = self.pending_change.take().filter(|_| self.id.is_some());
+        self.id = Some(response.id);
+        // the cancelled highlighting was the one of the previous code
+        if self.change.is_some() && self.cancel.is_cancelled() {
+            self.cancel.reset();
+            ui.ctx().request_repaint();
+        }
+        self.emit_events(ui, &response);
+
+        let mut save_result = None;
+        if save_requested {
+            self.emit(EditorEvent::SaveRequested, None);
+            if self.save_on_request && self.path.is_some() {
+                save_result = Some(self.save());
+            }
+        }
+
+        EditorOutput {
+            response,
+            save_requested,
+            save_result,
+            open_peek,
+            code_action,
+            code_action_error,
+            highlight_error,
+            run_cell,
+            changed: self.change.is_some(),
+            change: self.change.clone(),
+            cursor: self.cursor(),
+            selection: self.selection(),
+        }
+    }
+}
+
+/// Lines of the byte `range` of a selection, a selection ending at a line start does not include that line.
+fn selection_lines(index: &LineIndex, range: &Range<usize>) -> Range<usize> {
+    let first = index.line_of(range.start);
+    let last = index.line_of(range.end);
+    let end = if last > first && index.line_range(last).start == range.end { last } else { last + 1 };
+    first..end
+}
+
+/// Moves the caret (and the selection) of the `TextEdit` with `id` by `delta` chars.
+fn shift_caret(ui: &Ui, id: egui::Id, delta: isize) {
+    if delta != 0
+        && let Some(mut state) = TextEdit::load_state(ui.ctx(), id)
+        && let Some(mut range) = state.cursor.char_range()
+    {
+        for cursor in [&mut range.primary, &mut range.secondary] {
+            cursor.index = cursor.index.saturating_add_signed(delta);
+        }
+        state.cursor.set_char_range(Some(range));
+        TextEdit::store_state(ui.ctx(), id, state);
+    }
+}
+
+impl Default for CodeEditor {
+    fn default() -> Self {
+        Self::new("rs", "base16-ocean.dark")
+    }
+}
+
+/// `ui.add(&mut editor)`, like [`CodeEditor::ui`], so the editor works with `add_sized`, `add_enabled` and the layouts.
+impl egui::Widget for &mut CodeEditor {
+    fn ui(self, ui: &mut Ui) -> egui::Response {
+        CodeEditor::ui(self, ui)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Shows the editor in a frame of a headless context.
+    fn frame(ctx: &egui::Context, editor: &mut CodeEditor) -> EditorOutput {
+        frame_with(ctx, editor, Vec::new())
+    }
+
+    /// Like [`frame`] with the input `events`.
+    fn frame_with(ctx: &egui::Context, editor: &mut CodeEditor, events: Vec<egui::Event>) -> EditorOutput {
+        let mut output = None;
+        let input = egui::RawInput { events, ..Default::default() };
+        let _ = ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| output = Some(editor.show(ui)));
+        });
+        output.unwrap()
+    }
+
+    /// Press of `key` without modifiers, for [`frame_with`].
+    pub(crate) fn key_press(key: egui::Key) -> egui::Event {
+        egui::Event::Key { key, physical_key: None, pressed: true, repeat: false, modifiers: egui::Modifiers::NONE }
+    }
+
+    #[test]
+    fn dumps_the_highlighted_spans() {
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark");
+        editor.set_code("fn main() {}\nlet x = 1;");
+        let dump = editor.debug_layout_dump();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines[0], "1:1-1:2 #b48ead \"fn\"");
+        assert_eq!(lines[2], "1:4-1:7 #8fa1b3 \"main\"");
+        assert_eq!(lines[8], "2:1-2:3 #b48ead \"let\"");
+        assert_eq!(lines.last(), Some(&"2:10-2:10 fg \";\""));
+        assert_eq!(dump, editor.debug_layout_dump());
+
+        editor.set_theme("InspiredGitHub");
+        assert_ne!(dump, editor.debug_layout_dump());
+    }
+
+    #[test]
+    fn runs_the_sent_commands_without_focus() {
+        let ctx = egui::Context::default();
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark");
+        editor.set_code("let x = true;");
+        frame(&ctx, &mut editor);
+        assert!(editor.id().is_some_and(|id| !ctx.memory(|m| m.has_focus(id))));
+
+        editor.select_all();
+        editor.send_command(Command::ToggleComment);
+        editor.send_command(Command::ToggleZenMode);
+        let output = frame(&ctx, &mut editor);
+        assert_eq!(editor.code, "// let x = true;");
+        assert!(editor.is_zen());
+        assert!(output.changed);
+        assert!(!output.save_requested);
+
+        editor.send_command(Command::Undo);
+        editor.send_command(Command::Save);
+        let output = frame(&ctx, &mut editor);
+        assert_eq!(editor.code, "let x = true;");
+        assert!(output.save_requested);
+
+        // a command is run once
+        frame(&ctx, &mut editor);
+        assert_eq!(editor.code, "let x = true;");
+        assert!(!frame(&ctx, &mut editor).save_requested);
+    }
+
+    #[test]
+    fn escape_cancels_only_a_running_highlighting() {
+        let ctx = egui::Context::default();
+        // a large code highlighted on the UI thread, where nothing runs between the frames
+        let mut editor = CodeEditor::new("txt", "base16-ocean.dark");
+        editor.set_code("x\n".repeat(20_000));
+        frame(&ctx, &mut editor);
+        ctx.memory_mut(|m| m.request_focus(editor.id().unwrap()));
+        frame(&ctx, &mut editor);
+
+        let escape = key_press(egui::Key::Escape);
+        frame_with(&ctx, &mut editor, vec![escape]);
+        assert!(!editor.cancel_token().is_cancelled());
+    }
+
+    #[test]
+    fn escape_cancels_the_highlighting_of_the_worker() {
+        let ctx = egui::Context::default();
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark").background_highlighting(1);
+        editor.set_code("fn main() { let x = 1; }\n".repeat(100_000));
+        frame(&ctx, &mut editor);
+        ctx.memory_mut(|m| m.request_focus(editor.id().unwrap()));
+        frame(&ctx, &mut editor);
+        assert!(editor.highlight_worker.is_busy());
+
+        let escape = key_press(egui::Key::Escape);
+        frame_with(&ctx, &mut editor, vec![escape]);
+        assert!(editor.cancel_token().is_cancelled());
+        let start = std::time::Instant::now();
+        while editor.highlight_worker.is_busy() && start.elapsed() < std::time::Duration::from_secs(30) {
+            frame(&ctx, &mut editor);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        // the partial job of the cancelled run is not swapped in
+        assert!(!editor.highlight_worker.is_busy());
+        assert_eq!(editor.highlight_worker.revision(), 0);
+    }
+
+    #[test]
+    fn reports_the_changed_part_of_the_frame() {
+        let ctx = egui::Context::default();
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark");
+        editor.set_code("let x = 1;");
+        assert!(!frame(&ctx, &mut editor).changed);
+
+        editor.set_code("let x = 2;");
+        editor.set_code("let y = 2;");
+        let output = frame(&ctx, &mut editor);
+        assert_eq!(output.change, Some(CodeChange { removed: 4..9, inserted: 4..9 }));
+        assert!(!frame(&ctx, &mut editor).changed);
+
+        ctx.memory_mut(|m| m.request_focus(editor.id().unwrap()));
+        frame(&ctx, &mut editor);
+        let output = frame_with(&ctx, &mut editor, vec![egui::Event::Text("!".to_owned())]);
+        assert_eq!(editor.code, "let y = 2;!");
+        assert_eq!(output.change, Some(CodeChange { removed: 10..10, inserted: 10..11 }));
+    }
+
+    #[test]
+    fn changes_restart_the_cancelled_highlighting() {
+        let ctx = egui::Context::default();
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark");
+        editor.set_code("let x = 1;");
+        frame(&ctx, &mut editor);
+        editor.cancel();
+        frame(&ctx, &mut editor);
+        assert!(editor.cancel_token().is_cancelled());
+
+        editor.set_code("let x = 2;");
+        frame(&ctx, &mut editor);
+        assert!(!editor.cancel_token().is_cancelled());
+    }
+
+    #[test]
+    fn escape_clears_the_selection_then_closes_the_popups_then_leaves() {
+        let ctx = egui::Context::default();
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark");
+        editor.set_code("let x = 1;");
+        frame(&ctx, &mut editor);
+        let id = editor.id().unwrap();
+        ctx.memory_mut(|m| m.request_focus(id));
+        frame(&ctx, &mut editor);
+        let escape = key_press(egui::Key::Escape);
+        let focused = |ctx: &egui::Context| ctx.memory(|m| m.has_focus(id));
+
+        editor.select_all();
+        frame(&ctx, &mut editor);
+        frame_with(&ctx, &mut editor, vec![escape.clone()]);
+        assert!(editor.selection().is_some_and(|range| range.is_empty()));
+        assert!(focused(&ctx));
+
+        editor.send_command(Command::Find);
+        frame(&ctx, &mut editor);
+        assert!(editor.is_search_open());
+        // back in the code with the search bar open
+        ctx.memory_mut(|m| m.request_focus(id));
+        frame(&ctx, &mut editor);
+        frame_with(&ctx, &mut editor, vec![escape.clone()]);
+        assert!(!editor.is_search_open());
+        assert!(focused(&ctx));
+
+        frame_with(&ctx, &mut editor, vec![escape]);
+        assert!(!focused(&ctx));
+    }
+
+    #[test]
+    fn undo_command_reverts_the_code_set_since_the_last_frame() {
+        let ctx = egui::Context::default();
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark");
+        editor.set_code("let x = 1;");
+        frame(&ctx, &mut editor);
+        editor.set_code("let x = 2;");
+        editor.send_command(Command::Undo);
+        frame(&ctx, &mut editor);
+        assert_eq!(editor.code, "let x = 1;");
+
+        editor.send_command(Command::Redo);
+        frame(&ctx, &mut editor);
+        assert_eq!(editor.code, "let x = 2;");
+
+        editor.set_read_only(true);
+        assert!(!editor.undo());
+        assert_eq!(editor.code, "let x = 2;");
+    }
+
+    #[test]
+    fn read_only_refuses_the_edits() {
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark").read_only(true);
+        editor.set_code("let x = 1;\n");
+        editor.set_baseline("let x = 2;\n");
+        editor.update_hunks();
+        let checkpoint = editor.checkpoint("start");
+        let edit = (Position::new(0, 0)..Position::new(0, 3), "const".to_owned());
+
+        assert!(!editor.insert_text("y"));
+        assert!(!editor.revert_hunk(0));
+        assert_eq!(editor.apply_text_edits(&[edit]), Err(TextEditError::ReadOnly));
+        assert_eq!(editor.apply_unified_diff("@@ -1 +1 @@\n-let x = 1;\n+let x = 3;\n"), Err(PatchError::ReadOnly));
+        assert!(!editor.toggle_comment(0..1));
+        assert!(!editor.restore(checkpoint));
+        assert_eq!(editor.code, "let x = 1;\n");
+    }
+
+    #[test]
+    fn replace_all_is_its_own_undo_step() {
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark");
+        editor.set_code("x");
+        editor.history.record(&editor.code, None, 10.0);
+        editor.set_code("x a");
+        editor.history.record(&editor.code, None, 10.2);
+
+        editor.search_mut().query = "a".to_owned();
+        editor.search_mut().replacement = "b".to_owned();
+        assert_eq!(editor.replace_all(), 1);
+        // typed right after the replacement
+        editor.set_code("x b c");
+        editor.history.record(&editor.code, None, 10.4);
+
+        assert!(editor.undo());
+        assert_eq!(editor.code, "x b");
+        assert!(editor.undo());
+        assert_eq!(editor.code, "x a");
+    }
+
+    #[test]
+    fn comment_toggle_is_its_own_undo_step() {
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark");
+        editor.set_code("a");
+        editor.history.record(&editor.code, None, 10.0);
+        editor.set_code("a b");
+        editor.history.record(&editor.code, None, 10.2);
+
+        assert!(editor.toggle_comment(0..1));
+        assert_eq!(editor.code, "// a b");
+        editor.set_code("// a b c");
+        editor.history.record(&editor.code, None, 10.4);
+
+        assert!(editor.undo());
+        assert_eq!(editor.code, "// a b");
+        assert!(editor.undo());
+        assert_eq!(editor.code, "a b");
+    }
+
+    #[test]
+    fn types_over_and_deletes_the_auto_pairs() {
+        let ctx = egui::Context::default();
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark").auto_pairs(AutoPairs::default());
+        editor.set_code("x");
+        frame(&ctx, &mut editor);
+        ctx.memory_mut(|m| m.request_focus(editor.id().unwrap()));
+        editor.set_cursor(Position::new(0, 1));
+        frame(&ctx, &mut editor);
+        let typed = |editor: &mut CodeEditor, text: &str| {
+            frame_with(&ctx, editor, vec![egui::Event::Text(text.to_owned())]);
+        };
+
+        typed(&mut editor, "(");
+        assert_eq!(editor.code, "x()");
+        typed(&mut editor, ")");
+        assert_eq!(editor.code, "x()");
+
+        typed(&mut editor, " ");
+        typed(&mut editor, "[");
+        assert_eq!(editor.code, "x() []");
+        let backspace = key_press(egui::Key::Backspace);
+        frame_with(&ctx, &mut editor, vec![backspace]);
+        assert_eq!(editor.code, "x() ");
+    }
+
+    #[test]
+    fn no_max_line_length_disables_the_lint() {
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark").lint_line_length(4, Severity::Warning);
+        editor.set_code("abcdef\nab");
+        editor.update_lint();
+        assert_eq!(editor.long_line_count(), 1);
+        assert_eq!(editor.lint.len(), 1);
+
+        editor.set_max_line_length(None);
+        editor.update_lint();
+        assert_eq!(editor.long_line_count(), 0);
+        assert!(editor.lint.is_empty());
+    }
+
+    #[test]
+    fn rename_is_its_own_undo_step() {
+        let ctx = egui::Context::default();
+        let mut editor = CodeEditor::new("rs", "base16-ocean.dark");
+        editor.set_code("let a = 1;");
+        frame(&ctx, &mut editor);
+        editor.set_code("let a = 1; a");
+        editor.set_cursor(Position::new(0, 4));
+        frame(&ctx, &mut editor);
+
+        editor.start_rename();
+        frame(&ctx, &mut editor);
+        editor.rename.as_mut().unwrap().new_name = "b".to_owned();
+        let enter = key_press(egui::Key::Enter);
+        frame_with(&ctx, &mut editor, vec![enter]);
+        assert_eq!(editor.code, "let b = 1; b");
+
+        assert!(editor.step_history(false));
+        assert_eq!(editor.code, "let a = 1; a");
+    }
+}
@@ -0,0 +1,110 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::position::{LineIndex, Position, PositionEncoding};
+
+/// Error of [`apply_text_edits`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TextEditError {
+    /// Edits with the given indices overlap each other.
+    Overlapping(usize, usize),
+    /// The editor is read only, see [`CodeEditor::read_only`](crate::CodeEditor::read_only).
+    ReadOnly,
+}
+
+impl fmt::Display for TextEditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextEditError::Overlapping(a, b) => write!(f, "text edits {a} and {b} overlap"),
+            TextEditError::ReadOnly => write!(f, "the editor is read only"),
+        }
+    }
+}
+
+impl std::error::Error for TextEditError {}
+
+/// Applies the edits, like the LSP `TextEdit[]`, to `text`.
+///
+/// All ranges refer to the original text, the edits must not overlap, in any order.
+/// An insert at the start of a replaced range goes before the replacement.
+pub fn apply_text_edits(
+    text: &str,
+    edits: &[(Range<Position>, String)],
+    encoding: PositionEncoding,
+) -> Result<String, TextEditError> {
+    let index = LineIndex::new(text);
+    let mut ranges: Vec<(usize, Range<usize>)> = edits
+        .iter()
+        .enumerate()
+        .map(|(i, (range, _))| {
+            let start = index.offset(range.start, encoding);
+            let end = index.offset(range.end, encoding).max(start);
+            (i, start..end)
+        })
+        .collect();
+    // by start, the inserts before the replaced range starting at their position,
+    // the stable sort keeps the order of the inserts at the same position
+    ranges.sort_by_key(|(_, range)| (range.start, range.end));
+
+    // sorted, an edit overlapping any other overlaps the one before it
+    for pair in ranges.windows(2) {
+        let ((previous, previous_range), (i, range)) = (&pair[0], &pair[1]);
+        if previous_range.end > range.start {
+            return Err(TextEditError::Overlapping(*previous.min(i), *previous.max(i)));
+        }
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for (i, range) in ranges {
+        result.push_str(&text[last..range.start]);
+        result.push_str(&edits[i].1);
+        last = range.end;
+    }
+    result.push_str(&text[last..]);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(start: (usize, usize), end: (usize, usize), text: &str) -> (Range<Position>, String) {
+        (Position::new(start.0, start.1)..Position::new(end.0, end.1), text.to_owned())
+    }
+
+    #[test]
+    fn applies_edits_in_any_order() {
+        let text = "let a = 1;\nlet b = 2;\n";
+        let edits = [edit((1, 4), (1, 5), "y"), edit((0, 4), (0, 5), "x")];
+        let result = apply_text_edits(text, &edits, PositionEncoding::Utf16).unwrap();
+        assert_eq!(result, "let x = 1;\nlet y = 2;\n");
+    }
+
+    #[test]
+    fn keeps_the_order_of_the_inserts_at_a_position() {
+        let edits = [edit((0, 0), (0, 0), "a"), edit((0, 0), (0, 0), "b")];
+        assert_eq!(apply_text_edits("c", &edits, PositionEncoding::Utf8).unwrap(), "abc");
+    }
+
+    #[test]
+    fn inserts_before_a_replacement_at_the_same_start() {
+        let edits = [edit((0, 0), (0, 3), "new"), edit((0, 0), (0, 0), "use ")];
+        assert_eq!(apply_text_edits("old;", &edits, PositionEncoding::Utf8).unwrap(), "use new;");
+    }
+
+    #[test]
+    fn rejects_overlapping_edits() {
+        let edits = [edit((0, 0), (0, 2), ""), edit((0, 4), (0, 5), ""), edit((0, 1), (0, 3), "")];
+        assert_eq!(apply_text_edits("abcdef", &edits, PositionEncoding::Utf8), Err(TextEditError::Overlapping(0, 2)));
+    }
+
+    #[test]
+    fn counts_utf16_columns() {
+        // `😀` is 2 UTF-16 code units
+        let edits = [edit((0, 3), (0, 4), "B")];
+        assert_eq!(apply_text_edits("a😀b", &edits, PositionEncoding::Utf16).unwrap(), "a😀B");
+        // 3 chars is the end of the line
+        assert_eq!(apply_text_edits("a😀b", &edits, PositionEncoding::Char).unwrap(), "a😀bB");
+    }
+}
@@ -0,0 +1,28 @@
+use std::fmt;
+
+/// Error of [`CodeEditor::try_new`](crate::CodeEditor::try_new) and [`CodeViewer::try_new`](crate::CodeViewer::try_new),
+/// and of the loading of custom syntaxes and themes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LitecodeError {
+    /// No syntax for the file extension or with the name.
+    UnknownSyntax(String),
+    /// No built-in theme with the name.
+    UnknownTheme(String),
+    /// A syntax definition could not be loaded, with the syntect error.
+    LoadSyntax(String),
+    /// A `.tmTheme` could not be loaded, with the syntect error.
+    LoadTheme(String),
+}
+
+impl fmt::Display for LitecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LitecodeError::UnknownSyntax(ext) => write!(f, "no syntax for `{ext}`"),
+            LitecodeError::UnknownTheme(name) => write!(f, "no theme named `{name}`"),
+            LitecodeError::LoadSyntax(message) => write!(f, "cannot load the syntaxes: {message}"),
+            LitecodeError::LoadTheme(message) => write!(f, "cannot load the theme: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for LitecodeError {}
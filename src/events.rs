@@ -0,0 +1,199 @@
+use std::ops::Range;
+
+use egui::{DroppedFile, TextBuffer};
+
+use crate::linked;
+
+/// Events of the [`CodeEditor`](crate::CodeEditor) which can be handled with `on_event`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EditorEvent {
+    FocusGained,
+    FocusLost,
+    /// `Ctrl+S` was pressed while the editor has focus.
+    SaveRequested,
+    /// The code changed since the previous frame, by typing, a command or the API, see `EventArgs::change`.
+    TextChanged,
+    LanguageChanged,
+    /// A file was dropped on the editor, see `EventArgs::dropped_file`.
+    FileDropped,
+}
+
+/// Arguments passed to the event callbacks.
+#[derive(Debug)]
+pub struct EventArgs<'a> {
+    pub event: EditorEvent,
+    pub code: &'a str,
+    /// Name of the current syntax (for example "Rust").
+    pub language: &'a str,
+    pub dropped_file: Option<&'a DroppedFile>,
+    /// Change of the code in the frame, for [`EditorEvent::TextChanged`].
+    pub change: Option<&'a CodeChange>,
+}
+
+/// Change of the code between two frames, as the range of the replaced text.
+///
+/// The edits of a frame (like the ones at several carets) are merged into a single change covering them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CodeChange {
+    /// Byte range of the replaced text in the previous code.
+    pub removed: Range<usize>,
+    /// Byte range of the text replacing it in the new code.
+    pub inserted: Range<usize>,
+}
+
+impl CodeChange {
+    /// Change from `old` to `new`, `None` if they are equal.
+    pub fn between(old: &str, new: &str) -> Option<Self> {
+        if old == new {
+            return None;
+        }
+        let prefix = linked::common_prefix(old, new);
+        let suffix = linked::common_suffix(&old[prefix..], &new[prefix..]);
+        Some(Self {
+            removed: prefix..old.len() - suffix,
+            inserted: prefix..new.len() - suffix,
+        })
+    }
+
+    /// Change replacing the bytes of `removed` with `inserted` bytes.
+    pub(crate) fn replacing(removed: Range<usize>, inserted: usize) -> Self {
+        let start = removed.start;
+        Self { removed, inserted: start..start + inserted }
+    }
+
+    /// This change followed by `next` (a change of the code after this one), as a single change covering both.
+    pub(crate) fn then(&self, next: &CodeChange) -> CodeChange {
+        // the union of the two changes in the code between them
+        let start = self.inserted.start.min(next.removed.start);
+        let end = self.inserted.end.max(next.removed.end);
+        CodeChange {
+            removed: start..end - self.inserted.end + self.removed.end,
+            inserted: start..end - next.removed.end + next.inserted.end,
+        }
+    }
+}
+
+/// Merges `change` into the `pending` change of the frame, the edits replacing nothing with nothing are dropped.
+pub(crate) fn merge_change(pending: &mut Option<CodeChange>, change: CodeChange) {
+    let merged = match pending.take() {
+        Some(pending) => pending.then(&change),
+        None => change,
+    };
+    *pending = Some(merged).filter(|change| !change.removed.is_empty() || !change.inserted.is_empty());
+}
+
+/// The code edited by the `TextEdit`, its edits are recorded as a [`CodeChange`].
+pub(crate) struct TrackedCode<'a> {
+    pub(crate) code: &'a mut String,
+    pub(crate) change: Option<CodeChange>,
+}
+
+impl TrackedCode<'_> {
+    fn byte_of(&self, char_index: usize) -> usize {
+        self.code.char_indices().nth(char_index).map_or(self.code.len(), |(i, _)| i)
+    }
+}
+
+impl TextBuffer for TrackedCode<'_> {
+    fn is_mutable(&self) -> bool {
+        true
+    }
+
+    fn as_str(&self) -> &str {
+        self.code
+    }
+
+    fn insert_text(&mut self, text: &str, char_index: usize) -> usize {
+        let byte = self.byte_of(char_index);
+        self.code.insert_str(byte, text);
+        merge_change(&mut self.change, CodeChange::replacing(byte..byte, text.len()));
+        text.chars().count()
+    }
+
+    fn delete_char_range(&mut self, char_range: Range<usize>) {
+        let range = self.byte_of(char_range.start)..self.byte_of(char_range.end);
+        self.code.replace_range(range.clone(), "");
+        merge_change(&mut self.change, CodeChange::replacing(range, 0));
+    }
+
+    fn replace_with(&mut self, text: &str) {
+        if let Some(change) = CodeChange::between(self.code, text) {
+            merge_change(&mut self.change, change);
+            text.clone_into(self.code);
+        }
+    }
+
+    fn type_id(&self) -> std::any::TypeId {
+        std::any::TypeId::of::<TrackedCode<'static>>()
+    }
+}
+
+pub type EventCallback = Box<dyn FnMut(&EventArgs) + Send>;
+
+/// Registered callbacks of an editor.
+#[derive(Default)]
+pub(crate) struct EventRegistry {
+    callbacks: Vec<(EditorEvent, EventCallback)>,
+}
+
+impl EventRegistry {
+    pub(crate) fn add(&mut self, event: EditorEvent, callback: EventCallback) {
+        self.callbacks.push((event, callback));
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    pub(crate) fn emit(&mut self, args: &EventArgs) {
+        for (event, callback) in &mut self.callbacks {
+            if *event == args.event {
+                callback(args);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(removed: Range<usize>, inserted: Range<usize>) -> Option<CodeChange> {
+        Some(CodeChange { removed, inserted })
+    }
+
+    #[test]
+    fn change_between_codes() {
+        assert_eq!(CodeChange::between("abc", "abc"), None);
+        assert_eq!(CodeChange::between("abc", "aXc"), change(1..2, 1..2));
+        assert_eq!(CodeChange::between("aa", "aaa"), change(2..2, 2..3));
+        assert_eq!(CodeChange::between("abcd", "ad"), change(1..3, 1..1));
+        assert_eq!(CodeChange::between("é", "è"), change(0..2, 0..2));
+    }
+
+    #[test]
+    fn merges_the_changes_of_a_frame() {
+        // "abc" -> "aXbc" -> "aXb"
+        let mut pending = None;
+        merge_change(&mut pending, CodeChange::replacing(1..1, 1));
+        merge_change(&mut pending, CodeChange::replacing(3..4, 0));
+        assert_eq!(pending, change(1..3, 1..3));
+        // "abc" -> "aXbc" -> "abc"
+        let mut pending = None;
+        merge_change(&mut pending, CodeChange::replacing(1..1, 1));
+        merge_change(&mut pending, CodeChange::replacing(1..2, 0));
+        assert_eq!(pending, None);
+    }
+
+    #[test]
+    fn tracks_the_edits_of_the_text_edit() {
+        let mut code = "fn main() {}".to_owned();
+        let mut tracked = TrackedCode { code: &mut code, change: None };
+        tracked.delete_char_range(3..7);
+        tracked.insert_text("é", 3);
+        assert_eq!(tracked.change, change(3..7, 3..5));
+        tracked.replace_with("fn é() {}");
+        assert_eq!(tracked.change, change(3..7, 3..5));
+        assert_eq!(code, "fn é() {}");
+    }
+}
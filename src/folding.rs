@@ -0,0 +1,258 @@
+use std::ops::Range;
+
+use egui::text::LayoutJob;
+use egui::{Color32, FontId, Pos2, Rect, Stroke, Ui, Vec2};
+
+use crate::indent::indentation;
+use crate::position::LineIndex;
+
+/// How a [`CodeEditor`](crate::CodeEditor) finds the regions it offers to fold.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FoldMethod {
+    /// A line followed by more indented lines, like in Python or YAML.
+    #[default]
+    Indentation,
+    /// The lines between an opening and a closing bracket of the syntax, like `{` and `}` in Rust.
+    Scopes,
+}
+
+/// Width of the strip with the fold markers.
+pub(crate) const WIDTH: f32 = 14.0;
+
+/// Font size of the folded lines, big enough for egui and small enough to take no space.
+pub(crate) const HIDDEN_SIZE: f32 = 0.01;
+
+/// Foldable regions of `code` by [`FoldMethod::Indentation`], as ranges of lines starting with the kept header line.
+pub(crate) fn indent_regions(code: &str) -> Vec<Range<usize>> {
+    let lines: Vec<&str> = code.split('\n').collect();
+    let mut regions = Vec::new();
+    // header lines waiting for their end, with their indentation
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut last_text = None;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let width = indentation(line).len();
+        while let Some(&(header, header_width)) = open.last()
+            && header_width >= width
+        {
+            open.pop();
+            push_region(&mut regions, header, last_text.map_or(header, |last| last + 1));
+        }
+        open.push((i, width));
+        last_text = Some(i);
+    }
+    while let Some((header, _)) = open.pop() {
+        push_region(&mut regions, header, last_text.map_or(header, |last| last + 1));
+    }
+    regions.sort_by_key(|region| region.start);
+    regions
+}
+
+/// Foldable regions by [`FoldMethod::Scopes`], from the opened (`true`) and closed (`false`) blocks of every line.
+///
+/// The line of the closing bracket stays visible.
+pub(crate) fn scope_regions<'a>(blocks: impl Iterator<Item = &'a [bool]>) -> Vec<Range<usize>> {
+    let mut regions = Vec::new();
+    let mut open = Vec::new();
+    for (i, line) in blocks.enumerate() {
+        for &opens in line {
+            if opens {
+                open.push(i);
+            } else if let Some(header) = open.pop() {
+                push_region(&mut regions, header, i);
+            }
+        }
+    }
+    regions.sort_by_key(|region| (region.start, std::cmp::Reverse(region.end)));
+    // only the outermost region of a header, like `{ (` opened on one line
+    regions.dedup_by_key(|region| region.start);
+    regions
+}
+
+/// Adds the region when it hides at least one line.
+fn push_region(regions: &mut Vec<Range<usize>>, header: usize, end: usize) {
+    if end > header + 1 {
+        regions.push(header..end);
+    }
+}
+
+/// `folds` of `old` moved to the lines of `new`.
+///
+/// The edited lines are found by comparing the start and the end of the texts,
+/// the folds with an edited hidden line are dropped, the edits before keep their hidden lines under the header.
+pub(crate) fn shift_folds(folds: &[Range<usize>], old: &str, new: &str) -> Vec<Range<usize>> {
+    if old == new {
+        return folds.to_vec();
+    }
+    let old_lines: Vec<&str> = old.split('\n').collect();
+    let new_lines: Vec<&str> = new.split('\n').collect();
+    let prefix = old_lines.iter().zip(&new_lines).take_while(|(a, b)| a == b).count();
+    let max_suffix = old_lines.len().min(new_lines.len()) - prefix;
+    let suffix = old_lines
+        .iter()
+        .rev()
+        .zip(new_lines.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let changed = prefix..old_lines.len() - suffix;
+    let delta = new_lines.len() as isize - old_lines.len() as isize;
+
+    folds
+        .iter()
+        .filter_map(|fold| {
+            if changed.start >= fold.end {
+                Some(fold.clone())
+            } else if changed.end <= fold.start + 1 {
+                Some(fold.start.saturating_add_signed(delta)..fold.end.saturating_add_signed(delta))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Byte ranges of the lines hidden by `folds`, sorted and merged.
+pub(crate) fn hidden_bytes(text: &str, folds: &[Range<usize>]) -> Vec<Range<usize>> {
+    let index = LineIndex::new(text);
+    let line_start = |line: usize| {
+        if line < index.line_count() {
+            index.line_range(line).start
+        } else {
+            text.len()
+        }
+    };
+    let mut ranges: Vec<Range<usize>> = folds
+        .iter()
+        .filter(|fold| fold.start + 1 < index.line_count())
+        .map(|fold| line_start(fold.start + 1)..line_start(fold.end))
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Lays out the `hidden` bytes of the job with a tiny transparent font, so their rows take no space.
+pub(crate) fn hide(job: &mut LayoutJob, hidden: &[Range<usize>]) {
+    if hidden.is_empty() {
+        return;
+    }
+    let mut sections = Vec::with_capacity(job.sections.len() + 2 * hidden.len());
+    for section in job.sections.drain(..) {
+        let mut start = section.byte_range.start;
+        let end = section.byte_range.end;
+        for range in hidden {
+            if range.end <= start || range.start >= end {
+                continue;
+            }
+            if range.start > start {
+                let mut visible = section.clone();
+                visible.byte_range = start..range.start;
+                sections.push(visible);
+            }
+            let mut folded = section.clone();
+            folded.byte_range = range.start.max(start)..range.end.min(end);
+            folded.leading_space = 0.0;
+            folded.format.font_id = FontId::new(HIDDEN_SIZE, folded.format.font_id.family.clone());
+            folded.format.color = Color32::TRANSPARENT;
+            folded.format.line_height = Some(0.0);
+            sections.push(folded);
+            start = range.end.min(end);
+        }
+        if start < end {
+            let mut visible = section;
+            visible.byte_range = start..end;
+            sections.push(visible);
+        }
+    }
+    job.sections = sections;
+}
+
+/// Paints the marker of a foldable line in the `rect`, a triangle pointing right when `folded`, down otherwise.
+pub(crate) fn paint_marker(ui: &Ui, rect: Rect, folded: bool, hovered: bool) {
+    let color = if hovered {
+        ui.visuals().strong_text_color()
+    } else {
+        ui.visuals().weak_text_color()
+    };
+    let center = rect.center();
+    let size = 3.5;
+    let points = if folded {
+        vec![
+            center + Vec2::new(-size * 0.6, -size),
+            center + Vec2::new(size * 0.9, 0.0),
+            center + Vec2::new(-size * 0.6, size),
+        ]
+    } else {
+        vec![
+            center + Vec2::new(-size, -size * 0.6),
+            center + Vec2::new(size, -size * 0.6),
+            center + Vec2::new(0.0, size * 0.9),
+        ]
+    };
+    ui.painter().add(egui::Shape::convex_polygon(points, color, Stroke::NONE));
+}
+
+/// Rect of the placeholder shown after the header of a fold at `pos`.
+pub(crate) fn placeholder_rect(pos: Pos2, row_height: f32) -> Rect {
+    Rect::from_min_size(pos + Vec2::new(6.0, 1.0), Vec2::new(22.0, (row_height - 2.0).max(1.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nests_the_indentation_regions() {
+        assert_eq!(indent_regions("a:\n  b:\n    c\n  d\ne"), vec![0..4, 1..3]);
+        assert_eq!(indent_regions("a\nb"), Vec::<Range<usize>>::new());
+        assert_eq!(indent_regions(""), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn keeps_the_blank_lines_inside_a_region() {
+        assert_eq!(indent_regions("a:\n  b\n\n  c\nd"), vec![0..4]);
+        // not the blank lines after it
+        assert_eq!(indent_regions("a:\n  b\n\n\nc"), vec![0..2]);
+    }
+
+    #[test]
+    fn folds_the_outermost_scope_of_a_line() {
+        let blocks: [&[bool]; 4] = [&[true], &[true], &[false], &[false]];
+        assert_eq!(scope_regions(blocks.into_iter()), vec![0..3]);
+        let blocks: [&[bool]; 4] = [&[true, true], &[], &[false], &[false]];
+        assert_eq!(scope_regions(blocks.into_iter()), vec![0..3]);
+        let blocks: [&[bool]; 2] = [&[false], &[true]];
+        assert_eq!(scope_regions(blocks.into_iter()), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn moves_the_folds_with_the_edits_above() {
+        let old = "a\nb\nc:\n d\n e\nf";
+        let folds = [2..5];
+        assert_eq!(shift_folds(&folds, old, &format!("x\ny\n{old}")), vec![4..7]);
+        assert_eq!(shift_folds(&folds, old, "b\nc:\n d\n e\nf"), vec![1..4]);
+        // the header line and the lines after the fold
+        assert_eq!(shift_folds(&folds, old, "a\nb\nc: # x\n d\n e\nf"), vec![2..5]);
+        assert_eq!(shift_folds(&folds, old, "a\nb\nc:\n d\n e\nf\ng"), vec![2..5]);
+        // an edited hidden line
+        assert_eq!(shift_folds(&folds, old, "a\nb\nc:\n D\n e\nf"), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn hides_the_lines_after_the_headers() {
+        let text = "a\nb\nc\nd\ne";
+        assert_eq!(hidden_bytes(text, &[0..2, 1..3]), vec![2..6]);
+        assert_eq!(hidden_bytes(text, &[3..5]), vec![8..9]);
+        assert_eq!(hidden_bytes(text, &[4..6]), Vec::<Range<usize>>::new());
+    }
+}
@@ -0,0 +1,98 @@
+use std::borrow::Cow;
+
+use egui::{FontData, FontDefinitions, FontFamily, FontId, Ui};
+
+/// Name of the font family installed by [`CodeFont`].
+pub const CODE_FAMILY: &str = "litecode-code";
+
+/// Name of the font family of the bold text of the themes, installed with [`CODE_FAMILY`].
+pub const CODE_BOLD_FAMILY: &str = "litecode-code-bold";
+
+/// A programming font (like JetBrains Mono or Fira Code) and its fallbacks for the code of the editors.
+///
+/// # Implement
+///
+/// Use `CodeFont::new(name, bytes)` with the content of the `.ttf` or `.otf` file to create a new instance,
+/// and `fallback(name, bytes)` to add the fonts for the glyphs it misses (CJK, symbols).\
+/// Use `bold(name, bytes)` with the bold face of the font for the bold keywords of the themes.\
+/// Then `code_font.install(&mut fonts)` on the definitions given to `ctx.set_fonts(fonts)`,
+/// or `code_font.install_in(ctx)` over the default egui fonts.
+///
+/// # Usage
+///
+/// The fonts are installed in the [`CODE_FAMILY`] family, followed by the monospace family of egui,
+/// the editors and the viewers use it when it is installed. The other monospace text of the app is unchanged.\
+/// egui does not shape the text, so the ligatures of the font are not applied.\
+/// egui has no font weights, the bold text is in the regular font without a bold face.
+#[derive(Clone, Debug)]
+pub struct CodeFont {
+    fonts: Vec<(String, FontData)>,
+    bold: Option<(String, FontData)>,
+}
+
+impl CodeFont {
+    pub fn new(name: impl Into<String>, bytes: impl Into<Cow<'static, [u8]>>) -> Self {
+        Self {
+            fonts: vec![(name.into(), font_data(bytes))],
+            bold: None,
+        }
+    }
+
+    /// Adds a font used for the glyphs missing in the previous ones.
+    pub fn fallback(mut self, name: impl Into<String>, bytes: impl Into<Cow<'static, [u8]>>) -> Self {
+        self.fonts.push((name.into(), font_data(bytes)));
+        self
+    }
+
+    /// Sets the bold face of the font, the glyphs it misses are taken from the regular fonts.
+    pub fn bold(mut self, name: impl Into<String>, bytes: impl Into<Cow<'static, [u8]>>) -> Self {
+        self.bold = Some((name.into(), font_data(bytes)));
+        self
+    }
+
+    /// Adds the fonts and the [`CODE_FAMILY`] and [`CODE_BOLD_FAMILY`] families to `fonts`.
+    pub fn install(&self, fonts: &mut FontDefinitions) {
+        let mut family: Vec<String> = self.fonts.iter().map(|(name, _)| name.clone()).collect();
+        family.extend(fonts.families.get(&FontFamily::Monospace).into_iter().flatten().cloned());
+        for (name, data) in self.fonts.iter().chain(&self.bold) {
+            fonts.font_data.insert(name.clone(), data.clone().into());
+        }
+        let bold_family = self.bold.iter().map(|(name, _)| name.clone()).chain(family.iter().cloned()).collect();
+        fonts.families.insert(code_family(), family);
+        fonts.families.insert(FontFamily::Name(CODE_BOLD_FAMILY.into()), bold_family);
+    }
+
+    /// Installs the fonts over the default egui fonts, replacing the fonts set before.
+    pub fn install_in(&self, ctx: &egui::Context) {
+        let mut fonts = FontDefinitions::default();
+        self.install(&mut fonts);
+        ctx.set_fonts(fonts);
+    }
+}
+
+fn font_data(bytes: impl Into<Cow<'static, [u8]>>) -> FontData {
+    FontData {
+        font: bytes.into(),
+        index: 0,
+        tweak: Default::default(),
+    }
+}
+
+fn code_family() -> FontFamily {
+    FontFamily::Name(CODE_FAMILY.into())
+}
+
+/// Bold variant of the code `font`, `None` when it is not in the [`CODE_FAMILY`] (the bold family is installed with it).
+pub(crate) fn bold_font(font: &FontId) -> Option<FontId> {
+    (font.family == code_family()).then(|| FontId::new(font.size, FontFamily::Name(CODE_BOLD_FAMILY.into())))
+}
+
+/// Font of the code, in the [`CODE_FAMILY`] when it is installed.
+pub(crate) fn code_font(ui: &Ui, size: f32) -> FontId {
+    let family = code_family();
+    if ui.fonts(|f| f.families().contains(&family)) {
+        FontId::new(size, family)
+    } else {
+        FontId::monospace(size)
+    }
+}
@@ -0,0 +1,31 @@
+use std::ops::Range;
+
+use egui::{Galley, Pos2, Rect, pos2};
+
+/// Screen rectangles covering the chars of `range` in the galley, one per row.
+pub(crate) fn char_range_rects(galley: &Galley, galley_pos: Pos2, range: Range<usize>) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    let mut row_start = 0;
+
+    for row in &galley.rows {
+        let row_end = row_start + row.char_count_excluding_newline();
+        let start = range.start.max(row_start);
+        let end = range.end.min(row_end);
+        if start < end || (range.is_empty() && (row_start..=row_end).contains(&range.start)) {
+            let left = row.pos.x + row.x_offset(start - row_start);
+            let right = row.pos.x + row.x_offset(end.max(start) - row_start);
+            rects.push(
+                Rect::from_min_max(pos2(left, row.min_y()), pos2(right, row.max_y()))
+                    .translate(galley_pos.to_vec2()),
+            );
+            if range.is_empty() {
+                break;
+            }
+        }
+        row_start += row.char_count_including_newline();
+        if row_start > range.end {
+            break;
+        }
+    }
+    rects
+}
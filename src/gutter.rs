@@ -0,0 +1,169 @@
+use egui::{Align, Align2, Color32, FontId, Galley, Painter, Pos2, Rangef, Ui};
+
+use crate::diff::{Hunk, HunkKind};
+
+/// Space between the line numbers and the code.
+const PADDING: f32 = 8.0;
+
+/// Vertical span of every line of the galley (wrapped rows included), relative to the galley.
+pub(crate) fn line_spans(galley: &Galley) -> Vec<Rangef> {
+    let mut spans = Vec::new();
+    let mut start: Option<f32> = None;
+
+    for row in &galley.rows {
+        let first = *start.get_or_insert(row.min_y());
+        if row.ends_with_newline {
+            spans.push(Rangef::new(first, row.max_y()));
+            start = None;
+        }
+    }
+    if let Some(first) = start {
+        let last = galley.rows.last().map_or(first, |row| row.max_y());
+        spans.push(Rangef::new(first, last));
+    }
+
+    spans
+}
+
+/// Lines folded by the editor are laid out with no height.
+pub(crate) fn is_hidden(span: &Rangef) -> bool {
+    span.span() < 0.5
+}
+
+/// Look of the line numbers of [`CodeEditor`](crate::CodeEditor) and [`CodeViewer`](crate::CodeViewer).
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineNumbers {
+    /// Minimum width of the gutter in digits, it grows with the number of lines.
+    pub min_digits: usize,
+    /// Horizontal alignment of the numbers in the gutter.
+    pub align: Align,
+    /// Color of the numbers, the weak text color by default.
+    pub color: Option<Color32>,
+    /// Color of the number of the caret line in the editor, the normal text color by default.
+    pub current_color: Option<Color32>,
+}
+
+impl Default for LineNumbers {
+    fn default() -> Self {
+        Self {
+            min_digits: 1,
+            align: Align::Max,
+            color: None,
+            current_color: None,
+        }
+    }
+}
+
+/// Width needed to show the numbers up to `line_count`.
+pub(crate) fn width(ui: &Ui, font: &FontId, line_count: usize, style: &LineNumbers) -> f32 {
+    let digits = (line_count.max(1).ilog10() as usize + 1).max(style.min_digits);
+    let digit_width = ui.fonts(|f| f.glyph_width(font, '0'));
+    digits as f32 * digit_width + PADDING
+}
+
+/// Index of the line under `y` (in screen coordinates).
+pub(crate) fn line_at(spans: &[Rangef], galley_pos: Pos2, y: f32) -> Option<usize> {
+    spans
+        .iter()
+        .position(|span| (span.min..=span.max).contains(&(y - galley_pos.y)))
+}
+
+/// Paints the 1-based line numbers in the gutter spanning `x`, the `current` line highlighted.
+///
+/// The `spans` are the ones of the lines from `first` (0-based), the first line of the code unless virtualized.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn paint_numbers(
+    ui: &Ui,
+    first: usize,
+    spans: &[Rangef],
+    galley_pos: Pos2,
+    x: Rangef,
+    font: &FontId,
+    style: &LineNumbers,
+    current: Option<usize>,
+) {
+    let color = style.color.unwrap_or(ui.visuals().weak_text_color());
+    let current_color = style.current_color.unwrap_or(ui.visuals().text_color());
+    // the padding separates the numbers from the code
+    let (anchor, left) = match style.align {
+        Align::Min => (Align2::LEFT_TOP, x.min),
+        Align::Center => (Align2::CENTER_TOP, (x.min + x.max - PADDING) / 2.0),
+        Align::Max => (Align2::RIGHT_TOP, x.max - PADDING),
+    };
+    for (i, span) in (first..).zip(spans).filter(|(_, span)| !is_hidden(span)) {
+        ui.painter().text(
+            Pos2::new(left, galley_pos.y + span.min),
+            anchor,
+            (i + 1).to_string(),
+            font.clone(),
+            if current == Some(i) { current_color } else { color },
+        );
+    }
+}
+
+/// Width of the diagnostics strip, with the severity marks and the lightbulb.
+pub(crate) const DIAGNOSTICS_WIDTH: f32 = 16.0;
+
+/// Width of the change markers strip.
+pub(crate) const CHANGE_MARKERS_WIDTH: f32 = 6.0;
+
+/// Vertical span of the marker of `hunk`, relative to the galley.
+pub(crate) fn hunk_span(spans: &[Rangef], hunk: &Hunk) -> Option<Rangef> {
+    if hunk.new.is_empty() {
+        // deleted lines are marked between the lines
+        let y = match spans.get(hunk.new.start) {
+            Some(span) => span.min,
+            None => spans.last()?.max,
+        };
+        return Some(Rangef::new(y - 3.0, y + 3.0));
+    }
+
+    let first = spans.get(hunk.new.start)?;
+    let last = spans.get(hunk.new.end - 1).unwrap_or(spans.last()?);
+    Some(Rangef::new(first.min, last.max))
+}
+
+pub(crate) fn change_color(kind: HunkKind) -> Color32 {
+    match kind {
+        HunkKind::Added => Color32::from_rgb(87, 171, 90),
+        HunkKind::Modified => Color32::from_rgb(78, 140, 214),
+        HunkKind::Deleted => Color32::from_rgb(214, 84, 84),
+    }
+}
+
+/// Paints the change markers of `hunks` in the `x_range` strip.
+pub(crate) fn paint_change_markers(
+    painter: &Painter,
+    spans: &[Rangef],
+    hunks: &[Hunk],
+    galley_pos: Pos2,
+    x_range: Rangef,
+) {
+    for hunk in hunks {
+        let Some(span) = hunk_span(spans, hunk) else {
+            continue;
+        };
+        let rect = egui::Rect::from_x_y_ranges(x_range, offset(span, galley_pos.y));
+        let color = change_color(hunk.kind());
+        if hunk.kind() == HunkKind::Deleted {
+            painter.add(egui::Shape::convex_polygon(
+                vec![rect.left_top(), rect.left_bottom(), rect.right_center()],
+                color,
+                egui::Stroke::NONE,
+            ));
+        } else {
+            painter.rect_filled(rect.shrink2(egui::vec2(1.0, 0.0)), 0.0, color);
+        }
+    }
+}
+
+/// Index of the hunk whose marker is under `y` (in screen coordinates).
+pub(crate) fn hunk_at(spans: &[Rangef], hunks: &[Hunk], galley_pos: Pos2, y: f32) -> Option<usize> {
+    hunks.iter().position(|hunk| {
+        hunk_span(spans, hunk).is_some_and(|span| offset(span, galley_pos.y).contains(y))
+    })
+}
+
+fn offset(span: Rangef, y: f32) -> Rangef {
+    Rangef::new(span.min + y, span.max + y)
+}
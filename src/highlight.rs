@@ -0,0 +1,1103 @@
+use std::borrow::Cow;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
+use std::sync::{Arc, LazyLock, mpsc};
+use std::time::Duration;
+
+use egui::{Color32, FontId, Galley, Stroke, TextFormat, Visuals};
+use egui::text::LayoutJob;
+use syntect::highlighting::{Color, FontStyle, HighlightIterator, HighlightState, Highlighter, Theme};
+use syntect::parsing::{ParseState, Scope, ScopeStack, ScopeStackOp, SyntaxReference, SyntaxSet};
+
+use crate::cancel::CancelToken;
+use crate::events::CodeChange;
+use crate::fonts;
+use crate::language::Injection;
+use crate::spans::Span;
+
+/// Builds the highlighted layout job for the whole `text`.
+///
+/// The text without a style of its own (and the whole text when the highlighting is cancelled) uses the
+/// foreground of the theme, or the `plain` color if the theme has none.
+///
+/// Lines whose highlighting fails (broken grammar) are laid out as plain text,
+/// the first error is returned with the job.
+///
+/// Checks `cancel` between lines, if it was cancelled the partial result is discarded
+/// and the text is laid out without highlighting.
+pub(crate) fn layout_job(
+    text: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+    font: &FontId,
+    plain: Color32,
+    cancel: &CancelToken,
+) -> (LayoutJob, Option<String>) {
+    LineCache::default().layout_job(text, syntax, syntax_set, theme, font, plain, cancel)
+}
+
+/// Highlighted spans of `text`, the sections of its layout job without the font, with the first error of the grammar.
+pub(crate) fn spans(
+    text: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+    line_limit: usize,
+    injections: &[Injection],
+) -> (Vec<Span>, Option<String>) {
+    // the job is never laid out, so the fonts need not be installed
+    let font = FontId::new(14.0, egui::FontFamily::Name(fonts::CODE_FAMILY.into()));
+    let bold = fonts::bold_font(&font).map(|bold| bold.family);
+    let plain = foreground(theme, Color32::PLACEHOLDER);
+    let mut cache = LineCache::default();
+    cache.set_line_limit(line_limit);
+    cache.set_injections(injections);
+    let (job, error) = cache.layout_job(text, syntax, syntax_set, theme, &font, plain, &CancelToken::new());
+
+    let spans = job
+        .sections
+        .into_iter()
+        .map(|section| {
+            let format = section.format;
+            Span {
+                range: section.byte_range,
+                color: (format.color != plain).then_some(format.color),
+                bold: Some(&format.font_id.family) == bold.as_ref(),
+                italic: format.italics,
+                // the faint underline of the cut lines is not a style of the theme
+                underline: format.underline.width > 0.0 && format.underline.color == format.color,
+            }
+        })
+        .collect();
+    (spans, error)
+}
+
+/// Textual description of the highlighted spans of `text`, the same for the same code, syntax and theme.
+///
+/// Every span of a line is described on a line like `3:5-3:9 #b48ead bold italic "self"`, with its 1-based
+/// position in chars, its color (`fg` for the default foreground) and its styles. The line breaks are left out.
+/// An error of the grammar is described on the last line.
+pub(crate) fn debug_dump(
+    text: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+    line_limit: usize,
+    injections: &[Injection],
+) -> String {
+    let (spans, error) = spans(text, syntax, syntax_set, theme, line_limit, injections);
+    let mut dump = String::new();
+    let (mut line, mut column) = (1, 1);
+    for span in spans {
+        for (i, piece) in text[span.range].split('\n').enumerate() {
+            if i > 0 {
+                line += 1;
+                column = 1;
+            }
+            if piece.is_empty() {
+                continue;
+            }
+            let end = column + piece.chars().count() - 1;
+            let color = match span.color {
+                None => "fg".to_owned(),
+                Some(c) if c.is_opaque() => format!("#{:02x}{:02x}{:02x}", c.r(), c.g(), c.b()),
+                Some(c) => format!("#{:02x}{:02x}{:02x}{:02x}", c.r(), c.g(), c.b(), c.a()),
+            };
+            dump.push_str(&format!("{line}:{column}-{line}:{end} {color}"));
+            if span.bold {
+                dump.push_str(" bold");
+            }
+            if span.italic {
+                dump.push_str(" italic");
+            }
+            if span.underline {
+                dump.push_str(" underline");
+            }
+            dump.push_str(&format!(" {piece:?}\n"));
+            column = end + 1;
+        }
+    }
+    if let Some(error) = error {
+        dump.push_str(&format!("error: {error}\n"));
+    }
+    dump
+}
+
+/// Parser and highlighter state between two lines.
+#[derive(Clone, PartialEq)]
+struct LineState {
+    parse: ParseState,
+    highlight: HighlightState,
+    /// Injected region continued on the next line.
+    embedded: Option<Embedded>,
+    prefix: Prefix,
+}
+
+/// Lines at the start of the code highlighted with their own syntax, the host syntax continues after them as if
+/// they were not there: the YAML front matter of Markdown (between two `---` lines) and the shebang line of the scripts.
+#[derive(Clone, PartialEq)]
+enum Prefix {
+    /// Before the first line.
+    Start,
+    /// In the front matter, with the state of the YAML syntax.
+    FrontMatter(ParseState, HighlightState),
+    /// After the prefix lines.
+    Done,
+}
+
+/// State of the embedded syntax in an injected region.
+#[derive(Clone, PartialEq)]
+struct Embedded {
+    /// Index of the [`Rule`].
+    rule: usize,
+    parse: ParseState,
+    highlight: HighlightState,
+}
+
+/// [`Injection`] with its scope and syntax looked up, the code fences of Markdown have the syntax of their language name.
+struct Rule {
+    scope: Scope,
+    /// Index of the syntax, `None` for the one of the language name.
+    syntax: Option<usize>,
+    injection: Option<Injection>,
+}
+
+/// Injections of a host syntax: the rules of the regions, and the syntaxes of the built-in prefix lines.
+struct Rules {
+    regions: Vec<Rule>,
+    /// YAML, for the Markdown hosts.
+    front_matter: Option<usize>,
+    /// Bash.
+    shebang: Option<usize>,
+}
+
+static MARKDOWN: LazyLock<Scope> = LazyLock::new(|| Scope::new("text.html.markdown").expect("valid scope"));
+
+/// Rules of the `injections` found in `syntax_set`, then the built-in ones of the host `syntax`.
+fn rules(injections: &[Injection], syntax: &SyntaxReference, syntax_set: &SyntaxSet) -> Rules {
+    let find = |name: &str| syntax_set.find_syntax_by_name(name).or_else(|| syntax_set.find_syntax_by_token(name));
+    let index = |syntax| crate::syntaxes::index_of(syntax_set, syntax);
+    let regions = injections
+        .iter()
+        .filter_map(|injection| {
+            let syntax = find(&injection.syntax)?;
+            Some(Rule {
+                scope: Scope::new(&injection.scope).ok()?,
+                syntax: Some(index(syntax)),
+                injection: Some(injection.clone()),
+            })
+        })
+        .chain([Rule { scope: *CODE_FENCE, syntax: None, injection: None }])
+        .collect();
+    Rules {
+        regions,
+        front_matter: find("YAML").filter(|_| MARKDOWN.is_prefix_of(syntax.scope)).map(index),
+        shebang: find("Bourne Again Shell (bash)").map(index),
+    }
+}
+
+#[derive(Clone)]
+struct CachedLine {
+    text: String,
+    /// Byte length, foreground and font style of the highlighted pieces, `None` for plain text.
+    pieces: Vec<(usize, Option<Color>, FontStyle)>,
+    /// Byte offset after which the line is too long to be highlighted, the pieces end there.
+    cut: Option<usize>,
+    error: Option<String>,
+    /// Blocks opened (`true`) and closed (`false`) on the line, in order.
+    blocks: Vec<bool>,
+    /// Byte offsets of the brackets of the line outside the strings and the comments.
+    brackets: Vec<usize>,
+    after: LineState,
+}
+
+/// Default of [`LineCache::set_line_limit`].
+pub(crate) const LINE_LIMIT: usize = 10_000;
+
+/// Highlighted lines of the previous text of a widget.
+///
+/// When the text changes, the lines before the edit are reused as they are, the edited lines are highlighted again,
+/// and the lines after the edit are reused from the first one whose preceding state did not change.
+/// So typing at the bottom of a large file does not parse its top again, and typing at its top parses the following
+/// lines only until the state is the same as before (like after closing a new comment).
+pub(crate) struct LineCache {
+    lines: Vec<CachedLine>,
+    /// Addresses of the syntax and the theme the lines were highlighted with.
+    source: Option<(usize, usize)>,
+    line_limit: usize,
+    injections: Vec<Injection>,
+}
+
+impl Default for LineCache {
+    fn default() -> Self {
+        Self {
+            lines: Vec::new(),
+            source: None,
+            line_limit: LINE_LIMIT,
+            injections: Vec::new(),
+        }
+    }
+}
+
+impl LineCache {
+    /// Highlights only the first `chars` chars of the lines, the rest of the longer lines (minified code,
+    /// base64 blobs) is laid out plain with a faint underline, keeping their highlighting fast.
+    pub(crate) fn set_line_limit(&mut self, chars: usize) {
+        if chars != self.line_limit {
+            self.line_limit = chars;
+            self.lines.clear();
+        }
+    }
+
+    /// Highlights the regions of the `injections` with their syntaxes, see [`Injection`].
+    pub(crate) fn set_injections(&mut self, injections: &[Injection]) {
+        if injections != self.injections {
+            self.injections = injections.to_vec();
+            self.lines.clear();
+        }
+    }
+
+    /// Like [`layout_job`], highlighting only the lines changed since the previous call.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn layout_job(
+        &mut self,
+        text: &str,
+        syntax: &SyntaxReference,
+        syntax_set: &SyntaxSet,
+        theme: &Theme,
+        font: &FontId,
+        plain: Color32,
+        cancel: &CancelToken,
+    ) -> (LayoutJob, Option<String>) {
+        let plain = foreground(theme, plain);
+        if cancel.is_cancelled() {
+            return (plain_job(text, font, plain), None);
+        }
+
+        let source = (syntax as *const SyntaxReference as usize, theme as *const Theme as usize);
+        if self.source != Some(source) {
+            self.lines.clear();
+            self.source = Some(source);
+        }
+
+        // the line breaks are kept, so the galley has exactly the chars of the text
+        let new_lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let mut lines = std::mem::take(&mut self.lines);
+        let prefix = lines.iter().zip(&new_lines).take_while(|(old, new)| old.text == **new).count();
+        let suffix = lines[prefix..]
+            .iter()
+            .rev()
+            .zip(new_lines[prefix..].iter().rev())
+            .take_while(|(old, new)| old.text == **new)
+            .count();
+
+        let highlighter = Highlighter::new(theme);
+        let rules = rules(&self.injections, syntax, syntax_set);
+        let initial = || LineState {
+            parse: ParseState::new(syntax),
+            highlight: HighlightState::new(&highlighter, ScopeStack::new()),
+            embedded: None,
+            prefix: Prefix::Start,
+        };
+        // the lines after the edit, and the state before them
+        let old_suffix = lines.split_off(lines.len() - suffix);
+        let mut old_before = lines.last().map_or_else(initial, |line| line.after.clone());
+        lines.truncate(prefix);
+        let mut state = lines.last().map_or_else(initial, |line| line.after.clone());
+
+        let edited = prefix..new_lines.len() - suffix;
+        for line in &new_lines[edited.clone()] {
+            if cancel.is_cancelled() {
+                // the lines highlighted so far are still valid
+                self.lines = lines;
+                return (plain_job(text, font, plain), None);
+            }
+            lines.push(highlight_line(line, &mut state, &highlighter, syntax_set, &rules, self.line_limit));
+        }
+        let mut synced = false;
+        for (line, old) in new_lines[edited.end..].iter().zip(old_suffix) {
+            if cancel.is_cancelled() {
+                self.lines = lines;
+                return (plain_job(text, font, plain), None);
+            }
+            synced = synced || state == old_before;
+            if synced {
+                lines.push(old);
+            } else {
+                lines.push(highlight_line(line, &mut state, &highlighter, syntax_set, &rules, self.line_limit));
+                old_before = old.after;
+            }
+        }
+        self.lines = lines;
+
+        let mut job = LayoutJob::default();
+        let mut error = None;
+        for line in &self.lines {
+            if let Some(err) = &line.error {
+                error.get_or_insert_with(|| err.clone());
+            }
+            append_line(&mut job, line, theme, font, plain);
+        }
+        (job, error)
+    }
+
+    /// Layout jobs of the `rows` of `lines` (with their line breaks), without the line breaks, for the virtualized layout.
+    ///
+    /// Only the lines up to the last row are highlighted, at most `budget` lines per call: the rows not highlighted yet
+    /// are plain, and `false` is returned with the jobs until they are all highlighted.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn row_jobs(
+        &mut self,
+        lines: &[&str],
+        rows: Range<usize>,
+        syntax: &SyntaxReference,
+        syntax_set: &SyntaxSet,
+        theme: &Theme,
+        font: &FontId,
+        plain: Color32,
+        cancel: &CancelToken,
+        budget: usize,
+    ) -> (Vec<LayoutJob>, bool) {
+        let plain = foreground(theme, plain);
+        let source = (syntax as *const SyntaxReference as usize, theme as *const Theme as usize);
+        if self.source != Some(source) {
+            self.lines.clear();
+            self.source = Some(source);
+        }
+        let prefix = self.lines.iter().zip(lines).take_while(|(old, new)| old.text == **new).count();
+        self.lines.truncate(prefix);
+
+        let end = rows.end.min(lines.len());
+        if self.lines.len() < end && !cancel.is_cancelled() {
+            let highlighter = Highlighter::new(theme);
+            let rules = rules(&self.injections, syntax, syntax_set);
+            let mut state = match self.lines.last() {
+                Some(line) => line.after.clone(),
+                None => LineState {
+                    parse: ParseState::new(syntax),
+                    highlight: HighlightState::new(&highlighter, ScopeStack::new()),
+                    embedded: None,
+                    prefix: Prefix::Start,
+                },
+            };
+            let end = end.min(self.lines.len() + budget);
+            while self.lines.len() < end && !cancel.is_cancelled() {
+                let line = lines[self.lines.len()];
+                self.lines.push(highlight_line(line, &mut state, &highlighter, syntax_set, &rules, self.line_limit));
+            }
+        }
+
+        let jobs = rows
+            .filter_map(|row| {
+                let mut job = LayoutJob::default();
+                match self.lines.get(row).filter(|_| !cancel.is_cancelled()) {
+                    Some(line) => append_line(&mut job, line, theme, font, plain),
+                    None => job.append(lines.get(row)?, 0.0, format(font, plain)),
+                }
+                strip_line_break(&mut job);
+                Some(job)
+            })
+            .collect();
+        (jobs, self.lines.len() >= end || cancel.is_cancelled())
+    }
+
+    /// Blocks opened and closed on every line of the last highlighted text, see [`crate::folding::scope_regions`].
+    pub(crate) fn blocks(&self) -> impl Iterator<Item = &[bool]> {
+        self.lines.iter().map(|line| line.blocks.as_slice())
+    }
+
+    /// Brackets of every line of the last highlighted text, see [`crate::brackets::matching_pair`].
+    pub(crate) fn brackets(&self) -> impl Iterator<Item = &[usize]> {
+        self.lines.iter().map(|line| line.brackets.as_slice())
+    }
+}
+
+/// Appends the highlighted pieces of `line` to `job`, the text without a style of its own uses `plain`.
+///
+/// The bold, italic and underlined pieces of the theme are styled too, the bold ones in the bold code font if any.
+fn append_line(job: &mut LayoutJob, line: &CachedLine, theme: &Theme, font: &FontId, plain: Color32) {
+    let mut start = 0;
+    for &(len, color, style) in &line.pieces {
+        // syntect gives the unstyled text the theme foreground, or black without it
+        let color = match color {
+            Some(c) if c != theme.settings.foreground.unwrap_or(Color::BLACK) => Color32::from_rgb(c.r, c.g, c.b),
+            _ => plain,
+        };
+        let mut format = format(font, color);
+        if style.contains(FontStyle::BOLD)
+            && let Some(bold) = fonts::bold_font(font)
+        {
+            format.font_id = bold;
+        }
+        format.italics = style.contains(FontStyle::ITALIC);
+        if style.contains(FontStyle::UNDERLINE) {
+            format.underline = Stroke::new(1.0, color);
+        }
+        job.append(&line.text[start..start + len], 0.0, format);
+        start += len;
+    }
+    if let Some(cut) = line.cut {
+        let mut format = format(font, plain);
+        format.underline = Stroke::new(1.0, plain.gamma_multiply(0.3));
+        job.append(&line.text[cut..], 0.0, format);
+    }
+}
+
+/// Removes the `\n` or `\r\n` ending the text of `job`, and the sections left empty.
+fn strip_line_break(job: &mut LayoutJob) {
+    let len = job.text.trim_end_matches(['\n', '\r']).len();
+    job.text.truncate(len);
+    job.sections.retain_mut(|section| {
+        section.byte_range.end = section.byte_range.end.min(len);
+        !section.byte_range.is_empty()
+    });
+}
+
+/// Prefix of the scopes of the brackets, like `punctuation.section.block.begin.rust`.
+static SECTION: LazyLock<Scope> = LazyLock::new(|| Scope::new("punctuation.section").expect("valid scope"));
+
+/// Opened and closed blocks of the parse `ops` of a line.
+fn blocks(ops: &[(usize, ScopeStackOp)]) -> Vec<bool> {
+    ops.iter()
+        .filter_map(|(_, op)| match op {
+            ScopeStackOp::Push(scope) if SECTION.is_prefix_of(*scope) => {
+                let name = scope.build_string();
+                if name.contains(".begin") {
+                    Some(true)
+                } else if name.contains(".end") {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+static CODE_FENCE: LazyLock<Scope> = LazyLock::new(|| Scope::new("markup.raw.code-fence").expect("valid scope"));
+static LANGUAGE_NAME: LazyLock<Scope> = LazyLock::new(|| Scope::new("constant.other.language-name").expect("valid scope"));
+
+/// Scopes left to the host syntax in the injected regions: the quotes, the string prefixes and the fence info.
+static DELIMITERS: LazyLock<[Scope; 3]> = LazyLock::new(|| {
+    ["punctuation.definition", "storage.type.string", "constant.other.language-name"]
+        .map(|name| Scope::new(name).expect("valid scope"))
+});
+
+static STRING: LazyLock<Scope> = LazyLock::new(|| Scope::new("string").expect("valid scope"));
+static COMMENT: LazyLock<Scope> = LazyLock::new(|| Scope::new("comment").expect("valid scope"));
+
+/// Byte offsets of the brackets of the first `len` bytes of `line` that are outside the strings and the comments,
+/// following the parse `ops` from the scopes `path` before the line.
+fn brackets(line: &str, len: usize, ops: &[(usize, ScopeStackOp)], mut path: ScopeStack) -> Vec<usize> {
+    let mut ops = ops.iter().peekable();
+    let mut brackets = Vec::new();
+    for (i, c) in line[..len].char_indices() {
+        while let Some((_, op)) = ops.next_if(|(at, _)| *at <= i) {
+            // a broken stack only misses brackets
+            let _ = path.apply(op);
+        }
+        if "()[]{}<>".contains(c)
+            && !path.as_slice().iter().any(|scope| STRING.is_prefix_of(*scope) || COMMENT.is_prefix_of(*scope))
+        {
+            brackets.push(i);
+        }
+    }
+    brackets
+}
+
+/// Highlights the `line`, only its first `limit` chars if it is longer.
+///
+/// The end of a cut line is unknown (like a string closed after the cut), so the next line starts with the state
+/// from before the cut line.
+fn highlight_line(
+    line: &str,
+    state: &mut LineState,
+    highlighter: &Highlighter,
+    syntax_set: &SyntaxSet,
+    rules: &Rules,
+    limit: usize,
+) -> CachedLine {
+    let content = line.strip_suffix('\n').unwrap_or(line);
+    let cut = content.char_indices().nth(limit).map(|(i, _)| i);
+    if cut.is_none()
+        && let Some(prefix) = prefix_line(line, state, highlighter, syntax_set, rules)
+    {
+        return prefix;
+    }
+    let before = cut.map(|_| state.clone());
+    let parsed = match cut {
+        Some(cut) if line.ends_with('\n') => Cow::Owned(format!("{}\n", &line[..cut])),
+        Some(cut) => Cow::Borrowed(&line[..cut]),
+        None => Cow::Borrowed(line),
+    };
+    let highlighted = cut.unwrap_or(line.len());
+    let path = state.highlight.path.clone();
+    let (pieces, error, blocks, brackets) = match state.parse.parse_line(&parsed, syntax_set) {
+        Ok(ops) => {
+            let mut pieces = Vec::new();
+            let mut start = 0;
+            for (style, piece) in HighlightIterator::new(&mut state.highlight, &ops, &parsed, highlighter) {
+                if start >= highlighted {
+                    break;
+                }
+                let len = piece.len().min(highlighted - start);
+                pieces.push((len, Some(style.foreground), style.font_style));
+                start += len;
+            }
+            let regions = regions(&parsed, highlighted, &ops, path.clone(), &rules.regions);
+            inject(&parsed, regions, state, highlighter, syntax_set, &rules.regions, &mut pieces);
+            let brackets = brackets(&parsed, highlighted, &ops, path);
+            (pieces, None, blocks(&ops), brackets)
+        }
+        Err(err) => (vec![(highlighted, None, FontStyle::empty())], Some(err.to_string()), Vec::new(), Vec::new()),
+    };
+    if let Some(before) = before {
+        *state = before;
+    }
+    CachedLine {
+        text: line.to_owned(),
+        pieces,
+        cut,
+        error,
+        blocks,
+        brackets,
+        after: state.clone(),
+    }
+}
+
+/// Byte ranges of the first `len` bytes of `line` injected by a rule (with its index), following the parse `ops`
+/// from the scopes `path` before the line, and the language name of the code fence opened on the line.
+fn regions(
+    line: &str,
+    len: usize,
+    ops: &[(usize, ScopeStackOp)],
+    mut path: ScopeStack,
+    rules: &[Rule],
+) -> (Vec<(Range<usize>, usize)>, String) {
+    let mut ops = ops.iter().peekable();
+    let mut regions: Vec<(Range<usize>, usize)> = Vec::new();
+    let mut language = String::new();
+    for (i, c) in line[..len].char_indices() {
+        while let Some((_, op)) = ops.next_if(|(at, _)| *at <= i) {
+            let _ = path.apply(op);
+        }
+        let scopes = path.as_slice();
+        if scopes.last().is_some_and(|scope| LANGUAGE_NAME.is_prefix_of(*scope)) {
+            language.push(c);
+        }
+        // the outermost region, without its delimiters
+        let region = scopes.iter().enumerate().find_map(|(depth, scope)| {
+            let rule = rules.iter().position(|rule| rule.scope.is_prefix_of(*scope))?;
+            let delimiter = scopes[depth + 1..].iter().any(|inner| DELIMITERS.iter().any(|d| d.is_prefix_of(*inner)));
+            Some((!delimiter).then_some(rule))
+        });
+        let Some(Some(rule)) = region else {
+            continue;
+        };
+        match regions.last_mut() {
+            Some((range, last)) if range.end == i && *last == rule => range.end = i + c.len_utf8(),
+            _ => regions.push((i..i + c.len_utf8(), rule)),
+        }
+    }
+    (regions, language)
+}
+
+/// Highlights the `regions` of `line` with their embedded syntaxes, in the `pieces` of the host syntax.
+///
+/// A region at the start of the line continues the embedded region of the previous line, a region at its end
+/// is continued on the next line.
+fn inject(
+    line: &str,
+    (regions, language): (Vec<(Range<usize>, usize)>, String),
+    state: &mut LineState,
+    highlighter: &Highlighter,
+    syntax_set: &SyntaxSet,
+    rules: &[Rule],
+    pieces: &mut Vec<(usize, Option<Color>, FontStyle)>,
+) {
+    let mut continued = state.embedded.take();
+    for (range, index) in regions {
+        let text = &line[range.clone()];
+        let rule = &rules[index];
+        let embedded = match continued.take() {
+            Some(embedded) if range.start == 0 && embedded.rule == index => Some(embedded),
+            _ => {
+                let syntax = match rule.syntax {
+                    Some(syntax) => syntax_set.syntaxes().get(syntax),
+                    None => syntax_set.find_syntax_by_token(language.trim()),
+                };
+                syntax
+                    .filter(|_| rule.injection.as_ref().is_none_or(|injection| injection.accepts(text)))
+                    .map(|syntax| Embedded {
+                        rule: index,
+                        parse: ParseState::new(syntax),
+                        highlight: HighlightState::new(highlighter, ScopeStack::new()),
+                    })
+            }
+        };
+        let Some(mut embedded) = embedded else {
+            continue;
+        };
+        // a broken embedded grammar leaves the region to the host one
+        let Ok(injected) = embedded_pieces(text, &mut embedded.parse, &mut embedded.highlight, highlighter, syntax_set)
+        else {
+            continue;
+        };
+        splice(pieces, range.clone(), injected);
+        if range.end == line.len() {
+            state.embedded = Some(embedded);
+        }
+    }
+}
+
+/// Highlights the `line` of the prefix lines (see [`Prefix`]) with their syntax, `None` for the lines of the host.
+fn prefix_line(
+    line: &str,
+    state: &mut LineState,
+    highlighter: &Highlighter,
+    syntax_set: &SyntaxSet,
+    rules: &Rules,
+) -> Option<CachedLine> {
+    let content = line.trim_end_matches(['\n', '\r']);
+    let front_matter = matches!(state.prefix, Prefix::FrontMatter(..));
+    let (mut parse, mut highlight) = match std::mem::replace(&mut state.prefix, Prefix::Done) {
+        Prefix::Start => {
+            if let Some(yaml) = rules.front_matter.filter(|_| content == "---") {
+                let highlight = HighlightState::new(highlighter, ScopeStack::new());
+                state.prefix = Prefix::FrontMatter(ParseState::new(&syntax_set.syntaxes()[yaml]), highlight);
+                return None;
+            }
+            // not the inner attributes of Rust, like `#![allow(unused)]`
+            let shell = rules.shebang.filter(|_| content.starts_with("#!") && !content.starts_with("#!["))?;
+            (ParseState::new(&syntax_set.syntaxes()[shell]), HighlightState::new(highlighter, ScopeStack::new()))
+        }
+        Prefix::FrontMatter(..) if content == "---" || content == "..." => return None,
+        Prefix::FrontMatter(parse, highlight) => (parse, highlight),
+        Prefix::Done => return None,
+    };
+    let (pieces, error) = match embedded_pieces(line, &mut parse, &mut highlight, highlighter, syntax_set) {
+        Ok(pieces) => (pieces, None),
+        Err(err) => (vec![(line.len(), None, FontStyle::empty())], Some(err)),
+    };
+    if front_matter {
+        state.prefix = Prefix::FrontMatter(parse, highlight);
+    }
+    Some(CachedLine {
+        text: line.to_owned(),
+        pieces,
+        cut: None,
+        error,
+        blocks: Vec::new(),
+        brackets: Vec::new(),
+        after: state.clone(),
+    })
+}
+
+/// Pieces of `text` highlighted with an embedded syntax, from its states before the text.
+fn embedded_pieces(
+    text: &str,
+    parse: &mut ParseState,
+    highlight: &mut HighlightState,
+    highlighter: &Highlighter,
+    syntax_set: &SyntaxSet,
+) -> Result<Vec<(usize, Option<Color>, FontStyle)>, String> {
+    let ops = parse.parse_line(text, syntax_set).map_err(|err| err.to_string())?;
+    Ok(HighlightIterator::new(highlight, &ops, text, highlighter)
+        .map(|(style, piece)| (piece.len(), Some(style.foreground), style.font_style))
+        .collect())
+}
+
+/// Replaces the pieces of the bytes of `range` with the `injected` ones.
+fn splice(
+    pieces: &mut Vec<(usize, Option<Color>, FontStyle)>,
+    range: Range<usize>,
+    injected: Vec<(usize, Option<Color>, FontStyle)>,
+) {
+    let clip = |bounds: Range<usize>| {
+        let mut start = 0;
+        pieces.iter().filter_map(move |&(len, color, style)| {
+            let piece = start..start + len;
+            start += len;
+            let len = piece.end.min(bounds.end).saturating_sub(piece.start.max(bounds.start));
+            (len > 0).then_some((len, color, style))
+        })
+    };
+    let spliced = clip(0..range.start).chain(injected).chain(clip(range.end..usize::MAX)).collect();
+    *pieces = spliced;
+}
+
+fn format(font: &FontId, color: Color32) -> TextFormat {
+    TextFormat {
+        font_id: font.clone(),
+        color,
+        ..Default::default()
+    }
+}
+
+fn plain_job(text: &str, font: &FontId, color: Color32) -> LayoutJob {
+    LayoutJob::single_section(
+        text.to_owned(),
+        TextFormat {
+            font_id: font.clone(),
+            color,
+            ..Default::default()
+        },
+    )
+}
+
+/// The `job` of an older text, for `text`: the sections keep their format, shifted by the edit between the texts,
+/// the inserted text takes the format of the section before it.
+fn shifted_job(job: &LayoutJob, text: &str) -> LayoutJob {
+    let Some(change) = CodeChange::between(&job.text, text) else {
+        return job.clone();
+    };
+    // the positions in the removed text collapse to its start, the ones at an insert go after it
+    let shift = |offset: usize| {
+        if offset >= change.removed.end {
+            offset - change.removed.end + change.inserted.end
+        } else if offset <= change.removed.start {
+            offset
+        } else {
+            change.removed.start
+        }
+    };
+    let mut sections: Vec<egui::text::LayoutSection> = Vec::with_capacity(job.sections.len());
+    for section in &job.sections {
+        let mut section = section.clone();
+        section.byte_range = shift(section.byte_range.start)..shift(section.byte_range.end);
+        if let Some(last) = sections.last_mut() {
+            // covers the inserted text
+            last.byte_range.end = section.byte_range.start;
+        }
+        if !section.byte_range.is_empty() || sections.is_empty() {
+            sections.push(section);
+        }
+    }
+    if let Some(first) = sections.first_mut() {
+        first.byte_range.start = 0;
+    }
+    if let Some(last) = sections.last_mut() {
+        last.byte_range.end = text.len();
+    }
+    let mut shifted = job.clone();
+    shifted.text = text.to_owned();
+    shifted.sections = sections;
+    shifted
+}
+
+/// Uses the selection and caret colors of the theme, the egui style is kept for the settings missing in the theme.
+pub(crate) fn apply_theme_visuals(visuals: &mut Visuals, theme: &Theme) {
+    if let Some(selection) = theme.settings.selection {
+        visuals.selection.bg_fill = color(selection);
+    }
+    if let Some(caret) = theme.settings.caret {
+        visuals.text_cursor.stroke.color = color(caret);
+    }
+}
+
+/// Foreground of the theme, `fallback` if it has none.
+pub(crate) fn foreground(theme: &Theme, fallback: Color32) -> Color32 {
+    theme.settings.foreground.map_or(fallback, color)
+}
+
+/// Background of the theme, `fallback` if it has none.
+pub(crate) fn background(theme: &Theme, fallback: Color32) -> Color32 {
+    theme.settings.background.map_or(fallback, color)
+}
+
+fn color(color: Color) -> Color32 {
+    Color32::from_rgba_unmultiplied(color.r, color.g, color.b, color.a)
+}
+
+/// How long the UI waits for the worker before laying out the text plain, short edits are highlighted in time.
+const WORKER_WAIT: Duration = Duration::from_millis(10);
+
+/// Text to highlight, sent to the worker thread.
+struct Request {
+    text: String,
+    /// Hash of the text and of everything its job depends on.
+    key: u64,
+    /// Hash of everything the job depends on but the text.
+    style: u64,
+    syntax_set: Arc<SyntaxSet>,
+    syntax_index: usize,
+    theme: Arc<Theme>,
+    font: FontId,
+    plain: Color32,
+    line_limit: usize,
+    injections: Vec<Injection>,
+    cancel: CancelToken,
+    ctx: egui::Context,
+}
+
+/// Highlighted job of a [`Request`].
+struct Highlighted {
+    key: u64,
+    style: u64,
+    job: LayoutJob,
+    error: Option<String>,
+    /// See [`LineCache::blocks`].
+    blocks: Vec<Vec<bool>>,
+    /// See [`LineCache::brackets`].
+    brackets: Vec<Vec<usize>>,
+    /// The highlighting was cancelled during the run, the job is plain.
+    cancelled: bool,
+}
+
+/// Highlighting of the large texts on a worker thread, so a huge paste does not freeze the UI.
+///
+/// The thread keeps its own [`LineCache`], so it highlights only the changed lines too, and skips the texts
+/// replaced by newer ones while it was busy. Until the thread is done, the last highlighted job is shown
+/// with its sections shifted by the edit (the text is plain only before the first job),
+/// then it asks for a repaint and the new job is swapped in.
+#[derive(Default)]
+pub(crate) struct HighlightWorker {
+    /// Channels to the thread, started by the first request and stopped when the worker is dropped.
+    thread: Option<(mpsc::Sender<Request>, mpsc::Receiver<Highlighted>)>,
+    requested: Option<u64>,
+    done: Option<Highlighted>,
+    /// Whether the last text was highlighted by the worker.
+    in_use: bool,
+    /// Incremented when a job is swapped in, for the keys of the caches.
+    revision: u64,
+}
+
+impl HighlightWorker {
+    pub(crate) fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Whether the thread is highlighting the last requested text.
+    pub(crate) fn is_busy(&self) -> bool {
+        self.requested.is_some_and(|key| self.done.as_ref().is_none_or(|done| done.key != key))
+    }
+
+    /// Takes the jobs done since the last frame, before [`revision`](Self::revision) is read.
+    pub(crate) fn poll(&mut self) {
+        self.receive(self.requested.unwrap_or_default(), Duration::ZERO);
+    }
+
+    /// Blocks of the lines of the last text, see [`LineCache::blocks`], `None` if it was not highlighted by the worker.
+    pub(crate) fn blocks(&self) -> Option<impl Iterator<Item = &[bool]>> {
+        self.in_use.then(|| self.done.iter().flat_map(|done| done.blocks.iter().map(Vec::as_slice)))
+    }
+
+    /// Brackets of the lines of the last text, see [`LineCache::brackets`], `None` if it was not highlighted by the worker.
+    pub(crate) fn brackets(&self) -> Option<impl Iterator<Item = &[usize]>> {
+        self.in_use.then(|| self.done.iter().flat_map(|done| done.brackets.iter().map(Vec::as_slice)))
+    }
+
+    /// Records that the text is highlighted on the UI thread again.
+    pub(crate) fn set_unused(&mut self) {
+        self.in_use = false;
+    }
+
+    /// Like [`LineCache::layout_job`], the job of the worker once it is done, plain until then.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn layout_job(
+        &mut self,
+        text: &str,
+        syntax_set: &Arc<SyntaxSet>,
+        syntax_index: usize,
+        theme: &Arc<Theme>,
+        font: &FontId,
+        plain: Color32,
+        (line_limit, injections): (usize, &[Injection]),
+        cancel: &CancelToken,
+        ctx: &egui::Context,
+    ) -> (LayoutJob, Option<String>) {
+        self.in_use = true;
+        if cancel.is_cancelled() {
+            return (plain_job(text, font, foreground(theme, plain)), None);
+        }
+        let mut hasher = DefaultHasher::new();
+        (Arc::as_ptr(syntax_set), syntax_index, Arc::as_ptr(theme), font, plain, line_limit, injections).hash(&mut hasher);
+        let style = hasher.finish();
+        text.hash(&mut hasher);
+        let key = hasher.finish();
+
+        self.receive(key, Duration::ZERO);
+        if self.done.as_ref().is_none_or(|done| done.key != key) && self.requested != Some(key) {
+            let (requests, _) = self.thread.get_or_insert_with(spawn_worker);
+            let request = Request {
+                text: text.to_owned(),
+                key,
+                style,
+                syntax_set: syntax_set.clone(),
+                syntax_index,
+                theme: theme.clone(),
+                font: font.clone(),
+                plain,
+                line_limit,
+                injections: injections.to_vec(),
+                cancel: cancel.clone(),
+                ctx: ctx.clone(),
+            };
+            if requests.send(request).is_err() {
+                // the thread panicked, it is started again by the next request
+                self.thread = None;
+            }
+            self.requested = Some(key);
+            self.receive(key, WORKER_WAIT);
+        }
+        match &self.done {
+            Some(done) if done.key == key => (done.job.clone(), done.error.clone()),
+            // the colors of the last text do not jump to plain and back on every keystroke
+            Some(done) if done.style == style => (shifted_job(&done.job, text), done.error.clone()),
+            _ => (plain_job(text, font, foreground(theme, plain)), None),
+        }
+    }
+
+    /// Takes the jobs done by the thread, waiting up to `wait` for the one of `key`.
+    fn receive(&mut self, key: u64, wait: Duration) {
+        let Some((_, results)) = &self.thread else {
+            return;
+        };
+        let mut wait = Some(wait).filter(|wait| !wait.is_zero());
+        loop {
+            let received = match wait {
+                Some(wait) => results.recv_timeout(wait).ok(),
+                None => results.try_recv().ok(),
+            };
+            let Some(done) = received else {
+                return;
+            };
+            // the plain job of a cancelled run is not the one of its key, the text is requested again
+            if done.cancelled {
+                if self.requested == Some(done.key) {
+                    self.requested = None;
+                }
+                continue;
+            }
+            let found = done.key == key;
+            self.done = Some(done);
+            self.revision += 1;
+            if found {
+                wait = None;
+            }
+        }
+    }
+}
+
+/// Starts the thread highlighting the requests, until the sender is dropped.
+fn spawn_worker() -> (mpsc::Sender<Request>, mpsc::Receiver<Highlighted>) {
+    let (requests, received) = mpsc::channel::<Request>();
+    let (sender, results) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut cache = LineCache::default();
+        while let Ok(mut request) = received.recv() {
+            // only the newest text is highlighted
+            while let Ok(newer) = received.try_recv() {
+                request = newer;
+            }
+            cache.set_line_limit(request.line_limit);
+            cache.set_injections(&request.injections);
+            let syntax = &request.syntax_set.syntaxes()[request.syntax_index];
+            let (job, error) = cache.layout_job(
+                &request.text,
+                syntax,
+                &request.syntax_set,
+                &request.theme,
+                &request.font,
+                request.plain,
+                &request.cancel,
+            );
+            let blocks = cache.blocks().map(<[bool]>::to_vec).collect();
+            let brackets = cache.brackets().map(<[usize]>::to_vec).collect();
+            let cancelled = request.cancel.is_cancelled();
+            let highlighted = Highlighted { key: request.key, style: request.style, job, error, blocks, brackets, cancelled };
+            if sender.send(highlighted).is_err() {
+                return;
+            }
+            request.ctx.request_repaint();
+        }
+    });
+    (requests, results)
+}
+
+/// Last galley of a widget, laid out again only when its key changes.
+#[derive(Default)]
+pub(crate) struct GalleyCache {
+    key: Option<u64>,
+    galley: Option<Arc<Galley>>,
+    error: Option<String>,
+}
+
+impl GalleyCache {
+    /// Returns the cached galley if `key` (everything the layout depends on) did not change, otherwise calls `layout`.
+    pub(crate) fn get_or_layout(
+        &mut self,
+        key: impl Hash,
+        layout: impl FnOnce() -> (Arc<Galley>, Option<String>),
+    ) -> (Arc<Galley>, Option<String>) {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let key = hasher.finish();
+
+        if self.key != Some(key) || self.galley.is_none() {
+            let (galley, error) = layout();
+            self.key = Some(key);
+            self.galley = Some(galley);
+            self.error = error;
+        }
+        (self.galley.clone().expect("laid out above"), self.error.clone())
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    /// Lays out `text` with the worker until it is highlighted, `false` if it is still plain after a few seconds.
+    fn highlighted_by(worker: &mut HighlightWorker, text: &str, cancel: &CancelToken) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if layout(worker, text, cancel).sections.len() > 1 {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    fn layout(worker: &mut HighlightWorker, text: &str, cancel: &CancelToken) -> LayoutJob {
+        let syntax_set = crate::syntaxes::defaults();
+        let syntax_index = syntax_set.syntaxes().iter().position(|syntax| syntax.name == "Rust").unwrap();
+        let theme = crate::theme::built_in("base16-ocean.dark").unwrap();
+        let font = FontId::monospace(14.0);
+        let ctx = egui::Context::default();
+        worker.poll();
+        worker.layout_job(text, &syntax_set, syntax_index, &theme, &font, Color32::WHITE, (LINE_LIMIT, &[]), cancel, &ctx).0
+    }
+
+    #[test]
+    fn worker_drops_the_cancelled_runs() {
+        let cancel = CancelToken::new();
+        let mut worker = HighlightWorker::default();
+        cancel.cancel();
+        assert_eq!(layout(&mut worker, "fn main() {}", &cancel).sections.len(), 1);
+        assert!(!worker.is_busy());
+
+        // cancelled while the thread highlights the text
+        let text = "fn main() { let x = 1; }\n".repeat(2_000);
+        cancel.reset();
+        layout(&mut worker, &text, &cancel);
+        cancel.cancel();
+        let start = Instant::now();
+        while worker.is_busy() && start.elapsed() < Duration::from_secs(5) {
+            layout(&mut worker, &text, &cancel);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        cancel.reset();
+        assert!(highlighted_by(&mut worker, &text, &cancel));
+        assert!(!worker.is_busy());
+    }
+}
@@ -0,0 +1,459 @@
+use std::collections::BTreeMap;
+
+use egui::text::CCursorRange;
+use egui::{RichText, Ui};
+
+use crate::CodeEditor;
+
+/// Edits closer in time than this (seconds) are undone together.
+const COALESCE_TIME: f64 = 1.0;
+
+/// State of the code in the undo history.
+#[derive(Clone, Debug)]
+pub(crate) struct Snapshot {
+    pub(crate) code: String,
+    pub(crate) cursor: Option<CCursorRange>,
+}
+
+/// State in the history tree.
+#[derive(Clone, Debug)]
+pub(crate) struct Node {
+    pub(crate) snapshot: Snapshot,
+    pub(crate) parent: Option<usize>,
+    pub(crate) children: Vec<usize>,
+    /// Child restored by redo, the last visited or created one.
+    redo_child: Option<usize>,
+    /// Time of the last edit of the state, in seconds of the egui input time.
+    pub(crate) time: f64,
+    /// Chars inserted and removed since the parent state.
+    pub(crate) inserted: usize,
+    pub(crate) removed: usize,
+    /// Label of a checkpoint, the checkpoints are never trimmed.
+    pub(crate) label: Option<String>,
+    /// Logical time of the last visit, the least recently visited states are trimmed first.
+    visited: u64,
+}
+
+/// Undo history of the editor, a tree of states branching when editing after an undo.
+///
+/// Bounded by the number of states and their total size, the least recently visited leaves
+/// (and the oldest root) are dropped first. The states leading to the current one and the checkpoints are never dropped.
+#[derive(Clone, Debug)]
+pub(crate) struct History {
+    nodes: BTreeMap<usize, Node>,
+    root: Option<usize>,
+    current: Option<usize>,
+    next_id: usize,
+    clock: u64,
+    bytes: usize,
+    max_entries: usize,
+    max_bytes: usize,
+    /// Time of the last recorded edit, consecutive edits are merged into its state.
+    last_edit: Option<f64>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            nodes: BTreeMap::new(),
+            root: None,
+            current: None,
+            next_id: 0,
+            clock: 0,
+            bytes: 0,
+            max_entries: 100,
+            max_bytes: 16 * 1024 * 1024,
+            last_edit: None,
+        }
+    }
+}
+
+impl History {
+    pub(crate) fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries.max(1);
+        self.trim();
+    }
+
+    pub(crate) fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    pub(crate) fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    pub(crate) fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+        self.trim();
+    }
+
+    pub(crate) fn root(&self) -> Option<usize> {
+        self.root
+    }
+
+    pub(crate) fn current_id(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub(crate) fn node(&self, id: usize) -> Option<&Node> {
+        self.nodes.get(&id)
+    }
+
+    /// Code of the current state, `None` without states.
+    pub(crate) fn current_code(&self) -> Option<&str> {
+        Some(&self.nodes.get(&self.current?)?.snapshot.code)
+    }
+
+    /// Drops all the states and the checkpoints, keeping the limits.
+    pub(crate) fn clear(&mut self) {
+        *self = Self {
+            max_entries: self.max_entries,
+            max_bytes: self.max_bytes,
+            ..Self::default()
+        };
+    }
+
+    /// Records `code` if it differs from the current state.
+    ///
+    /// Edits at `time` (seconds) shortly after the previous one replace its state instead of adding a new one.
+    pub(crate) fn record(&mut self, code: &str, cursor: Option<CCursorRange>, time: f64) {
+        let Some(current) = self.current else {
+            let id = self.insert(None, code, cursor, time);
+            self.root = Some(id);
+            self.current = Some(id);
+            return;
+        };
+        let node = &self.nodes[&current];
+        if node.snapshot.code == code {
+            self.nodes.get_mut(&current).expect("current node").snapshot.cursor = cursor;
+            return;
+        }
+
+        let coalesce = node.parent.is_some()
+            && node.children.is_empty()
+            && self.last_edit.is_some_and(|last| time - last < COALESCE_TIME);
+        if coalesce {
+            let parent = node.parent.and_then(|id| self.nodes.get(&id));
+            let (inserted, removed) = parent.map_or((0, 0), |parent| change_size(&parent.snapshot.code, code));
+            let node = self.nodes.get_mut(&current).expect("current node");
+            self.bytes = self.bytes - node.snapshot.code.len() + code.len();
+            node.snapshot = Snapshot {
+                code: code.to_owned(),
+                cursor,
+            };
+            node.time = time;
+            (node.inserted, node.removed) = (inserted, removed);
+        } else {
+            let id = self.insert(Some(current), code, cursor, time);
+            let parent = self.nodes.get_mut(&current).expect("current node");
+            parent.children.push(id);
+            parent.redo_child = Some(id);
+            self.current = Some(id);
+        }
+        self.last_edit = Some(time);
+        self.trim();
+    }
+
+    fn insert(&mut self, parent: Option<usize>, code: &str, cursor: Option<CCursorRange>, time: f64) -> usize {
+        let (inserted, removed) = parent
+            .and_then(|id| self.nodes.get(&id))
+            .map_or((0, 0), |parent| change_size(&parent.snapshot.code, code));
+        let id = self.next_id;
+        self.next_id += 1;
+        self.clock += 1;
+        self.bytes += code.len();
+        self.nodes.insert(
+            id,
+            Node {
+                snapshot: Snapshot {
+                    code: code.to_owned(),
+                    cursor,
+                },
+                parent,
+                children: Vec::new(),
+                redo_child: None,
+                time,
+                inserted,
+                removed,
+                label: None,
+                visited: self.clock,
+            },
+        );
+        id
+    }
+
+    /// Records `code` as its own state and labels it, returns the id of the state.
+    pub(crate) fn checkpoint(&mut self, code: &str, cursor: Option<CCursorRange>, label: String) -> usize {
+        self.commit(code, cursor);
+        let id = self.current.expect("recorded above");
+        self.nodes.get_mut(&id).expect("current node").label = Some(label);
+        id
+    }
+
+    /// Records `code` as its own state, when it changed outside of the frames (between two `show`).
+    pub(crate) fn commit(&mut self, code: &str, cursor: Option<CCursorRange>) {
+        let time = self.current.and_then(|id| self.nodes.get(&id)).map_or(0.0, |node| node.time);
+        self.seal();
+        self.record(code, cursor, time);
+        self.seal();
+    }
+
+    pub(crate) fn checkpoints(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.nodes.iter().filter_map(|(id, node)| Some((*id, node.label.as_deref()?)))
+    }
+
+    /// Ends the current group of edits, the next edit starts a new undo step.
+    pub(crate) fn seal(&mut self) {
+        self.last_edit = None;
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        self.current.and_then(|id| self.nodes.get(&id)).is_some_and(|node| node.parent.is_some())
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        self.current.and_then(|id| self.nodes.get(&id)).is_some_and(|node| node.redo_child.is_some())
+    }
+
+    pub(crate) fn undo(&mut self) -> Option<&Snapshot> {
+        let parent = self.nodes.get(&self.current?)?.parent?;
+        self.jump(parent)
+    }
+
+    pub(crate) fn redo(&mut self) -> Option<&Snapshot> {
+        let child = self.nodes.get(&self.current?)?.redo_child?;
+        self.jump(child)
+    }
+
+    /// Makes the state `id` current, redo then follows the path to the previous state.
+    pub(crate) fn jump(&mut self, id: usize) -> Option<&Snapshot> {
+        if !self.nodes.contains_key(&id) {
+            return None;
+        }
+        let mut child = id;
+        while let Some(parent) = self.nodes[&child].parent {
+            self.nodes.get_mut(&parent).expect("parent node").redo_child = Some(child);
+            child = parent;
+        }
+        self.clock += 1;
+        self.current = Some(id);
+        self.seal();
+        let node = self.nodes.get_mut(&id).expect("checked above");
+        node.visited = self.clock;
+        Some(&node.snapshot)
+    }
+
+    /// Drops the least recently visited states over the limits.
+    fn trim(&mut self) {
+        while self.nodes.len() > self.max_entries || self.bytes > self.max_bytes {
+            let removable = self.nodes.iter().filter(|(id, node)| {
+                Some(**id) != self.current
+                    && node.label.is_none()
+                    && (node.children.is_empty() || (node.parent.is_none() && node.children.len() == 1))
+            });
+            let Some(id) = removable.min_by_key(|(_, node)| node.visited).map(|(id, _)| *id) else {
+                break;
+            };
+            let node = self.nodes.remove(&id).expect("removable node");
+            self.bytes -= node.snapshot.code.len();
+            match node.parent {
+                Some(parent) => {
+                    let parent = self.nodes.get_mut(&parent).expect("parent node");
+                    parent.children.retain(|child| *child != id);
+                    if parent.redo_child == Some(id) {
+                        parent.redo_child = parent.children.last().copied();
+                    }
+                }
+                None => {
+                    let child = node.children[0];
+                    self.nodes.get_mut(&child).expect("child node").parent = None;
+                    self.root = Some(child);
+                }
+            }
+        }
+    }
+}
+
+/// Number of chars inserted and removed between `old` and `new`.
+fn change_size(old: &str, new: &str) -> (usize, usize) {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((i, _), _)| i);
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .char_indices()
+        .rev()
+        .zip(new.chars().rev())
+        .find(|((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((i, a), _)| old.len() - i - a.len_utf8());
+    (
+        new[..new.len() - suffix].chars().count(),
+        old[..old.len() - suffix].chars().count(),
+    )
+}
+
+/// State of the undo history marked with [`CodeEditor::checkpoint`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CheckpointId(pub(crate) usize);
+
+/// Tree of the undo history of a [`CodeEditor`].
+///
+/// # Implement
+///
+/// Use `HistoryPanel::new()` to create a new instance.\
+/// Then call its `ui` method with the editor, for example `panel.ui(ui, &mut editor)`.
+///
+/// # Usage
+///
+/// Editing after an undo starts a new branch, so no state is lost.\
+/// Clicking a state restores it in the editor, undo and redo then move along its branch.\
+/// The checkpoints are shown with their label.
+#[derive(Clone, Debug, Default)]
+pub struct HistoryPanel {}
+
+impl HistoryPanel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shows the panel, returns `true` when a state was restored.
+    pub fn ui(&mut self, ui: &mut Ui, editor: &mut CodeEditor) -> bool {
+        let now = ui.input(|i| i.time);
+        let mut clicked = None;
+        let mut step = None;
+        ui.horizontal(|ui| {
+            if ui.add_enabled(editor.history().can_undo(), egui::Button::new("Undo")).clicked() {
+                step = Some(false);
+            }
+            if ui.add_enabled(editor.history().can_redo(), egui::Button::new("Redo")).clicked() {
+                step = Some(true);
+            }
+        });
+        ui.separator();
+        egui::ScrollArea::vertical().id_salt("history").auto_shrink([false, true]).show(ui, |ui| {
+            let history = editor.history();
+            let Some(root) = history.root() else {
+                ui.weak("No history");
+                return;
+            };
+
+            // depth first, the later branches are indented below their parent
+            let mut stack = vec![(root, 0usize)];
+            while let Some((id, branch)) = stack.pop() {
+                let Some(node) = history.node(id) else {
+                    continue;
+                };
+                for (i, child) in node.children.iter().enumerate().rev() {
+                    stack.push((*child, if i == 0 { branch } else { branch + 1 }));
+                }
+
+                let current = history.current_id() == Some(id);
+                let label = match (&node.label, node.parent) {
+                    (Some(label), _) => format!("⚑ {label}"),
+                    (None, None) => "Initial".to_owned(),
+                    (None, Some(_)) => format!("+{} −{}", node.inserted, node.removed),
+                };
+                let age = (now - node.time).max(0.0) as u64;
+                ui.horizontal(|ui| {
+                    ui.add_space(branch as f32 * ui.spacing().indent);
+                    let marker = if current { "●" } else { "○" };
+                    if ui.selectable_label(current, format!("{marker} {label}")).clicked() {
+                        clicked = Some(id);
+                    }
+                    ui.label(RichText::new(format_age(age)).weak().small());
+                });
+            }
+        });
+
+        match (clicked, step) {
+            (Some(id), _) => editor.jump_to_state(id),
+            (None, Some(redo)) => editor.step_history(redo),
+            (None, None) => false,
+        }
+    }
+}
+
+pub(crate) fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..60 => format!("{seconds}s ago"),
+        60..3600 => format!("{}m ago", seconds / 60),
+        _ => format!("{}h ago", seconds / 3600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(history: &History) -> &str {
+        history.current_code().unwrap()
+    }
+
+    #[test]
+    fn coalesces_quick_edits() {
+        let mut history = History::default();
+        history.record("", None, 0.0);
+        history.record("a", None, 10.0);
+        history.record("ab", None, 10.5);
+        history.record("abc", None, 10.5 + COALESCE_TIME * 0.9);
+        assert_eq!(history.nodes.len(), 2);
+        let node = history.node(history.current_id().unwrap()).unwrap();
+        assert_eq!((node.inserted, node.removed), (3, 0));
+
+        // a pause starts a new step
+        history.record("abcd", None, 20.0);
+        assert_eq!(history.nodes.len(), 3);
+        assert_eq!(history.undo().unwrap().code, "abc");
+        assert_eq!(history.undo().unwrap().code, "");
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn commit_is_its_own_step() {
+        let mut history = History::default();
+        history.record("", None, 0.0);
+        history.record("a", None, 1.0);
+        history.commit("ab", None);
+        history.record("abc", None, 1.0);
+        assert_eq!(history.undo().unwrap().code, "ab");
+        assert_eq!(history.undo().unwrap().code, "a");
+    }
+
+    #[test]
+    fn branches_after_undo() {
+        let mut history = History::default();
+        history.record("", None, 0.0);
+        history.record("a", None, 10.0);
+        history.undo();
+        history.record("b", None, 20.0);
+        let children = history.node(history.root().unwrap()).unwrap().children.clone();
+        assert_eq!(children.len(), 2);
+        assert_eq!(code(&history), "b");
+
+        // redo follows the last visited branch
+        history.undo();
+        assert_eq!(history.redo().unwrap().code, "b");
+        history.jump(children[0]);
+        history.undo();
+        assert_eq!(history.redo().unwrap().code, "a");
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn trims_the_oldest_root_but_not_the_checkpoints() {
+        let mut history = History::default();
+        history.set_max_entries(3);
+        history.record("", None, 0.0);
+        let checkpoint = history.checkpoint("0", None, "start".to_owned());
+        for (i, text) in ["01", "012", "0123"].into_iter().enumerate() {
+            history.record(text, None, 10.0 * (i + 1) as f64);
+        }
+        // the states leading to the current one are kept over the limit
+        assert_eq!(history.nodes.len(), 4);
+        assert_eq!(history.root(), Some(checkpoint));
+        assert_eq!(history.checkpoints().collect::<Vec<_>>(), [(checkpoint, "start")]);
+        assert_eq!(code(&history), "0123");
+    }
+}
@@ -0,0 +1,67 @@
+use std::ops::Range;
+
+use egui::ImageSource;
+
+/// Largest side of the preview, the larger images are scaled down.
+pub(crate) const MAX_SIZE: f32 = 160.0;
+
+/// Extensions of the image paths, compared ignoring the case.
+const EXTENSIONS: [&str; 11] = ["png", "jpg", "jpeg", "gif", "bmp", "webp", "svg", "ico", "tga", "tif", "tiff"];
+
+/// Loads the images previewed when hovering a string of the [`CodeEditor`](crate::CodeEditor) with their path
+/// or a data URI.
+///
+/// # Implement
+///
+/// Implement `image` returning the image of `source`, a path as written in the code (like `assets/logo.png`)
+/// or a `data:image/...` URI, `None` if it cannot be loaded, and set it with `CodeEditor::image_preview_provider`.
+/// The image is shown by egui: an [`ImageSource::Uri`] needs an installed image loader (see `egui_extras`),
+/// decode the data URIs to [`ImageSource::Bytes`] or a texture.\
+/// The editor calls it again only when another string is hovered.
+pub trait ImagePreviewProvider: Send {
+    fn image(&mut self, source: &str) -> Option<ImageSource<'static>>;
+}
+
+impl<F> ImagePreviewProvider for F
+where
+    F: FnMut(&str) -> Option<ImageSource<'static>> + Send,
+{
+    fn image(&mut self, source: &str) -> Option<ImageSource<'static>> {
+        self(source)
+    }
+}
+
+/// Whether `text` is a data URI of an image or a path with an image extension (ignoring its query and fragment).
+pub(crate) fn is_image_source(text: &str) -> bool {
+    if text.starts_with("data:image/") {
+        return true;
+    }
+    let path = text.split(['?', '#']).next().unwrap_or_default();
+    !path.is_empty()
+        && !path.contains(char::is_whitespace)
+        && path
+            .rsplit_once('.')
+            .is_some_and(|(stem, extension)| !stem.is_empty() && EXTENSIONS.iter().any(|e| e.eq_ignore_ascii_case(extension)))
+}
+
+/// Byte range of the contents of the quoted string of `line` around the byte `offset`, if it is an image source.
+pub(crate) fn image_source_at(line: &str, offset: usize) -> Option<Range<usize>> {
+    let mut open: Option<(char, usize)> = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match open {
+            _ if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some((quote, start)) if c == quote => {
+                if (start..=i).contains(&offset) {
+                    return Some(start..i).filter(|range| is_image_source(&line[range.clone()]));
+                }
+                open = None;
+            }
+            Some(_) => {}
+            None if matches!(c, '"' | '\'' | '`') => open = Some((c, i + 1)),
+            None => {}
+        }
+    }
+    None
+}
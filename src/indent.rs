@@ -0,0 +1,242 @@
+use std::ops::Range;
+
+use crate::editor::IndentStyle;
+use crate::language::LanguageConfig;
+use crate::position::LineIndex;
+
+/// Leading whitespace of `line`.
+pub(crate) fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Indentation of one level of `style` in `code`, `None` for a tab and the width for spaces.
+///
+/// [`IndentStyle::Detect`] gives a tab if the first indented line of `code` starts with one, `tab_size` spaces otherwise.
+pub(crate) fn indent_width(code: &str, style: IndentStyle, tab_size: usize) -> Option<usize> {
+    match style {
+        IndentStyle::Tabs => None,
+        IndentStyle::Spaces(width) => Some(width.max(1)),
+        IndentStyle::Detect => {
+            let first = code.lines().map(indentation).find(|indentation| !indentation.is_empty());
+            match first {
+                Some(indentation) if indentation.starts_with('\t') => None,
+                _ => Some(tab_size.max(1)),
+            }
+        }
+    }
+}
+
+/// Text of one level of indentation, see [`indent_width`].
+pub(crate) fn indent_unit(code: &str, style: IndentStyle, tab_size: usize) -> String {
+    indent_width(code, style, tab_size).map_or_else(|| "\t".to_owned(), |width| " ".repeat(width))
+}
+
+/// Edits inserting one `unit` of indentation at the start of the non-blank `lines` of `code`.
+pub(crate) fn indent_lines(code: &str, lines: Range<usize>, unit: &str) -> Vec<(Range<usize>, String)> {
+    let index = LineIndex::new(code);
+    lines
+        .filter(|&line| line < index.line_count() && !index.line(line).trim().is_empty())
+        .map(|line| {
+            let start = index.line_range(line).start;
+            (start..start, unit.to_owned())
+        })
+        .collect()
+}
+
+/// Edits removing one level of indentation from the start of the `lines` of `code`: a tab, or up to `width` spaces.
+pub(crate) fn dedent_lines(code: &str, lines: Range<usize>, width: usize) -> Vec<(Range<usize>, String)> {
+    let index = LineIndex::new(code);
+    lines
+        .filter(|&line| line < index.line_count())
+        .filter_map(|line| {
+            let start = index.line_range(line).start;
+            let text = index.line(line);
+            let len = if text.starts_with('\t') {
+                1
+            } else {
+                text.bytes().take(width.max(1)).take_while(|&b| b == b' ').count()
+            };
+            (len > 0).then(|| (start..start + len, String::new()))
+        })
+        .collect()
+}
+
+/// Indentation of the new line after the line break just typed before the byte `offset`: the one of the previous
+/// line, with one more `unit` after an [`indent_after`](LanguageConfig::indent_after) ending.
+///
+/// Returns the text to insert at `offset` and the byte length of the indentation the caret goes after. Between
+/// an opened bracket and its close char (like after typing `{` and `Enter` in `{}`), the close char goes on a line
+/// of its own, at the indentation of the opening line.
+pub(crate) fn newline_indent(code: &str, offset: usize, config: &LanguageConfig, unit: &str) -> Option<(String, usize)> {
+    let previous = code[..offset].strip_suffix('\n')?;
+    let line = &previous[previous.rfind('\n').map_or(0, |i| i + 1)..];
+    let base = indentation(line);
+    let opens = config.indents_after(line);
+    let mut text = base.to_owned();
+    if opens {
+        text.push_str(unit);
+    }
+    let caret = text.len();
+    let closes = opens
+        && line.trim_end().chars().next_back().and_then(|open| config.closing_bracket(open)).is_some_and(|close| {
+            code[offset..].starts_with(close)
+        });
+    if closes {
+        text.push('\n');
+        text.push_str(base);
+    }
+    (!text.is_empty()).then_some((text, caret))
+}
+
+/// New indentation of the line at the byte `offset` after the electric char `typed` was typed before it.
+///
+/// Returns the byte range of the current indentation and its replacement, `None` if it does not change.
+pub(crate) fn electric_indent(
+    code: &str,
+    offset: usize,
+    typed: char,
+    config: &LanguageConfig,
+) -> Option<(Range<usize>, String)> {
+    if !config.is_electric(typed) {
+        return None;
+    }
+    let index = LineIndex::new(code);
+    let line = index.line_of(offset);
+    let line_range = index.line_range(line);
+    let text = &code[line_range.clone()];
+    let current = indentation(text);
+    let content = text.trim_start();
+
+    let target = if let Some(open) = config.opening_bracket(typed)
+        && content.starts_with(typed)
+        && offset == line_range.start + current.len() + typed.len_utf8()
+    {
+        let open_offset = matching_open(&code[..line_range.start + current.len()], open, typed)?;
+        indentation(index.line(index.line_of(open_offset))).to_owned()
+    } else if config.dedent_words.iter().any(|word| {
+        // a whole word, ended by the typed letter (the `e` of `else`, not a later `e` of the line)
+        // or followed by a typed punctuation (the `:` of `else:`)
+        let word_end = line_range.start + current.len() + word.len();
+        content.strip_prefix(word.as_str()).is_some_and(|rest| !rest.starts_with(|c| config.is_word_char(c)))
+            && (offset == word_end || !config.is_word_char(typed))
+    }) {
+        // the closest less indented line opening a block
+        (0..line)
+            .rev()
+            .map(|l| index.line(l))
+            .find(|l| !l.trim().is_empty() && indentation(l).len() < current.len() && config.indents_after(l))
+            .map(|l| indentation(l).to_owned())?
+    } else {
+        return None;
+    };
+
+    (target != current).then(|| (line_range.start..line_range.start + current.len(), target))
+}
+
+/// Byte offset of the unmatched `open` bracket closest to the end of `code`.
+fn matching_open(code: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, c) in code.char_indices().rev() {
+        if c == close {
+            depth += 1;
+        } else if c == open {
+            if depth == 0 {
+                return Some(i);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comment::shift_offset;
+
+    #[test]
+    fn indents_the_non_blank_lines() {
+        let edits = indent_lines("a\n\n  \nb", 0..4, "\t");
+        assert_eq!(edits, vec![(0..0, "\t".to_owned()), (6..6, "\t".to_owned())]);
+        assert_eq!(indent_lines("a", 0..3, "  "), vec![(0..0, "  ".to_owned())]);
+    }
+
+    #[test]
+    fn dedents_a_tab_or_up_to_the_width() {
+        let edits = dedent_lines("  a\n    b\n      c", 0..3, 4);
+        assert_eq!(edits, vec![(0..2, String::new()), (4..8, String::new()), (10..14, String::new())]);
+        assert_eq!(dedent_lines("\t\ta\n\t  b", 0..2, 4), vec![(0..1, String::new()), (4..5, String::new())]);
+        assert_eq!(dedent_lines("a\n\n   ", 0..3, 4), vec![(3..6, String::new())]);
+    }
+
+    #[test]
+    fn keeps_the_selection_on_the_indented_lines() {
+        let edits = indent_lines("a\nb", 0..2, "  ");
+        assert_eq!((shift_offset(0, &edits), shift_offset(3, &edits)), (0, 7));
+
+        let edits = dedent_lines("    a\n    b", 0..2, 4);
+        assert_eq!((shift_offset(4, &edits), shift_offset(11, &edits)), (0, 3));
+    }
+
+    #[test]
+    fn dedents_a_closer_alone_on_its_line() {
+        let rust = LanguageConfig::for_syntax("Rust");
+        let code = "fn f() {\n    a();\n    }";
+        assert_eq!(electric_indent(code, code.len(), '}', &rust), Some((18..22, String::new())));
+        let code = "    {\n        }";
+        assert_eq!(electric_indent(code, code.len(), '}', &rust), Some((6..14, "    ".to_owned())));
+    }
+
+    #[test]
+    fn keeps_a_closer_after_code() {
+        let rust = LanguageConfig::for_syntax("Rust");
+        let code = "fn f() {\n    a(); }";
+        assert_eq!(electric_indent(code, code.len(), '}', &rust), None);
+        let code = "fn f() {\n    a}";
+        assert_eq!(electric_indent(code, code.len(), '}', &rust), None);
+    }
+
+    #[test]
+    fn keeps_an_unmatched_closer() {
+        let rust = LanguageConfig::for_syntax("Rust");
+        assert_eq!(electric_indent("    }", 5, '}', &rust), None);
+        let code = "a)\n    }";
+        assert_eq!(electric_indent(code, code.len(), '}', &rust), None);
+    }
+
+    #[test]
+    fn dedents_the_dedent_words() {
+        let python = LanguageConfig::for_syntax("Python");
+        let code = "if a:\n    b\n    else:";
+        assert_eq!(electric_indent(code, code.len(), ':', &python), Some((12..16, String::new())));
+        let code = "if a:\n    b\n    elsewhere:";
+        assert_eq!(electric_indent(code, code.len(), ':', &python), None);
+    }
+
+    #[test]
+    fn carries_the_indentation_over() {
+        let rust = LanguageConfig::for_syntax("Rust");
+        assert_eq!(newline_indent("    a\n", 6, &rust, "    "), Some(("    ".to_owned(), 4)));
+        assert_eq!(newline_indent("\ta\n\tb", 3, &rust, "    "), Some(("\t".to_owned(), 1)));
+        assert_eq!(newline_indent("a\n", 2, &rust, "    "), None);
+        assert_eq!(newline_indent("a", 1, &rust, "    "), None);
+    }
+
+    #[test]
+    fn indents_after_an_opener() {
+        let rust = LanguageConfig::for_syntax("Rust");
+        let code = "  fn f() {  \n";
+        assert_eq!(newline_indent(code, code.len(), &rust, "    "), Some(("      ".to_owned(), 6)));
+        let python = LanguageConfig::for_syntax("Python");
+        assert_eq!(newline_indent("if a:\n", 6, &python, "\t"), Some(("\t".to_owned(), 1)));
+    }
+
+    #[test]
+    fn splits_the_brackets() {
+        let rust = LanguageConfig::for_syntax("Rust");
+        assert_eq!(newline_indent("{\n}", 2, &rust, "    "), Some(("    \n".to_owned(), 4)));
+        assert_eq!(newline_indent("  f(\n)", 5, &rust, "  "), Some(("    \n  ".to_owned(), 4)));
+        // not the closer of the opened bracket
+        assert_eq!(newline_indent("{\n)", 2, &rust, "    "), Some(("    ".to_owned(), 4)));
+    }
+}
@@ -0,0 +1,379 @@
+use std::cmp::Reverse;
+
+use egui::{Key, KeyboardShortcut, Modifiers, Ui};
+
+/// Action of the editor bound to keyboard shortcuts in a [`Keymap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Command {
+    Save,
+    Undo,
+    Redo,
+    Rename,
+    QuickFix,
+    Find,
+    FindNext,
+    FindPrevious,
+    Replace,
+    PasteFromHistory,
+    CopyWithLineNumbers,
+    CopyAsMarkdown,
+    CopyPathLine,
+    NextDuplicate,
+    PreviousDuplicate,
+    ShortcutHelp,
+    ToggleZenMode,
+    ToggleComment,
+    RunCell,
+    ToggleLiteral,
+}
+
+impl Command {
+    pub const ALL: &[Command] = &[
+        Command::Save,
+        Command::Undo,
+        Command::Redo,
+        Command::Rename,
+        Command::QuickFix,
+        Command::Find,
+        Command::FindNext,
+        Command::FindPrevious,
+        Command::Replace,
+        Command::PasteFromHistory,
+        Command::CopyWithLineNumbers,
+        Command::CopyAsMarkdown,
+        Command::CopyPathLine,
+        Command::NextDuplicate,
+        Command::PreviousDuplicate,
+        Command::ShortcutHelp,
+        Command::ToggleZenMode,
+        Command::ToggleComment,
+        Command::RunCell,
+        Command::ToggleLiteral,
+    ];
+
+    /// Identifier of the command in the serialized keymaps, like `"quick_fix"`.
+    pub fn id(self) -> &'static str {
+        match self {
+            Command::Save => "save",
+            Command::Undo => "undo",
+            Command::Redo => "redo",
+            Command::Rename => "rename",
+            Command::QuickFix => "quick_fix",
+            Command::Find => "find",
+            Command::FindNext => "find_next",
+            Command::FindPrevious => "find_previous",
+            Command::Replace => "replace",
+            Command::PasteFromHistory => "paste_from_history",
+            Command::CopyWithLineNumbers => "copy_with_line_numbers",
+            Command::CopyAsMarkdown => "copy_as_markdown",
+            Command::CopyPathLine => "copy_path_line",
+            Command::NextDuplicate => "next_duplicate",
+            Command::PreviousDuplicate => "previous_duplicate",
+            Command::ShortcutHelp => "shortcut_help",
+            Command::ToggleZenMode => "toggle_zen_mode",
+            Command::ToggleComment => "toggle_comment",
+            Command::RunCell => "run_cell",
+            Command::ToggleLiteral => "toggle_literal",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|command| command.id() == id)
+    }
+
+    /// Name shown in the shortcut help.
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::Save => "Save",
+            Command::Undo => "Undo",
+            Command::Redo => "Redo",
+            Command::Rename => "Rename symbol",
+            Command::QuickFix => "Quick fixes",
+            Command::Find => "Find",
+            Command::FindNext => "Find next",
+            Command::FindPrevious => "Find previous",
+            Command::Replace => "Replace",
+            Command::PasteFromHistory => "Paste from clipboard history",
+            Command::CopyWithLineNumbers => "Copy with line numbers",
+            Command::CopyAsMarkdown => "Copy as Markdown code fence",
+            Command::CopyPathLine => "Copy file path:line",
+            Command::NextDuplicate => "Next duplicate line",
+            Command::PreviousDuplicate => "Previous duplicate line",
+            Command::ShortcutHelp => "Keyboard shortcuts",
+            Command::ToggleZenMode => "Zen mode",
+            Command::ToggleComment => "Toggle line comment",
+            Command::RunCell => "Run cell",
+            Command::ToggleLiteral => "Toggle true/false",
+        }
+    }
+
+    /// Group of the command in the shortcut help.
+    pub fn category(self) -> &'static str {
+        match self {
+            Command::Save => "File",
+            Command::Undo | Command::Redo | Command::PasteFromHistory => "Edit",
+            Command::CopyWithLineNumbers | Command::CopyAsMarkdown | Command::CopyPathLine => "Edit",
+            Command::Rename | Command::QuickFix | Command::ToggleComment | Command::RunCell => "Code",
+            Command::ToggleLiteral => "Code",
+            Command::Find | Command::FindNext | Command::FindPrevious | Command::Replace => "Search",
+            Command::NextDuplicate | Command::PreviousDuplicate => "Search",
+            Command::ToggleZenMode => "View",
+            Command::ShortcutHelp => "Help",
+        }
+    }
+
+    /// Categories of the commands, each once, in the order of their first command in [`Command::ALL`].
+    pub(crate) fn categories() -> Vec<&'static str> {
+        let mut categories = Vec::new();
+        for command in Self::ALL {
+            if !categories.contains(&command.category()) {
+                categories.push(command.category());
+            }
+        }
+        categories
+    }
+}
+
+/// Keyboard shortcuts of the editor commands.
+///
+/// `Keymap::default()` has the built-in bindings, `Keymap::new()` none. The copy variants, the zen mode
+/// and the run cell are not bound by default.
+/// A command can have several shortcuts, a shortcut triggers one command.
+///
+/// With the `serde` feature the keymap is (de)serialized as a map from the command ids to the shortcuts,
+/// like `{"redo": ["Ctrl+Shift+Z", "Ctrl+Y"]}` in JSON or `redo = ["Ctrl+Shift+Z", "Ctrl+Y"]` in TOML.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: Vec<(KeyboardShortcut, Command)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self::new();
+        keymap.bind(KeyboardShortcut::new(Modifiers::COMMAND, Key::S), Command::Save);
+        keymap.bind(KeyboardShortcut::new(Modifiers::COMMAND, Key::Z), Command::Undo);
+        keymap.bind(KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z), Command::Redo);
+        keymap.bind(KeyboardShortcut::new(Modifiers::COMMAND, Key::Y), Command::Redo);
+        keymap.bind(KeyboardShortcut::new(Modifiers::NONE, Key::F2), Command::Rename);
+        keymap.bind(KeyboardShortcut::new(Modifiers::COMMAND, Key::Period), Command::QuickFix);
+        keymap.bind(KeyboardShortcut::new(Modifiers::COMMAND, Key::Slash), Command::ToggleComment);
+        keymap.bind(KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::T), Command::ToggleLiteral);
+        keymap.bind(KeyboardShortcut::new(Modifiers::COMMAND, Key::F), Command::Find);
+        keymap.bind(KeyboardShortcut::new(Modifiers::NONE, Key::F3), Command::FindNext);
+        keymap.bind(KeyboardShortcut::new(Modifiers::SHIFT, Key::F3), Command::FindPrevious);
+        keymap.bind(KeyboardShortcut::new(Modifiers::COMMAND, Key::H), Command::Replace);
+        keymap.bind(KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::V), Command::PasteFromHistory);
+        keymap.bind(KeyboardShortcut::new(Modifiers::ALT, Key::F3), Command::NextDuplicate);
+        keymap.bind(KeyboardShortcut::new(Modifiers::ALT | Modifiers::SHIFT, Key::F3), Command::PreviousDuplicate);
+        keymap.bind(KeyboardShortcut::new(Modifiers::NONE, Key::F1), Command::ShortcutHelp);
+        keymap
+    }
+}
+
+impl Keymap {
+    /// Keymap without any binding.
+    pub fn new() -> Self {
+        Self { bindings: Vec::new() }
+    }
+
+    /// Binds `shortcut` to `command`, replacing its previous command.
+    pub fn bind(&mut self, shortcut: KeyboardShortcut, command: Command) {
+        self.unbind(&shortcut);
+        self.bindings.push((shortcut, command));
+    }
+
+    /// Removes the binding of `shortcut`, returns its command.
+    pub fn unbind(&mut self, shortcut: &KeyboardShortcut) -> Option<Command> {
+        let index = self.bindings.iter().position(|(s, _)| s == shortcut)?;
+        Some(self.bindings.remove(index).1)
+    }
+
+    /// Removes all the shortcuts of `command`.
+    pub fn unbind_command(&mut self, command: Command) {
+        self.bindings.retain(|(_, c)| *c != command);
+    }
+
+    pub fn command(&self, shortcut: &KeyboardShortcut) -> Option<Command> {
+        self.bindings.iter().find(|(s, _)| s == shortcut).map(|(_, c)| *c)
+    }
+
+    /// Shortcuts of `command`, in the order they were bound.
+    pub fn shortcuts(&self, command: Command) -> impl Iterator<Item = &KeyboardShortcut> {
+        self.bindings.iter().filter(move |(_, c)| *c == command).map(|(s, _)| s)
+    }
+
+    pub fn bindings(&self) -> &[(KeyboardShortcut, Command)] {
+        &self.bindings
+    }
+
+    /// Replaces the shortcuts of the commands bound in `other`, the other commands keep theirs.
+    ///
+    /// Used to apply the user settings over the default keymap.
+    pub fn merge(&mut self, other: &Keymap) {
+        for (_, command) in &other.bindings {
+            self.unbind_command(*command);
+        }
+        for (shortcut, command) in &other.bindings {
+            self.bind(*shortcut, *command);
+        }
+    }
+
+    /// Consumes the pressed shortcuts, returns their commands.
+    ///
+    /// The shortcuts with more modifiers are matched first, so `Ctrl+Shift+Z` is not taken for `Ctrl+Z`.
+    pub(crate) fn consume(&self, ui: &Ui) -> Vec<Command> {
+        let mut bindings: Vec<&(KeyboardShortcut, Command)> = self.bindings.iter().collect();
+        bindings.sort_by_key(|(shortcut, _)| {
+            let m = shortcut.modifiers;
+            Reverse([m.alt, m.ctrl, m.shift, m.mac_cmd, m.command].iter().filter(|m| **m).count())
+        });
+        ui.input_mut(|i| {
+            bindings
+                .into_iter()
+                .filter(|(shortcut, _)| i.consume_shortcut(shortcut))
+                .map(|(_, command)| *command)
+                .collect()
+        })
+    }
+
+    /// Lists the bound commands grouped by category, the overlay toggled by [`Command::ShortcutHelp`].
+    pub(crate) fn help_ui(&self, ui: &mut Ui) {
+        for category in Command::categories() {
+            let commands = Command::ALL
+                .iter()
+                .filter(|c| c.category() == category && self.shortcuts(**c).next().is_some());
+            let rows: Vec<(Command, String)> = commands
+                .map(|c| {
+                    let shortcuts: Vec<String> = self.shortcuts(*c).map(|s| ui.ctx().format_shortcut(s)).collect();
+                    (*c, shortcuts.join(" / "))
+                })
+                .collect();
+            if rows.is_empty() {
+                continue;
+            }
+            ui.strong(category);
+            egui::Grid::new(("shortcut_help", category)).num_columns(2).striped(true).show(ui, |ui| {
+                for (command, shortcuts) in rows {
+                    ui.label(command.name());
+                    ui.monospace(shortcuts);
+                    ui.end_row();
+                }
+            });
+            ui.add_space(4.0);
+        }
+    }
+}
+
+/// Parses a shortcut like `"Ctrl+Shift+Z"` or `"F2"`.
+///
+/// `Ctrl` and `Cmd` both stand for the command modifier (`Cmd` on macOS, `Ctrl` elsewhere),
+/// the key names are the ones of [`Key::from_name`].
+pub fn parse_shortcut(text: &str) -> Option<KeyboardShortcut> {
+    let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    // the `+` key itself
+    if text.trim_end().ends_with("++") || text.trim() == "+" {
+        parts.retain(|part| !part.is_empty());
+        parts.push("+");
+    }
+    let (key, modifier_names) = parts.split_last()?;
+    let mut modifiers = Modifiers::NONE;
+    for name in modifier_names {
+        modifiers |= match name.to_ascii_lowercase().as_str() {
+            "ctrl" | "cmd" | "command" => Modifiers::COMMAND,
+            "shift" => Modifiers::SHIFT,
+            "alt" | "option" => Modifiers::ALT,
+            _ => return None,
+        };
+    }
+    Some(KeyboardShortcut::new(modifiers, Key::from_name(key)?))
+}
+
+/// Writes `shortcut` in the format of [`parse_shortcut`].
+pub fn format_shortcut(shortcut: &KeyboardShortcut) -> String {
+    let m = shortcut.modifiers;
+    let mut text = String::new();
+    if m.command || m.ctrl || m.mac_cmd {
+        text.push_str("Ctrl+");
+    }
+    if m.alt {
+        text.push_str("Alt+");
+    }
+    if m.shift {
+        text.push_str("Shift+");
+    }
+    text.push_str(shortcut.logical_key.name());
+    text
+}
+
+/// A map from the command ids to their shortcuts, like `{"redo": ["Ctrl+Shift+Z", "Ctrl+Y"]}`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Keymap {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let bound = Command::ALL.iter().filter(|command| self.shortcuts(**command).next().is_some());
+        let mut map = serializer.serialize_map(None)?;
+        for command in bound {
+            let shortcuts: Vec<String> = self.shortcuts(*command).map(format_shortcut).collect();
+            map.serialize_entry(command.id(), &shortcuts)?;
+        }
+        map.end()
+    }
+}
+
+/// Only the listed commands are bound, use [`Keymap::merge`] to apply the result over the default keymap.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Keymap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let map = std::collections::BTreeMap::<String, Vec<String>>::deserialize(deserializer)?;
+        let mut keymap = Keymap::new();
+        for (id, shortcuts) in map {
+            let command = Command::from_id(&id).ok_or_else(|| D::Error::custom(format!("unknown command `{id}`")))?;
+            for text in shortcuts {
+                let shortcut =
+                    parse_shortcut(&text).ok_or_else(|| D::Error::custom(format!("invalid shortcut `{text}`")))?;
+                keymap.bind(shortcut, command);
+            }
+        }
+        Ok(keymap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_shortcuts() {
+        let shortcut = parse_shortcut("Ctrl+Shift+Z").unwrap();
+        assert_eq!(shortcut, KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z));
+        assert_eq!(parse_shortcut("cmd + alt + F2"), Some(KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::ALT, Key::F2)));
+        assert_eq!(parse_shortcut("F1"), Some(KeyboardShortcut::new(Modifiers::NONE, Key::F1)));
+        assert_eq!(parse_shortcut("Ctrl++"), Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::Plus)));
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        assert_eq!(parse_shortcut("Hyper+A"), None);
+        assert_eq!(parse_shortcut("Ctrl+Nope"), None);
+        assert_eq!(parse_shortcut(""), None);
+    }
+
+    #[test]
+    fn formats_parsable_shortcuts() {
+        for text in ["Ctrl+Shift+Z", "Ctrl+Alt+Enter", "F3"] {
+            assert_eq!(format_shortcut(&parse_shortcut(text).unwrap()), text);
+        }
+    }
+
+    #[test]
+    fn lists_each_category_once() {
+        let categories = Command::categories();
+        assert_eq!(categories, ["File", "Edit", "Code", "Search", "Help", "View"]);
+        for command in Command::ALL {
+            assert_eq!(categories.iter().filter(|c| **c == command.category()).count(), 1);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+/// Editing rules of a language: comments, brackets, indentation and words.
+///
+/// The built-in configs are looked up by the syntect syntax name with [`LanguageConfig::for_syntax`],
+/// give a [`LanguageRegistry`] to [`CodeEditor::with_language_registry`](crate::CodeEditor::with_language_registry)
+/// to override them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LanguageConfig {
+    /// Start of a line comment, like `//`.
+    pub line_comment: Option<String>,
+    /// Start and end of a block comment, like `/*` and `*/`.
+    pub block_comment: Option<(String, String)>,
+    /// Open and close chars of the brackets.
+    pub brackets: Vec<(char, char)>,
+    /// Line endings (ignoring the trailing whitespace) after which the next line is indented.
+    pub indent_after: Vec<String>,
+    /// Chars besides the alphanumerics and `_` belonging to the words (identifiers).
+    pub word_chars: Vec<char>,
+    /// Chars re-indenting the line when typed: a closing bracket typed first on the line aligns it with
+    /// the line of the opening bracket, other chars align the lines starting with a `dedent_words` word.
+    pub electric_chars: Vec<char>,
+    /// Words continuing the enclosing block (like `else` in Python),
+    /// their line is aligned with the block start when an electric char is typed.
+    pub dedent_words: Vec<String>,
+    /// The indentation is part of the syntax, like in Python or YAML.
+    pub significant_indentation: bool,
+}
+
+impl Default for LanguageConfig {
+    /// Plain text: no comments, the common brackets, no indentation rules.
+    fn default() -> Self {
+        Self {
+            line_comment: None,
+            block_comment: None,
+            brackets: vec![('(', ')'), ('[', ']'), ('{', '}')],
+            indent_after: Vec::new(),
+            word_chars: Vec::new(),
+            electric_chars: Vec::new(),
+            dedent_words: Vec::new(),
+            significant_indentation: false,
+        }
+    }
+}
+
+impl LanguageConfig {
+    /// Built-in config of the syntax `name` (like `"Rust"` or `"Python"`), the plain text one for unknown syntaxes.
+    pub fn for_syntax(name: &str) -> Self {
+        let c_like = || Self {
+            line_comment: Some("//".into()),
+            block_comment: Some(("/*".into(), "*/".into())),
+            indent_after: vec!["{".into(), "(".into(), "[".into()],
+            electric_chars: vec!['}', ')', ']'],
+            ..Self::default()
+        };
+        let hash = || Self {
+            line_comment: Some("#".into()),
+            ..Self::default()
+        };
+
+        match name {
+            "Rust" | "C" | "C++" | "C#" | "D" | "Go" | "Java" | "Groovy" | "Scala" | "Objective-C" | "Objective-C++"
+            | "ActionScript" => c_like(),
+            "JavaScript" | "PHP" => Self {
+                word_chars: vec!['$'],
+                ..c_like()
+            },
+            "CSS" => Self {
+                line_comment: None,
+                word_chars: vec!['-'],
+                ..c_like()
+            },
+            "JSON" => Self {
+                line_comment: None,
+                block_comment: None,
+                ..c_like()
+            },
+            "Python" => Self {
+                indent_after: vec![":".into(), "(".into(), "[".into(), "{".into()],
+                electric_chars: vec![':', ')', ']', '}'],
+                dedent_words: ["else", "elif", "except", "finally"].map(Into::into).to_vec(),
+                significant_indentation: true,
+                ..hash()
+            },
+            "YAML" => Self {
+                indent_after: vec![":".into()],
+                word_chars: vec!['-'],
+                significant_indentation: true,
+                ..hash()
+            },
+            "Bourne Again Shell (bash)" | "Shell-Unix-Generic" | "Makefile" | "Perl" | "R" => Self {
+                indent_after: vec!["{".into(), "then".into(), "do".into()],
+                word_chars: vec!['$'],
+                electric_chars: vec!['}', 'e', 'i', 'f', 'c'],
+                dedent_words: ["else", "elif", "fi", "done", "esac"].map(Into::into).to_vec(),
+                ..hash()
+            },
+            "Ruby" => Self {
+                indent_after: vec!["do".into(), "{".into()],
+                electric_chars: vec!['}', 'd', 'e', 'f'],
+                dedent_words: ["end", "else", "elsif", "rescue", "ensure"].map(Into::into).to_vec(),
+                ..hash()
+            },
+            "HTML" | "XML" | "Markdown" => Self {
+                block_comment: Some(("<!--".into(), "-->".into())),
+                brackets: vec![('<', '>'), ('(', ')'), ('[', ']'), ('{', '}')],
+                word_chars: vec!['-'],
+                ..Self::default()
+            },
+            "Lisp" | "Clojure" => Self {
+                line_comment: Some(";".into()),
+                indent_after: vec!["(".into(), "[".into()],
+                electric_chars: vec![')', ']'],
+                word_chars: vec!['-', '?', '!', '*', '+', '<', '>', '=', '/'],
+                ..Self::default()
+            },
+            "Haskell" | "Lua" | "SQL" => Self {
+                line_comment: Some("--".into()),
+                block_comment: match name {
+                    "Haskell" => Some(("{-".into(), "-}".into())),
+                    "Lua" => Some(("--[[".into(), "]]".into())),
+                    _ => Some(("/*".into(), "*/".into())),
+                },
+                ..Self::default()
+            },
+            "Erlang" | "LaTeX" | "TeX" | "MATLAB" => Self {
+                line_comment: Some("%".into()),
+                ..Self::default()
+            },
+            "OCaml" | "Pascal" => Self {
+                block_comment: Some(("(*".into(), "*)".into())),
+                ..Self::default()
+            },
+            _ => Self::default(),
+        }
+    }
+
+    /// The char belongs to a word (identifier) of the language.
+    pub fn is_word_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || self.word_chars.contains(&c)
+    }
+
+    /// The next line after `line` should be indented one level more.
+    pub fn indents_after(&self, line: &str) -> bool {
+        let line = line.trim_end();
+        self.indent_after.iter().any(|end| {
+            line.strip_suffix(end.as_str())
+                // the word endings (`do`, `then`) must be whole words
+                .is_some_and(|rest| !end.starts_with(|c| self.is_word_char(c)) || !rest.ends_with(|c| self.is_word_char(c)))
+        })
+    }
+
+    /// Close char of the bracket opened by `open`.
+    pub fn closing_bracket(&self, open: char) -> Option<char> {
+        self.brackets.iter().find(|(o, _)| *o == open).map(|(_, close)| *close)
+    }
+
+    pub fn is_electric(&self, c: char) -> bool {
+        self.electric_chars.contains(&c)
+    }
+
+    /// Open char of the bracket closed by `close`.
+    pub fn opening_bracket(&self, close: char) -> Option<char> {
+        self.brackets.iter().find(|(_, c)| *c == close).map(|(open, _)| *open)
+    }
+}
+
+/// Language configs by syntax name, falling back to the built-in ones.
+#[derive(Clone, Debug, Default)]
+pub struct LanguageRegistry {
+    overrides: HashMap<String, LanguageConfig>,
+}
+
+impl LanguageRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the config of the syntax `name`.
+    pub fn register(&mut self, name: impl Into<String>, config: LanguageConfig) {
+        self.overrides.insert(name.into(), config);
+    }
+
+    /// Removes the override of the syntax `name`, returning to the built-in config.
+    pub fn unregister(&mut self, name: &str) -> Option<LanguageConfig> {
+        self.overrides.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> LanguageConfig {
+        self.overrides
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| LanguageConfig::for_syntax(name))
+    }
+}
+
+/// Language embedded in the regions of a scope of the host syntax, like SQL in the strings of Rust.
+///
+/// The regions are highlighted with the embedded syntax, their quotes and delimiters with the host one,
+/// a region continued on the next lines (like a raw string) is highlighted as a whole.
+/// The syntaxes embedding a language themselves (JavaScript and CSS in HTML) need no injection,
+/// and the code fences of Markdown are highlighted with the syntax of their language name,
+/// like its YAML front matter with the YAML syntax and the shebang line of the scripts with the Bash one.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Injection {
+    /// Scope of the regions in the host syntax, or its prefix, like `string.quoted.double.rust`.
+    pub scope: String,
+    /// Name (like `"SQL"`) or extension (like `"sql"`) of the embedded syntax.
+    pub syntax: String,
+    /// Words starting the regions of the language (ignoring the case and the leading whitespace), like `SELECT`,
+    /// all the regions of the scope if empty.
+    pub starts_with: Vec<String>,
+}
+
+impl Injection {
+    pub fn new(scope: impl Into<String>, syntax: impl Into<String>) -> Self {
+        Self {
+            scope: scope.into(),
+            syntax: syntax.into(),
+            starts_with: Vec::new(),
+        }
+    }
+
+    /// Injects the language only in the regions starting with one of the `words`.
+    pub fn starts_with<S: Into<String>>(mut self, words: impl IntoIterator<Item = S>) -> Self {
+        self.starts_with = words.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The region `text` (from its start) is in the language.
+    pub(crate) fn accepts(&self, text: &str) -> bool {
+        let text = text.trim_start();
+        self.starts_with.is_empty()
+            || self.starts_with.iter().any(|word| {
+                text.get(..word.len()).is_some_and(|start| start.eq_ignore_ascii_case(word))
+                    && !text[word.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+            })
+    }
+}
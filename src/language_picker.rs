@@ -0,0 +1,169 @@
+use egui::{Key, Modifiers, RichText, Ui};
+
+use crate::CodeEditor;
+use crate::settings;
+
+/// Searchable list of the syntaxes of an editor, to change its language like "Change Language Mode" in VSCode.
+///
+/// # Implement
+///
+/// Use `LanguagePicker::new()` to create a new instance.\
+/// Then call its `button_ui` method in a status bar, for example `picker.button_ui(ui, &mut editor)`,
+/// or call `picker.open()` from a command palette and its `ui` method every frame.
+///
+/// # Usage
+///
+/// The typed text is matched fuzzily against the names and the file extensions of the syntaxes,
+/// `Up` and `Down` move in the list, `Enter` or a click switches the editor to the selected language.
+#[derive(Clone, Debug, Default)]
+pub struct LanguagePicker {
+    open: bool,
+    query: String,
+    /// Index of the selected entry in the filtered list.
+    selected: usize,
+    /// The search field is focused on the first frame of the popup.
+    focus_query: bool,
+}
+
+impl LanguagePicker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the popup with an empty search.
+    pub fn open(&mut self) {
+        self.open = true;
+        self.query.clear();
+        self.selected = 0;
+        self.focus_query = true;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Shows a button with the name of the editor language opening the popup, and the popup.
+    ///
+    /// Returns `true` when the language changed.
+    pub fn button_ui(&mut self, ui: &mut Ui, editor: &mut CodeEditor) -> bool {
+        if ui.button(editor.syntax_name()).on_hover_text("Select the language").clicked() {
+            self.open();
+        }
+        self.ui(ui, editor)
+    }
+
+    /// Shows the popup while it is open, returns `true` when the language changed.
+    pub fn ui(&mut self, ui: &Ui, editor: &mut CodeEditor) -> bool {
+        if !self.open {
+            return false;
+        }
+
+        let mut matches: Vec<(u32, &str, &[String])> = editor
+            .syntaxes()
+            .iter()
+            .filter(|syntax| !syntax.hidden)
+            .filter_map(|syntax| {
+                let by_extension = syntax.file_extensions.iter().filter_map(|ext| fuzzy_score(&self.query, ext)).max();
+                let score = fuzzy_score(&self.query, &syntax.name).max(by_extension)?;
+                Some((score, syntax.name.as_str(), syntax.file_extensions.as_slice()))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+        let (up, down, enter) = ui.input_mut(|i| {
+            (
+                i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                i.consume_key(Modifiers::NONE, Key::Enter),
+            )
+        });
+        if up {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if down {
+            self.selected += 1;
+        }
+        self.selected = self.selected.min(matches.len().saturating_sub(1));
+
+        let current = editor.syntax_name().to_owned();
+        let mut picked = enter.then(|| matches.get(self.selected).map(|(_, name, _)| name.to_string())).flatten();
+        let modal = egui::Modal::new(egui::Id::new("language_picker")).show(ui.ctx(), |ui| {
+            ui.set_width(320.0);
+            let search = ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .hint_text("Select the language")
+                    .desired_width(f32::INFINITY),
+            );
+            if std::mem::take(&mut self.focus_query) {
+                search.request_focus();
+            }
+            if search.changed() {
+                self.selected = 0;
+            }
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                if matches.is_empty() {
+                    ui.weak("No matching language");
+                }
+                for (i, (_, name, extensions)) in matches.iter().enumerate() {
+                    let mut text = RichText::new(*name);
+                    if *name == current {
+                        text = text.strong();
+                    }
+                    let row = ui
+                        .horizontal(|ui| {
+                            let row = ui.selectable_label(i == self.selected, text);
+                            ui.weak(extensions.join(", "));
+                            row
+                        })
+                        .inner;
+                    if i == self.selected && (up || down) {
+                        row.scroll_to_me_animation(None, settings::scroll_animation(ui));
+                    }
+                    if row.clicked() {
+                        picked = Some(name.to_string());
+                    }
+                }
+            });
+        });
+
+        if modal.should_close() || picked.is_some() {
+            self.open = false;
+        }
+        picked.is_some_and(|name| name != current && editor.set_syntax_by_name(&name).is_ok())
+    }
+}
+
+/// Score of `text` for the `query` typed in the picker, `None` if the chars of the query are not all in it, in order.
+///
+/// The consecutive chars and the chars at the start of a word score higher.
+fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let mut score = 0;
+    let mut chars = text.char_indices();
+    let mut previous: Option<(usize, char)> = None;
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let (i, c) = loop {
+            let (i, c) = chars.next()?;
+            if c.to_lowercase().eq(q.to_lowercase()) {
+                break (i, c);
+            }
+        };
+        score += 1;
+        let word_start = match text[..i].chars().next_back() {
+            None => true,
+            Some(before) => !before.is_alphanumeric() || (before.is_lowercase() && c.is_uppercase()),
+        };
+        if word_start {
+            score += 4;
+        }
+        if previous.is_some_and(|(p, pc)| p + pc.len_utf8() == i) {
+            score += 2;
+        }
+        previous = Some((i, c));
+    }
+    // the whole text typed
+    if query.trim().eq_ignore_ascii_case(text) {
+        score += 8;
+    }
+    Some(score)
+}
@@ -0,0 +1,139 @@
+use egui::epaint::text::{Fonts, TAB_SIZE};
+use egui::text::LayoutJob;
+use egui::{FontId, Galley};
+
+use crate::control;
+use crate::long_lines::Gap;
+use crate::position::display_width;
+
+/// Aligns the text of the job on a grid of columns of the width of a space in `font`, like a terminal.
+///
+/// egui gives a tab the width of 4 spaces wherever it is, so the text after a tab is moved to the next tab stop,
+/// every `tab_size` columns. The other non ASCII chars take the advance of their glyph in the font,
+/// so the text after them is moved to take 2 columns for the wide CJK chars and the emoji,
+/// 0 for the combining marks and 1 for the others.
+/// With `control_chars`, the control chars take a column per letter of their mnemonic, for their boxes.\
+/// The `gaps` are the columns of the long lines hidden from the text, the text after them is moved by their width.\
+/// The moves are the `leading_space` of the sections, split after the chars to move.
+/// egui rounds the position after every glyph to the pixels, so the advances are rounded too.
+pub(crate) fn align_columns(
+    job: &mut LayoutJob,
+    tab_size: usize,
+    control_chars: bool,
+    gaps: &[Gap],
+    fonts: &Fonts,
+    font: &FontId,
+) {
+    let plain = job.text.is_ascii() && !job.text.contains('\t') && !(control_chars && control::has_control(&job.text));
+    if plain && gaps.is_empty() {
+        return;
+    }
+    let pixels_per_point = fonts.pixels_per_point();
+    let round = |width: f32| (width * pixels_per_point).round() / pixels_per_point;
+    let space = fonts.glyph_width(font, ' ');
+    let char_width = column_width(fonts, font);
+    let tab_advance = round(TAB_SIZE as f32 * space);
+    let tab_size = tab_size.max(1);
+    let text = &job.text;
+    let mut sections = Vec::with_capacity(job.sections.len());
+    let mut column = 0;
+    // leading space of a char ending the previous section
+    let mut carried = 0.0;
+    let mut gaps = gaps.iter().peekable();
+    for section in job.sections.drain(..) {
+        let range = section.byte_range.clone();
+        let mut start = range.start;
+        let mut leading = section.leading_space + std::mem::take(&mut carried);
+        for (i, c) in text[range.clone()].char_indices() {
+            let at = range.start + i;
+            while let Some((_, columns)) = gaps.next_if(|(offset, _)| *offset <= at) {
+                column += columns;
+                let width = *columns as f32 * char_width;
+                if start < at {
+                    let mut piece = section.clone();
+                    piece.byte_range = start..at;
+                    piece.leading_space = leading;
+                    sections.push(piece);
+                    start = at;
+                    leading = width;
+                } else {
+                    leading += width;
+                }
+            }
+            let name = control::mnemonic(text, range.start + i, c).filter(|_| control_chars);
+            let (width, shift) = match (c, name) {
+                ('\n', _) => {
+                    column = 0;
+                    continue;
+                }
+                ('\t', _) => {
+                    let width = tab_size - column % tab_size;
+                    (width, width as f32 * char_width - tab_advance)
+                }
+                (c, Some(name)) => {
+                    (name.len(), name.len() as f32 * char_width - round(fonts.glyph_width(font, c)))
+                }
+                (c, None) if c.is_ascii() => {
+                    column += 1;
+                    continue;
+                }
+                (c, None) => {
+                    let width = display_width(c);
+                    (width, width as f32 * char_width - round(fonts.glyph_width(font, c)))
+                }
+            };
+            column += width;
+            if shift.abs() < 0.01 {
+                continue;
+            }
+            let end = range.start + i + c.len_utf8();
+            let mut piece = section.clone();
+            piece.byte_range = start..end;
+            piece.leading_space = leading;
+            sections.push(piece);
+            start = end;
+            leading = shift;
+        }
+        if start < range.end {
+            let mut piece = section;
+            piece.byte_range = start..range.end;
+            piece.leading_space = leading;
+            sections.push(piece);
+        } else {
+            carried = leading;
+        }
+    }
+    job.sections = sections;
+}
+
+/// Width of a column of the grid of [`align_columns`], the advance of a space rounded to the pixels like egui does.
+pub(crate) fn column_width(fonts: &Fonts, font: &FontId) -> f32 {
+    let pixels_per_point = fonts.pixels_per_point();
+    (fonts.glyph_width(font, ' ') * pixels_per_point).round() / pixels_per_point
+}
+
+/// Whether a line of the galley is wrapped on several rows.
+pub(crate) fn is_wrapped(galley: &Galley) -> bool {
+    galley.rows.iter().rev().skip(1).any(|row| !row.ends_with_newline)
+}
+
+/// Moves the continuation rows of the wrapped lines to the indentation of their line plus `marker`, the room of
+/// the wrap indicator, for a hanging indent.
+///
+/// egui breaks all the rows at the same width, so the moved rows may overrun `wrap_width` by their indent.
+/// The indent is limited to half of `wrap_width`, the deeply indented lines keep room for their text.
+pub(crate) fn hang_wrapped_rows(galley: &mut Galley, marker: f32, wrap_width: f32) {
+    let mut indent = 0.0;
+    let mut continued = false;
+    for placed in &mut galley.rows {
+        if continued {
+            placed.pos.x += indent;
+            galley.rect = galley.rect.union(placed.rect());
+            galley.mesh_bounds = galley.mesh_bounds.union(placed.visuals.mesh_bounds.translate(placed.pos.to_vec2()));
+        } else {
+            let text = placed.glyphs.iter().find(|glyph| !glyph.chr.is_whitespace());
+            indent = (text.map_or(0.0, |glyph| glyph.pos.x) + marker).min(wrap_width / 2.0);
+        }
+        continued = !placed.ends_with_newline;
+    }
+}
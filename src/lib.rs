@@ -1,7 +1,94 @@
+mod block;
+mod brackets;
+pub mod cancel;
+pub mod cells;
+pub mod clipboard;
+mod comment;
+mod control;
+pub mod copy;
+pub mod diagnostics;
+pub mod diff;
+pub mod duplicates;
 pub mod editor;
+pub mod edits;
+pub mod error;
+pub mod events;
+pub mod folding;
+pub mod fonts;
+mod gutter;
+mod geometry;
+mod highlight;
+pub mod history;
+pub mod image_preview;
+mod indent;
+mod layout;
+mod long_lines;
+pub mod keymap;
+pub mod language;
+pub mod language_picker;
+pub mod linked;
+mod literate;
+pub mod lint;
+pub mod local_history;
+pub mod merge;
+pub mod pairs;
+pub mod paste;
+pub mod patch;
+mod peek;
+pub mod popup;
+pub mod problems;
+pub mod position;
+pub mod presentation;
+pub mod print;
+pub mod rename;
+mod scrub;
+pub mod search;
+pub mod settings;
+pub mod signature;
+pub mod spans;
+mod syntaxes;
+pub mod tasks;
+pub mod theme;
+mod toggle;
 pub mod viewer;
+mod watermark;
+mod whitespace;
 
 
-pub use editor::CodeEditor;
+pub use cancel::CancelToken;
+pub use cells::CodeCell;
+pub use clipboard::ClipboardRing;
+pub use copy::CopyMode;
+pub use diagnostics::{CodeAction, Diagnostic, Severity};
+pub use editor::{CodeEditor, EditorOutput, EscapeBehavior, IndentStyle, Whitespace, WrapMode};
+pub use error::LitecodeError;
+pub use events::{CodeChange, EditorEvent, EventArgs};
+pub use folding::FoldMethod;
+pub use fonts::CodeFont;
+pub use gutter::LineNumbers;
+pub use history::{CheckpointId, HistoryPanel};
+pub use image_preview::ImagePreviewProvider;
+pub use keymap::{Command, Keymap};
+pub use language::{Injection, LanguageConfig, LanguageRegistry};
+pub use language_picker::LanguagePicker;
+pub use pairs::AutoPairs;
+pub use linked::LinkedEditingProvider;
+pub use local_history::LocalHistory;
+pub use merge::MergeView;
+pub use paste::PasteMode;
+pub use position::{LineIndex, Position, PositionEncoding};
+pub use presentation::Presentation;
+pub use print::PrintStyle;
+pub use problems::ProblemsPanel;
+pub use rename::RenameProvider;
+pub use search::SearchState;
+pub use settings::{
+    EditorSettings, EditorSettingsUi, ThemeGallery, animations, global_defaults, reduced_motion, set_animations,
+    set_global_defaults, set_reduced_motion,
+};
+pub use signature::{SignatureHelp, SignatureHelpProvider};
+pub use spans::Span;
+pub use tasks::TaskPanel;
+pub use theme::{theme_from_bytes, theme_from_file};
 
 pub use viewer::CodeViewer;
\ No newline at end of file
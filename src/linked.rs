@@ -0,0 +1,170 @@
+use std::ops::Range;
+
+use crate::rename::is_word_char;
+
+/// Provides the linked editing ranges of the [`CodeEditor`](crate::CodeEditor).
+///
+/// # Implement
+///
+/// Implement `linked_ranges` returning the byte ranges (with identical content) linked with the one at the byte
+/// `offset` of the `code` and set it with `CodeEditor::linked_editing_provider`.
+/// It is called when the caret moves. Without a provider the HTML and XML tag pairs are linked.
+pub trait LinkedEditingProvider: Send {
+    fn linked_ranges(&mut self, code: &str, offset: usize) -> Option<Vec<Range<usize>>>;
+}
+
+impl<F> LinkedEditingProvider for F
+where
+    F: FnMut(&str, usize) -> Option<Vec<Range<usize>>> + Send,
+{
+    fn linked_ranges(&mut self, code: &str, offset: usize) -> Option<Vec<Range<usize>>> {
+        self(code, offset)
+    }
+}
+
+/// Checks that all ranges are valid and have identical content.
+pub(crate) fn are_identical(code: &str, ranges: &[Range<usize>]) -> bool {
+    let text = |range: &Range<usize>| code.get(range.clone());
+    ranges.len() > 1 && text(&ranges[0]).is_some() && ranges.iter().all(|range| text(range) == text(&ranges[0]))
+}
+
+/// Result of a mirrored edit.
+pub(crate) struct MirroredEdit {
+    pub(crate) code: String,
+    pub(crate) ranges: Vec<Range<usize>>,
+    /// Change of the number of chars before the edited range, the caret must be moved by it.
+    pub(crate) caret_shift: isize,
+}
+
+/// Repeats the edit between `old` and `new` in all linked `ranges` (of `old`).
+///
+/// Returns `None` when the edit is not inside one of the ranges or inserts a whitespace
+/// (like a space after the tag name, starting the attributes).
+pub(crate) fn mirror_edit(old: &str, new: &str, ranges: &[Range<usize>]) -> Option<MirroredEdit> {
+    let prefix = common_prefix(old, new);
+    let suffix = common_suffix(&old[prefix..], &new[prefix..]);
+    let removed = prefix..old.len() - suffix;
+    let inserted = &new[prefix..new.len() - suffix];
+    if inserted.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let mut sorted = ranges.to_vec();
+    sorted.sort_by_key(|range| range.start);
+    let edited = sorted
+        .iter()
+        .position(|range| range.start <= removed.start && removed.end <= range.end)?;
+    let relative = removed.start - sorted[edited].start..removed.end - sorted[edited].start;
+
+    let mut code = String::with_capacity(new.len());
+    let mut new_ranges = Vec::with_capacity(sorted.len());
+    let mut last = 0;
+    for range in &sorted {
+        code.push_str(&old[last..range.start]);
+        let start = code.len();
+        code.push_str(&old[range.start..range.start + relative.start]);
+        code.push_str(inserted);
+        code.push_str(&old[range.start + relative.end..range.end]);
+        new_ranges.push(start..code.len());
+        last = range.end;
+    }
+    code.push_str(&old[last..]);
+
+    let delta = inserted.chars().count() as isize - old[removed].chars().count() as isize;
+    Some(MirroredEdit {
+        code,
+        ranges: new_ranges,
+        caret_shift: delta * edited as isize,
+    })
+}
+
+pub(crate) fn common_prefix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i)
+}
+
+pub(crate) fn common_suffix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .rev()
+        .zip(b.chars().rev())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, x), _)| a.len() - i - x.len_utf8())
+}
+
+/// Ranges of the names of the HTML/XML tag at the byte `offset` and its matching tag.
+pub(crate) fn tag_pair(code: &str, offset: usize) -> Option<Vec<Range<usize>>> {
+    let name = tag_name_at(code, offset)?;
+    let closing = code[..name.start].ends_with("</");
+    let tag = &code[name.clone()];
+
+    let mut depth = 0usize;
+    if closing {
+        // search backwards for the opening tag
+        let mut end = name.start - 2;
+        while let Some(open) = code[..end].rfind('<') {
+            end = open;
+            let is_closing = code[open..].starts_with("</");
+            let name_start = if is_closing { open + 2 } else { open + 1 };
+            let Some(other) = tag_name_at(code, name_start).filter(|r| &code[r.clone()] == tag) else {
+                continue;
+            };
+            if is_closing {
+                depth += 1;
+            } else if !is_self_closing(code, other.end) {
+                if depth == 0 {
+                    return Some(vec![other, name]);
+                }
+                depth -= 1;
+            }
+        }
+    } else {
+        if is_self_closing(code, name.end) {
+            return None;
+        }
+        let mut start = name.end;
+        while let Some(open) = code[start..].find('<').map(|i| start + i) {
+            start = open + 1;
+            let is_closing = code[open..].starts_with("</");
+            let name_start = if is_closing { open + 2 } else { open + 1 };
+            let Some(other) = tag_name_at(code, name_start).filter(|r| &code[r.clone()] == tag) else {
+                continue;
+            };
+            if !is_closing {
+                if !is_self_closing(code, other.end) {
+                    depth += 1;
+                }
+            } else if depth == 0 {
+                return Some(vec![name, other]);
+            } else {
+                depth -= 1;
+            }
+        }
+    }
+    None
+}
+
+/// Range of the tag name touching the byte `offset`, if it follows `<` or `</`.
+fn tag_name_at(code: &str, offset: usize) -> Option<Range<usize>> {
+    let is_name_char = |c: char| is_word_char(c) || c == '-' || c == ':' || c == '.';
+    let start = code[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_name_char(*c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let end = code[offset..]
+        .char_indices()
+        .find(|(_, c)| !is_name_char(*c))
+        .map_or(code.len(), |(i, _)| offset + i);
+
+    let before = &code[..start];
+    (start < end && (before.ends_with('<') || before.ends_with("</"))).then_some(start..end)
+}
+
+fn is_self_closing(code: &str, name_end: usize) -> bool {
+    code[name_end..]
+        .find('>')
+        .is_some_and(|close| code[name_end..name_end + close].ends_with('/'))
+}
@@ -0,0 +1,187 @@
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::indent::indentation;
+use crate::language::LanguageConfig;
+use crate::position::{Position, PositionEncoding, display_columns};
+
+/// Width of a tab when comparing the indentation levels, like Python does.
+const TAB_WIDTH: usize = 8;
+
+/// Checks the indentation of a whitespace-significant language (see `LanguageConfig::significant_indentation`).
+///
+/// Flags the lines mixing tabs and spaces, the lines indented with another style than the first indented line,
+/// and the dedents not returning to any outer indentation level.
+/// Blank lines, comments and the continuation lines inside brackets are skipped.
+pub fn indentation_diagnostics(code: &str, config: &LanguageConfig) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    // indentation style of the first indented line, `true` for tabs
+    let mut tabs = None;
+    let mut levels = vec![0];
+    let mut depth = 0usize;
+
+    for (line, text) in code.lines().enumerate() {
+        let indent = indentation(text);
+        let content = text.trim_start();
+        let inside_brackets = depth > 0;
+        depth = bracket_depth(content, config, depth);
+        let comment = config.line_comment.as_ref().is_some_and(|start| content.starts_with(start.as_str()));
+        if content.is_empty() || comment || inside_brackets {
+            continue;
+        }
+
+        let range = Position::new(line, 0)..Position::new(line, indent.len());
+        let has_tabs = indent.contains('\t');
+        if has_tabs && indent.contains(' ') {
+            diagnostics.push(Diagnostic::new(range.clone(), Severity::Warning, "Indentation mixes tabs and spaces"));
+        } else if !indent.is_empty() {
+            match tabs {
+                None => tabs = Some(has_tabs),
+                Some(tabs) if tabs != has_tabs => {
+                    let (found, expected) = if has_tabs { ("tabs", "spaces") } else { ("spaces", "tabs") };
+                    let message = format!("Indentation uses {found}, the file is indented with {expected}");
+                    diagnostics.push(Diagnostic::new(range.clone(), Severity::Warning, message));
+                }
+                Some(_) => {}
+            }
+        }
+
+        let width = indent.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum::<usize>();
+        let last = *levels.last().unwrap_or(&0);
+        if width > last {
+            levels.push(width);
+        } else if width < last {
+            levels.retain(|level| *level <= width);
+            if levels.last() != Some(&width) {
+                diagnostics.push(Diagnostic::new(
+                    range,
+                    Severity::Error,
+                    "Dedent does not match any outer indentation level",
+                ));
+                levels.push(width);
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Flags the lines longer than `max` columns, counting the tabs up to their stop and the wide chars as 2 columns.
+///
+/// The diagnostics cover the part of the line past the limit, their message counts the long lines of the code.
+/// The columns of their positions are in `encoding`.
+pub fn line_length_diagnostics(
+    code: &str,
+    max: usize,
+    severity: Severity,
+    tab_size: usize,
+    encoding: PositionEncoding,
+) -> Vec<Diagnostic> {
+    let long: Vec<(usize, &str, usize)> = code
+        .lines()
+        .enumerate()
+        .map(|(line, text)| (line, text, display_columns(text, 0, tab_size)))
+        .filter(|(_, _, width)| *width > max)
+        .collect();
+    let count = long.len();
+    let summary = if count == 1 { "1 long line".to_owned() } else { format!("{count} long lines") };
+
+    long.into_iter()
+        .map(|(line, text, width)| {
+            let mut column = 0;
+            let mut start = text.len();
+            for (i, c) in text.char_indices() {
+                if column >= max {
+                    start = i;
+                    break;
+                }
+                column = display_columns(&text[i..i + c.len_utf8()], column, tab_size);
+            }
+            let encoded = |end: usize| text[..end].chars().map(|c| encoding.len(c)).sum();
+            let range = Position::new(line, encoded(start))..Position::new(line, encoded(text.len()));
+            let message = format!("Line is {width} columns long, the limit is {max} ({summary})");
+            Diagnostic::new(range, severity, message)
+        })
+        .collect()
+}
+
+/// Bracket depth after `line`, ignoring the brackets in the strings and after a line comment.
+fn bracket_depth(line: &str, config: &LanguageConfig, mut depth: usize) -> usize {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if config.line_comment.as_ref().is_some_and(|start| line[i..].starts_with(start.as_str())) => break,
+            None if config.closing_bracket(c).is_some() => depth += 1,
+            None if config.opening_bracket(c).is_some() => depth = depth.saturating_sub(1),
+            None => {}
+        }
+    }
+    depth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_the_mixed_tabs_and_spaces() {
+        let python = LanguageConfig::for_syntax("Python");
+        let diagnostics = indentation_diagnostics("if a:\n\t  b\n", &python);
+        let expected = Diagnostic::new(
+            Position::new(1, 0)..Position::new(1, 3),
+            Severity::Warning,
+            "Indentation mixes tabs and spaces",
+        );
+        assert_eq!(diagnostics, vec![expected]);
+
+        let diagnostics = indentation_diagnostics("if a:\n    b\nif c:\n\td\n", &python);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, Position::new(3, 0)..Position::new(3, 1));
+        assert_eq!(diagnostics[0].message, "Indentation uses tabs, the file is indented with spaces");
+    }
+
+    #[test]
+    fn flags_the_dedents_to_no_outer_level() {
+        let python = LanguageConfig::for_syntax("Python");
+        let diagnostics = indentation_diagnostics("if a:\n        b\n    c\n", &python);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, Position::new(2, 0)..Position::new(2, 4));
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn accepts_a_clean_file() {
+        let python = LanguageConfig::for_syntax("Python");
+        let code = "def f(x):\n    if x:\n        return [1,\n  2]\n\n  # comment\n    return 0\n";
+        assert_eq!(indentation_diagnostics(code, &python), Vec::new());
+        assert_eq!(indentation_diagnostics("", &python), Vec::new());
+    }
+
+    #[test]
+    fn counts_the_tab_stops_and_the_wide_chars() {
+        let code = "abcd\n日本語x\n\tab";
+        let diagnostics = line_length_diagnostics(code, 4, Severity::Info, 4, PositionEncoding::Utf16);
+        let expected = [
+            Diagnostic::new(
+                Position::new(1, 2)..Position::new(1, 4),
+                Severity::Info,
+                "Line is 7 columns long, the limit is 4 (2 long lines)",
+            ),
+            Diagnostic::new(
+                Position::new(2, 1)..Position::new(2, 3),
+                Severity::Info,
+                "Line is 6 columns long, the limit is 4 (2 long lines)",
+            ),
+        ];
+        assert_eq!(diagnostics, expected);
+        assert_eq!(line_length_diagnostics(code, 7, Severity::Info, 4, PositionEncoding::Utf16), Vec::new());
+    }
+}
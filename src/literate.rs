@@ -0,0 +1,324 @@
+use std::ops::Range;
+
+use egui::text::LayoutJob;
+use egui::{Color32, FontFamily, FontId, Visuals};
+
+use crate::folding;
+use crate::language::LanguageConfig;
+
+/// Doc comment markers of a language: `///` and `//!` or `#'` (roxygen) on the lines, and the `/** */` and
+/// `/*! */` blocks.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub(crate) struct DocMarkers {
+    line: Vec<&'static str>,
+    block: bool,
+}
+
+impl DocMarkers {
+    /// Markers of the comments of the `language`, none for the languages without doc comments.
+    pub(crate) fn new(language: &LanguageConfig) -> Self {
+        let line = match language.line_comment.as_deref() {
+            Some("//") => vec!["///", "//!"],
+            Some("#") => vec!["#'"],
+            _ => Vec::new(),
+        };
+        let block = language.block_comment.as_ref().is_some_and(|(start, end)| start == "/*" && end == "*/");
+        Self { line, block }
+    }
+}
+
+/// Role of a piece of the doc comments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// The comment markers and the markup, the whole line when it has nothing else.
+    Hidden { line: bool },
+    /// The markers of the blank lines between the paragraphs, transparent but keeping the height of their row.
+    Gap,
+    Prose,
+    Heading,
+    Strong,
+    Emphasis,
+    Link,
+    Code,
+}
+
+/// Lays out the doc comments of the job as prose, in the proportional font, between the code in the monospace font.
+///
+/// The comment markers are hidden, the lines with nothing else (like `/**` and `*/`) take no room.
+/// The Markdown of the comments is formatted: the `#` headings, the `**strong**` and `*emphasized*` text,
+/// the `` `code` `` spans in the code `font` and the `[links](url)`, their markup is hidden too.\
+/// The text of the job is unchanged, the hidden chars are still there for the caret.
+pub(crate) fn render(job: &mut LayoutJob, markers: &DocMarkers, font: &FontId, visuals: &Visuals) {
+    let pieces = doc_pieces(&job.text, markers);
+    if pieces.is_empty() {
+        return;
+    }
+    let mut sections = Vec::with_capacity(job.sections.len() + pieces.len());
+    let mut pieces = pieces.iter().peekable();
+    for section in job.sections.drain(..) {
+        let mut start = section.byte_range.start;
+        let end = section.byte_range.end;
+        while start < end {
+            while pieces.next_if(|(range, _)| range.end <= start).is_some() {}
+            let (range, kind) = match pieces.peek() {
+                Some((range, kind)) if range.start < end => (range.start.max(start)..range.end.min(end), Some(*kind)),
+                _ => (start..end, None),
+            };
+            let mut piece = section.clone();
+            if range.start > start {
+                piece.byte_range = start..range.start;
+                sections.push(piece);
+                start = range.start;
+                continue;
+            }
+            piece.byte_range = range.clone();
+            if let Some(kind) = kind {
+                piece.leading_space = 0.0;
+                format(&mut piece.format, kind, font, visuals);
+            } else if start > section.byte_range.start {
+                piece.leading_space = 0.0;
+            }
+            sections.push(piece);
+            start = range.end;
+        }
+    }
+    job.sections = sections;
+}
+
+fn format(format: &mut egui::TextFormat, kind: Kind, font: &FontId, visuals: &Visuals) {
+    let prose = FontId::new(font.size, FontFamily::Proportional);
+    format.background = Color32::TRANSPARENT;
+    format.italics = false;
+    format.underline = egui::Stroke::NONE;
+    format.color = visuals.text_color();
+    format.font_id = prose.clone();
+    match kind {
+        Kind::Hidden { line } => {
+            format.font_id = FontId::new(folding::HIDDEN_SIZE, prose.family);
+            format.color = Color32::TRANSPARENT;
+            if line {
+                format.line_height = Some(0.0);
+            }
+        }
+        Kind::Gap => format.color = Color32::TRANSPARENT,
+        Kind::Prose => {}
+        Kind::Heading => {
+            format.font_id = FontId::new(font.size * 1.3, prose.family);
+            format.color = visuals.strong_text_color();
+        }
+        Kind::Strong => format.color = visuals.strong_text_color(),
+        Kind::Emphasis => format.italics = true,
+        Kind::Link => format.color = visuals.hyperlink_color,
+        Kind::Code => {
+            format.font_id = font.clone();
+            format.background = visuals.code_bg_color;
+        }
+    }
+}
+
+/// Pieces of the doc comments of `text`, sorted and without overlaps, the rest of the text is code.
+fn doc_pieces(text: &str, markers: &DocMarkers) -> Vec<(Range<usize>, Kind)> {
+    let mut pieces = Vec::new();
+    let mut in_block = false;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let content = line.trim_end_matches(['\n', '\r']);
+        let indent = start + content.len() - content.trim_start().len();
+        let end = start + content.len();
+        let rest = &text[indent..end];
+
+        if in_block {
+            let close = rest.find("*/").map(|i| indent + i);
+            // the ` * ` before the text of the block lines
+            let mut body = indent;
+            if rest.starts_with('*') && !rest.starts_with("*/") {
+                body += if rest[1..].starts_with(' ') { 2 } else { 1 };
+            }
+            match close {
+                Some(close) => {
+                    in_block = false;
+                    let body = body.min(close);
+                    let text_end = body + text[body..close].trim_end().len();
+                    if text_end == body && text[close + 2..end].trim().is_empty() {
+                        pieces.push((start..offset, Kind::Hidden { line: true }));
+                    } else {
+                        push_hidden(&mut pieces, indent..body);
+                        prose(text, body..text_end, &mut pieces);
+                        push_hidden(&mut pieces, text_end..close + 2);
+                    }
+                }
+                None if body > indent && body == end => pieces.push((indent..body, Kind::Gap)),
+                None => {
+                    push_hidden(&mut pieces, indent..body);
+                    prose(text, body..end, &mut pieces);
+                }
+            }
+            continue;
+        }
+
+        if let Some(marker) = markers.line.iter().find(|marker| is_doc(rest, marker)) {
+            let body = indent + marker.len() + usize::from(rest[marker.len()..].starts_with(' '));
+            if body == end {
+                pieces.push((indent..body, Kind::Gap));
+            } else {
+                push_hidden(&mut pieces, indent..body);
+                prose(text, body..end, &mut pieces);
+            }
+        } else if markers.block && (is_doc(rest, "/**") || rest.starts_with("/*!")) && !rest.starts_with("/**/") {
+            let body = (indent + 3 + usize::from(rest[3..].starts_with(' '))).min(end);
+            match rest.find("*/").map(|i| indent + i).filter(|&close| close >= indent + 3) {
+                Some(close) => {
+                    let text_end = body.min(close) + text[body.min(close)..close].trim_end().len();
+                    push_hidden(&mut pieces, indent..body.min(close));
+                    prose(text, body.min(close)..text_end, &mut pieces);
+                    push_hidden(&mut pieces, text_end..close + 2);
+                }
+                None => {
+                    in_block = true;
+                    if text[body..end].trim().is_empty() {
+                        pieces.push((start..offset, Kind::Hidden { line: true }));
+                    } else {
+                        push_hidden(&mut pieces, indent..body);
+                        prose(text, body..end, &mut pieces);
+                    }
+                }
+            }
+        }
+    }
+    pieces
+}
+
+/// Whether `line` starts with the doc comment `marker`, and not with a longer run of its last char (like `////`).
+fn is_doc(line: &str, marker: &str) -> bool {
+    let last = marker.chars().last();
+    line.strip_prefix(marker).is_some_and(|rest| rest.chars().next() != last)
+}
+
+fn push_hidden(pieces: &mut Vec<(Range<usize>, Kind)>, range: Range<usize>) {
+    if range.is_empty() {
+        return;
+    }
+    match pieces.last_mut() {
+        Some((last, Kind::Hidden { line: false })) if last.end == range.start => last.end = range.end,
+        _ => pieces.push((range, Kind::Hidden { line: false })),
+    }
+}
+
+/// Pieces of the Markdown text of a doc comment in the `range` of `text`.
+fn prose(text: &str, range: Range<usize>, pieces: &mut Vec<(Range<usize>, Kind)>) {
+    let line = &text[range.clone()];
+    let hashes = line.len() - line.trim_start_matches('#').len();
+    if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+        push_hidden(pieces, range.start..range.start + hashes + 1);
+        inline(text, range.start + hashes + 1..range.end, Kind::Heading, pieces);
+    } else {
+        inline(text, range, Kind::Prose, pieces);
+    }
+}
+
+/// Pieces of the inline Markdown in the `range` of `text`, the plain text is of the `base` kind.
+fn inline(text: &str, range: Range<usize>, base: Kind, pieces: &mut Vec<(Range<usize>, Kind)>) {
+    let push = |pieces: &mut Vec<(Range<usize>, Kind)>, range: Range<usize>, kind: Kind| match pieces.last_mut() {
+        _ if range.is_empty() => {}
+        Some((last, last_kind)) if *last_kind == kind && last.end == range.start => last.end = range.end,
+        _ => pieces.push((range, kind)),
+    };
+    let find = |from: usize, pattern: &str| text[from..range.end].find(pattern).map(|i| from + i);
+    let mut at = range.start;
+    while at < range.end {
+        let rest = &text[at..range.end];
+        if rest.starts_with('`')
+            && let Some(close) = find(at + 1, "`")
+        {
+            push_hidden(pieces, at..at + 1);
+            push(pieces, at + 1..close, Kind::Code);
+            push_hidden(pieces, close..close + 1);
+            at = close + 1;
+        } else if rest.starts_with("**")
+            && let Some(close) = find(at + 2, "**").filter(|&close| close > at + 2)
+        {
+            push_hidden(pieces, at..at + 2);
+            inline(text, at + 2..close, Kind::Strong, pieces);
+            push_hidden(pieces, close..close + 2);
+            at = close + 2;
+        } else if rest.starts_with('*')
+            && rest[1..].starts_with(|c: char| !c.is_whitespace() && c != '*')
+            && let Some(close) = find(at + 1, "*")
+        {
+            push_hidden(pieces, at..at + 1);
+            inline(text, at + 1..close, Kind::Emphasis, pieces);
+            push_hidden(pieces, close..close + 1);
+            at = close + 1;
+        } else if let Some((label, end)) = link(text, at..range.end) {
+            push_hidden(pieces, at..label.start);
+            inline(text, label.clone(), Kind::Link, pieces);
+            push_hidden(pieces, label.end..end);
+            at = end;
+        } else {
+            let len = rest.chars().next().map_or(1, char::len_utf8);
+            push(pieces, at..at + len, base);
+            at += len;
+        }
+    }
+}
+
+/// Label and end of the link at the start of the `range` of `text`: `[label](url)`, or ``[`item`]`` like the
+/// intra-doc links of Rust.
+fn link(text: &str, range: Range<usize>) -> Option<(Range<usize>, usize)> {
+    let rest = text[range.clone()].strip_prefix('[')?;
+    let close = rest.find(']')?;
+    let label = range.start + 1..range.start + 1 + close;
+    let after = &rest[close + 1..];
+    if after.starts_with('(')
+        && let Some(end) = after.find(')')
+    {
+        return Some((label.clone(), label.end + 1 + end + 1));
+    }
+    rest.starts_with('`').then_some((label.clone(), label.end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HIDDEN: Kind = Kind::Hidden { line: false };
+
+    fn rust() -> DocMarkers {
+        DocMarkers::new(&LanguageConfig::for_syntax("Rust"))
+    }
+
+    #[test]
+    fn formats_the_line_doc_comments() {
+        let pieces = doc_pieces("/// Hi `x`\nfn f() {}", &rust());
+        let expected = [(0..4, HIDDEN), (4..7, Kind::Prose), (7..8, HIDDEN), (8..9, Kind::Code), (9..10, HIDDEN)];
+        assert_eq!(pieces, expected);
+        assert_eq!(doc_pieces("/// # Title", &rust()), [(0..6, HIDDEN), (6..11, Kind::Heading)]);
+        assert_eq!(doc_pieces("///\n", &rust()), [(0..3, Kind::Gap)]);
+    }
+
+    #[test]
+    fn formats_the_block_doc_comments() {
+        let pieces = doc_pieces("/** **Bold** */", &rust());
+        assert_eq!(pieces, [(0..6, HIDDEN), (6..10, Kind::Strong), (10..15, HIDDEN)]);
+    }
+
+    #[test]
+    fn formats_an_unclosed_block_to_the_end() {
+        let pieces = doc_pieces("/**\n * Doc\n", &rust());
+        assert_eq!(pieces, [(0..4, Kind::Hidden { line: true }), (5..7, HIDDEN), (7..10, Kind::Prose)]);
+    }
+
+    #[test]
+    fn keeps_an_unclosed_link_as_text() {
+        assert_eq!(doc_pieces("/// [a](b", &rust()), [(0..4, HIDDEN), (4..9, Kind::Prose)]);
+    }
+
+    #[test]
+    fn skips_the_code_and_the_plain_comments() {
+        assert_eq!(doc_pieces("", &rust()), []);
+        assert_eq!(doc_pieces("//// x\n// y\n/**/ z\nlet a = 1;", &rust()), []);
+        assert_eq!(doc_pieces("/// x", &DocMarkers::new(&LanguageConfig::default())), []);
+    }
+}
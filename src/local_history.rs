@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use egui::{RichText, Ui};
+
+use crate::CodeEditor;
+use crate::diff::{self, Hunk, HunkKind};
+use crate::gutter::change_color;
+use crate::history::format_age;
+
+/// Saved version of a file in the [`LocalHistory`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalSnapshot {
+    pub time: SystemTime,
+    /// File holding the code of the snapshot.
+    pub file: PathBuf,
+}
+
+impl LocalSnapshot {
+    pub fn read(&self) -> io::Result<String> {
+        std::fs::read_to_string(&self.file)
+    }
+}
+
+/// Snapshot of the selected entry, with its diff against the current code.
+struct Preview {
+    index: usize,
+    code: String,
+    /// Hash of the code the diff was computed with.
+    code_hash: Option<u64>,
+    hunks: Vec<Hunk>,
+}
+
+/// Periodic snapshots of the edited files on disk, with a browser to compare and restore them.
+///
+/// # Implement
+///
+/// Use `LocalHistory::new(dir)` to create a new instance keeping the snapshots in `dir`.\
+/// Then call `history.snapshot(&editor)` every frame and its `ui` method to browse the snapshots of the editor.
+///
+/// # Usage
+///
+/// A snapshot is written at most every `interval` (5 minutes by default) when the code changed,
+/// only for the editors with a path, see [`CodeEditor::with_path`]. The snapshots are kept per path,
+/// the oldest ones over `max_snapshots` are deleted.\
+/// Selecting a snapshot shows what changed since then, restoring it replaces the code (and can be undone).
+pub struct LocalHistory {
+    dir: PathBuf,
+    interval: Duration,
+    max_snapshots: usize,
+    /// Time of the last snapshot and hash of its code, by path.
+    last: HashMap<PathBuf, (SystemTime, u64)>,
+    /// Snapshots listed by `ui` and their path.
+    listed: Option<(PathBuf, Vec<LocalSnapshot>)>,
+    preview: Option<Preview>,
+}
+
+impl LocalHistory {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            interval: Duration::from_secs(5 * 60),
+            max_snapshots: 50,
+            last: HashMap::new(),
+            listed: None,
+            preview: None,
+        }
+    }
+
+    /// Minimum time between two snapshots of a file.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Maximum number of snapshots kept per file.
+    pub fn max_snapshots(mut self, max_snapshots: usize) -> Self {
+        self.max_snapshots = max_snapshots.max(1);
+        self
+    }
+
+    /// Writes a snapshot of the editor code if its interval elapsed and the code changed since the last one.
+    ///
+    /// Returns `true` when a snapshot was written, does nothing for editors without a path.
+    pub fn snapshot(&mut self, editor: &CodeEditor) -> io::Result<bool> {
+        match editor.path() {
+            Some(path) => self.record(path, &editor.code),
+            None => Ok(false),
+        }
+    }
+
+    /// Like [`snapshot`](Self::snapshot) for the `code` of the file `path`.
+    pub fn record(&mut self, path: &Path, code: &str) -> io::Result<bool> {
+        let now = SystemTime::now();
+        let last = match self.last.get(path) {
+            Some(&last) => Some(last),
+            // the newest snapshot of an earlier run
+            None => {
+                let snapshots = self.snapshots(path)?;
+                snapshots.first().and_then(|snapshot| Some((snapshot.time, hash(snapshot.read().ok()?))))
+            }
+        };
+        if let Some(last) = last {
+            self.last.insert(path.to_owned(), last);
+        }
+        if last.is_some_and(|(time, _)| now.duration_since(time).unwrap_or_default() < self.interval) {
+            return Ok(false);
+        }
+        let hash = hash(code);
+        if last.is_some_and(|(_, last_hash)| last_hash == hash) {
+            self.last.insert(path.to_owned(), (now, hash));
+            return Ok(false);
+        }
+
+        let dir = self.file_dir(path);
+        std::fs::create_dir_all(&dir)?;
+        let millis = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        std::fs::write(dir.join(format!("{millis}.txt")), code)?;
+        self.last.insert(path.to_owned(), (now, hash));
+
+        let snapshots = self.snapshots(path)?;
+        for snapshot in snapshots.iter().skip(self.max_snapshots) {
+            std::fs::remove_file(&snapshot.file)?;
+        }
+        if self.listed.as_ref().is_some_and(|(listed, _)| listed == path) {
+            self.listed = None;
+        }
+        Ok(true)
+    }
+
+    /// Snapshots of the file `path`, newest first.
+    pub fn snapshots(&self, path: &Path) -> io::Result<Vec<LocalSnapshot>> {
+        let entries = match std::fs::read_dir(self.file_dir(path)) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let file = entry?.path();
+            let millis = file.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse().ok());
+            if let Some(millis) = millis {
+                snapshots.push(LocalSnapshot {
+                    time: UNIX_EPOCH + Duration::from_millis(millis),
+                    file,
+                });
+            }
+        }
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.time));
+        Ok(snapshots)
+    }
+
+    /// Directory of the snapshots of `path`, named by the hash of the path.
+    fn file_dir(&self, path: &Path) -> PathBuf {
+        self.dir.join(format!("{:016x}", hash(path.as_os_str().as_encoded_bytes())))
+    }
+
+    /// Shows the snapshots of the editor file and the diff of the selected one, returns `true` when it was restored.
+    pub fn ui(&mut self, ui: &mut Ui, editor: &mut CodeEditor) -> bool {
+        let Some(path) = editor.path().map(Path::to_path_buf) else {
+            ui.weak("The file has no path");
+            return false;
+        };
+        if self.listed.as_ref().is_none_or(|(listed, _)| *listed != path) {
+            self.preview = None;
+            // a missing or unreadable directory lists nothing
+            self.listed = Some((path.clone(), self.snapshots(&path).unwrap_or_default()));
+        }
+        let snapshots = &self.listed.as_ref().expect("listed above").1;
+
+        let now = SystemTime::now();
+        let mut selected = None;
+        let refresh = ui
+            .horizontal(|ui| {
+                ui.label(format!("{} snapshots", snapshots.len()));
+                ui.button("Refresh").clicked()
+            })
+            .inner;
+        ui.separator();
+        egui::ScrollArea::vertical().id_salt("local_history").max_height(ui.available_height() / 3.0).show(ui, |ui| {
+            for (index, snapshot) in snapshots.iter().enumerate() {
+                let age = now.duration_since(snapshot.time).unwrap_or_default().as_secs();
+                let current = self.preview.as_ref().is_some_and(|preview| preview.index == index);
+                if ui.selectable_label(current, format_age(age)).clicked() {
+                    selected = Some(index);
+                }
+            }
+        });
+
+        if let Some(index) = selected
+            && let Ok(code) = snapshots[index].read()
+        {
+            self.preview = Some(Preview {
+                index,
+                code,
+                code_hash: None,
+                hunks: Vec::new(),
+            });
+        }
+        if refresh {
+            self.listed = None;
+        }
+        let Some(preview) = &mut self.preview else {
+            return false;
+        };
+        let code_hash = hash(&editor.code);
+        if preview.code_hash != Some(code_hash) {
+            preview.hunks = diff::diff(&preview.code, &editor.code);
+            preview.code_hash = Some(code_hash);
+        }
+
+        ui.separator();
+        let restore = ui
+            .horizontal(|ui| {
+                ui.label(format!("{} changes since the snapshot", preview.hunks.len()));
+                ui.add_enabled(!preview.hunks.is_empty(), egui::Button::new("Restore")).clicked()
+            })
+            .inner;
+        let old: Vec<&str> = preview.code.lines().collect();
+        let new: Vec<&str> = editor.code.lines().collect();
+        egui::ScrollArea::vertical().id_salt("local_history_diff").show(ui, |ui| {
+            for hunk in &preview.hunks {
+                ui.weak(format!("@@ line {}", hunk.new.start + 1));
+                for line in &old[hunk.old.clone()] {
+                    ui.label(RichText::new(format!("- {line}")).monospace().color(change_color(HunkKind::Deleted)));
+                }
+                for line in &new[hunk.new.clone()] {
+                    ui.label(RichText::new(format!("+ {line}")).monospace().color(change_color(HunkKind::Added)));
+                }
+            }
+        });
+
+        if restore {
+            editor.set_code(preview.code.clone());
+        }
+        restore
+    }
+}
+
+/// FNV-1a hash of `bytes`, the same in every Rust release, unlike the `DefaultHasher`,
+/// so the directories of the snapshots are found again after a toolchain update.
+fn hash(bytes: impl AsRef<[u8]>) -> u64 {
+    let bytes = bytes.as_ref().iter();
+    bytes.fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty directory for the snapshots of a test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("egui_litecode_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Waits for the next millisecond, the snapshots are named by their time.
+    fn tick() {
+        std::thread::sleep(Duration::from_millis(2));
+    }
+
+    #[test]
+    fn hashes_stably() {
+        assert_eq!(hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn records_a_snapshot_per_interval() {
+        let dir = temp_dir("local_history_interval");
+        let path = Path::new("src/main.rs");
+        let mut history = LocalHistory::new(&dir);
+        assert!(history.record(path, "fn main() {}").unwrap());
+        tick();
+        assert!(!history.record(path, "fn main() { run() }").unwrap());
+        let snapshots = history.snapshots(path).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].read().unwrap(), "fn main() {}");
+        // the other files have their own interval
+        assert!(history.record(Path::new("src/lib.rs"), "").unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn skips_the_unchanged_code() {
+        let dir = temp_dir("local_history_unchanged");
+        let path = Path::new("src/main.rs");
+        let mut history = LocalHistory::new(&dir).interval(Duration::ZERO);
+        assert!(history.record(path, "fn main() {}").unwrap());
+        tick();
+        assert!(!history.record(path, "fn main() {}").unwrap());
+        // nor written again on the next start
+        let mut history = LocalHistory::new(&dir).interval(Duration::ZERO);
+        tick();
+        assert!(!history.record(path, "fn main() {}").unwrap());
+        assert_eq!(history.snapshots(path).unwrap().len(), 1);
+        tick();
+        assert!(history.record(path, "fn main() { run() }").unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_newest_snapshots() {
+        let dir = temp_dir("local_history_max");
+        let path = Path::new("src/main.rs");
+        let mut history = LocalHistory::new(&dir).interval(Duration::ZERO).max_snapshots(2);
+        for code in ["a", "b", "c"] {
+            tick();
+            assert!(history.record(path, code).unwrap());
+        }
+        let codes: Vec<String> = history.snapshots(path).unwrap().iter().map(|s| s.read().unwrap()).collect();
+        assert_eq!(codes, ["c", "b"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
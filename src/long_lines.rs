@@ -0,0 +1,358 @@
+use std::cell::RefCell;
+use std::ops::Range;
+
+use egui::TextBuffer;
+
+use crate::events::{CodeChange, merge_change};
+use crate::position::display_width;
+
+/// Lines longer than this (in bytes) are laid out only around the visible columns when the lines are not wrapped.
+pub(crate) const LONG_LINE_BYTES: usize = 10_000;
+
+/// Columns hidden before a byte offset of the window text.
+pub(crate) type Gap = (usize, usize);
+
+/// Code without the parts of the long lines far from the view, so a line of megabytes is laid out in a galley of
+/// a few screens. The hidden parts keep their width, as the leading space of the text after them (the [`Gap`]s),
+/// so the scroll extents and the columns are the ones of the whole code.
+///
+/// The first and the last char of the long lines and the chars next to the carets are always kept,
+/// for `Home`, `End` and the typing out of the view.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct HorizontalWindow {
+    text: String,
+    /// Kept parts of the code, in order, contiguous in the window text and separated by hidden text in the code.
+    pieces: Vec<Piece>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Piece {
+    code_byte: usize,
+    code_char: usize,
+    window_byte: usize,
+    window_char: usize,
+    bytes: usize,
+    chars: usize,
+}
+
+impl Piece {
+    fn window_end(&self) -> usize {
+        self.window_char + self.chars
+    }
+}
+
+impl HorizontalWindow {
+    /// Window of `code` keeping the display `columns` of the long lines and the chars next to the `carets` (chars),
+    /// `None` without long lines. Also returns the gaps of the hidden parts.
+    pub(crate) fn new(code: &str, columns: Range<usize>, carets: &[usize], tab_size: usize) -> Option<(Self, Vec<Gap>)> {
+        if !code.split('\n').any(|line| line.len() > LONG_LINE_BYTES) {
+            return None;
+        }
+        let tab_size = tab_size.max(1);
+        let mut window = Self::default();
+        let mut gaps = Vec::new();
+        let (mut byte, mut char) = (0, 0);
+        for line in code.split_inclusive('\n') {
+            if line.len() <= LONG_LINE_BYTES {
+                window.keep(line, byte, char);
+                byte += line.len();
+                char += line.chars().count();
+                continue;
+            }
+            let last = line.trim_end_matches('\n').char_indices().next_back().map(|(i, _)| i);
+            let mut column = 0;
+            let mut hidden = 0;
+            for (i, c) in line.char_indices() {
+                let width = match c {
+                    '\t' => tab_size - column % tab_size,
+                    '\n' => 0,
+                    c => display_width(c),
+                };
+                let kept = c == '\n'
+                    || i == 0
+                    || Some(i) == last
+                    || columns.contains(&column)
+                    || carets.iter().any(|&caret| caret == char || caret == char + 1);
+                if kept {
+                    if hidden > 0 {
+                        gaps.push((window.text.len(), hidden));
+                        hidden = 0;
+                    }
+                    window.keep(&line[i..i + c.len_utf8()], byte + i, char);
+                } else {
+                    hidden += width;
+                }
+                column += width;
+                char += 1;
+            }
+            byte += line.len();
+        }
+        Some((window, gaps))
+    }
+
+    /// Appends the `text` at the byte and the char offsets of the code to the window.
+    fn keep(&mut self, text: &str, code_byte: usize, code_char: usize) {
+        let chars = text.chars().count();
+        match self.pieces.last_mut() {
+            Some(piece) if piece.code_byte + piece.bytes == code_byte => {
+                piece.bytes += text.len();
+                piece.chars += chars;
+            }
+            _ => self.pieces.push(Piece {
+                code_byte,
+                code_char,
+                window_byte: self.text.len(),
+                window_char: self.pieces.last().map_or(0, Piece::window_end),
+                bytes: text.len(),
+                chars,
+            }),
+        }
+        self.text.push_str(text);
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Char of the window at the `char` of the code, the chars of a hidden part go before the text after it.
+    pub(crate) fn to_window(&self, char: usize) -> usize {
+        let i = self.pieces.partition_point(|piece| piece.code_char <= char).saturating_sub(1);
+        self.pieces.get(i).map_or(0, |piece| piece.window_char + (char - piece.code_char.min(char)).min(piece.chars))
+    }
+
+    pub(crate) fn to_window_chars(&self, chars: Range<usize>) -> Range<usize> {
+        self.to_window(chars.start)..self.to_window(chars.end)
+    }
+
+    /// Char of the code at the `char` of the window, before the hidden part at a gap.
+    pub(crate) fn to_code(&self, char: usize) -> usize {
+        self.piece_ending_at(char)
+            .map_or(0, |piece| piece.code_char + (char - piece.window_char).min(piece.chars))
+    }
+
+    pub(crate) fn to_code_range(&self, range: egui::text::CCursorRange) -> egui::text::CCursorRange {
+        let map = |cursor: egui::text::CCursor| egui::text::CCursor { index: self.to_code(cursor.index), ..cursor };
+        egui::text::CCursorRange { primary: map(range.primary), secondary: map(range.secondary), ..range }
+    }
+
+    pub(crate) fn to_window_range(&self, range: egui::text::CCursorRange) -> egui::text::CCursorRange {
+        let map = |cursor: egui::text::CCursor| egui::text::CCursor { index: self.to_window(cursor.index), ..cursor };
+        egui::text::CCursorRange { primary: map(range.primary), secondary: map(range.secondary), ..range }
+    }
+
+    /// The piece containing the window `char`, or ending at it.
+    fn piece_ending_at(&self, char: usize) -> Option<&Piece> {
+        let i = self.pieces.partition_point(|piece| piece.window_end() < char);
+        self.pieces.get(i.min(self.pieces.len().saturating_sub(1)))
+    }
+
+    /// Byte of the window and of the code at the `char` (a char of the window) of `piece`.
+    fn bytes_at(&self, piece: &Piece, char: usize) -> (usize, usize) {
+        let text = &self.text[piece.window_byte..piece.window_byte + piece.bytes];
+        let offset = text.char_indices().nth(char - piece.window_char).map_or(piece.bytes, |(i, _)| i);
+        (piece.window_byte + offset, piece.code_byte + offset)
+    }
+}
+
+/// The code seen through a [`HorizontalWindow`] by the `TextEdit`, its edits are applied to the code.
+pub(crate) struct WindowedCode<'a> {
+    pub(crate) code: &'a mut String,
+    pub(crate) window: HorizontalWindow,
+    /// Shared with the layouter, moved by the edits.
+    pub(crate) gaps: &'a RefCell<Vec<Gap>>,
+    pub(crate) mutable: bool,
+    /// Change of the code made by the edits.
+    pub(crate) change: Option<CodeChange>,
+}
+
+impl TextBuffer for WindowedCode<'_> {
+    fn is_mutable(&self) -> bool {
+        self.mutable
+    }
+
+    fn as_str(&self) -> &str {
+        self.window.text()
+    }
+
+    fn insert_text(&mut self, text: &str, char_index: usize) -> usize {
+        let window = &mut self.window;
+        let Some(&piece) = window.piece_ending_at(char_index) else {
+            // an empty code
+            window.keep("", 0, 0);
+            return self.insert_text(text, char_index);
+        };
+        let (window_byte, code_byte) = window.bytes_at(&piece, char_index);
+        let chars = text.chars().count();
+        self.code.insert_str(code_byte, text);
+        merge_change(&mut self.change, CodeChange::replacing(code_byte..code_byte, text.len()));
+        window.text.insert_str(window_byte, text);
+        for other in &mut window.pieces {
+            if other.window_char > piece.window_char {
+                other.code_byte += text.len();
+                other.code_char += chars;
+                other.window_byte += text.len();
+                other.window_char += chars;
+            } else if other.window_char == piece.window_char {
+                other.bytes += text.len();
+                other.chars += chars;
+            }
+        }
+        for gap in self.gaps.borrow_mut().iter_mut().filter(|gap| gap.0 >= window_byte) {
+            gap.0 += text.len();
+        }
+        chars
+    }
+
+    fn delete_char_range(&mut self, char_range: Range<usize>) {
+        if char_range.is_empty() {
+            return;
+        }
+        let window = &mut self.window;
+        // the hidden text at the ends of the range is kept
+        let first = window.pieces.partition_point(|piece| piece.window_end() <= char_range.start);
+        let last = window.pieces.partition_point(|piece| piece.window_end() < char_range.end);
+        let (Some(&head), Some(&tail)) = (window.pieces.get(first), window.pieces.get(last)) else {
+            return;
+        };
+        let (window_start, code_start) = window.bytes_at(&head, char_range.start);
+        let (window_end, code_end) = window.bytes_at(&tail, char_range.end);
+        let code_chars = (tail.code_char + char_range.end - tail.window_char)
+            - (head.code_char + char_range.start - head.window_char);
+        self.code.replace_range(code_start..code_end, "");
+        merge_change(&mut self.change, CodeChange::replacing(code_start..code_end, 0));
+        window.text.replace_range(window_start..window_end, "");
+
+        // the start of the first piece and the end of the last one are contiguous in the code now
+        let merged = Piece {
+            bytes: window_start - head.window_byte + (tail.window_byte + tail.bytes - window_end),
+            chars: char_range.start - head.window_char + (tail.window_end() - char_range.end),
+            ..head
+        };
+        let after = window.pieces[last + 1..].iter().map(|piece| Piece {
+            code_byte: piece.code_byte - (code_end - code_start),
+            code_char: piece.code_char - code_chars,
+            window_byte: piece.window_byte - (window_end - window_start),
+            window_char: piece.window_char - char_range.len(),
+            ..*piece
+        });
+        let mut pieces = window.pieces[..first].to_vec();
+        pieces.extend(Some(merged).filter(|piece| piece.chars > 0));
+        pieces.extend(after);
+        window.pieces = pieces;
+
+        let mut gaps = self.gaps.borrow_mut();
+        gaps.retain(|gap| !(window_start < gap.0 && gap.0 < window_end));
+        for gap in gaps.iter_mut().filter(|gap| gap.0 >= window_end) {
+            gap.0 -= window_end - window_start;
+        }
+    }
+
+    /// Replaces only the changed middle of the window, the hidden text around it is kept.
+    fn replace_with(&mut self, text: &str) {
+        let old = self.window.text();
+        let prefix = old.chars().zip(text.chars()).take_while(|(a, b)| a == b).count();
+        let old_chars = old.chars().count();
+        let new_chars = text.chars().count();
+        let suffix = old
+            .chars()
+            .rev()
+            .zip(text.chars().rev())
+            .take(old_chars.min(new_chars) - prefix)
+            .take_while(|(a, b)| a == b)
+            .count();
+        self.delete_char_range(prefix..old_chars - suffix);
+        let inserted: String = text.chars().skip(prefix).take(new_chars - suffix - prefix).collect();
+        self.insert_text(&inserted, prefix);
+    }
+
+    fn type_id(&self) -> std::any::TypeId {
+        std::any::TypeId::of::<WindowedCode<'static>>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code() -> String {
+        format!("short\n{}\nend", "a".repeat(20_000))
+    }
+
+    #[test]
+    fn keeps_short_codes_whole() {
+        assert!(HorizontalWindow::new("short\nlines", 0..100, &[], 4).is_none());
+    }
+
+    #[test]
+    fn hides_the_far_columns() {
+        let (window, gaps) = HorizontalWindow::new(&code(), 100..200, &[], 4).unwrap();
+        assert_eq!(window.text(), format!("short\na{}a\nend", "a".repeat(100)));
+        assert_eq!(gaps, vec![(7, 99), (107, 19_799)]);
+    }
+
+    #[test]
+    fn keeps_the_chars_next_to_the_carets() {
+        let (window, gaps) = HorizontalWindow::new(&code(), 100..200, &[1_006], 4).unwrap();
+        assert_eq!(window.text().len(), 114);
+        assert_eq!(gaps, vec![(7, 99), (107, 799), (109, 18_998)]);
+    }
+
+    #[test]
+    fn maps_the_chars() {
+        let (window, _) = HorizontalWindow::new(&code(), 100..200, &[], 4).unwrap();
+        assert_eq!(window.to_window(106), 7);
+        assert_eq!(window.to_code(8), 107);
+        // the hidden chars go before the text after them
+        assert_eq!(window.to_window(50), 7);
+        assert_eq!(window.to_code(7), 7);
+        let end = 20_007;
+        assert_eq!(window.to_window(end), 109);
+        assert_eq!(window.to_code(109), end);
+        assert_eq!(window.to_window_chars(106..end), 7..109);
+    }
+
+    /// Applies `edit` to the code seen through its window, returns the code, the window text and the gaps.
+    fn edit(edit: impl FnOnce(&mut WindowedCode<'_>)) -> (String, String, Vec<Gap>) {
+        let mut code = code();
+        let (window, gaps) = HorizontalWindow::new(&code, 100..200, &[], 4).unwrap();
+        let gaps = RefCell::new(gaps);
+        let mut windowed = WindowedCode { code: &mut code, window, gaps: &gaps, mutable: true, change: None };
+        edit(&mut windowed);
+        let text = windowed.window.text().to_owned();
+        (code, text, gaps.into_inner())
+    }
+
+    #[test]
+    fn inserts_before_a_hidden_part() {
+        let (code, text, gaps) = edit(|windowed| {
+            windowed.insert_text("X", 7);
+            assert_eq!(windowed.change, Some(CodeChange { removed: 7..7, inserted: 7..8 }));
+        });
+        assert_eq!(code, format!("short\naX{}\nend", "a".repeat(19_999)));
+        assert_eq!(text, format!("short\naX{}a\nend", "a".repeat(100)));
+        assert_eq!(gaps, vec![(8, 99), (108, 19_799)]);
+    }
+
+    #[test]
+    fn deletes_across_a_hidden_part() {
+        let (code, text, gaps) = edit(|windowed| {
+            windowed.delete_char_range(6..8);
+            assert_eq!(windowed.change, Some(CodeChange { removed: 6..107, inserted: 6..6 }));
+        });
+        assert_eq!(code, format!("short\n{}\nend", "a".repeat(19_899)));
+        assert_eq!(text, format!("short\n{}\nend", "a".repeat(100)));
+        assert_eq!(gaps, vec![(105, 19_799)]);
+    }
+
+    #[test]
+    fn replaces_the_changed_middle() {
+        let (code, text, gaps) = edit(|windowed| {
+            let mut text = windowed.as_str().to_owned();
+            text.replace_range(50..51, "b");
+            windowed.replace_with(&text);
+        });
+        assert_eq!(code, format!("short\n{}b{}\nend", "a".repeat(143), "a".repeat(19_856)));
+        assert_eq!(text, format!("short\na{}b{}a\nend", "a".repeat(43), "a".repeat(56)));
+        assert_eq!(gaps, vec![(7, 99), (107, 19_799)]);
+    }
+}
@@ -0,0 +1,529 @@
+use std::ops::Range;
+
+use egui::Ui;
+
+use crate::diff::{Hunk, diff_lines};
+use crate::events::CodeChange;
+use crate::fonts;
+use crate::{CodeEditor, CodeViewer};
+
+/// Part of the three-way merge result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MergeChunk {
+    /// Unchanged or changed only on one side.
+    Resolved(String),
+    Conflict(Conflict),
+}
+
+/// Region changed differently on both sides, with the line breaks included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+    /// First line of the conflict in `ours`.
+    pub ours_line: usize,
+}
+
+/// How a conflict is resolved in the result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// Written with the conflict markers.
+    Unresolved,
+    /// Take ours (the left pane).
+    Left,
+    /// Take theirs (the right pane).
+    Right,
+    Edited(String),
+}
+
+/// Three-way line merge of `ours` and `theirs` changes of `base`.
+pub fn merge(base: &str, ours: &str, theirs: &str) -> Vec<MergeChunk> {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+    let mut chunks = Vec::new();
+    for region in regions(&base_lines, &ours_lines, &theirs_lines) {
+        let ours_region = &ours_lines[region.ours.clone()];
+        let theirs_region = &theirs_lines[region.theirs];
+        match region.take {
+            Take::Base => push_resolved(&mut chunks, &base_lines[region.base]),
+            Take::Ours => push_resolved(&mut chunks, ours_region),
+            Take::Theirs => push_resolved(&mut chunks, theirs_region),
+            Take::Conflict => chunks.push(MergeChunk::Conflict(Conflict {
+                base: base_lines[region.base].concat(),
+                ours: ours_region.concat(),
+                theirs: theirs_region.concat(),
+                ours_line: region.ours.start,
+            })),
+        }
+    }
+    chunks
+}
+
+/// Side of a [`Region`] taken in the merge result.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Take {
+    /// Unchanged on both sides.
+    Base,
+    Ours,
+    Theirs,
+    Conflict,
+}
+
+/// Lines of the three texts corresponding to each other, as ranges of lines.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Region {
+    base: Range<usize>,
+    ours: Range<usize>,
+    theirs: Range<usize>,
+    take: Take,
+}
+
+/// Regions of the texts, from the first lines to the last, the unchanged stretches between the changed ones.
+fn regions(base_lines: &[&str], ours_lines: &[&str], theirs_lines: &[&str]) -> Vec<Region> {
+    // compare without the line breaks, so the last line matches with or without it
+    let trimmed = |lines: &[&str]| -> Vec<String> {
+        lines.iter().map(|line| line.trim_end_matches(['\r', '\n']).to_owned()).collect()
+    };
+    let ours_hunks = diff_lines(&trimmed(base_lines), &trimmed(ours_lines));
+    let theirs_hunks = diff_lines(&trimmed(base_lines), &trimmed(theirs_lines));
+
+    let mut regions = Vec::new();
+    let (mut a, mut b) = (0, 0);
+    let (mut ours_offset, mut theirs_offset) = (0isize, 0isize);
+    let mut base_pos = 0;
+    let unchanged = |regions: &mut Vec<Region>, base: Range<usize>, ours_offset: isize, theirs_offset: isize| {
+        if !base.is_empty() {
+            let ours = shift(base.start, ours_offset)..shift(base.end, ours_offset);
+            let theirs = shift(base.start, theirs_offset)..shift(base.end, theirs_offset);
+            regions.push(Region { base, ours, theirs, take: Take::Base });
+        }
+    };
+
+    loop {
+        let start = match (ours_hunks.get(a), theirs_hunks.get(b)) {
+            (Some(x), Some(y)) => x.old.start.min(y.old.start),
+            (Some(x), None) => x.old.start,
+            (None, Some(y)) => y.old.start,
+            (None, None) => break,
+        };
+        unchanged(&mut regions, base_pos..start, ours_offset, theirs_offset);
+
+        // grow the region while hunks of any side overlap it
+        let mut end = start;
+        let (ours_start, theirs_start) = (shift(start, ours_offset), shift(start, theirs_offset));
+        let (mut ours_changed, mut theirs_changed) = (false, false);
+        loop {
+            if let Some(hunk) = ours_hunks.get(a).filter(|hunk| hunk.old.start <= end) {
+                end = end.max(hunk.old.end);
+                ours_offset += growth(hunk);
+                ours_changed = true;
+                a += 1;
+            } else if let Some(hunk) = theirs_hunks.get(b).filter(|hunk| hunk.old.start <= end) {
+                end = end.max(hunk.old.end);
+                theirs_offset += growth(hunk);
+                theirs_changed = true;
+                b += 1;
+            } else {
+                break;
+            }
+        }
+
+        let ours = ours_start..shift(end, ours_offset);
+        let theirs = theirs_start..shift(end, theirs_offset);
+        let take = if !theirs_changed || ours_lines[ours.clone()] == theirs_lines[theirs.clone()] {
+            Take::Ours
+        } else if !ours_changed {
+            Take::Theirs
+        } else {
+            Take::Conflict
+        };
+        regions.push(Region { base: start..end, ours, theirs, take });
+        base_pos = end;
+    }
+    unchanged(&mut regions, base_pos..base_lines.len(), ours_offset, theirs_offset);
+    regions
+}
+
+/// The three texts padded with blank lines, so the lines of the regions start on the same rows side by side,
+/// and the first row of each conflict.
+fn aligned(base: &str, ours: &str, theirs: &str) -> ([String; 3], Vec<usize>) {
+    let lines = [base, ours, theirs].map(|text| text.split_inclusive('\n').collect::<Vec<_>>());
+    let mut panes: [String; 3] = Default::default();
+    let mut conflict_rows = Vec::new();
+    let mut row = 0;
+    for region in regions(&lines[0], &lines[1], &lines[2]) {
+        if region.take == Take::Conflict {
+            conflict_rows.push(row);
+        }
+        let ranges = [region.base, region.ours, region.theirs];
+        let height = ranges.iter().map(ExactSizeIterator::len).max().unwrap_or_default();
+        for ((pane, lines), range) in panes.iter_mut().zip(&lines).zip(ranges) {
+            let padding = height - range.len();
+            for line in &lines[range] {
+                if !pane.is_empty() && !pane.ends_with('\n') {
+                    pane.push('\n');
+                }
+                pane.push_str(line);
+            }
+            for _ in 0..padding {
+                if !pane.is_empty() && !pane.ends_with('\n') {
+                    pane.push('\n');
+                }
+                pane.push('\n');
+            }
+        }
+        row += height;
+    }
+    (panes, conflict_rows)
+}
+
+fn growth(hunk: &Hunk) -> isize {
+    hunk.new.len() as isize - hunk.old.len() as isize
+}
+
+fn shift(line: usize, offset: isize) -> usize {
+    (line as isize + offset) as usize
+}
+
+fn push_resolved(chunks: &mut Vec<MergeChunk>, lines: &[&str]) {
+    if lines.is_empty() {
+        return;
+    }
+    match chunks.last_mut() {
+        Some(MergeChunk::Resolved(text)) => {
+            // the last line of one side might miss the line break
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(&lines.concat());
+        }
+        _ => chunks.push(MergeChunk::Resolved(lines.concat())),
+    }
+}
+
+/// Joins the chunks, writing the unresolved conflicts with the git-like markers.
+pub fn merged_text(chunks: &[MergeChunk], resolutions: &[Resolution]) -> String {
+    merge_result(chunks, resolutions).0
+}
+
+/// Like [`merged_text`], with the byte ranges of the conflicts in the text.
+fn merge_result(chunks: &[MergeChunk], resolutions: &[Resolution]) -> (String, Vec<Range<usize>>) {
+    let mut result = String::new();
+    let mut ranges = Vec::new();
+    let push = |result: &mut String, text: &str| {
+        if !result.is_empty() && !result.ends_with('\n') && !text.is_empty() {
+            result.push('\n');
+        }
+        result.push_str(text);
+    };
+
+    let mut conflicts = resolutions.iter();
+    for chunk in chunks {
+        let conflict = match chunk {
+            MergeChunk::Resolved(text) => {
+                push(&mut result, text);
+                continue;
+            }
+            MergeChunk::Conflict(conflict) => conflict,
+        };
+        // the line break joining a side without one belongs to the text before
+        if !result.is_empty() && !result.ends_with('\n') {
+            result.push('\n');
+        }
+        let start = result.len();
+        match conflicts.next().unwrap_or(&Resolution::Unresolved) {
+            Resolution::Unresolved => {
+                push(&mut result, "<<<<<<< ours\n");
+                push(&mut result, &conflict.ours);
+                push(&mut result, "=======\n");
+                push(&mut result, &conflict.theirs);
+                push(&mut result, ">>>>>>> theirs\n");
+            }
+            Resolution::Left => push(&mut result, &conflict.ours),
+            Resolution::Right => push(&mut result, &conflict.theirs),
+            Resolution::Edited(text) => push(&mut result, text),
+        }
+        ranges.push(start..result.len());
+    }
+    (result, ranges)
+}
+
+/// Three-way merge widget, showing base, ours and theirs side by side above the editable result.
+///
+/// # Implement
+///
+/// Use `MergeView::new(base, ours, theirs, syntax_ext, color_theme)` to create a new instance.\
+/// Then use call `ui` method to integrate it into your egui application.
+///
+/// # Usage
+///
+/// Navigate the conflicts and take the left (ours) or the right (theirs) side, or edit the result.\
+/// The panes are padded with blank lines, so the changes of the three sides stay side by side.\
+/// Resolving a conflict rewrites only its lines of the result, the edits made in the result elsewhere are kept.\
+/// Use `mymergeview.result.code` to access the merged code.
+#[derive(Debug)]
+pub struct MergeView {
+    base: CodeViewer,
+    ours: CodeViewer,
+    theirs: CodeViewer,
+    pub result: CodeEditor,
+    chunks: Vec<MergeChunk>,
+    resolutions: Vec<Resolution>,
+    /// First row of each conflict in the aligned panes.
+    conflict_rows: Vec<usize>,
+    /// Byte ranges of the conflicts in the result, following its edits.
+    result_ranges: Vec<Range<usize>>,
+    /// Result as of the last rewrite or frame, to follow the edits made in the result editor.
+    result_code: String,
+    current: usize,
+    scroll_to_current: bool,
+}
+
+impl MergeView {
+    pub fn new(base: &str, ours: &str, theirs: &str, syntax_ext: &str, color_theme: &str) -> Self {
+        let viewer = |code: &str| {
+            let mut viewer = CodeViewer::new(syntax_ext, color_theme).wrap(false);
+            viewer.code = code.to_owned();
+            viewer
+        };
+        let chunks = merge(base, ours, theirs);
+        let conflicts = chunks.iter().filter(|chunk| matches!(chunk, MergeChunk::Conflict(_))).count();
+        let resolutions = vec![Resolution::Unresolved; conflicts];
+        let ([base_pane, ours_pane, theirs_pane], conflict_rows) = aligned(base, ours, theirs);
+        let (result_code, result_ranges) = merge_result(&chunks, &resolutions);
+        let mut result = CodeEditor::new(syntax_ext, color_theme);
+        result.code = result_code.clone();
+
+        Self {
+            base: viewer(&base_pane),
+            ours: viewer(&ours_pane),
+            theirs: viewer(&theirs_pane),
+            result,
+            chunks,
+            resolutions,
+            conflict_rows,
+            result_ranges,
+            result_code,
+            current: 0,
+            scroll_to_current: false,
+        }
+    }
+
+    pub fn conflicts(&self) -> impl Iterator<Item = &Conflict> {
+        self.chunks.iter().filter_map(|chunk| match chunk {
+            MergeChunk::Conflict(conflict) => Some(conflict),
+            MergeChunk::Resolved(_) => None,
+        })
+    }
+
+    pub fn resolutions(&self) -> &[Resolution] {
+        &self.resolutions
+    }
+
+    pub fn unresolved_count(&self) -> usize {
+        self.resolutions.iter().filter(|r| **r == Resolution::Unresolved).count()
+    }
+
+    /// Resolves the conflict with `index` and rewrites its lines of the result.
+    pub fn resolve(&mut self, index: usize, resolution: Resolution) {
+        if let Some(r) = self.resolutions.get_mut(index) {
+            *r = resolution;
+            self.rewrite_conflict(index);
+        }
+    }
+
+    /// Replaces the lines of the conflict with `index` in the result with its resolution, keeping the rest.
+    fn rewrite_conflict(&mut self, index: usize) {
+        self.follow_result_edits();
+        let (merged, ranges) = merge_result(&self.chunks, &self.resolutions);
+        let (Some(old), Some(new)) = (self.result_ranges.get(index).cloned(), ranges.get(index)) else {
+            return;
+        };
+        let text = &merged[new.clone()];
+        let delta = text.len() as isize - old.len() as isize;
+        self.result.edit_code(old.clone(), text);
+        self.result_ranges[index] = old.start..old.start + text.len();
+        for range in &mut self.result_ranges[index + 1..] {
+            *range = shift(range.start, delta)..shift(range.end, delta);
+        }
+        self.result_code.clone_from(&self.result.code);
+    }
+
+    /// Moves the ranges of the conflicts with the edits made in the result editor since the last call.
+    fn follow_result_edits(&mut self) {
+        let Some(change) = CodeChange::between(&self.result_code, &self.result.code) else {
+            return;
+        };
+        let delta = change.inserted.len() as isize - change.removed.len() as isize;
+        for range in &mut self.result_ranges {
+            if change.removed.end <= range.start {
+                *range = shift(range.start, delta)..shift(range.end, delta);
+            } else if change.removed.start < range.end {
+                // an edit across the conflict extends it
+                let start = range.start.min(change.removed.start);
+                *range = start..shift(range.end.max(change.removed.end), delta);
+            }
+        }
+        self.result_code.clone_from(&self.result.code);
+    }
+
+    fn select(&mut self, index: usize) {
+        self.current = index;
+        self.scroll_to_current = true;
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) -> egui::Response {
+        let count = self.resolutions.len();
+
+        ui.horizontal(|ui| {
+            if count == 0 {
+                ui.label("No conflicts");
+                return;
+            }
+            ui.label(format!(
+                "Conflict {}/{} ({} unresolved)",
+                self.current + 1,
+                count,
+                self.unresolved_count()
+            ));
+            if ui.add_enabled(self.current > 0, egui::Button::new("Previous")).clicked() {
+                self.select(self.current - 1);
+            }
+            if ui.add_enabled(self.current + 1 < count, egui::Button::new("Next")).clicked() {
+                self.select(self.current + 1);
+            }
+            ui.separator();
+            if ui.button("Take left").clicked() {
+                self.resolve(self.current, Resolution::Left);
+            }
+            if ui.button("Take right").clicked() {
+                self.resolve(self.current, Resolution::Right);
+            }
+            let current_conflict = self.conflicts().nth(self.current).cloned();
+            if let Some(conflict) = current_conflict
+                && ui.button("Edit result").clicked()
+            {
+                let text = match &self.resolutions[self.current] {
+                    Resolution::Edited(text) => text.clone(),
+                    _ => conflict.ours.clone(),
+                };
+                self.resolve(self.current, Resolution::Edited(text));
+            }
+        });
+
+        // edit the current conflict in place, its lines of the result are rewritten on every change
+        if let Some(Resolution::Edited(text)) = self.resolutions.get_mut(self.current) {
+            let changed = ui
+                .add(egui::TextEdit::multiline(text).code_editor().desired_width(f32::INFINITY))
+                .changed();
+            if changed {
+                self.rewrite_conflict(self.current);
+            }
+        }
+
+        let mut scroll = egui::ScrollArea::vertical()
+            .id_salt("merge_panes")
+            .max_height(ui.available_height() / 2.0);
+        // after the navigation above, so the view scrolls to the newly selected conflict
+        if self.scroll_to_current
+            && let Some(&row) = self.conflict_rows.get(self.current)
+        {
+            let font = fonts::code_font(ui, 14.0);
+            let row_height = ui.fonts(|f| f.row_height(&font));
+            scroll = scroll.vertical_scroll_offset(row as f32 * row_height);
+            self.scroll_to_current = false;
+        }
+        // a single scroll area keeps the aligned panes scrolled together
+        scroll.show(ui, |ui| {
+            ui.columns(3, |columns| {
+                columns[0].strong("Ours");
+                self.ours.ui(&mut columns[0]);
+                columns[1].strong("Base");
+                self.base.ui(&mut columns[1]);
+                columns[2].strong("Theirs");
+                self.theirs.ui(&mut columns[2]);
+            });
+        });
+
+        ui.separator();
+        ui.strong("Result");
+        let response = egui::ScrollArea::vertical()
+            .id_salt("merge_result")
+            .show(ui, |ui| self.result.ui(ui))
+            .inner;
+        self.follow_result_edits();
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "a\nb\nc\nd\ne\n";
+
+    #[test]
+    fn merges_changes_of_both_sides() {
+        let chunks = merge(BASE, "a\nB\nc\nd\ne\n", "a\nb\nc\nd\nE\n");
+        assert_eq!(merged_text(&chunks, &[]), "a\nB\nc\nd\nE\n");
+        assert!(chunks.iter().all(|chunk| matches!(chunk, MergeChunk::Resolved(_))));
+    }
+
+    #[test]
+    fn same_change_on_both_sides_is_no_conflict() {
+        let chunks = merge(BASE, "a\nX\nc\nd\ne\n", "a\nX\nc\nd\ne\n");
+        assert_eq!(merged_text(&chunks, &[]), "a\nX\nc\nd\ne\n");
+    }
+
+    #[test]
+    fn conflicts_with_markers_and_resolutions() {
+        let chunks = merge(BASE, "a\nB1\nc\nd\ne\n", "a\nB2\nB3\nc\nd\ne\n");
+        let conflict = chunks
+            .iter()
+            .find_map(|chunk| match chunk {
+                MergeChunk::Conflict(conflict) => Some(conflict),
+                MergeChunk::Resolved(_) => None,
+            })
+            .unwrap();
+        assert_eq!(conflict.base, "b\n");
+        assert_eq!(conflict.ours, "B1\n");
+        assert_eq!(conflict.theirs, "B2\nB3\n");
+        assert_eq!(conflict.ours_line, 1);
+
+        let markers = merged_text(&chunks, &[Resolution::Unresolved]);
+        assert_eq!(markers, "a\n<<<<<<< ours\nB1\n=======\nB2\nB3\n>>>>>>> theirs\nc\nd\ne\n");
+        assert_eq!(merged_text(&chunks, &[Resolution::Right]), "a\nB2\nB3\nc\nd\ne\n");
+        assert_eq!(merged_text(&chunks, &[Resolution::Edited("mine\n".to_owned())]), "a\nmine\nc\nd\ne\n");
+    }
+
+    #[test]
+    fn conflict_ranges_in_the_result() {
+        let chunks = merge(BASE, "a\nB1\nc\nd\nE1\n", "a\nB2\nc\nd\nE2\n");
+        let (text, ranges) = merge_result(&chunks, &[Resolution::Left, Resolution::Right]);
+        assert_eq!(text, "a\nB1\nc\nd\nE2\n");
+        assert_eq!(ranges.iter().map(|range| &text[range.clone()]).collect::<Vec<_>>(), ["B1\n", "E2\n"]);
+    }
+
+    #[test]
+    fn aligns_the_panes() {
+        let ([base, ours, theirs], rows) = aligned(BASE, "a\nB1\nc\nd\ne\n", "a\nB2\nB3\nc\nd\ne\n");
+        assert_eq!(base, "a\nb\n\nc\nd\ne\n");
+        assert_eq!(ours, "a\nB1\n\nc\nd\ne\n");
+        assert_eq!(theirs, "a\nB2\nB3\nc\nd\ne\n");
+        assert_eq!(rows, vec![1]);
+    }
+
+    #[test]
+    fn resolving_keeps_the_edits_of_the_result() {
+        let mut view = MergeView::new(BASE, "a\nB1\nc\nd\nE1\n", "a\nB2\nc\nd\nE2\n", "txt", "base16-ocean.dark");
+        view.result.code.insert_str(0, "header\n");
+        view.resolve(0, Resolution::Left);
+        view.result.code = view.result.code.replacen("c\n", "c2\n", 1);
+        view.resolve(1, Resolution::Right);
+        assert_eq!(view.result.code, "header\na\nB1\nc2\nd\nE2\n");
+        assert_eq!(view.unresolved_count(), 0);
+    }
+}
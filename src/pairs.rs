@@ -0,0 +1,117 @@
+use std::ops::Range;
+
+/// Brackets and quotes typed in pairs by the [`CodeEditor`](crate::CodeEditor), see
+/// [`CodeEditor::auto_pairs`](crate::CodeEditor::auto_pairs).
+///
+/// # Usage
+///
+/// Use `AutoPairs::default()` to close, skip over and wrap with the brackets and the quotes, and change the fields
+/// to keep only some of them. [`AutoPairs::disabled`] types every char as is, like the editor does by default.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AutoPairs {
+    /// Open and close chars of the pairs, the same char twice for the quotes.
+    pub pairs: Vec<(char, char)>,
+    /// Inserts the close char after the typed open char, when the caret is before whitespace, a close char or the end
+    /// of the code. A quote is not closed after a word char, like the apostrophe of `don't` or a lifetime.
+    /// `Backspace` between the open char and the inserted close char deletes both.
+    pub close: bool,
+    /// Typing a close char inserted by `close` moves the caret over it instead of adding another one.
+    pub skip_over: bool,
+    /// Typing an open char with a selection wraps the selection in the pair instead of replacing it.
+    pub wrap_selection: bool,
+}
+
+impl Default for AutoPairs {
+    fn default() -> Self {
+        Self {
+            pairs: vec![('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')],
+            close: true,
+            skip_over: true,
+            wrap_selection: true,
+        }
+    }
+}
+
+impl AutoPairs {
+    /// No pairs, the typed chars are inserted as they are.
+    pub fn disabled() -> Self {
+        Self {
+            pairs: Vec::new(),
+            close: false,
+            skip_over: false,
+            wrap_selection: false,
+        }
+    }
+
+    /// Close char of the pair opened by `open`.
+    pub fn closing(&self, open: char) -> Option<char> {
+        self.pairs.iter().find(|(o, _)| *o == open).map(|(_, close)| *close)
+    }
+
+    pub fn is_closing(&self, c: char) -> bool {
+        self.pairs.iter().any(|(_, close)| *close == c)
+    }
+
+    /// Close char to insert at the byte `offset` of `code`, right after the typed `open` char.
+    pub(crate) fn auto_close(&self, code: &str, offset: usize, open: char) -> Option<char> {
+        let close = self.closing(open).filter(|_| self.close)?;
+        let next = code[offset..].chars().next();
+        if next.is_some_and(|c| !c.is_whitespace() && !self.is_closing(c)) {
+            return None;
+        }
+        let previous = code[..offset - open.len_utf8()].chars().next_back();
+        // an apostrophe or the end of a string, rather than an opening quote
+        let closes_quote = previous.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == open);
+        if open == close && (closes_quote || next == Some(open)) {
+            return None;
+        }
+        Some(close)
+    }
+
+    /// Byte range of the close char right after the byte `offset` of `code`, when it closes the open char right
+    /// before `offset`, like in `(|)`.
+    pub(crate) fn empty_pair(&self, code: &str, offset: usize) -> Option<Range<usize>> {
+        let open = code[..offset].chars().next_back()?;
+        let close = self.closing(open)?;
+        code[offset..].starts_with(close).then(|| offset..offset + close.len_utf8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closes_before_whitespace_and_close_chars() {
+        let pairs = AutoPairs::default();
+        assert_eq!(pairs.auto_close("(", 1, '('), Some(')'));
+        assert_eq!(pairs.auto_close("f( x", 2, '('), Some(')'));
+        assert_eq!(pairs.auto_close("[{]", 2, '{'), Some('}'));
+        assert_eq!(pairs.auto_close("(x", 1, '('), None);
+        assert_eq!(pairs.auto_close("a", 1, 'a'), None);
+        assert_eq!(AutoPairs::disabled().auto_close("(", 1, '('), None);
+    }
+
+    #[test]
+    fn skips_the_quotes_after_word_chars() {
+        let pairs = AutoPairs::default();
+        assert_eq!(pairs.auto_close("x = \"", 5, '"'), Some('"'));
+        assert_eq!(pairs.auto_close("don'", 4, '\''), None);
+        assert_eq!(pairs.auto_close("&'", 2, '\''), Some('\''));
+        assert_eq!(pairs.auto_close("&a_'", 4, '\''), None);
+        // the end of an empty string
+        assert_eq!(pairs.auto_close("\"\"", 2, '"'), None);
+        assert_eq!(pairs.auto_close("\"\"", 1, '"'), None);
+    }
+
+    #[test]
+    fn finds_the_empty_pairs() {
+        let pairs = AutoPairs::default();
+        assert_eq!(pairs.empty_pair("f()", 2), Some(2..3));
+        assert_eq!(pairs.empty_pair("''", 1), Some(1..2));
+        assert_eq!(pairs.empty_pair("(x)", 1), None);
+        assert_eq!(pairs.empty_pair("(]", 1), None);
+        assert_eq!(pairs.empty_pair(")", 0), None);
+        assert_eq!(AutoPairs::disabled().empty_pair("()", 1), None);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::language::LanguageConfig;
+
+/// Transform of the pasted text, offered by the context menu of the editor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PasteMode {
+    /// Quoted and escaped as a string literal of the language, see [`string_literal`].
+    StringLiteral,
+    /// Commented out, see [`comment`].
+    Comment,
+    /// With the line breaks of the code, see [`normalize_line_endings`].
+    NormalizedLineEndings,
+}
+
+impl PasteMode {
+    pub const ALL: &[PasteMode] = &[PasteMode::StringLiteral, PasteMode::Comment, PasteMode::NormalizedLineEndings];
+
+    /// Label in the context menu.
+    pub fn label(self) -> &'static str {
+        match self {
+            PasteMode::StringLiteral => "Paste as string literal",
+            PasteMode::Comment => "Paste as comment",
+            PasteMode::NormalizedLineEndings => "Paste with normalized line endings",
+        }
+    }
+}
+
+/// Escape of the control chars without a short escape like `\n`.
+#[derive(Clone, Copy)]
+enum CharEscape {
+    /// `\u{1b}`
+    Braced,
+    /// `\u001b`
+    Unicode,
+    /// `\x1b`
+    Hex,
+}
+
+/// `text` as a string literal of the syntax `name` (like `"Rust"` or `"SQL"`), in double quotes with
+/// the escapes of the language when it has them.
+///
+/// The languages interpolating in double quotes also escape their sigils (`$` in PHP, `#` in Ruby),
+/// SQL doubles the single quotes and the shells close and reopen the single quotes around them.
+/// The unknown syntaxes use the JSON escapes.
+pub fn string_literal(text: &str, name: &str) -> String {
+    match name {
+        "Rust" => escaped(text, CharEscape::Braced, &[]),
+        "C" | "C++" | "Objective-C" | "Objective-C++" | "D" | "Python" | "Lua" => escaped(text, CharEscape::Hex, &[]),
+        "PHP" => escaped(text, CharEscape::Hex, &['$']),
+        "Perl" => escaped(text, CharEscape::Hex, &['$', '@']),
+        "Ruby" => escaped(text, CharEscape::Hex, &['#']),
+        "SQL" => format!("'{}'", text.replace('\'', "''")),
+        "Bourne Again Shell (bash)" | "Shell-Unix-Generic" => format!("'{}'", text.replace('\'', r"'\''")),
+        _ => escaped(text, CharEscape::Unicode, &[]),
+    }
+}
+
+/// `text` in double quotes, with the backslash escapes and the `extra` chars escaped.
+fn escaped(text: &str, control: CharEscape, extra: &[char]) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            '\n' => literal.push_str(r"\n"),
+            '\r' => literal.push_str(r"\r"),
+            '\t' => literal.push_str(r"\t"),
+            c if c.is_ascii_control() => {
+                let code = c as u32;
+                literal.push_str(&match control {
+                    CharEscape::Braced => format!("\\u{{{code:x}}}"),
+                    CharEscape::Unicode => format!("\\u{code:04x}"),
+                    CharEscape::Hex => format!("\\x{code:02x}"),
+                });
+            }
+            c if extra.contains(&c) => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// `text` commented out with the comments of the `language`, `None` if it has none.
+///
+/// A block comment is used when the language has one and `text` does not close it, otherwise every line
+/// gets a line comment. The lines are broken with `line_break`.
+pub fn comment(text: &str, language: &LanguageConfig, line_break: &str) -> Option<String> {
+    let text = normalize_line_endings(text, line_break);
+    // the final line break stays after the comment
+    let (text, trailing) = match text.strip_suffix(line_break) {
+        Some(text) => (text, line_break),
+        None => (text.as_str(), ""),
+    };
+    if let Some((start, end)) = &language.block_comment
+        && !text.contains(end.as_str())
+    {
+        return Some(if text.contains(line_break) {
+            format!("{start}{line_break}{text}{line_break}{end}{trailing}")
+        } else {
+            format!("{start} {text} {end}{trailing}")
+        });
+    }
+    let prefix = language.line_comment.as_ref()?;
+    let lines: Vec<String> = text
+        .split(line_break)
+        .map(|line| if line.is_empty() { prefix.clone() } else { format!("{prefix} {line}") })
+        .collect();
+    Some(lines.join(line_break) + trailing)
+}
+
+/// `text` with all its line breaks (`\r\n`, `\r` and the Unicode line and paragraph separators) replaced by `line_break`.
+pub fn normalize_line_endings(text: &str, line_break: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                normalized.push_str(line_break);
+            }
+            '\n' | '\u{2028}' | '\u{2029}' => normalized.push_str(line_break),
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_the_string_literals_of_the_language() {
+        assert_eq!(string_literal("a\"b\\\n\t\u{1b}", "Rust"), r#""a\"b\\\n\t\u{1b}""#);
+        assert_eq!(string_literal("\u{1}", "JSON"), r#""\u0001""#);
+        assert_eq!(string_literal("\u{1b}", "Python"), r#""\x1b""#);
+        assert_eq!(string_literal("$x #{y}", "PHP"), r#""\$x #{y}""#);
+        assert_eq!(string_literal("#{y}", "Ruby"), r#""\#{y}""#);
+        assert_eq!(string_literal("it's", "SQL"), "'it''s'");
+        assert_eq!(string_literal("it's", "Bourne Again Shell (bash)"), r"'it'\''s'");
+        assert_eq!(string_literal("", "Rust"), r#""""#);
+    }
+
+    #[test]
+    fn comments_with_a_block_comment() {
+        let rust = LanguageConfig::for_syntax("Rust");
+        assert_eq!(comment("a", &rust, "\n").as_deref(), Some("/* a */"));
+        assert_eq!(comment("a\r\nb\n", &rust, "\n").as_deref(), Some("/*\na\nb\n*/\n"));
+    }
+
+    #[test]
+    fn comments_every_line_when_the_block_comment_is_closed() {
+        let rust = LanguageConfig::for_syntax("Rust");
+        assert_eq!(comment("a */\nb", &rust, "\n").as_deref(), Some("// a */\n// b"));
+        let python = LanguageConfig::for_syntax("Python");
+        assert_eq!(comment("a\n\nb\n", &python, "\r\n").as_deref(), Some("# a\r\n#\r\n# b\r\n"));
+        assert_eq!(comment("a", &LanguageConfig::for_syntax("JSON"), "\n"), None);
+    }
+
+    #[test]
+    fn normalizes_every_line_break() {
+        assert_eq!(normalize_line_endings("a\r\nb\rc\nd\u{2028}e\u{2029}", "\n"), "a\nb\nc\nd\ne\n");
+        assert_eq!(normalize_line_endings("a\nb\r\n", "\r\n"), "a\r\nb\r\n");
+        assert_eq!(normalize_line_endings("\r\r\n", "\n"), "\n\n");
+    }
+}
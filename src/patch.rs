@@ -0,0 +1,299 @@
+use std::fmt;
+
+/// Maximum number of context lines ignored at each end of a hunk which does not match.
+const MAX_FUZZ: usize = 2;
+
+/// Error of [`apply_unified_diff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatchError {
+    /// The patch has no hunks.
+    Empty,
+    /// The hunk header at `line` (1-based line of the patch) can not be parsed.
+    InvalidHeader { line: usize },
+    /// The editor is read only, see [`CodeEditor::read_only`](crate::CodeEditor::read_only).
+    ReadOnly,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Empty => write!(f, "patch has no hunks"),
+            PatchError::InvalidHeader { line } => write!(f, "invalid hunk header at line {line}"),
+            PatchError::ReadOnly => write!(f, "the editor is read only"),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Result of the applied patch.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatchReport {
+    pub applied: usize,
+    /// Hunks whose context was not found, they are not applied.
+    pub rejected: Vec<RejectedHunk>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RejectedHunk {
+    /// 0-based index of the hunk in the patch.
+    pub index: usize,
+    /// The `@@ ... @@` line of the hunk.
+    pub header: String,
+}
+
+#[derive(Debug)]
+struct PatchHunk {
+    header: String,
+    old_start: usize,
+    /// Lines with their `' '`, `'-'` or `'+'` prefix.
+    lines: Vec<(char, String)>,
+}
+
+impl PatchHunk {
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines.iter().filter(|(op, _)| *op != '+').map(|(_, l)| l.as_str()).collect()
+    }
+
+    fn new_lines(&self) -> Vec<&str> {
+        self.lines.iter().filter(|(op, _)| *op != '-').map(|(_, l)| l.as_str()).collect()
+    }
+
+    /// Number of context lines at the start and at the end of the hunk.
+    fn context(&self) -> (usize, usize) {
+        let leading = self.lines.iter().take_while(|(op, _)| *op == ' ').count();
+        let trailing = self.lines.iter().rev().take_while(|(op, _)| *op == ' ').count();
+        (leading, trailing)
+    }
+}
+
+/// Applies the unified diff `patch` to `text`.
+///
+/// Hunks are searched near their original position and then in the whole text, ignoring the trailing
+/// whitespaces and up to two context lines when the exact match fails.
+pub fn apply_unified_diff(text: &str, patch: &str) -> Result<(String, PatchReport), PatchError> {
+    let hunks = parse(patch)?;
+    if hunks.is_empty() {
+        return Err(PatchError::Empty);
+    }
+
+    let line_break = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let trailing_break = text.ends_with('\n');
+    let mut lines: Vec<String> = text.lines().map(str::to_owned).collect();
+    let mut report = PatchReport::default();
+    // shift of the positions caused by the previous hunks
+    let mut delta: isize = 0;
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        // hunks without old lines insert after `old_start`
+        let start = if hunk.old_lines().is_empty() {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        };
+        let expected = (start as isize + delta).max(0) as usize;
+        match locate(&lines, hunk, expected) {
+            Some((pos, fuzz_front, fuzz_back)) => {
+                let old = hunk.old_lines();
+                let new = hunk.new_lines();
+                let old = &old[fuzz_front..old.len() - fuzz_back];
+                let new = &new[fuzz_front..new.len() - fuzz_back];
+                lines.splice(pos..pos + old.len(), new.iter().map(|l| (*l).to_owned()));
+                delta += new.len() as isize - old.len() as isize + pos as isize
+                    - (expected + fuzz_front) as isize;
+                report.applied += 1;
+            }
+            None => report.rejected.push(RejectedHunk {
+                index,
+                header: hunk.header.clone(),
+            }),
+        }
+    }
+
+    let mut result = lines.join(line_break);
+    if trailing_break && !result.is_empty() {
+        result.push_str(line_break);
+    }
+    Ok((result, report))
+}
+
+/// Position of the hunk and the number of ignored context lines at its start and end.
+fn locate(lines: &[String], hunk: &PatchHunk, expected: usize) -> Option<(usize, usize, usize)> {
+    let old = hunk.old_lines();
+    let (leading, trailing) = hunk.context();
+
+    for fuzz in 0..=MAX_FUZZ {
+        // the context lines of a hunk without changes are both leading and trailing, keep a line to match
+        let front = fuzz.min(leading).min(old.len().saturating_sub(1));
+        let back = fuzz.min(trailing).min(old.len().saturating_sub(1) - front);
+        if fuzz > 0 && front + back == 0 {
+            break;
+        }
+        let pattern = &old[front..old.len() - back];
+        for exact in [true, false] {
+            if let Some(pos) = search(lines, pattern, expected + front, exact) {
+                return Some((pos, front, back));
+            }
+        }
+    }
+    None
+}
+
+/// Searches `pattern` starting at `expected` and moving away from it in both directions.
+fn search(lines: &[String], pattern: &[&str], expected: usize, exact: bool) -> Option<usize> {
+    if pattern.len() > lines.len() {
+        return None;
+    }
+    let last = lines.len() - pattern.len();
+    let expected = expected.min(last);
+    let matches = |pos: usize| {
+        lines[pos..pos + pattern.len()].iter().zip(pattern).all(|(line, p)| {
+            if exact {
+                line == p
+            } else {
+                line.trim_end() == p.trim_end()
+            }
+        })
+    };
+
+    (0..=last).find_map(|distance| {
+        [expected.checked_sub(distance), Some(expected + distance)]
+            .into_iter()
+            .flatten()
+            .filter(|pos| *pos <= last)
+            .find(|pos| matches(*pos))
+    })
+}
+
+fn parse(patch: &str) -> Result<Vec<PatchHunk>, PatchError> {
+    let mut hunks = Vec::new();
+    let mut lines = patch.lines().enumerate().peekable();
+
+    while let Some((number, line)) = lines.next() {
+        if !line.starts_with("@@") {
+            continue;
+        }
+        let invalid = PatchError::InvalidHeader { line: number + 1 };
+        let (old_start, old_count, new_count) = parse_header(line).ok_or(invalid)?;
+
+        let mut hunk = PatchHunk {
+            header: line.to_owned(),
+            old_start,
+            lines: Vec::new(),
+        };
+        let (mut old_left, mut new_left) = (old_count, new_count);
+        while old_left > 0 || new_left > 0 {
+            let Some((_, line)) = lines.next_if(|(_, l)| !l.starts_with("@@")) else {
+                break;
+            };
+            let (op, content) = match line.chars().next() {
+                Some(op @ ('+' | '-' | ' ')) => (op, &line[1..]),
+                Some('\\') => continue, // "\ No newline at end of file"
+                // some tools strip the space of empty context lines
+                _ => (' ', line),
+            };
+            if op != '+' {
+                old_left = old_left.saturating_sub(1);
+            }
+            if op != '-' {
+                new_left = new_left.saturating_sub(1);
+            }
+            hunk.lines.push((op, content.to_owned()));
+        }
+        hunks.push(hunk);
+    }
+    Ok(hunks)
+}
+
+/// Parses `@@ -old_start,old_count +new_start,new_count @@`.
+fn parse_header(line: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = line.trim_start_matches('@').split_whitespace();
+    let range = |part: &str| -> Option<(usize, usize)> {
+        match part.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((part.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = range(parts.next()?.strip_prefix('-')?)?;
+    let (_, new_count) = range(parts.next()?.strip_prefix('+')?)?;
+    Some((old_start, old_count, new_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "a\nb\nc\nd\ne\n";
+
+    #[test]
+    fn applies_exact_hunk() {
+        let patch = "--- a\n+++ b\n@@ -2,3 +2,3 @@\n b\n-c\n+C\n d\n";
+        let (result, report) = apply_unified_diff(TEXT, patch).unwrap();
+        assert_eq!(result, "a\nb\nC\nd\ne\n");
+        assert_eq!(report, PatchReport { applied: 1, rejected: Vec::new() });
+    }
+
+    #[test]
+    fn finds_moved_hunk() {
+        let text = format!("x\ny\n{TEXT}");
+        let patch = "@@ -2,3 +2,3 @@\n b\n-c\n+C\n d\n";
+        let (result, report) = apply_unified_diff(&text, patch).unwrap();
+        assert_eq!(result, "x\ny\na\nb\nC\nd\ne\n");
+        assert_eq!(report.applied, 1);
+    }
+
+    #[test]
+    fn ignores_trailing_whitespace() {
+        let text = "a\nb  \nc\nd\ne\n";
+        let patch = "@@ -2,3 +2,3 @@\n b\n-c\n+C\n d\n";
+        let (result, report) = apply_unified_diff(text, patch).unwrap();
+        assert_eq!(report.applied, 1);
+        assert_eq!(result.lines().nth(2), Some("C"));
+    }
+
+    #[test]
+    fn fuzzes_changed_context() {
+        // the first context line changed since the patch was made
+        let text = "a\nB\nc\nd\ne\n";
+        let patch = "@@ -1,4 +1,4 @@\n a\n b\n-c\n+C\n d\n";
+        let (result, report) = apply_unified_diff(text, patch).unwrap();
+        assert_eq!(result, "a\nB\nC\nd\ne\n");
+        assert!(report.rejected.is_empty());
+    }
+
+    #[test]
+    fn rejects_missing_context() {
+        let patch = "@@ -2,3 +2,3 @@\n x\n-y\n+Y\n z\n@@ -4,1 +4,1 @@\n-d\n+D\n";
+        let (result, report) = apply_unified_diff(TEXT, patch).unwrap();
+        assert_eq!(result, "a\nb\nc\nD\ne\n");
+        assert_eq!(report.applied, 1);
+        assert_eq!(report.rejected, vec![RejectedHunk { index: 0, header: "@@ -2,3 +2,3 @@".to_owned() }]);
+    }
+
+    #[test]
+    fn applies_context_only_hunks() {
+        let (result, report) = apply_unified_diff("a\n", "@@ -1 +1 @@\n a\n").unwrap();
+        assert_eq!(result, "a\n");
+        assert_eq!(report.applied, 1);
+
+        // fuzzing would leave no line to match
+        let (result, report) = apply_unified_diff(TEXT, "@@ -1,2 +1,2 @@\n x\n y\n").unwrap();
+        assert_eq!(result, TEXT);
+        assert_eq!(report.applied, 0);
+        let (_, report) = apply_unified_diff(TEXT, "@@ -1 +1 @@\n x\n").unwrap();
+        assert_eq!(report.rejected.len(), 1);
+    }
+
+    #[test]
+    fn keeps_crlf_line_breaks() {
+        let patch = "@@ -1,2 +1,2 @@\n a\n-b\n+B\n";
+        let (result, _) = apply_unified_diff("a\r\nb\r\n", patch).unwrap();
+        assert_eq!(result, "a\r\nB\r\n");
+    }
+
+    #[test]
+    fn reports_invalid_patches() {
+        assert_eq!(apply_unified_diff(TEXT, "no hunks"), Err(PatchError::Empty));
+        assert_eq!(apply_unified_diff(TEXT, "--- a\n@@ -x +1 @@\n"), Err(PatchError::InvalidHeader { line: 2 }));
+    }
+}
@@ -0,0 +1,54 @@
+use egui::{Rect, Ui};
+
+use crate::CodeViewer;
+use crate::settings;
+
+/// Maximum height of the peek panel.
+const MAX_HEIGHT: f32 = 240.0;
+
+/// Location peeked in the editor.
+#[derive(Debug)]
+pub(crate) struct Peek {
+    pub(crate) location: String,
+    pub(crate) viewer: CodeViewer,
+}
+
+pub(crate) enum PeekAction {
+    Open,
+    Close,
+}
+
+impl Peek {
+    /// Shows the panel below `line_rect`, across the editor.
+    pub(crate) fn ui(&mut self, ui: &Ui, id: egui::Id, editor_rect: Rect, line_rect: Rect) -> Option<PeekAction> {
+        let mut action = None;
+        egui::Area::new(id)
+            .order(egui::Order::Foreground)
+            .fade_in(settings::fade_in(ui.ctx()))
+            .fixed_pos(egui::pos2(editor_rect.left(), line_rect.bottom()))
+            .show(ui.ctx(), |ui| {
+                ui.set_width(editor_rect.width());
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.strong(&self.location);
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.small_button("Close").clicked() {
+                                action = Some(PeekAction::Close);
+                            }
+                            if ui.small_button("Open").clicked() {
+                                action = Some(PeekAction::Open);
+                            }
+                        });
+                    });
+                    egui::ScrollArea::vertical()
+                        .max_height(MAX_HEIGHT)
+                        .show(ui, |ui| self.viewer.ui(ui));
+                });
+            });
+
+        if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+            action = Some(PeekAction::Close);
+        }
+        action
+    }
+}
@@ -0,0 +1,54 @@
+use egui::{Align2, Context, Id, InnerResponse, Order, Rect, Ui};
+
+use crate::settings;
+
+/// Shows a popup anchored at `caret_rect`.
+///
+/// The popup is placed below the caret, or above it when there is not enough space below
+/// (based on the size of the popup in the previous frame). It is kept inside the screen horizontally.
+pub fn caret_popup<R>(
+    ctx: &Context,
+    id: Id,
+    caret_rect: Rect,
+    add_contents: impl FnOnce(&mut Ui) -> R,
+) -> InnerResponse<R> {
+    show(ctx, id, caret_rect, false, add_contents)
+}
+
+/// Like [`caret_popup`], but placed above the caret, or below it near the top of the screen.
+pub fn caret_popup_above<R>(
+    ctx: &Context,
+    id: Id,
+    caret_rect: Rect,
+    add_contents: impl FnOnce(&mut Ui) -> R,
+) -> InnerResponse<R> {
+    show(ctx, id, caret_rect, true, add_contents)
+}
+
+fn show<R>(
+    ctx: &Context,
+    id: Id,
+    caret_rect: Rect,
+    prefer_above: bool,
+    add_contents: impl FnOnce(&mut Ui) -> R,
+) -> InnerResponse<R> {
+    let screen = ctx.screen_rect();
+    let height = ctx.memory(|m| m.area_rect(id)).map_or(0.0, |rect| rect.height());
+    let fits_below = caret_rect.bottom() + height <= screen.bottom();
+    let fits_above = caret_rect.top() - height >= screen.top();
+    let above = if prefer_above { fits_above || !fits_below } else { !fits_below && fits_above };
+
+    let (pivot, pos) = if above {
+        (Align2::LEFT_BOTTOM, caret_rect.left_top())
+    } else {
+        (Align2::LEFT_TOP, caret_rect.left_bottom())
+    };
+
+    egui::Area::new(id)
+        .order(Order::Foreground)
+        .fade_in(settings::fade_in(ctx))
+        .pivot(pivot)
+        .fixed_pos(pos)
+        .constrain(true)
+        .show(ctx, |ui| egui::Frame::popup(ui.style()).show(ui, add_contents).inner)
+}
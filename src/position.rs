@@ -0,0 +1,332 @@
+use std::ops::Range;
+
+/// Line and column in the text, both 0-based.
+///
+/// The unit of the column depends on the [`PositionEncoding`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+/// Unit of the columns in [`Position`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PositionEncoding {
+    /// Bytes of UTF-8.
+    Utf8,
+    /// UTF-16 code units, the default of the Language Server Protocol.
+    #[default]
+    Utf16,
+    /// Unicode scalar values (Rust `char`s).
+    Char,
+}
+
+impl PositionEncoding {
+    /// Length of `c` in this encoding.
+    pub fn len(self, c: char) -> usize {
+        match self {
+            PositionEncoding::Utf8 => c.len_utf8(),
+            PositionEncoding::Utf16 => c.len_utf16(),
+            PositionEncoding::Char => 1,
+        }
+    }
+}
+
+/// Index of the line starts of a text, converting between byte offsets, offsets in other
+/// [`PositionEncoding`]s and [`Position`]s.
+///
+/// # Usage
+///
+/// Build it once with `LineIndex::new(text)` and use it for many conversions,
+/// each one only walks a single line.
+#[derive(Clone, Debug)]
+pub struct LineIndex<'a> {
+    text: &'a str,
+    /// Byte offset of the start of every line.
+    starts: Vec<usize>,
+    /// Number of chars before every line.
+    chars: Vec<usize>,
+    /// Number of UTF-16 code units before every line.
+    utf16: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let mut index = Self {
+            text,
+            starts: vec![0],
+            chars: vec![0],
+            utf16: vec![0],
+        };
+        let (mut chars, mut utf16) = (0, 0);
+        for (i, c) in text.char_indices() {
+            chars += 1;
+            utf16 += c.len_utf16();
+            if c == '\n' {
+                index.starts.push(i + 1);
+                index.chars.push(chars);
+                index.utf16.push(utf16);
+            }
+        }
+        index
+    }
+
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Byte range of the `line`, without the line break.
+    pub fn line_range(&self, line: usize) -> Range<usize> {
+        let line = line.min(self.starts.len() - 1);
+        let start = self.starts[line];
+        let mut end = self.starts.get(line + 1).map_or(self.text.len(), |next| next - 1);
+        if self.text[start..end].ends_with('\r') {
+            end -= 1;
+        }
+        start..end
+    }
+
+    pub fn line(&self, line: usize) -> &'a str {
+        &self.text[self.line_range(line)]
+    }
+
+    /// Line containing the byte `offset`.
+    pub fn line_of(&self, offset: usize) -> usize {
+        self.starts.partition_point(|start| *start <= offset) - 1
+    }
+
+    /// Position of the byte `offset`, an offset inside a char is moved to the char start.
+    pub fn position(&self, offset: usize, encoding: PositionEncoding) -> Position {
+        let offset = self.char_boundary(offset);
+        let line = self.line_of(offset);
+        let start = self.starts[line];
+        let column = self.text[start..offset].chars().map(|c| encoding.len(c)).sum();
+        Position { line, column }
+    }
+
+    /// Visual column of the byte `offset` in its line, counting the [`display_width`] of the chars
+    /// and moving a tab to the next multiple of `tab_size`.
+    pub fn display_column(&self, offset: usize, tab_size: usize) -> usize {
+        let offset = self.char_boundary(offset);
+        let start = self.starts[self.line_of(offset)];
+        display_columns(&self.text[start..offset], 0, tab_size)
+    }
+
+    /// Byte offset of `position`.
+    ///
+    /// Like in LSP, a column past the end of the line means the end of the line
+    /// and a line past the end of the text means the end of the text.
+    pub fn offset(&self, position: Position, encoding: PositionEncoding) -> usize {
+        if position.line >= self.starts.len() {
+            return self.text.len();
+        }
+        let range = self.line_range(position.line);
+        self.walk(range, position.column, encoding)
+    }
+
+    /// Converts the byte `offset` to the offset in `encoding` from the start of the text.
+    pub fn to_encoded(&self, offset: usize, encoding: PositionEncoding) -> usize {
+        let offset = self.char_boundary(offset);
+        let line = self.line_of(offset);
+        let before = match encoding {
+            PositionEncoding::Utf8 => return offset,
+            PositionEncoding::Utf16 => self.utf16[line],
+            PositionEncoding::Char => self.chars[line],
+        };
+        before + self.position(offset, encoding).column
+    }
+
+    /// Converts the `offset` in `encoding` from the start of the text to the byte offset.
+    pub fn from_encoded(&self, offset: usize, encoding: PositionEncoding) -> usize {
+        let prefix = match encoding {
+            PositionEncoding::Utf8 => return self.char_boundary(offset),
+            PositionEncoding::Utf16 => &self.utf16,
+            PositionEncoding::Char => &self.chars,
+        };
+        let line = prefix.partition_point(|before| *before <= offset) - 1;
+        let end = self.starts.get(line + 1).map_or(self.text.len(), |next| *next);
+        self.walk(self.starts[line]..end, offset - prefix[line], encoding)
+    }
+
+    /// Byte offset after `units` of `encoding` from the start of `range`, clamped to its end.
+    fn walk(&self, range: Range<usize>, units: usize, encoding: PositionEncoding) -> usize {
+        let mut walked = 0;
+        for (i, c) in self.text[range.clone()].char_indices() {
+            if walked >= units {
+                return range.start + i;
+            }
+            walked += encoding.len(c);
+        }
+        range.end
+    }
+
+    fn char_boundary(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+}
+
+/// Number of monospace cells taken by `c`: 2 for the wide East Asian chars and the emoji,
+/// 0 for the combining marks and the zero width chars, 1 otherwise.
+///
+/// The tabs depend on their column, see [`LineIndex::display_column`].
+pub fn display_width(c: char) -> usize {
+    if c.is_ascii() {
+        return usize::from(!c.is_ascii_control());
+    }
+    let c = c as u32;
+    let zero = [
+        0x0300..=0x036F,
+        0x0483..=0x0489,
+        0x0591..=0x05BD,
+        0x0610..=0x061A,
+        0x064B..=0x065F,
+        0x1AB0..=0x1AFF,
+        0x1DC0..=0x1DFF,
+        0x200B..=0x200F,
+        0x2028..=0x202E,
+        0x2060..=0x2064,
+        0x20D0..=0x20FF,
+        0xFE00..=0xFE0F,
+        0xFE20..=0xFE2F,
+        0xFEFF..=0xFEFF,
+        0x1F3FB..=0x1F3FF,
+        0xE0000..=0xE0FFF,
+    ];
+    let wide = [
+        0x1100..=0x115F,
+        0x231A..=0x231B,
+        0x23E9..=0x23EC,
+        0x25FD..=0x25FE,
+        0x2614..=0x2615,
+        0x2648..=0x2653,
+        0x26AA..=0x26AB,
+        0x26BD..=0x26BE,
+        0x26C4..=0x26C5,
+        0x26F2..=0x26F5,
+        0x2705..=0x2705,
+        0x270A..=0x270B,
+        0x2728..=0x2728,
+        0x274C..=0x274C,
+        0x2753..=0x2757,
+        0x2795..=0x2797,
+        0x2B1B..=0x2B1C,
+        0x2E80..=0x303E,
+        0x3041..=0x33FF,
+        0x3400..=0x4DBF,
+        0x4E00..=0x9FFF,
+        0xA000..=0xA4CF,
+        0xA960..=0xA97F,
+        0xAC00..=0xD7A3,
+        0xF900..=0xFAFF,
+        0xFE10..=0xFE19,
+        0xFE30..=0xFE6F,
+        0xFF00..=0xFF60,
+        0xFFE0..=0xFFE6,
+        0x1F004..=0x1F004,
+        0x1F0CF..=0x1F0CF,
+        0x1F18E..=0x1F18E,
+        0x1F191..=0x1F19A,
+        0x1F200..=0x1F251,
+        0x1F300..=0x1F64F,
+        0x1F680..=0x1F6FF,
+        0x1F7E0..=0x1F7EB,
+        0x1F900..=0x1F9FF,
+        0x1FA70..=0x1FAFF,
+        0x20000..=0x3FFFD,
+    ];
+    if zero.iter().any(|range| range.contains(&c)) {
+        0
+    } else if wide.iter().any(|range| range.contains(&c)) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Visual column after `text` starting at `column`, `'\n'` goes back to the column 0.
+pub(crate) fn display_columns(text: &str, mut column: usize, tab_size: usize) -> usize {
+    let tab_size = tab_size.max(1);
+    for c in text.chars() {
+        column = match c {
+            '\n' => 0,
+            '\t' => column + tab_size - column % tab_size,
+            c => column + display_width(c),
+        };
+    }
+    column
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `é` is 2 bytes and 1 UTF-16 unit, `😀` is 4 bytes and 2 UTF-16 units
+    const TEXT: &str = "aé😀b\r\nxy\n";
+
+    #[test]
+    fn lines() {
+        let index = LineIndex::new(TEXT);
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(index.line(0), "aé😀b");
+        assert_eq!(index.line(1), "xy");
+        assert_eq!(index.line(2), "");
+        assert_eq!(index.line_of(0), 0);
+        assert_eq!(index.line_of(10), 1);
+        assert_eq!(index.line_of(TEXT.len()), 2);
+    }
+
+    #[test]
+    fn positions_in_each_encoding() {
+        let index = LineIndex::new(TEXT);
+        let b = TEXT.find('b').unwrap();
+        assert_eq!(index.position(b, PositionEncoding::Utf8), Position::new(0, 7));
+        assert_eq!(index.position(b, PositionEncoding::Utf16), Position::new(0, 4));
+        assert_eq!(index.position(b, PositionEncoding::Char), Position::new(0, 3));
+        for encoding in [PositionEncoding::Utf8, PositionEncoding::Utf16, PositionEncoding::Char] {
+            assert_eq!(index.offset(index.position(b, encoding), encoding), b);
+        }
+        // inside the emoji
+        assert_eq!(index.position(4, PositionEncoding::Utf16), Position::new(0, 2));
+    }
+
+    #[test]
+    fn clamps_positions_past_the_end() {
+        let index = LineIndex::new(TEXT);
+        assert_eq!(index.offset(Position::new(1, 99), PositionEncoding::Utf16), TEXT.find("\n").unwrap() + 3);
+        assert_eq!(index.offset(Position::new(0, 99), PositionEncoding::Char), TEXT.find('\r').unwrap());
+        assert_eq!(index.offset(Position::new(9, 0), PositionEncoding::Char), TEXT.len());
+    }
+
+    #[test]
+    fn encoded_offsets() {
+        let index = LineIndex::new(TEXT);
+        let x = TEXT.find('x').unwrap();
+        assert_eq!(index.to_encoded(x, PositionEncoding::Char), 6);
+        assert_eq!(index.to_encoded(x, PositionEncoding::Utf16), 7);
+        assert_eq!(index.from_encoded(6, PositionEncoding::Char), x);
+        assert_eq!(index.from_encoded(7, PositionEncoding::Utf16), x);
+        assert_eq!(index.from_encoded(2, PositionEncoding::Utf8), 1);
+    }
+
+    #[test]
+    fn display_columns_of_tabs_and_wide_chars() {
+        let index = LineIndex::new("\tab\n漢字x");
+        assert_eq!(index.display_column(3, 4), 6);
+        assert_eq!(index.display_column(4 + 6, 4), 4);
+        assert_eq!(display_width('\u{0301}'), 0);
+    }
+}
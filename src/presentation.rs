@@ -0,0 +1,61 @@
+use std::ops::Range;
+
+use egui::{Color32, Rangef, Rect, Ui};
+
+/// Presentation of the code in a [`CodeViewer`](crate::CodeViewer) for the talks and the teaching tools, see
+/// [`CodeViewer::presentation`](crate::CodeViewer::presentation).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Presentation {
+    /// Lines focused one after the other, 0-based with the end excluded.
+    pub ranges: Vec<Range<usize>>,
+    /// Factor of the font size of the viewer, 1.5 by default.
+    pub zoom: f32,
+    /// Opacity of the veil over the lines out of focus, from 0 (not dimmed) to 1 (hidden), 0.6 by default.
+    pub dim: f32,
+    /// Steps through the ranges with the arrow keys when no text field has the keyboard focus, enabled by default.
+    pub arrow_keys: bool,
+}
+
+impl Presentation {
+    /// Presentation of the `ranges` of lines to explain one after the other, the viewer shows the code in a larger
+    /// font and dims the lines out of the current range. The arrow keys step to the next and the previous range.
+    pub fn new(ranges: Vec<Range<usize>>) -> Self {
+        Self {
+            ranges,
+            zoom: 1.5,
+            dim: 0.6,
+            arrow_keys: true,
+        }
+    }
+}
+
+/// Step taken with the arrow keys: `1` for the next range (`→` or `↓`), `-1` for the previous one (`←` or `↑`).
+pub(crate) fn arrow_step(ui: &Ui) -> isize {
+    if ui.ctx().wants_keyboard_input() {
+        return 0;
+    }
+    ui.input_mut(|i| {
+        let mut step = 0;
+        for key in [egui::Key::ArrowRight, egui::Key::ArrowDown] {
+            step += i.consume_key(egui::Modifiers::NONE, key) as isize;
+        }
+        for key in [egui::Key::ArrowLeft, egui::Key::ArrowUp] {
+            step -= i.consume_key(egui::Modifiers::NONE, key) as isize;
+        }
+        step
+    })
+}
+
+/// Covers the lines of `rect` out of the `focus` lines with `veil`, the `spans` of the lines are relative to `top`.
+///
+/// Returns the rectangle of the focused lines.
+pub(crate) fn paint_veil(ui: &Ui, rect: Rect, spans: &[Rangef], top: f32, focus: &Range<usize>, veil: Color32) -> Rect {
+    let start = spans.get(focus.start).map_or(rect.bottom(), |span| top + span.min);
+    let end = focus.end.checked_sub(1).and_then(|last| spans.get(last)).map_or(start, |span| top + span.max).max(start);
+    for y in [Rangef::new(rect.top(), start), Rangef::new(end, rect.bottom())] {
+        if y.span() > 0.0 {
+            ui.painter().rect_filled(Rect::from_x_y_ranges(rect.x_range(), y), 0.0, veil);
+        }
+    }
+    Rect::from_x_y_ranges(rect.x_range(), Rangef::new(start, end))
+}
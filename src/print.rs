@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use egui::{Color32, Ui};
+use syntect::highlighting::Theme;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+use crate::cancel::CancelToken;
+use crate::gutter::{self, LineNumbers};
+use crate::{control, fonts, highlight, layout, theme};
+
+/// Built-in light theme of [`PrintStyle::default`].
+pub const DEFAULT_THEME: &str = "InspiredGitHub";
+
+/// Look of the code rendered for printing and exporting (HTML, PDF, images), see
+/// [`CodeEditor::print_ui`](crate::CodeEditor::print_ui) and [`CodeViewer::print_ui`](crate::CodeViewer::print_ui).
+///
+/// # Usage
+///
+/// Use `PrintStyle::default()` for black on white code in the `InspiredGitHub` theme, and change the fields
+/// for the page of the export. The widget keeps its own theme, font size and wrapping.
+#[derive(Clone, Debug)]
+pub struct PrintStyle {
+    /// Light theme of the code.
+    pub theme: Arc<Theme>,
+    /// Font size of the code in points, 10 by default.
+    pub font_size: f32,
+    /// Shows the line numbers to the left of the code, in gray.
+    pub line_numbers: bool,
+    /// Wraps the long lines at the available width, so they are not cut at the edge of the page.
+    pub wrap: bool,
+    /// Background of the code, the theme background (white for the default theme) if `None`.
+    pub background: Option<Color32>,
+}
+
+impl Default for PrintStyle {
+    fn default() -> Self {
+        Self {
+            theme: theme::built_in(DEFAULT_THEME).expect("built-in light theme"),
+            font_size: 10.0,
+            line_numbers: false,
+            wrap: true,
+            background: None,
+        }
+    }
+}
+
+/// Shows `code` in the print `style`, highlighted from scratch without the caches of the widget:
+/// no caret, selection, gutter markers or other decorations.
+pub(crate) fn print_ui(
+    ui: &mut Ui,
+    code: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    tab_size: usize,
+    control_chars: bool,
+    style: &PrintStyle,
+) -> egui::Response {
+    let font = fonts::code_font(ui, style.font_size);
+    let plain = highlight::foreground(&style.theme, Color32::BLACK);
+    let background = style.background.unwrap_or_else(|| highlight::background(&style.theme, Color32::WHITE));
+    let numbers = LineNumbers {
+        color: Some(Color32::GRAY),
+        ..LineNumbers::default()
+    };
+    let gutter_width = if style.line_numbers {
+        gutter::width(ui, &font, code.lines().count(), &numbers)
+    } else {
+        0.0
+    };
+
+    let (mut job, _) = highlight::layout_job(code, syntax, syntax_set, &style.theme, &font, plain, &CancelToken::new());
+    if control_chars {
+        control::conceal(&mut job);
+    }
+    ui.fonts(|f| layout::align_columns(&mut job, tab_size, control_chars, &[], f, &font));
+    job.wrap.max_width = if style.wrap { ui.available_width() - gutter_width } else { f32::INFINITY };
+    let galley = ui.fonts(|f| f.layout_job(job));
+
+    let size = egui::vec2(gutter_width + galley.size().x, galley.size().y);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    ui.painter().rect_filled(rect, 0.0, background);
+    let galley_pos = rect.min + egui::vec2(gutter_width, 0.0);
+    if control_chars {
+        control::paint(ui, &galley, galley_pos, &font, plain, background);
+    }
+    if style.line_numbers {
+        let spans = gutter::line_spans(&galley);
+        let x = egui::Rangef::new(rect.left(), rect.left() + gutter_width);
+        gutter::paint_numbers(ui, 0, &spans, galley_pos, x, &font, &numbers, None);
+    }
+    ui.painter().galley(galley_pos, galley, plain);
+    response
+}
@@ -0,0 +1,115 @@
+use egui::{RichText, Ui};
+
+use crate::CodeEditor;
+use crate::diagnostics::Severity;
+
+const SEVERITIES: [Severity; 4] = [Severity::Error, Severity::Warning, Severity::Info, Severity::Hint];
+
+/// List of the diagnostics of one or more editors.
+///
+/// # Implement
+///
+/// Use `ProblemsPanel::new()` to create a new instance.\
+/// Then call its `ui` method with the named editors, for example `panel.ui(ui, &mut [("main.rs", &mut editor)])`.
+///
+/// # Usage
+///
+/// Toggle the severities in the header to filter the list.\
+/// Clicking a problem moves the caret of its editor there, `ui` returns the index of that editor
+/// so the application can bring it to front.
+#[derive(Clone, Debug)]
+pub struct ProblemsPanel {
+    /// Shown severities, in the order of `SEVERITIES`.
+    shown: [bool; 4],
+}
+
+impl Default for ProblemsPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProblemsPanel {
+    pub fn new() -> Self {
+        Self { shown: [true; 4] }
+    }
+
+    /// Shows or hides the problems with `severity`.
+    pub fn show_severity(mut self, severity: Severity, show: bool) -> Self {
+        self.set_show_severity(severity, show);
+        self
+    }
+
+    pub fn set_show_severity(&mut self, severity: Severity, show: bool) {
+        self.shown[slot(severity)] = show;
+    }
+
+    pub fn is_severity_shown(&self, severity: Severity) -> bool {
+        self.shown[slot(severity)]
+    }
+
+    /// Shows the panel, returns the index of the editor whose problem was clicked.
+    pub fn ui(&mut self, ui: &mut Ui, editors: &mut [(&str, &mut CodeEditor)]) -> Option<usize> {
+        let mut counts = [0; 4];
+        for (_, editor) in editors.iter() {
+            for diagnostic in editor.diagnostics() {
+                counts[slot(diagnostic.severity)] += 1;
+            }
+        }
+
+        ui.horizontal(|ui| {
+            for severity in SEVERITIES {
+                let text = RichText::new(format!("{} {}", counts[slot(severity)], label(severity)))
+                    .color(severity.color());
+                ui.toggle_value(&mut self.shown[slot(severity)], text);
+            }
+        });
+        ui.separator();
+
+        let mut clicked = None;
+        egui::ScrollArea::vertical().id_salt("problems").auto_shrink([false, true]).show(ui, |ui| {
+            let mut empty = true;
+            for (index, (name, editor)) in editors.iter_mut().enumerate() {
+                let mut problems: Vec<_> = editor
+                    .diagnostics()
+                    .filter(|d| self.shown[slot(d.severity)])
+                    .map(|d| (d.range.start, d.severity, d.message.clone()))
+                    .collect();
+                problems.sort_by_key(|(start, severity, _)| (*start, std::cmp::Reverse(*severity)));
+                empty &= problems.is_empty();
+
+                for (i, (start, severity, message)) in problems.into_iter().enumerate() {
+                    let row = ui.horizontal(|ui| {
+                        ui.colored_label(severity.color(), "●");
+                        ui.label(message);
+                        ui.weak(format!("{name}:{}:{}", start.line + 1, start.column + 1));
+                    });
+                    let row = ui
+                        .interact(row.response.rect, ui.id().with((index, i)), egui::Sense::click())
+                        .on_hover_cursor(egui::CursorIcon::PointingHand);
+                    if row.clicked() {
+                        editor.go_to(start);
+                        clicked = Some(index);
+                    }
+                }
+            }
+            if empty {
+                ui.weak("No problems");
+            }
+        });
+        clicked
+    }
+}
+
+fn slot(severity: Severity) -> usize {
+    SEVERITIES.iter().position(|s| *s == severity).unwrap_or_default()
+}
+
+fn label(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "Errors",
+        Severity::Warning => "Warnings",
+        Severity::Info => "Infos",
+        Severity::Hint => "Hints",
+    }
+}
@@ -0,0 +1,97 @@
+use std::ops::Range;
+
+/// Finds the occurrences of the symbol to rename in the [`CodeEditor`](crate::CodeEditor).
+///
+/// # Implement
+///
+/// Implement `occurrences` returning the byte ranges of all occurrences of the symbol at the byte `offset`
+/// of the `code` and set it with `CodeEditor::rename_provider`.
+/// Without a provider all whole word occurrences of the word under the caret are renamed.
+pub trait RenameProvider: Send {
+    fn occurrences(&mut self, code: &str, offset: usize) -> Option<Vec<Range<usize>>>;
+}
+
+impl<F> RenameProvider for F
+where
+    F: FnMut(&str, usize) -> Option<Vec<Range<usize>>> + Send,
+{
+    fn occurrences(&mut self, code: &str, offset: usize) -> Option<Vec<Range<usize>>> {
+        self(code, offset)
+    }
+}
+
+/// Rename in progress.
+#[derive(Clone, Debug)]
+pub(crate) struct RenameState {
+    pub(crate) occurrences: Vec<Range<usize>>,
+    pub(crate) new_name: String,
+    pub(crate) focus_requested: bool,
+}
+
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Byte range of the word touching the byte `offset`, made of the chars accepted by `is_word`.
+pub(crate) fn word_at(code: &str, offset: usize, is_word: impl Fn(char) -> bool) -> Option<Range<usize>> {
+    let start = code[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word(*c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let end = code[offset..]
+        .char_indices()
+        .find(|(_, c)| !is_word(*c))
+        .map_or(code.len(), |(i, _)| offset + i);
+    (start < end).then_some(start..end)
+}
+
+/// Byte ranges of the whole word occurrences of `word`.
+pub(crate) fn word_occurrences(code: &str, word: &str, is_word: impl Fn(char) -> bool) -> Vec<Range<usize>> {
+    code.match_indices(word)
+        .map(|(i, _)| i..i + word.len())
+        .filter(|range| {
+            let before = code[..range.start].chars().next_back();
+            let after = code[range.end..].chars().next();
+            !before.is_some_and(&is_word) && !after.is_some_and(&is_word)
+        })
+        .collect()
+}
+
+/// Replaces all `occurrences` with `new_name`.
+pub(crate) fn replace_all(code: &str, occurrences: &[Range<usize>], new_name: &str) -> String {
+    let mut sorted = occurrences.to_vec();
+    sorted.sort_by_key(|range| range.start);
+
+    let mut result = String::with_capacity(code.len());
+    let mut last = 0;
+    for range in sorted {
+        if range.start < last || code.get(range.clone()).is_none() {
+            continue;
+        }
+        result.push_str(&code[last..range.start]);
+        result.push_str(new_name);
+        last = range.end;
+    }
+    result.push_str(&code[last..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_the_occurrences_in_any_order() {
+        assert_eq!(replace_all("a + a", &[4..5, 0..1], "bc"), "bc + bc");
+    }
+
+    #[test]
+    fn skips_invalid_occurrences() {
+        #[allow(clippy::reversed_empty_ranges)]
+        let occurrences = [3..1, 1..2, 2..3, 1..9];
+        assert_eq!(replace_all("éa", &occurrences, "x"), "éx");
+        assert_eq!(replace_all("aaa", &[0..2, 1..3], "x"), "xa");
+    }
+}
@@ -0,0 +1,130 @@
+use std::ops::Range;
+
+/// Horizontal pointer distance of a step of the number dragged with `Alt`.
+pub(crate) const DRAG_POINTS: f32 = 6.0;
+
+/// Scroll distance of a step of the number scrolled with `Ctrl`, for the touchpads scrolling by points.
+pub(crate) const WHEEL_POINTS: f32 = 24.0;
+
+/// Number dragged with `Alt`, see [`CodeEditor::number_scrubbing`](crate::CodeEditor::number_scrubbing).
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Scrub {
+    /// Byte range of the number in the code, following its edits.
+    pub(crate) range: Range<usize>,
+    /// Text of the number when the drag started.
+    pub(crate) start: String,
+    /// Pointer x where the drag started.
+    pub(crate) origin: f32,
+    pub(crate) steps: i64,
+}
+
+/// Byte range of the decimal number literal of `code` at the byte `offset`, with its sign after an operator,
+/// `None` in the identifiers and the hex literals.
+///
+/// The type suffix (like `f32` in `1.5f32`) and the exponent are not part of the range.
+pub(crate) fn number_at(code: &str, offset: usize) -> Option<Range<usize>> {
+    let bytes = code.as_bytes();
+    let is_number = |b: u8| b.is_ascii_digit() || b == b'.';
+    let mut start = offset.min(bytes.len());
+    // the number after a range `0..10`
+    while start > 0 && is_number(bytes[start - 1]) && !code[..start].ends_with("..") {
+        start -= 1;
+    }
+    let mut end = offset.min(bytes.len());
+    while end < bytes.len() && is_number(bytes[end]) {
+        end += 1;
+    }
+    // a `.` ending the range is the member access of `1.max(2)` or a range `0..`
+    while end > start && (bytes[end - 1] == b'.' || code[start..end].matches('.').count() > 1) {
+        end -= 1;
+    }
+    while start < end && bytes[start] == b'.' {
+        start += 1;
+    }
+    if start == end || !bytes[start..end].iter().any(u8::is_ascii_digit) || !(start..=end).contains(&offset) {
+        return None;
+    }
+    let before = code[..start].chars().next_back();
+    if before.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '.' && !code[..start].ends_with(".."))
+        || code[end..].starts_with(['x', 'X', 'b', 'B', 'o', 'O']) && &code[start..end] == "0"
+    {
+        return None;
+    }
+    // a minus after an operator, a bracket or the line start is the sign of the number
+    let operand = code[..start - usize::from(before == Some('-'))].trim_end().chars().next_back();
+    if before == Some('-') && operand.is_none_or(|c| !(c.is_alphanumeric() || c == '_' || c == ')' || c == ']')) {
+        start -= 1;
+    }
+    Some(start..end)
+}
+
+/// The number `text` moved by `steps` times `step` units of its last digit, with the same count of decimals.
+pub(crate) fn step_number(text: &str, steps: i64, step: f64) -> Option<String> {
+    let Some((_, fraction)) = text.split_once('.') else {
+        // the integers above 2^53 are not exact in a `f64`
+        let value: i128 = text.parse().ok()?;
+        return Some(value.checked_add((steps as f64 * step).round() as i128)?.to_string());
+    };
+    let decimals = fraction.len();
+    let value: f64 = text.parse().ok()?;
+    let unit = 10f64.powi(-(decimals as i32));
+    let moved = value + steps as f64 * step * unit;
+    let text = format!("{moved:.decimals$}");
+    // `-0.0` reads like a typo
+    Some(match text.strip_prefix('-') {
+        Some(unsigned) if unsigned.chars().all(|c| c == '0' || c == '.') => unsigned.to_owned(),
+        _ => text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_number_at_the_offset() {
+        assert_eq!(number_at("x = 1.5;", 5), Some(4..7));
+        assert_eq!(number_at("x = 1.5;", 7), Some(4..7));
+        assert_eq!(number_at("x = 1.5;", 3), None);
+        assert_eq!(number_at("1.5f32", 1), Some(0..3));
+        assert_eq!(number_at("1.max(2)", 0), Some(0..1));
+        assert_eq!(number_at("0..10", 4), Some(3..5));
+    }
+
+    #[test]
+    fn skips_identifiers_and_hex() {
+        assert_eq!(number_at("a1", 2), None);
+        assert_eq!(number_at("0x1F", 0), None);
+        assert_eq!(number_at("0x1F", 3), None);
+    }
+
+    #[test]
+    fn takes_the_sign_after_an_operator() {
+        assert_eq!(number_at("x = -2", 5), Some(4..6));
+        assert_eq!(number_at("f(-2)", 3), Some(2..4));
+        assert_eq!(number_at("a-2", 2), Some(2..3));
+        assert_eq!(number_at("a -2", 3), Some(3..4));
+    }
+
+    #[test]
+    fn steps_the_last_digit() {
+        assert_eq!(step_number("1.5", 1, 1.0).as_deref(), Some("1.6"));
+        assert_eq!(step_number("9", 2, 1.0).as_deref(), Some("11"));
+        assert_eq!(step_number("1.50", -3, 10.0).as_deref(), Some("1.20"));
+        assert_eq!(step_number("0.2", -3, 1.0).as_deref(), Some("-0.1"));
+        assert_eq!(step_number("abc", 1, 1.0), None);
+    }
+
+    #[test]
+    fn steps_the_large_integers_exactly() {
+        assert_eq!(step_number("9007199254740993", 1, 1.0).as_deref(), Some("9007199254740994"));
+        assert_eq!(step_number("-9007199254740993", -1, 1.0).as_deref(), Some("-9007199254740994"));
+        assert_eq!(step_number("18446744073709551615", 5, 1.0).as_deref(), Some("18446744073709551620"));
+    }
+
+    #[test]
+    fn drops_the_sign_of_zero() {
+        assert_eq!(step_number("0.3", -3, 1.0).as_deref(), Some("0.0"));
+        assert_eq!(step_number("-0.1", 1, 1.0).as_deref(), Some("0.0"));
+    }
+}
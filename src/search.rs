@@ -0,0 +1,195 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
+
+/// Query, options and matches of a find and replace in a text.
+///
+/// # Usage
+///
+/// Set the `query` (and the options), then `next(code, offset)` and `previous(code, offset)` return the
+/// byte range of the match after or before `offset`, wrapping around the end of the code.\
+/// `replace(code)` replaces the current match with the `replacement`, `replace_all(code)` every match.\
+/// The matches follow the changes of the code and of the options, they are searched again when needed.
+///
+/// [`CodeEditor`](crate::CodeEditor) has a search state of its own, see `CodeEditor::search_mut`.
+#[derive(Clone, Debug, Default)]
+pub struct SearchState {
+    pub query: String,
+    pub replacement: String,
+    pub case_sensitive: bool,
+    /// Matches only the whole words (the query is not preceded or followed by a letter, a digit or `_`).
+    pub whole_word: bool,
+    matches: Vec<Range<usize>>,
+    current: Option<usize>,
+    /// Hash of the code and the options the matches were found with.
+    key: Option<u64>,
+}
+
+impl SearchState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Searches the `code` again if it or the options changed since the last search.
+    pub fn update(&mut self, code: &str) {
+        let mut hasher = DefaultHasher::new();
+        (code, &self.query, self.case_sensitive, self.whole_word).hash(&mut hasher);
+        let key = hasher.finish();
+        if self.key == Some(key) {
+            return;
+        }
+        let current = self.current.and_then(|i| self.matches.get(i)).map(|range| range.start);
+        self.matches = find_all(code, &self.query, self.case_sensitive, self.whole_word);
+        // the current match stays the one at the same place, if any
+        self.current = current.and_then(|start| self.matches.iter().position(|range| range.start == start));
+        self.key = Some(key);
+    }
+
+    /// Byte ranges of the matches, as of the last search.
+    pub fn matches(&self) -> &[Range<usize>] {
+        &self.matches
+    }
+
+    /// Index of the current match in `matches`.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Makes the first match starting at or after the byte `offset` current, the first one past the end.
+    pub fn next(&mut self, code: &str, offset: usize) -> Option<Range<usize>> {
+        self.update(code);
+        let index = self.matches.iter().position(|range| range.start >= offset).unwrap_or(0);
+        self.select(index)
+    }
+
+    /// Makes the last match ending at or before the byte `offset` current, the last one before the start.
+    pub fn previous(&mut self, code: &str, offset: usize) -> Option<Range<usize>> {
+        self.update(code);
+        let index = self.matches.iter().rposition(|range| range.end <= offset).or(self.matches.len().checked_sub(1))?;
+        self.select(index)
+    }
+
+    fn select(&mut self, index: usize) -> Option<Range<usize>> {
+        let range = self.matches.get(index)?.clone();
+        self.current = Some(index);
+        Some(range)
+    }
+
+    /// Replaces the current match with the `replacement`, returns the byte range of the inserted text.
+    pub fn replace(&mut self, code: &mut String) -> Option<Range<usize>> {
+        self.update(code);
+        let range = self.matches.get(self.current?)?.clone();
+        code.replace_range(range.clone(), &self.replacement);
+        self.current = None;
+        self.update(code);
+        Some(range.start..range.start + self.replacement.len())
+    }
+
+    /// Replaces every match with the `replacement`, returns the number of replaced matches.
+    pub fn replace_all(&mut self, code: &mut String) -> usize {
+        self.update(code);
+        let count = self.matches.len();
+        for range in self.matches.iter().rev() {
+            code.replace_range(range.clone(), &self.replacement);
+        }
+        self.current = None;
+        self.update(code);
+        count
+    }
+}
+
+/// Byte ranges of the non overlapping occurrences of `query` in `code`.
+fn find_all(code: &str, query: &str, case_sensitive: bool, whole_word: bool) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while let Some((found, len)) = find(&code[start..], query, case_sensitive) {
+        let range = start + found..start + found + len;
+        let bounded = !whole_word
+            || (!code[..range.start].chars().next_back().is_some_and(is_word)
+                && !code[range.end..].chars().next().is_some_and(is_word));
+        if bounded {
+            matches.push(range.clone());
+            start = range.end;
+        } else {
+            start = range.start + code[range.start..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    matches
+}
+
+/// Byte offset and length of the first occurrence of `query` in `text`.
+fn find(text: &str, query: &str, case_sensitive: bool) -> Option<(usize, usize)> {
+    if case_sensitive {
+        return text.find(query).map(|offset| (offset, query.len()));
+    }
+    // the lowercase of a char can have another length, so the chars are compared one by one
+    text.char_indices().find_map(|(offset, _)| {
+        let mut chars = text[offset..].char_indices();
+        for q in query.chars() {
+            let (_, c) = chars.next()?;
+            if !c.to_lowercase().eq(q.to_lowercase()) {
+                return None;
+            }
+        }
+        let len = chars.next().map_or(text.len() - offset, |(end, _)| end);
+        Some((offset, len))
+    })
+}
+
+/// Field of the search bar of the editor to focus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SearchField {
+    Query,
+    Replacement,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_all_matches() {
+        assert_eq!(find_all("abcabc", "bc", true, false), vec![1..3, 4..6]);
+        assert_eq!(find_all("aaaa", "aa", true, false), vec![0..2, 2..4]);
+        assert!(find_all("abc", "", true, false).is_empty());
+    }
+
+    #[test]
+    fn ignores_the_case() {
+        assert_eq!(find_all("Foo foo FOO", "foo", false, false), vec![0..3, 4..7, 8..11]);
+        assert_eq!(find_all("Foo foo FOO", "foo", true, false), vec![4..7]);
+        assert_eq!(find_all("ÄPFEL", "äpfel", false, false), vec![0..6]);
+    }
+
+    #[test]
+    fn matches_whole_words() {
+        assert_eq!(find_all("cat concat cat_ cat.", "cat", true, true), vec![0..3, 16..19]);
+    }
+
+    #[test]
+    fn next_and_previous_wrap_around() {
+        let mut search = SearchState::new();
+        search.query = "x".to_owned();
+        let code = "x-x-x";
+        assert_eq!(search.next(code, 1), Some(2..3));
+        assert_eq!(search.next(code, 5), Some(0..1));
+        assert_eq!(search.previous(code, 0), Some(4..5));
+        assert_eq!(search.current(), Some(2));
+    }
+
+    #[test]
+    fn replaces_matches() {
+        let mut search = SearchState::new();
+        search.query = "a".to_owned();
+        search.replacement = "bb".to_owned();
+        let mut code = "a a a".to_owned();
+        search.next(&code, 1);
+        assert_eq!(search.replace(&mut code), Some(2..4));
+        assert_eq!(code, "a bb a");
+        assert_eq!(search.replace_all(&mut code), 2);
+        assert_eq!(code, "bb bb bb");
+    }
+}
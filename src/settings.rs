@@ -0,0 +1,607 @@
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use egui::Ui;
+use egui::style::ScrollAnimation;
+
+use crate::CodeViewer;
+use crate::editor::{EscapeBehavior, IndentStyle, Whitespace, WrapMode};
+use crate::folding::FoldMethod;
+use crate::keymap::Keymap;
+use crate::theme;
+
+/// Options of a [`CodeEditor`](crate::CodeEditor) in one place, to build a settings UI and persist it.
+///
+/// Get the current options with `editor.settings()` and change them at runtime with `editor.apply_settings(&settings)`.
+/// With the `serde` feature the settings are (de)serializable, the missing fields keep their default.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
+pub struct EditorSettings {
+    /// Name of the built-in syntect theme, like `"base16-ocean.dark"`.
+    pub theme: String,
+    /// Size of the monospace font of the code.
+    pub font_size: f32,
+    /// Wraps the lines longer than the editor width, see [`CodeEditor::wrap`](crate::CodeEditor::wrap).
+    pub wrap: bool,
+    /// Where the long lines break, see [`CodeEditor::wrap_mode`](crate::CodeEditor::wrap_mode).
+    pub wrap_mode: WrapMode,
+    /// Wraps at this column instead of the editor width.
+    pub wrap_column: Option<usize>,
+    /// Marks and indents the continuation rows of the wrapped lines.
+    pub wrap_indicators: bool,
+    /// Width of the tab stops in columns.
+    pub tab_size: usize,
+    /// What `Tab` and the automatic indentation insert.
+    pub indent_style: IndentStyle,
+    /// Indents the new lines like the previous one, see [`CodeEditor::auto_indent`](crate::CodeEditor::auto_indent).
+    pub auto_indent: bool,
+    /// Shows the control chars as boxes with their mnemonic.
+    pub control_chars: bool,
+    /// Marks the spaces and the tabs, see [`CodeEditor::show_whitespace`](crate::CodeEditor::show_whitespace).
+    pub show_whitespace: Whitespace,
+    /// Lets the last line scroll to the top of the editor.
+    pub scroll_past_end: bool,
+    /// Animates the scrolling to the caret and to the jump targets.
+    pub smooth_scrolling: bool,
+    /// Paints the background of the theme behind the code.
+    pub theme_background: bool,
+    /// Tints the line of the caret.
+    pub current_line: bool,
+    /// Outlines the bracket at the caret and its match.
+    pub match_brackets: bool,
+    /// Keeps the caret vertically centered.
+    pub typewriter: bool,
+    /// Hides the gutter and the decorations, see [`CodeEditor::zen`](crate::CodeEditor::zen).
+    pub zen: bool,
+    pub line_numbers: bool,
+    /// Shows the fold markers in the gutter.
+    pub folding: bool,
+    pub fold_method: FoldMethod,
+    pub read_only: bool,
+    /// Shows the strip with the diagnostic markers and the quick fix lightbulb.
+    pub diagnostics_gutter: bool,
+    /// Shows the changed lines against the baseline.
+    pub change_markers: bool,
+    pub caret_width: f32,
+    pub caret_blink: bool,
+    /// See [`CodeEditor::lint_indentation`](crate::CodeEditor::lint_indentation).
+    pub lint_indentation: bool,
+    /// See [`CodeEditor::lint_line_length`](crate::CodeEditor::lint_line_length), `None` disables it.
+    pub max_line_length: Option<usize>,
+    /// See [`CodeEditor::insert_final_newline`](crate::CodeEditor::insert_final_newline).
+    pub insert_final_newline: bool,
+    /// See [`CodeEditor::save_on_request`](crate::CodeEditor::save_on_request).
+    pub save_on_request: bool,
+    pub max_undo_entries: usize,
+    pub max_undo_bytes: usize,
+    pub escape_behavior: EscapeBehavior,
+    /// A deserialized keymap has only its listed shortcuts, see [`Keymap::merge`].
+    pub keymap: Keymap,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        Self {
+            theme: "base16-ocean.dark".to_owned(),
+            font_size: 14.0,
+            wrap: true,
+            wrap_mode: WrapMode::default(),
+            wrap_column: None,
+            wrap_indicators: true,
+            tab_size: 4,
+            indent_style: IndentStyle::default(),
+            auto_indent: true,
+            control_chars: true,
+            show_whitespace: Whitespace::None,
+            scroll_past_end: false,
+            smooth_scrolling: true,
+            theme_background: true,
+            current_line: false,
+            match_brackets: true,
+            typewriter: false,
+            zen: false,
+            line_numbers: false,
+            folding: false,
+            fold_method: FoldMethod::default(),
+            read_only: false,
+            diagnostics_gutter: true,
+            change_markers: true,
+            caret_width: 2.0,
+            caret_blink: true,
+            lint_indentation: false,
+            max_line_length: None,
+            insert_final_newline: false,
+            save_on_request: false,
+            max_undo_entries: 100,
+            max_undo_bytes: 16 * 1024 * 1024,
+            escape_behavior: EscapeBehavior::default(),
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+static GLOBAL_DEFAULTS: RwLock<Option<EditorSettings>> = RwLock::new(None);
+/// Incremented by every `set_global_defaults`, so the editors notice the change without locking.
+static GLOBAL_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Sets the settings of the new editors, the existing editors take them too on their next frame.
+///
+/// An editor keeps the options set on it (the theme given to `CodeEditor::new`, the options changed by `apply_settings`,
+/// the builders and the setters), even when set to the default value, the other options follow.
+pub fn set_global_defaults(settings: EditorSettings) {
+    *GLOBAL_DEFAULTS.write().unwrap_or_else(|err| err.into_inner()) = Some(settings);
+    GLOBAL_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The settings given to `set_global_defaults`, `EditorSettings::default()` before.
+pub fn global_defaults() -> EditorSettings {
+    GLOBAL_DEFAULTS.read().unwrap_or_else(|err| err.into_inner()).clone().unwrap_or_default()
+}
+
+static ANIMATIONS: AtomicBool = AtomicBool::new(true);
+
+/// Enables or disables the animations of all the widgets of the crate: the blinking caret, the smooth scrolling
+/// and the fade-in of the popups, enabled by default.
+///
+/// Disable them for the screenshot tests and the screen recordings, so every frame is the same for the same input.
+/// The egui widgets of the app (and the menus of the editors) follow `style.animation_time` instead.
+///
+/// The switch is global to the process, shared by all the egui contexts and threads. The tests run in parallel
+/// by `cargo test` see the changes of each other: set it once before the tests, or hold a lock in the tests
+/// changing it and restore it before releasing the lock.
+pub fn set_animations(enabled: bool) {
+    ANIMATIONS.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the animations are enabled, see [`set_animations`].
+pub fn animations() -> bool {
+    ANIMATIONS.load(Ordering::Relaxed)
+}
+
+static REDUCED_MOTION: AtomicBool = AtomicBool::new(false);
+
+/// Asks for less motion, like the accessibility setting of the OS: the widgets of the crate scroll to their targets
+/// at once and show their popups without fading in. Off by default, the caret still blinks.
+///
+/// Pass the preference of the platform when the integration provides it. Global to the process like
+/// [`set_animations`], prefer a zero `style.animation_time` to reduce the motion of a single context.
+pub fn set_reduced_motion(reduced: bool) {
+    REDUCED_MOTION.store(reduced, Ordering::Relaxed);
+}
+
+/// Whether the motion is reduced, by [`set_reduced_motion`] or by the app disabling the egui animations
+/// with a zero `style.animation_time`.
+pub fn reduced_motion(ctx: &egui::Context) -> bool {
+    REDUCED_MOTION.load(Ordering::Relaxed) || ctx.style().animation_time <= 0.0
+}
+
+/// Animation of the scrolling to a target in `ui`, none when the animations are disabled or the motion is reduced.
+pub(crate) fn scroll_animation(ui: &Ui) -> ScrollAnimation {
+    if animations() && !reduced_motion(ui.ctx()) { ui.style().scroll_animation } else { ScrollAnimation::none() }
+}
+
+/// Whether the popups fade in, not when the animations are disabled or the motion is reduced.
+pub(crate) fn fade_in(ctx: &egui::Context) -> bool {
+    animations() && !reduced_motion(ctx)
+}
+
+/// Generation and value of the global defaults, if they changed since `generation`.
+pub(crate) fn global_defaults_since(generation: u64) -> Option<(u64, EditorSettings)> {
+    let current = GLOBAL_GENERATION.load(Ordering::Relaxed);
+    (current != generation).then(|| (current, global_defaults()))
+}
+
+/// Declares [`Overridden`] and [`inherit`] with a flag per field of [`EditorSettings`].
+macro_rules! overridable {
+    ($($field:ident),* $(,)?) => {
+        /// Options set on one editor (by `CodeEditor::new`, `apply_settings`, the builders or the setters),
+        /// kept when the global defaults change.
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+        pub(crate) struct Overridden {
+            $(pub(crate) $field: bool,)*
+        }
+
+        impl Overridden {
+            /// Marks the options of `new` differing from the `current` ones.
+            pub(crate) fn mark_changes(&mut self, current: &EditorSettings, new: &EditorSettings) {
+                $(self.$field |= current.$field != new.$field;)*
+            }
+        }
+
+        /// `current` with the options not `overridden` on the editor taken from the `defaults`.
+        pub(crate) fn inherit(current: &EditorSettings, overridden: &Overridden, defaults: &EditorSettings) -> EditorSettings {
+            // fails to compile when a field is missing
+            EditorSettings {
+                $($field: if overridden.$field { current.$field.clone() } else { defaults.$field.clone() },)*
+            }
+        }
+    };
+}
+
+overridable!(
+    theme,
+    font_size,
+    wrap,
+    wrap_mode,
+    wrap_column,
+    wrap_indicators,
+    tab_size,
+    indent_style,
+    auto_indent,
+    control_chars,
+    show_whitespace,
+    scroll_past_end,
+    smooth_scrolling,
+    theme_background,
+    current_line,
+    match_brackets,
+    typewriter,
+    zen,
+    line_numbers,
+    folding,
+    fold_method,
+    read_only,
+    diagnostics_gutter,
+    change_markers,
+    caret_width,
+    caret_blink,
+    lint_indentation,
+    max_line_length,
+    insert_final_newline,
+    save_on_request,
+    max_undo_entries,
+    max_undo_bytes,
+    escape_behavior,
+    keymap,
+);
+
+/// Code shown in the theme preview of [`EditorSettingsUi`].
+const PREVIEW_CODE: &str = "// preview\nfn greet(name: &str) -> String {\n    let count = 42;\n    format!(\"Hello, {name}! {count}\")\n}\n";
+
+/// Preferences page editing an [`EditorSettings`].
+///
+/// # Implement
+///
+/// Use `EditorSettingsUi::new()` to create a new instance.\
+/// Then call its `ui` method with the settings, for example `settings_ui.ui(ui, &mut settings)`,
+/// and give them to `editor.apply_settings(&settings)` when it returns `true`.
+#[derive(Debug)]
+pub struct EditorSettingsUi {
+    themes: Vec<String>,
+    preview: Option<CodeViewer>,
+    /// Theme of the preview.
+    preview_theme: String,
+}
+
+impl Default for EditorSettingsUi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EditorSettingsUi {
+    pub fn new() -> Self {
+        Self {
+            themes: theme::built_in_names().map(str::to_owned).collect(),
+            preview: None,
+            preview_theme: String::new(),
+        }
+    }
+
+    /// Shows the controls, returns `true` when a setting changed.
+    ///
+    /// The theme picker shows a preview of the selected theme, the other options are sliders and toggles.
+    pub fn ui(&mut self, ui: &mut Ui, settings: &mut EditorSettings) -> bool {
+        let mut changed = false;
+        egui::Grid::new("editor_settings").num_columns(2).spacing([12.0, 6.0]).show(ui, |ui| {
+            ui.label("Theme");
+            egui::ComboBox::from_id_salt("editor_settings_theme").selected_text(&settings.theme).show_ui(ui, |ui| {
+                for theme in &self.themes {
+                    changed |= ui.selectable_value(&mut settings.theme, theme.clone(), theme).changed();
+                }
+            });
+            ui.end_row();
+
+            ui.label("Font size");
+            changed |= ui.add(egui::Slider::new(&mut settings.font_size, 8.0..=32.0).step_by(1.0)).changed();
+            ui.end_row();
+
+            ui.label("Tab size");
+            changed |= ui.add(egui::DragValue::new(&mut settings.tab_size).range(1..=16)).changed();
+            ui.end_row();
+
+            ui.label("Wrap");
+            egui::ComboBox::from_id_salt("editor_settings_wrap")
+                .selected_text(format!("{:?}", settings.wrap_mode))
+                .show_ui(ui, |ui| {
+                    for mode in [WrapMode::Word, WrapMode::Anywhere] {
+                        changed |= ui.selectable_value(&mut settings.wrap_mode, mode, format!("{mode:?}")).changed();
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Wrap column");
+            ui.horizontal(|ui| {
+                let mut enabled = settings.wrap_column.is_some();
+                if ui.checkbox(&mut enabled, "").changed() {
+                    settings.wrap_column = enabled.then_some(80);
+                    changed = true;
+                }
+                if let Some(column) = &mut settings.wrap_column {
+                    changed |= ui.add(egui::DragValue::new(column).range(20..=1000)).changed();
+                }
+            });
+            ui.end_row();
+
+            ui.label("Indentation");
+            egui::ComboBox::from_id_salt("editor_settings_indent")
+                .selected_text(format!("{:?}", settings.indent_style))
+                .show_ui(ui, |ui| {
+                    let styles = [IndentStyle::Detect, IndentStyle::Tabs, IndentStyle::Spaces(2), IndentStyle::Spaces(4)];
+                    for style in styles {
+                        changed |= ui.selectable_value(&mut settings.indent_style, style, format!("{style:?}")).changed();
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Whitespace");
+            egui::ComboBox::from_id_salt("editor_settings_whitespace")
+                .selected_text(format!("{:?}", settings.show_whitespace))
+                .show_ui(ui, |ui| {
+                    for whitespace in [Whitespace::None, Whitespace::Trailing, Whitespace::All] {
+                        changed |= ui
+                            .selectable_value(&mut settings.show_whitespace, whitespace, format!("{whitespace:?}"))
+                            .changed();
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Caret width");
+            changed |= ui.add(egui::Slider::new(&mut settings.caret_width, 1.0..=4.0).step_by(0.5)).changed();
+            ui.end_row();
+
+            ui.label("Esc");
+            egui::ComboBox::from_id_salt("editor_settings_escape")
+                .selected_text(format!("{:?}", settings.escape_behavior))
+                .show_ui(ui, |ui| {
+                    for behavior in [EscapeBehavior::Staged, EscapeBehavior::KeepFocus, EscapeBehavior::ReleaseFocus] {
+                        changed |= ui
+                            .selectable_value(&mut settings.escape_behavior, behavior, format!("{behavior:?}"))
+                            .changed();
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Folding");
+            egui::ComboBox::from_id_salt("editor_settings_folding")
+                .selected_text(format!("{:?}", settings.fold_method))
+                .show_ui(ui, |ui| {
+                    for method in [FoldMethod::Indentation, FoldMethod::Scopes] {
+                        changed |= ui.selectable_value(&mut settings.fold_method, method, format!("{method:?}")).changed();
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Line length limit");
+            ui.horizontal(|ui| {
+                let mut enabled = settings.max_line_length.is_some();
+                if ui.checkbox(&mut enabled, "").changed() {
+                    settings.max_line_length = enabled.then_some(100);
+                    changed = true;
+                }
+                if let Some(max) = &mut settings.max_line_length {
+                    changed |= ui.add(egui::DragValue::new(max).range(20..=1000)).changed();
+                }
+            });
+            ui.end_row();
+
+            ui.label("Undo steps");
+            changed |= ui.add(egui::DragValue::new(&mut settings.max_undo_entries).range(1..=10_000)).changed();
+            ui.end_row();
+        });
+
+        ui.add_space(4.0);
+        let toggles = [
+            (&mut settings.wrap, "Wrap long lines"),
+            (&mut settings.wrap_indicators, "Wrap indicators"),
+            (&mut settings.line_numbers, "Line numbers"),
+            (&mut settings.control_chars, "Control characters"),
+            (&mut settings.auto_indent, "Auto indent"),
+            (&mut settings.theme_background, "Theme background"),
+            (&mut settings.current_line, "Highlight the current line"),
+            (&mut settings.match_brackets, "Matching brackets"),
+            (&mut settings.typewriter, "Typewriter scrolling"),
+            (&mut settings.scroll_past_end, "Scroll past the end"),
+            (&mut settings.smooth_scrolling, "Smooth scrolling"),
+            (&mut settings.zen, "Zen mode"),
+            (&mut settings.folding, "Fold markers"),
+            (&mut settings.read_only, "Read only"),
+            (&mut settings.diagnostics_gutter, "Diagnostics gutter"),
+            (&mut settings.change_markers, "Change markers"),
+            (&mut settings.caret_blink, "Blinking caret"),
+            (&mut settings.lint_indentation, "Check the indentation"),
+            (&mut settings.insert_final_newline, "Insert a final newline on save"),
+            (&mut settings.save_on_request, "Save on Ctrl+S"),
+        ];
+        for (value, label) in toggles {
+            changed |= ui.checkbox(value, label).changed();
+        }
+
+        ui.add_space(4.0);
+        self.preview_ui(ui, settings);
+        changed
+    }
+
+    fn preview_ui(&mut self, ui: &mut Ui, settings: &EditorSettings) {
+        if self.preview.is_none() || self.preview_theme != settings.theme {
+            if !self.themes.contains(&settings.theme) {
+                return;
+            }
+            let mut viewer = CodeViewer::new("rs", &settings.theme).selectable(false);
+            viewer.code = PREVIEW_CODE.to_owned();
+            self.preview = Some(viewer);
+            self.preview_theme = settings.theme.clone();
+        }
+        if let Some(preview) = &mut self.preview {
+            preview.set_theme_background(settings.theme_background);
+            egui::Frame::canvas(ui.style()).show(ui, |ui| preview.ui(ui));
+        }
+    }
+}
+
+/// Grid of small previews of a sample snippet in every built-in theme, to pick a theme by its look.
+///
+/// # Implement
+///
+/// Use `ThemeGallery::new()` to create a new instance.\
+/// Then call its `ui` method with the current theme, for example `gallery.ui(ui, &settings.theme)`,
+/// it returns the name of the clicked theme.
+#[derive(Debug)]
+pub struct ThemeGallery {
+    /// Themes and their preview, created on the first `ui` call.
+    previews: Vec<(String, CodeViewer)>,
+    preview_width: f32,
+    font_size: f32,
+}
+
+impl Default for ThemeGallery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThemeGallery {
+    pub fn new() -> Self {
+        Self {
+            previews: Vec::new(),
+            preview_width: 220.0,
+            font_size: 8.0,
+        }
+    }
+
+    /// Width of a preview, 220 points by default.
+    pub fn preview_width(mut self, width: f32) -> Self {
+        self.preview_width = width;
+        self
+    }
+
+    /// Font size of the previewed code, 8 by default.
+    pub fn font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self.previews.clear();
+        self
+    }
+
+    /// Shows the previews wrapped in rows, returns the theme clicked this frame.
+    ///
+    /// The `current` theme is framed, [`preview_width`](Self::preview_width) and [`font_size`](Self::font_size)
+    /// set the size of the previews.
+    pub fn ui(&mut self, ui: &mut Ui, current: &str) -> Option<String> {
+        if self.previews.is_empty() {
+            let mut themes: Vec<String> = theme::built_in_names().map(str::to_owned).collect();
+            themes.sort();
+            self.previews = themes
+                .into_iter()
+                .map(|theme| {
+                    let mut viewer = CodeViewer::new("rs", &theme).selectable(false).font_size(self.font_size);
+                    viewer.code = PREVIEW_CODE.to_owned();
+                    (theme, viewer)
+                })
+                .collect();
+        }
+
+        let mut clicked = None;
+        ui.horizontal_wrapped(|ui| {
+            for (theme, preview) in &mut self.previews {
+                let stroke = if theme == current {
+                    ui.visuals().selection.stroke
+                } else {
+                    ui.visuals().widgets.noninteractive.bg_stroke
+                };
+                let frame = egui::Frame::group(ui.style()).stroke(stroke).show(ui, |ui| {
+                    ui.set_width(self.preview_width);
+                    ui.small(theme.as_str());
+                    egui::Frame::canvas(ui.style()).show(ui, |ui| preview.ui(ui));
+                });
+                let response = frame.response.interact(egui::Sense::click()).on_hover_cursor(egui::CursorIcon::PointingHand);
+                if response.clicked() {
+                    clicked = Some(theme.clone());
+                }
+            }
+        });
+        clicked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Held by the tests changing the global motion switches, they would race with the parallel tests.
+    static MOTION: Mutex<()> = Mutex::new(());
+
+    fn scroll_animation_of(ctx: &egui::Context) -> ScrollAnimation {
+        let mut animation = None;
+        let _ = ctx.run(Default::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| animation = Some(scroll_animation(ui)));
+        });
+        animation.unwrap()
+    }
+
+    #[test]
+    fn disables_the_animations() {
+        let _lock = MOTION.lock().unwrap_or_else(|err| err.into_inner());
+        let ctx = egui::Context::default();
+        assert!(animations() && fade_in(&ctx));
+        assert_eq!(scroll_animation_of(&ctx), ctx.style().scroll_animation);
+
+        set_animations(false);
+        let disabled = (animations(), fade_in(&ctx), scroll_animation_of(&ctx));
+        set_animations(true);
+        assert_eq!(disabled, (false, false, ScrollAnimation::none()));
+        // the reduced motion is a separate switch
+        assert!(!reduced_motion(&ctx));
+    }
+
+    #[test]
+    fn reduces_the_motion() {
+        let _lock = MOTION.lock().unwrap_or_else(|err| err.into_inner());
+        let ctx = egui::Context::default();
+        assert!(!reduced_motion(&ctx));
+
+        set_reduced_motion(true);
+        let reduced = (reduced_motion(&ctx), fade_in(&ctx), scroll_animation_of(&ctx));
+        set_reduced_motion(false);
+        assert_eq!(reduced, (true, false, ScrollAnimation::none()));
+        assert!(animations());
+
+        // a context without the egui animations only
+        ctx.style_mut(|style| style.animation_time = 0.0);
+        assert!(reduced_motion(&ctx) && !fade_in(&ctx));
+        assert!(!reduced_motion(&egui::Context::default()));
+    }
+
+    #[test]
+    fn editors_keep_their_options_and_follow_the_other_defaults() {
+        let ctx = egui::Context::default();
+        let mut editor = crate::CodeEditor::new("rs", "base16-ocean.dark");
+        // set to the default value, still kept
+        editor.set_max_undo_entries(EditorSettings::default().max_undo_entries);
+
+        // only the undo limits change, the parallel tests do not depend on them
+        set_global_defaults(EditorSettings { max_undo_entries: 10, max_undo_bytes: 1 << 20, ..EditorSettings::default() });
+        let _ = ctx.run(Default::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| editor.show(ui));
+        });
+        let kept = editor.settings();
+        let new = crate::CodeEditor::new("rs", "base16-ocean.dark").settings();
+        set_global_defaults(EditorSettings::default());
+
+        assert_eq!((kept.max_undo_entries, kept.max_undo_bytes), (100, 1 << 20));
+        assert_eq!((new.max_undo_entries, new.max_undo_bytes), (10, 1 << 20));
+        assert_eq!(kept.theme, "base16-ocean.dark");
+    }
+}
+
@@ -0,0 +1,114 @@
+use std::ops::Range;
+
+use egui::text::LayoutJob;
+use egui::{Color32, FontId, TextFormat};
+
+use crate::fonts;
+
+/// Signature of the called function, shown above the caret.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SignatureHelp {
+    /// Whole signature, like `fn add(a: i32, b: i32) -> i32`.
+    pub label: String,
+    /// Byte ranges of the parameters in the `label`.
+    pub parameters: Vec<Range<usize>>,
+    /// Index in the `parameters` of the parameter shown in bold.
+    ///
+    /// The bold is the bold face of the installed [`CodeFont`](crate::CodeFont), without the code font
+    /// the parameter is only in the strong text color.
+    pub active_parameter: Option<usize>,
+    pub documentation: Option<String>,
+}
+
+impl SignatureHelp {
+    /// Job of the label, the active parameter in bold (when the bold code font is installed) and in `active_color`.
+    pub(crate) fn layout_job(&self, font: &FontId, color: Color32, active_color: Color32) -> LayoutJob {
+        let format = TextFormat::simple(font.clone(), color);
+        let mut job = LayoutJob::default();
+        // a range of the provider out of the label, reversed or inside a char shows the plain label
+        let active = self.active_parameter.and_then(|i| self.parameters.get(i)).filter(|range| {
+            range.start <= range.end
+                && range.end <= self.label.len()
+                && self.label.is_char_boundary(range.start)
+                && self.label.is_char_boundary(range.end)
+        });
+
+        match active {
+            Some(range) => {
+                job.append(&self.label[..range.start], 0.0, format.clone());
+                job.append(
+                    &self.label[range.clone()],
+                    0.0,
+                    TextFormat {
+                        font_id: fonts::bold_font(font).unwrap_or_else(|| font.clone()),
+                        color: active_color,
+                        ..format.clone()
+                    },
+                );
+                job.append(&self.label[range.end..], 0.0, format);
+            }
+            _ => job.append(&self.label, 0.0, format),
+        }
+        job
+    }
+}
+
+/// Provides the signature help of the [`CodeEditor`](crate::CodeEditor).
+///
+/// # Implement
+///
+/// Implement `signature_help` returning the signature of the call around the byte `offset`
+/// of the `code` and set it with `CodeEditor::signature_help_provider`.
+/// It is called when `(` or `,` is typed.
+pub trait SignatureHelpProvider: Send {
+    fn signature_help(&mut self, code: &str, offset: usize) -> Option<SignatureHelp>;
+}
+
+impl<F> SignatureHelpProvider for F
+where
+    F: FnMut(&str, usize) -> Option<SignatureHelp> + Send,
+{
+    fn signature_help(&mut self, code: &str, offset: usize) -> Option<SignatureHelp> {
+        self(code, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn help(parameters: Vec<Range<usize>>) -> SignatureHelp {
+        SignatureHelp {
+            label: "fn add(a: i32, b: i32)".to_owned(),
+            parameters,
+            active_parameter: Some(1),
+            documentation: None,
+        }
+    }
+
+    #[test]
+    fn bolds_the_active_parameter() {
+        let font = FontId::new(13.0, egui::FontFamily::Name(fonts::CODE_FAMILY.into()));
+        let job = help(vec![7..13, 15..21]).layout_job(&font, Color32::GRAY, Color32::WHITE);
+        let active = &job.sections[1];
+        assert_eq!(&job.text[active.byte_range.clone()], "b: i32");
+        assert_eq!(active.format.font_id, fonts::bold_font(&font).unwrap());
+        assert_eq!(active.format.color, Color32::WHITE);
+    }
+
+    #[test]
+    fn colors_the_active_parameter_without_the_bold_font() {
+        let font = FontId::monospace(13.0);
+        let job = help(vec![7..13, 15..21]).layout_job(&font, Color32::GRAY, Color32::WHITE);
+        assert_eq!(job.sections[1].format.font_id, font);
+        assert_eq!(job.sections[1].format.color, Color32::WHITE);
+    }
+
+    #[test]
+    fn shows_the_plain_label_for_invalid_parameters() {
+        let font = FontId::monospace(13.0);
+        #[allow(clippy::reversed_empty_ranges)]
+        let job = help(vec![7..13, 21..15]).layout_job(&font, Color32::GRAY, Color32::WHITE);
+        assert_eq!(job.sections.len(), 1);
+    }
+}
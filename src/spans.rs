@@ -0,0 +1,48 @@
+use std::ops::Range;
+
+use egui::Color32;
+use syntect::highlighting::Theme;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+use crate::error::LitecodeError;
+use crate::language::Injection;
+use crate::{highlight, syntaxes, theme};
+
+/// Highlighted piece of a text, as shown by the editor and the viewer.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    /// Byte range of the piece in the text, the spans cover the whole text in order.
+    pub range: Range<usize>,
+    /// Color of the piece, `None` for the default foreground (the one of the theme, or the text color of the app).
+    pub color: Option<Color32>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+/// Highlights `text` like the widgets do, without a `Ui` or a `Context`, for the tests and the server-side tools.
+///
+/// The regions of the `injections` are highlighted with their embedded syntax, like with
+/// [`CodeEditor::injections`](crate::CodeEditor::injections), pass `&[]` for none.\
+/// The lines whose highlighting fails (broken grammar) are one plain span, the chars of the lines beyond
+/// the first 10 000 are plain too.
+pub fn highlight(
+    text: &str,
+    syntax: &SyntaxReference,
+    syntax_set: &SyntaxSet,
+    theme: &Theme,
+    injections: &[Injection],
+) -> Vec<Span> {
+    highlight::spans(text, syntax, syntax_set, theme, highlight::LINE_LIMIT, injections).0
+}
+
+/// Like [`highlight`], with the built-in syntax of the files with the extension `syntax_ext`
+/// and the built-in theme `color_theme`, without injections.
+pub fn highlight_by_extension(text: &str, syntax_ext: &str, color_theme: &str) -> Result<Vec<Span>, LitecodeError> {
+    let syntax_set = syntaxes::defaults();
+    let syntax = syntax_set
+        .find_syntax_by_extension(syntax_ext)
+        .ok_or_else(|| LitecodeError::UnknownSyntax(syntax_ext.to_owned()))?;
+    let theme = theme::built_in(color_theme).ok_or_else(|| LitecodeError::UnknownTheme(color_theme.to_owned()))?;
+    Ok(highlight(text, syntax, &syntax_set, &theme, &[]))
+}
@@ -0,0 +1,55 @@
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+use crate::error::LitecodeError;
+
+/// Built-in syntaxes of syntect, loaded once and shared by all the widgets.
+static DEFAULTS: LazyLock<Arc<SyntaxSet>> = LazyLock::new(|| Arc::new(SyntaxSet::load_defaults_newlines()));
+
+pub(crate) fn defaults() -> Arc<SyntaxSet> {
+    DEFAULTS.clone()
+}
+
+/// `syntax_set` with the `.sublime-syntax` files of `folder` (and its subfolders) added.
+pub(crate) fn add_folder(syntax_set: &SyntaxSet, folder: &Path) -> Result<SyntaxSet, LitecodeError> {
+    let mut builder = syntax_set.clone().into_builder();
+    builder
+        .add_from_folder(folder, true)
+        .map_err(|err| LitecodeError::LoadSyntax(err.to_string()))?;
+    Ok(builder.build())
+}
+
+/// `syntax_set` with a "Plain Text" syntax, the fallback of the lookups.
+pub(crate) fn with_plain_text(syntax_set: Arc<SyntaxSet>) -> Arc<SyntaxSet> {
+    if syntax_set.find_syntax_by_name("Plain Text").is_some() {
+        return syntax_set;
+    }
+    let mut builder = Arc::unwrap_or_clone(syntax_set).into_builder();
+    builder.add_plain_text_syntax();
+    Arc::new(builder.build())
+}
+
+/// Index of the syntax of a new set: the one of the `extension` missing in the previous set, then the one named `name`,
+/// then the plain text.
+///
+/// The syntaxes refer to the contexts of their set, so they are always looked up again in the new set.
+pub(crate) fn find(syntax_set: &SyntaxSet, extension: Option<&str>, name: &str) -> usize {
+    let syntax = extension
+        .and_then(|ext| syntax_set.find_syntax_by_extension(ext))
+        .or_else(|| syntax_set.find_syntax_by_name(name))
+        .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
+    index_of(syntax_set, syntax)
+}
+
+/// Index of `syntax`, found by a lookup in `syntax_set`.
+///
+/// The widgets keep the index of their syntax instead of a reference, so they can own their set.
+pub(crate) fn index_of(syntax_set: &SyntaxSet, syntax: &SyntaxReference) -> usize {
+    syntax_set
+        .syntaxes()
+        .iter()
+        .position(|s| std::ptr::eq(s, syntax))
+        .expect("syntax of the set")
+}
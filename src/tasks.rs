@@ -0,0 +1,119 @@
+use std::ops::Range;
+
+use egui::Ui;
+
+use crate::CodeEditor;
+use crate::rename::is_word_char;
+
+/// Markers found by [`TaskPanel`] when no other keywords are set.
+pub const DEFAULT_KEYWORDS: &[&str] = &["TODO", "FIXME", "HACK", "XXX"];
+
+/// `TODO`-like marker in the code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Task {
+    pub keyword: String,
+    /// Byte range of the keyword.
+    pub range: Range<usize>,
+    /// 0-based line of the keyword.
+    pub line: usize,
+    /// Rest of the line after the keyword.
+    pub text: String,
+}
+
+/// Finds the `keywords` written in upper case as whole words, for example `// TODO: remove`.
+pub fn find_tasks(code: &str, keywords: &[&str]) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    let mut line_start = 0;
+    for (line, text) in code.split_inclusive('\n').enumerate() {
+        for keyword in keywords.iter().filter(|k| !k.is_empty()) {
+            for (i, _) in text.match_indices(keyword) {
+                let end = i + keyword.len();
+                let bounded = !text[..i].ends_with(is_word_char) && !text[end..].starts_with(is_word_char);
+                if !bounded {
+                    continue;
+                }
+                let rest = text[end..]
+                    .trim_end()
+                    .trim_end_matches("*/")
+                    .trim_end_matches("-->")
+                    .trim_start_matches(':')
+                    .trim();
+                tasks.push(Task {
+                    keyword: (*keyword).to_owned(),
+                    range: line_start + i..line_start + end,
+                    line,
+                    text: rest.to_owned(),
+                });
+            }
+        }
+        line_start += text.len();
+    }
+    tasks.sort_by_key(|task| task.range.start);
+    tasks
+}
+
+/// List of the `TODO`, `FIXME`, ... markers of one or more editors.
+///
+/// # Implement
+///
+/// Use `TaskPanel::new()` to create a new instance.\
+/// Then call its `ui` method with the named editors, for example `panel.ui(ui, &mut [("main.rs", &mut editor)])`.
+///
+/// # Usage
+///
+/// Use `TaskPanel::new().keywords(["TODO", "NOTE"])` to search other markers than [`DEFAULT_KEYWORDS`].\
+/// Clicking a task moves the caret of its editor there, `ui` returns the index of that editor
+/// so the application can bring it to front.
+#[derive(Clone, Debug)]
+pub struct TaskPanel {
+    keywords: Vec<String>,
+}
+
+impl Default for TaskPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TaskPanel {
+    pub fn new() -> Self {
+        Self {
+            keywords: DEFAULT_KEYWORDS.iter().map(|k| (*k).to_owned()).collect(),
+        }
+    }
+
+    pub fn keywords(mut self, keywords: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.keywords = keywords.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Shows the panel, returns the index of the editor whose task was clicked.
+    pub fn ui(&mut self, ui: &mut Ui, editors: &mut [(&str, &mut CodeEditor)]) -> Option<usize> {
+        let keywords: Vec<&str> = self.keywords.iter().map(String::as_str).collect();
+        let tasks: Vec<Vec<Task>> = editors.iter().map(|(_, editor)| find_tasks(&editor.code, &keywords)).collect();
+
+        ui.label(format!("{} tasks", tasks.iter().map(Vec::len).sum::<usize>()));
+        ui.separator();
+
+        let mut clicked = None;
+        egui::ScrollArea::vertical().id_salt("tasks").auto_shrink([false, true]).show(ui, |ui| {
+            for (index, ((name, editor), tasks)) in editors.iter_mut().zip(tasks).enumerate() {
+                for (i, task) in tasks.into_iter().enumerate() {
+                    let row = ui.horizontal(|ui| {
+                        ui.strong(&task.keyword);
+                        ui.label(&task.text);
+                        ui.weak(format!("{name}:{}", task.line + 1));
+                    });
+                    let row = ui
+                        .interact(row.response.rect, ui.id().with((index, i)), egui::Sense::click())
+                        .on_hover_cursor(egui::CursorIcon::PointingHand);
+                    if row.clicked() {
+                        editor.go_to_offset(task.range.start);
+                        clicked = Some(index);
+                    }
+                }
+            }
+        });
+        clicked
+    }
+}
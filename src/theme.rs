@@ -0,0 +1,36 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+
+use syntect::highlighting::{Theme, ThemeSet};
+
+use crate::error::LitecodeError;
+
+/// Built-in themes of syntect, loaded once and shared by all the widgets.
+static BUILT_IN: LazyLock<BTreeMap<String, Arc<Theme>>> = LazyLock::new(|| {
+    ThemeSet::load_defaults().themes.into_iter().map(|(name, theme)| (name, Arc::new(theme))).collect()
+});
+
+/// Built-in theme `name`, like `"base16-ocean.dark"`.
+pub(crate) fn built_in(name: &str) -> Option<Arc<Theme>> {
+    BUILT_IN.get(name).cloned()
+}
+
+/// Names of the built-in themes, sorted.
+pub(crate) fn built_in_names() -> impl Iterator<Item = &'static str> {
+    BUILT_IN.keys().map(String::as_str)
+}
+
+/// Reads a `.tmTheme` color theme from the content of the file, for example embedded with `include_bytes!`.
+///
+/// Give it to [`CodeEditor::with_theme`](crate::CodeEditor::with_theme) or [`CodeViewer::with_theme`](crate::CodeViewer::with_theme).
+pub fn theme_from_bytes(bytes: &[u8]) -> Result<Theme, LitecodeError> {
+    ThemeSet::load_from_reader(&mut Cursor::new(bytes)).map_err(|err| LitecodeError::LoadTheme(err.to_string()))
+}
+
+/// Reads a `.tmTheme` color theme from the file at `path`, see [`theme_from_bytes`].
+pub fn theme_from_file(path: impl AsRef<Path>) -> Result<Theme, LitecodeError> {
+    let path = path.as_ref();
+    ThemeSet::get_theme(path).map_err(|err| LitecodeError::LoadTheme(format!("{}: {err}", path.display())))
+}
@@ -0,0 +1,72 @@
+use std::ops::Range;
+
+use crate::rename;
+
+/// Built-in pairs of [`CodeEditor::toggle_literal`](crate::CodeEditor::toggle_literal), in the case of the common
+/// languages and config formats.
+pub(crate) const DEFAULT_PAIRS: [(&str, &str); 10] = [
+    ("true", "false"),
+    ("True", "False"),
+    ("TRUE", "FALSE"),
+    ("Some", "None"),
+    ("on", "off"),
+    ("On", "Off"),
+    ("ON", "OFF"),
+    ("yes", "no"),
+    ("Yes", "No"),
+    ("YES", "NO"),
+];
+
+/// Byte range of the word of `code` at the byte `offset` found in the `pairs`, and the other word of its pair.
+///
+/// The words are matched whole and with their case, the first pair with the word wins.
+pub(crate) fn toggled_word<'a>(
+    code: &str,
+    offset: usize,
+    pairs: &'a [(String, String)],
+    is_word: impl Fn(char) -> bool,
+) -> Option<(Range<usize>, &'a str)> {
+    let range = rename::word_at(code, offset, is_word)?;
+    let word = &code[range.clone()];
+    pairs.iter().find_map(|(a, b)| {
+        let other = if word == a {
+            b
+        } else if word == b {
+            a
+        } else {
+            return None;
+        };
+        Some((range.clone(), other.as_str()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toggled(code: &str, offset: usize) -> Option<(Range<usize>, String)> {
+        let pairs: Vec<(String, String)> = DEFAULT_PAIRS.iter().map(|&(a, b)| (a.to_owned(), b.to_owned())).collect();
+        toggled_word(code, offset, &pairs, |c| c.is_alphanumeric() || c == '_')
+            .map(|(range, word)| (range, word.to_owned()))
+    }
+
+    #[test]
+    fn toggles_both_words_of_a_pair() {
+        assert_eq!(toggled("x = true;", 5), Some((4..8, "false".to_owned())));
+        assert_eq!(toggled("x = false;", 9), Some((4..9, "true".to_owned())));
+        assert_eq!(toggled("Some(1)", 0), Some((0..4, "None".to_owned())));
+    }
+
+    #[test]
+    fn matches_the_case() {
+        assert_eq!(toggled("TRUE", 1), Some((0..4, "FALSE".to_owned())));
+        assert_eq!(toggled("tRUE", 1), None);
+    }
+
+    #[test]
+    fn matches_whole_words() {
+        assert_eq!(toggled("trueish", 2), None);
+        assert_eq!(toggled("is_on", 4), None);
+        assert_eq!(toggled("x = 1", 4), None);
+    }
+}
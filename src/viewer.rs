@@ -1,24 +1,76 @@
-use egui::{Color32, FontId, Galley, TextEdit, TextFormat, Ui};
-use egui::text::LayoutJob;
+use egui::{Color32, Galley, TextEdit, Ui};
+use std::borrow::Cow;
 use std::fmt;
+use std::ops::Range;
+use std::path::Path;
 use std::sync::Arc;
-use syntect::easy::HighlightLines;
-use syntect::highlighting::{Theme, ThemeSet, Style};
+use syntect::highlighting::Theme;
 use syntect::parsing::{SyntaxSet, SyntaxReference};
 
+use crate::cancel::CancelToken;
+use crate::control;
+use crate::error::LitecodeError;
+use crate::fonts;
+use crate::gutter::{self, LineNumbers};
+use crate::highlight::{self, GalleyCache, LineCache};
+use crate::language::Injection;
+use crate::layout;
+use crate::presentation::{self, Presentation};
+use crate::print::{self, PrintStyle};
+use crate::settings;
+use crate::spans::Span;
+use crate::syntaxes;
+use crate::theme;
+use crate::watermark;
+
+/// Lines highlighted per frame at most by the virtualized layout, the rows below them stay plain until the next frames.
+const HIGHLIGHT_BUDGET: usize = 1_000;
+
 /// Basical code viewer widget for [egui](https://crates.io/crates/egui), supporting syntax highlighting and themes.
 /// 
 /// # Implement
 /// 
 /// Use `CodeEditor::new(syntax_ext, color_theme)` to create a new instance.\
 /// Set the `code` field to the code you want to display.\
-/// Then use call `ui` method to integrate it into your egui application.
+/// Then use call `ui` method, or `ui.add(&mut myviewer)`, to integrate it into your egui application.
 pub struct CodeViewer {
     pub code: String,
-    syntax_set: SyntaxSet,
+    syntax_set: Arc<SyntaxSet>,
     theme: Arc<Theme>,
-    syntax: &'static SyntaxReference,
-    highlighter: Option<HighlightLines<'static>>,
+    /// Index of the syntax in `syntax_set`, see [`syntaxes::index_of`].
+    syntax_index: usize,
+    /// Extension given to `new` without a syntax, looked up again in the syntax sets given later.
+    missing_extension: Option<String>,
+    cancel: CancelToken,
+    selectable: bool,
+    font_size: f32,
+    wrap: bool,
+    tab_size: usize,
+    control_chars: bool,
+    theme_background: bool,
+    highlight_line_limit: usize,
+    injections: Vec<Injection>,
+    truncate_lines: Option<usize>,
+    max_collapsed_lines: Option<usize>,
+    expanded: bool,
+    virtualized: bool,
+    line_numbers: bool,
+    line_numbers_style: LineNumbers,
+    watermark: Option<String>,
+    presentation: Option<Presentation>,
+    /// Index of the focused range of the presentation.
+    focus_step: usize,
+    /// The focused range changed, it is scrolled into view.
+    reveal_focus: bool,
+    on_line_click: Option<Box<dyn FnMut(Range<usize>) + Send>>,
+    anchor_line: Option<usize>,
+    permalink: Option<Box<dyn FnMut(Range<usize>) -> String + Send>>,
+    /// Lines of the last click on the line numbers.
+    clicked_lines: Option<Range<usize>>,
+    /// Lines of the open context menu of the line numbers.
+    menu_lines: Option<Range<usize>>,
+    galley_cache: GalleyCache,
+    line_cache: LineCache,
 }
 
 impl Clone for CodeViewer {
@@ -27,20 +79,60 @@ impl Clone for CodeViewer {
             code: self.code.clone(),
             syntax_set: self.syntax_set.clone(),
             theme: self.theme.clone(),
-            syntax: self.syntax, // static reference, just copy
-            highlighter: None,   // do not clone highlighter
+            syntax_index: self.syntax_index,
+            missing_extension: self.missing_extension.clone(),
+            cancel: CancelToken::new(),
+            selectable: self.selectable,
+            font_size: self.font_size,
+            wrap: self.wrap,
+            tab_size: self.tab_size,
+            control_chars: self.control_chars,
+            theme_background: self.theme_background,
+            highlight_line_limit: self.highlight_line_limit,
+            injections: self.injections.clone(),
+            truncate_lines: self.truncate_lines,
+            max_collapsed_lines: self.max_collapsed_lines,
+            expanded: self.expanded,
+            virtualized: self.virtualized,
+            line_numbers: self.line_numbers,
+            line_numbers_style: self.line_numbers_style.clone(),
+            watermark: self.watermark.clone(),
+            presentation: self.presentation.clone(),
+            focus_step: self.focus_step,
+            reveal_focus: false,
+            on_line_click: None, // callback is not cloned
+            anchor_line: None,
+            permalink: None, // callback is not cloned
+            clicked_lines: None,
+            menu_lines: None,
+            galley_cache: GalleyCache::default(),
+            line_cache: LineCache::default(),
         }
     }
 }
 
 impl fmt::Debug for CodeViewer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("CodeEditor")
+        f.debug_struct("CodeViewer")
             .field("code", &self.code)
             .field("syntax_set", &"...")
             .field("theme", &"...")
-            .field("syntax", &self.syntax.name)
-            .field("highlighter", &self.highlighter.is_some())
+            .field("syntax", &self.syntax().name)
+            .field("cancel", &self.cancel.is_cancelled())
+            .field("selectable", &self.selectable)
+            .field("wrap", &self.wrap)
+            .field("control_chars", &self.control_chars)
+            .field("theme_background", &self.theme_background)
+            .field("truncate_lines", &self.truncate_lines)
+            .field("max_collapsed_lines", &self.max_collapsed_lines)
+            .field("expanded", &self.expanded)
+            .field("virtualized", &self.virtualized)
+            .field("line_numbers", &self.line_numbers)
+            .field("watermark", &self.watermark)
+            .field("presentation", &self.presentation)
+            .field("focus_step", &self.focus_step)
+            .field("on_line_click", &self.on_line_click.is_some())
+            .field("permalink", &self.permalink.is_some())
             .finish()
     }
 }
@@ -49,102 +141,767 @@ impl PartialEq for CodeViewer {
     fn eq(&self, other: &Self) -> bool {
         self.code == other.code
             && self.theme == other.theme
-            && std::ptr::eq(self.syntax, other.syntax)
+            && Arc::ptr_eq(&self.syntax_set, &other.syntax_set)
+            && self.syntax_index == other.syntax_index
     }
 }
 
 impl CodeViewer {
+    /// Like [`try_new`](Self::try_new), but the unknown extensions are highlighted as Rust.
+    ///
+    /// # Panics
+    ///
+    /// If there is no built-in theme `color_theme`.
     pub fn new(syntax_ext: &str, color_theme: &str) -> Self {
-        let ps = SyntaxSet::load_defaults_newlines();
-        let ts = ThemeSet::load_defaults();
-        let theme = Arc::new(ts.themes[color_theme].clone());
-        let syntax = ps.find_syntax_by_extension(syntax_ext).unwrap_or(ps.find_syntax_by_extension("rs").unwrap());
+        Self::create(syntax_ext, color_theme, true).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a viewer for the files with the extension `syntax_ext`, in the built-in theme `color_theme`.
+    pub fn try_new(syntax_ext: &str, color_theme: &str) -> Result<Self, LitecodeError> {
+        Self::create(syntax_ext, color_theme, false)
+    }
 
-        Self {
+    fn create(syntax_ext: &str, color_theme: &str, fallback_syntax: bool) -> Result<Self, LitecodeError> {
+        let ps = syntaxes::defaults();
+        let theme = theme::built_in(color_theme).ok_or_else(|| LitecodeError::UnknownTheme(color_theme.to_owned()))?;
+        let missing_extension = ps.find_syntax_by_extension(syntax_ext).is_none().then(|| syntax_ext.to_owned());
+        let syntax = match ps.find_syntax_by_extension(syntax_ext) {
+            Some(syntax) => syntax,
+            None if fallback_syntax => ps.find_syntax_by_extension("rs").expect("built-in Rust syntax"),
+            None => return Err(LitecodeError::UnknownSyntax(syntax_ext.to_owned())),
+        };
+
+        let syntax_index = syntaxes::index_of(&ps, syntax);
+
+        Ok(Self {
             code: "".into(),
             syntax_set: ps.clone(),
             theme,
-            syntax: Box::leak(Box::new(syntax.clone())), // static lifetime workaround
-            highlighter: None,
+            syntax_index,
+            missing_extension,
+            cancel: CancelToken::new(),
+            selectable: true,
+            font_size: 14.0,
+            wrap: true,
+            tab_size: 4,
+            control_chars: true,
+            theme_background: true,
+            highlight_line_limit: highlight::LINE_LIMIT,
+            injections: Vec::new(),
+            truncate_lines: None,
+            max_collapsed_lines: None,
+            expanded: false,
+            virtualized: false,
+            line_numbers: false,
+            line_numbers_style: LineNumbers::default(),
+            watermark: None,
+            presentation: None,
+            focus_step: 0,
+            reveal_focus: false,
+            on_line_click: None,
+            anchor_line: None,
+            permalink: None,
+            clicked_lines: None,
+            menu_lines: None,
+            galley_cache: GalleyCache::default(),
+            line_cache: LineCache::default(),
+        })
+    }
+
+    /// Highlights the code with the syntaxes of `syntax_set` instead of the syntect defaults, for example with GLSL or Zig.
+    ///
+    /// The set must be built for the lines with their newline, like `SyntaxSet::load_defaults_newlines()`,
+    /// an `Arc<SyntaxSet>` can be shared by many widgets. The syntax is looked up again in the new set: the extension given to `new` if it had no syntax,
+    /// then the name of the current syntax, then the plain text.
+    pub fn with_syntax_set(mut self, syntax_set: impl Into<Arc<SyntaxSet>>) -> Self {
+        let syntax_set = syntaxes::with_plain_text(syntax_set.into());
+        self.syntax_index = syntaxes::find(&syntax_set, self.missing_extension.as_deref(), &self.syntax().name);
+        if self.missing_extension.as_deref().is_some_and(|ext| syntax_set.find_syntax_by_extension(ext).is_some()) {
+            self.missing_extension = None;
         }
+        self.syntax_set = syntax_set;
+        self.invalidate_highlight();
+        self
+    }
+
+    fn syntax(&self) -> &SyntaxReference {
+        &self.syntax_set.syntaxes()[self.syntax_index]
+    }
+
+    /// Adds the `.sublime-syntax` definitions of `folder` and its subfolders to the syntaxes of the viewer,
+    /// see [`with_syntax_set`](Self::with_syntax_set).
+    pub fn add_syntax_from_folder(self, folder: impl AsRef<Path>) -> Result<Self, LitecodeError> {
+        let syntax_set = syntaxes::add_folder(&self.syntax_set, folder.as_ref())?;
+        Ok(self.with_syntax_set(syntax_set))
+    }
+
+    /// Highlights the code with a color theme of the application, like one read by [`theme_from_bytes`](crate::theme_from_bytes).
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = Arc::new(theme);
+        self.invalidate_highlight();
+        self
+    }
+
+    /// Allows to select the code with the mouse or the keyboard and to copy it with `Ctrl+C`, enabled by default.\
+    /// Disable it for the purely decorative snippets.
+    pub fn selectable(mut self, selectable: bool) -> Self {
+        self.selectable = selectable;
+        self
+    }
+
+    /// Size of the monospace font of the code, 14 by default.
+    pub fn font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    /// Wraps the long lines, enabled by default.\
+    /// When disabled the code is shown in a horizontal scroll area, see also [`truncate_lines`](Self::truncate_lines).
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Width of the tab stops in columns, 4 by default.
+    pub fn tab_size(mut self, tab_size: usize) -> Self {
+        self.tab_size = tab_size;
+        self
+    }
+
+    /// Shows the control chars (like `\x00` or `\x1b`) as boxes with their mnemonic (`NUL`, `ESC`), enabled by default.
+    pub fn control_chars(mut self, control_chars: bool) -> Self {
+        self.control_chars = control_chars;
+        self
+    }
+
+    /// Paints the background of the theme behind the code and the line numbers, enabled by default.
+    pub fn theme_background(mut self, theme_background: bool) -> Self {
+        self.theme_background = theme_background;
+        self
+    }
+
+    /// Like [`theme_background`](Self::theme_background), for a viewer already built.
+    pub fn set_theme_background(&mut self, theme_background: bool) {
+        self.theme_background = theme_background;
+    }
+
+    /// Highlights only the first `chars` chars of every line, 10 000 by default.
+    ///
+    /// The rest of the longer lines (minified code, base64 blobs) is plain with a faint underline,
+    /// so a single huge line does not slow down every frame.
+    pub fn highlight_line_limit(mut self, chars: usize) -> Self {
+        self.highlight_line_limit = chars;
+        self
+    }
+
+    /// Highlights the languages embedded in the regions of the code with their syntaxes, like SQL in the strings,
+    /// see [`Injection`].
+    pub fn injections(mut self, injections: Vec<Injection>) -> Self {
+        self.injections = injections;
+        self
+    }
+
+    /// Cuts the lines longer than `max_chars` characters and adds an ellipsis, so very long lines do not break
+    /// the layout. The "Expand" button below the code shows them whole.
+    pub fn truncate_lines(mut self, max_chars: usize) -> Self {
+        self.truncate_lines = Some(max_chars);
+        self
+    }
+
+    /// Shows only the first `n` lines of a long snippet fading out, with a "Show more" button expanding the code in place.
+    pub fn max_collapsed_lines(mut self, n: usize) -> Self {
+        self.max_collapsed_lines = Some(n);
+        self
+    }
+
+    /// Highlights and lays out only the visible lines, in a scroll area filling the available space.
+    ///
+    /// Keeps the scrolling smooth on multi-megabyte files: the highlighting advances up to the visible lines
+    /// (1000 lines per frame at most, the lines below are plain meanwhile), and each visible line is laid out alone.
+    /// The lines are not wrapped nor selectable, and `max_collapsed_lines` and `truncate_lines` are ignored.
+    pub fn virtualized(mut self, virtualized: bool) -> Self {
+        self.virtualized = virtualized;
+        self
+    }
+
+    /// Shows the line numbers to the left of the code, see [`on_line_click`](Self::on_line_click) to react on their clicks.
+    pub fn line_numbers(mut self, line_numbers: bool) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    /// Width, alignment and colors of the line numbers.
+    pub fn line_numbers_style(mut self, style: LineNumbers) -> Self {
+        self.line_numbers_style = style;
+        self
+    }
+
+    /// Stamps `text` dimmed across the code, like "GENERATED — DO NOT EDIT".
+    pub fn watermark(mut self, text: impl Into<String>) -> Self {
+        self.watermark = Some(text.into());
+        self
+    }
+
+    /// Shows the code for a talk or a lesson: in a larger font, not selectable, with the lines out of the focused range
+    /// of `presentation` dimmed. The focus starts on the first range, the arrow keys step through the ranges,
+    /// see [`set_focus_step`](Self::set_focus_step).
+    pub fn presentation(mut self, presentation: Presentation) -> Self {
+        self.set_presentation(Some(presentation));
+        self
+    }
+
+    /// Starts (or ends with `None`) the presentation, see [`presentation`](Self::presentation).
+    pub fn set_presentation(&mut self, presentation: Option<Presentation>) {
+        self.presentation = presentation;
+        self.focus_step = 0;
+        self.reveal_focus = true;
+    }
+
+    /// Lines of the focused range of the presentation, `None` without a presentation.
+    pub fn focused_lines(&self) -> Option<Range<usize>> {
+        self.presentation.as_ref()?.ranges.get(self.focus_step).cloned()
+    }
+
+    /// Index of the focused range of the presentation.
+    pub fn focus_step(&self) -> usize {
+        self.focus_step
+    }
+
+    /// Focuses the range `step` of the presentation (the last one if it is larger) and scrolls to it.
+    pub fn set_focus_step(&mut self, step: usize) {
+        let last = self.presentation.as_ref().map_or(0, |p| p.ranges.len().saturating_sub(1));
+        self.focus_step = step.min(last);
+        self.reveal_focus = true;
+    }
+
+    /// Focuses the next range of the presentation, `false` on the last one.
+    pub fn next_focus(&mut self) -> bool {
+        let step = self.focus_step;
+        self.set_focus_step(step + 1);
+        self.focus_step != step
+    }
+
+    /// Focuses the previous range of the presentation, `false` on the first one.
+    pub fn previous_focus(&mut self) -> bool {
+        let step = self.focus_step;
+        self.set_focus_step(step.saturating_sub(1));
+        self.focus_step != step
+    }
+
+    /// Called with the 0-based range of lines when a line number is clicked.\
+    /// Shift-click selects the range from the previously clicked line.
+    pub fn on_line_click(mut self, callback: impl FnMut(Range<usize>) + Send + 'static) -> Self {
+        self.on_line_click = Some(Box::new(callback));
+        self
+    }
+
+    /// Offers "Copy permalink" in the context menu of the line numbers, `format` is called with the 0-based range
+    /// of the right-clicked line (or of the clicked lines containing it) and returns the copied link.
+    ///
+    /// For a file on GitHub, use [`copy::github_permalink`](crate::copy::github_permalink)
+    /// with the URL of the file at a commit.
+    pub fn permalink(mut self, format: impl FnMut(Range<usize>) -> String + Send + 'static) -> Self {
+        self.permalink = Some(Box::new(format));
+        self
+    }
+
+    /// Cancels the full re-highlight, the code is shown without highlighting
+    /// until `rehighlight` is called.
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Enables the highlighting again after `cancel`.
+    pub fn rehighlight(&self) {
+        self.cancel.reset();
+    }
+
+    /// Highlights and lays out the whole code again on the next frame.
+    ///
+    /// The cached layout follows the changes of `code`, the wrap width, the font and the zoom,
+    /// use this when the highlighting depends on something else.
+    pub fn invalidate_highlight(&mut self) {
+        self.galley_cache.clear();
+        self.line_cache = LineCache::default();
+    }
+
+    /// Token to cancel the long operations from other places (for example from another thread).
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Highlighted spans of the code, with the syntaxes, the theme and the line limit of the viewer,
+    /// see [`spans::highlight`](crate::spans::highlight).
+    pub fn highlight_spans(&self) -> Vec<Span> {
+        let syntax = &self.syntax_set.syntaxes()[self.syntax_index];
+        highlight::spans(&self.code, syntax, &self.syntax_set, &self.theme, self.highlight_line_limit, &self.injections).0
+    }
+
+    /// Textual description of the highlighted spans of the code (position, color and styles),
+    /// for the snapshot tests of the highlighting and the theme of an application, see [`CodeEditor::debug_layout_dump`](crate::CodeEditor::debug_layout_dump).
+    pub fn debug_layout_dump(&self) -> String {
+        let syntax = &self.syntax_set.syntaxes()[self.syntax_index];
+        highlight::debug_dump(&self.code, syntax, &self.syntax_set, &self.theme, self.highlight_line_limit, &self.injections)
+    }
+
+    /// Shows the code for printing or exporting, in the light theme and the font size of `style`,
+    /// without the watermark and the collapsing, and without changing the viewer.
+    ///
+    /// The code is highlighted again on every call, call it for the frames of the export only.
+    pub fn print_ui(&self, ui: &mut Ui, style: &PrintStyle) -> egui::Response {
+        let syntax = &self.syntax_set.syntaxes()[self.syntax_index];
+        print::print_ui(ui, &self.code, syntax, &self.syntax_set, self.tab_size, self.control_chars, style)
     }
 
     pub fn ui(&mut self, ui: &mut Ui) -> egui::Response {
-        let font = FontId::monospace(14.0);
+        if self.presentation.as_ref().is_some_and(|p| p.arrow_keys) {
+            match presentation::arrow_step(ui) {
+                1.. => _ = self.next_focus(),
+                ..0 => _ = self.previous_focus(),
+                0 => {}
+            }
+        }
+        if self.virtualized {
+            return self.virtualized_ui(ui);
+        }
+        let zoom = self.presentation.as_ref().map_or(1.0, |p| p.zoom);
+        let font = fonts::code_font(ui, self.font_size * zoom);
         let syntax_set = self.syntax_set.clone();
         let theme = self.theme.clone();
-        let syntax = self.syntax;
+        let syntax_index = self.syntax_index;
+        let cancel = self.cancel.clone();
+        let wrap = self.wrap;
+        let tab_size = self.tab_size;
+        let control_chars = self.control_chars;
+        let line_limit = self.highlight_line_limit;
+        self.line_cache.set_line_limit(line_limit);
+        self.line_cache.set_injections(&self.injections);
+        let injections = self.injections.clone();
 
         let mut layouter = {
             let font = font.clone();
+            let cache = &mut self.galley_cache;
+            let line_cache = &mut self.line_cache;
             Box::new(move |ui: &Ui, text_buffer: &dyn egui::TextBuffer, wrap_width: f32| {
-                let mut job = LayoutJob::default();
-                let mut highlighter = HighlightLines::new(syntax, &theme);
                 let text = text_buffer.as_str();
-
-                for (i, line) in text.lines().enumerate() {
-                    if let Ok(ranges) = highlighter.highlight_line(line, &syntax_set) {
-                        for (style, text) in ranges {
-                            let color = Color32::from_rgb(
-                                style.foreground.r,
-                                style.foreground.g,
-                                style.foreground.b,
-                            );
-                            job.append(
-                                text,
-                                0.0,
-                                TextFormat {
-                                    font_id: font.clone(),
-                                    color,
-                                    ..Default::default()
-                                },
-                            );
+                let plain = ui.visuals().text_color();
+                let wrap_width = if wrap { wrap_width } else { f32::INFINITY };
+                let key = (
+                    text,
+                    wrap_width.to_bits(),
+                    &font,
+                    ui.ctx().pixels_per_point().to_bits(),
+                    plain,
+                    Arc::as_ptr(&theme),
+                    cancel.is_cancelled(),
+                    tab_size,
+                    control_chars,
+                    (line_limit, &injections),
+                );
+                cache
+                    .get_or_layout(key, || {
+                        let syntax = &syntax_set.syntaxes()[syntax_index];
+                        let (mut job, error) =
+                            line_cache.layout_job(text, syntax, &syntax_set, &theme, &font, plain, &cancel);
+                        if control_chars {
+                            control::conceal(&mut job);
                         }
-                    }
+                        ui.fonts(|f| layout::align_columns(&mut job, tab_size, control_chars, &[], f, &font));
+                        job.wrap.max_width = wrap_width;
+                        (ui.fonts(|f| f.layout_job(job)), error)
+                    })
+                    .0
+            }) as Box<dyn FnMut(&Ui, &dyn egui::TextBuffer, f32) -> Arc<Galley> + '_>
+        };
+
+        let line_count = self.code.lines().count();
+        let hidden_lines = match self.max_collapsed_lines {
+            Some(n) if !self.expanded && line_count > n => line_count - n,
+            _ => 0,
+        };
+
+        let mut shown = Cow::Borrowed(self.code.as_str());
+        if hidden_lines > 0 {
+            let first_lines: Vec<&str> = self.code.lines().take(line_count - hidden_lines).collect();
+            shown = Cow::Owned(first_lines.join("\n"));
+        }
+        let mut truncated = false;
+        if let Some(max_chars) = self.truncate_lines.filter(|_| !self.expanded)
+            && let Some(cut) = truncate_long_lines(&shown, max_chars)
+        {
+            shown = Cow::Owned(cut);
+            truncated = true;
+        }
+        // immutable buffer, so the code can be selected but not edited
+        let mut code = shown.as_ref();
+
+        let mut text_edit = TextEdit::multiline(&mut code)
+            .font(font.clone())
+            .desired_width(f32::INFINITY)
+            .interactive(self.selectable && self.presentation.is_none())
+            .code_editor()
+            .layouter(&mut layouter);
+        let background = self
+            .theme_background
+            .then(|| highlight::background(&self.theme, ui.visuals().text_edit_bg_color()));
+        if let Some(background) = background {
+            text_edit = text_edit.background_color(background);
+        }
+        // fills the height given by `ui.add_sized` and the justified layouts
+        if ui.layout().vertical_justify() && ui.available_height().is_finite() {
+            text_edit = text_edit.min_size(egui::vec2(0.0, ui.available_height()));
+        }
+
+        let gutter_width = if self.line_numbers {
+            gutter::width(ui, &font, line_count, &self.line_numbers_style)
+        } else {
+            0.0
+        };
+
+        // painted below the gutter and the code once their size is known
+        let background_shape = ui.painter().add(egui::Shape::Noop);
+        let (gutter_left, output) = ui
+            .horizontal_top(|ui| {
+                ui.spacing_mut().item_spacing.x = 0.0;
+                highlight::apply_theme_visuals(ui.visuals_mut(), &self.theme);
+                let gutter_left = ui.cursor().left();
+                ui.add_space(gutter_width);
 
-                    if i + 1 < text.lines().count() {
-                        job.append(
-                            "\n",
-                            0.0,
-                            TextFormat {
-                                font_id: font.clone(),
-                                color: Color32::WHITE,
-                                ..Default::default()
-                            },
-                        );
+                let output = if self.wrap {
+                    text_edit.show(ui)
+                } else {
+                    egui::ScrollArea::horizontal()
+                        .show(ui, |ui| text_edit.show(ui))
+                        .inner
+                };
+                (gutter_left, output)
+            })
+            .inner;
+        drop(layouter);
+        let response = output.response;
+        if let Some(background) = background {
+            let rect = egui::Rect::from_x_y_ranges(gutter_left..=response.rect.right(), response.rect.y_range());
+            ui.painter().set(background_shape, egui::Shape::rect_filled(rect, 0.0, background));
+        }
+
+        if self.control_chars {
+            let fill = highlight::foreground(&self.theme, ui.visuals().text_color());
+            let text = highlight::background(&self.theme, ui.visuals().extreme_bg_color);
+            control::paint(ui, &output.galley, output.galley_pos, &font, fill, text);
+        }
+
+        if let Some(text) = &self.watermark {
+            watermark::paint_watermark(ui, response.rect, text);
+        }
+
+        if self.line_numbers {
+            let gutter_rect = egui::Rect::from_x_y_ranges(
+                gutter_left..=gutter_left + gutter_width,
+                response.rect.y_range(),
+            );
+            let spans = gutter::line_spans(&output.galley);
+            gutter::paint_numbers(
+                ui,
+                0,
+                &spans,
+                output.galley_pos,
+                gutter_rect.x_range(),
+                &font,
+                &self.line_numbers_style,
+                None,
+            );
+            self.gutter_clicks(ui, gutter_rect, response.id, 0, &spans, output.galley_pos);
+        }
+
+        if let Some(presentation) = &self.presentation
+            && let Some(focus) = presentation.ranges.get(self.focus_step)
+        {
+            let rect = egui::Rect::from_x_y_ranges(gutter_left..=response.rect.right(), response.rect.y_range());
+            let background = highlight::background(&self.theme, ui.visuals().text_edit_bg_color());
+            let veil = background.gamma_multiply(presentation.dim.clamp(0.0, 1.0));
+            let spans = gutter::line_spans(&output.galley);
+            let focus_rect = presentation::paint_veil(ui, rect, &spans, output.galley_pos.y, focus, veil);
+            if std::mem::take(&mut self.reveal_focus) {
+                ui.scroll_to_rect_animation(focus_rect, Some(egui::Align::Center), settings::scroll_animation(ui));
+            }
+        }
+
+        if hidden_lines > 0 {
+            paint_fade(ui, response.rect);
+            if ui.small_button(format!("Show more ({hidden_lines} lines)")).clicked() {
+                self.expanded = true;
+            }
+        } else if truncated {
+            if ui.small_button("Expand").clicked() {
+                self.expanded = true;
+            }
+        } else if self.expanded && ui.small_button("Show less").clicked() {
+            self.expanded = false;
+        }
+
+        response
+    }
+
+    /// Shows the visible rows of the code in a scroll area, see [`virtualized`](Self::virtualized).
+    fn virtualized_ui(&mut self, ui: &mut Ui) -> egui::Response {
+        let zoom = self.presentation.as_ref().map_or(1.0, |p| p.zoom);
+        let font = fonts::code_font(ui, self.font_size * zoom);
+        let row_height = ui.fonts(|f| f.row_height(&font));
+        let plain = ui.visuals().text_color();
+        let mut background = ui.visuals().extreme_bg_color;
+        if self.theme_background {
+            background = highlight::background(&self.theme, background);
+        }
+        // the line breaks are kept for the parser, and removed from the rows
+        let mut lines: Vec<&str> = self.code.split_inclusive('\n').collect();
+        if lines.is_empty() {
+            lines.push("");
+        }
+        let gutter_width = if self.line_numbers {
+            gutter::width(ui, &font, lines.len(), &self.line_numbers_style)
+        } else {
+            0.0
+        };
+        self.line_cache.set_line_limit(self.highlight_line_limit);
+        self.line_cache.set_injections(&self.injections);
+        let syntax = &self.syntax_set.syntaxes()[self.syntax_index];
+        let focus = self.focused_lines();
+        let veil = self.presentation.as_ref().map(|p| background.gamma_multiply(p.dim.clamp(0.0, 1.0)));
+
+        let frame = egui::Frame::NONE.fill(background).show(ui, |ui| {
+            let mut scroll_area = egui::ScrollArea::both().id_salt("virtualized").auto_shrink([false, false]);
+            if let Some(focus) = focus.as_ref().filter(|_| std::mem::take(&mut self.reveal_focus)) {
+                // the focused lines centered, like `scroll_to_rect` does without the virtualization
+                let row = row_height + ui.spacing().item_spacing.y;
+                let margin = (ui.available_height() - focus.len() as f32 * row) / 2.0;
+                scroll_area = scroll_area.vertical_scroll_offset((focus.start as f32 * row - margin).max(0.0));
+            }
+            scroll_area.show_rows(ui, row_height, lines.len(), |ui, rows| {
+                let (jobs, complete) = self.line_cache.row_jobs(
+                    &lines,
+                    rows.clone(),
+                    syntax,
+                    &self.syntax_set,
+                    &self.theme,
+                    &font,
+                    plain,
+                    &self.cancel,
+                    HIGHLIGHT_BUDGET,
+                );
+                if !complete {
+                    ui.ctx().request_repaint();
+                }
+                ui.spacing_mut().item_spacing.x = 0.0;
+                let top = ui.cursor().top();
+                let mut spans = Vec::with_capacity(jobs.len());
+                for (line, mut job) in (rows.start..).zip(jobs) {
+                    if self.control_chars {
+                        control::conceal(&mut job);
                     }
+                    ui.fonts(|f| layout::align_columns(&mut job, self.tab_size, self.control_chars, &[], f, &font));
+                    let galley = ui.fonts(|f| f.layout_job(job));
+                    let size = egui::vec2(gutter_width + galley.size().x, row_height);
+                    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+                    let pos = rect.min + egui::vec2(gutter_width, 0.0);
+                    if self.control_chars {
+                        let text = highlight::background(&self.theme, background);
+                        control::paint(ui, &galley, pos, &font, ui.visuals().weak_text_color(), text);
+                    }
+                    ui.painter().galley(pos, galley, plain);
+                    if let (Some(focus), Some(veil)) = (&focus, veil)
+                        && !focus.contains(&line)
+                    {
+                        let row = egui::Rect::from_x_y_ranges(ui.clip_rect().x_range(), rect.y_range());
+                        ui.painter().rect_filled(row, 0.0, veil);
+                    }
+                    spans.push(egui::Rangef::new(rect.top() - top, rect.bottom() - top));
                 }
+                (rows.start, spans, top)
+            })
+        });
+        let scroll = frame.inner;
+        let response = ui.interact(scroll.inner_rect, scroll.id, egui::Sense::hover());
+        let (first_line, spans, top) = scroll.inner;
+
+        if self.line_numbers {
+            // the gutter stays on the left when the code is scrolled horizontally
+            let clip = scroll.inner_rect;
+            let gutter_rect = egui::Rect::from_x_y_ranges(clip.left()..=clip.left() + gutter_width, clip.y_range());
+            let painter = ui.painter().with_clip_rect(clip);
+            painter.rect_filled(gutter_rect, 0.0, background);
+            let mut numbers_ui = ui.new_child(egui::UiBuilder::new().max_rect(clip));
+            numbers_ui.set_clip_rect(clip);
+            let galley_pos = egui::pos2(clip.left(), top);
+            gutter::paint_numbers(
+                &numbers_ui,
+                first_line,
+                &spans,
+                galley_pos,
+                gutter_rect.x_range(),
+                &font,
+                &self.line_numbers_style,
+                None,
+            );
+            self.gutter_clicks(ui, gutter_rect, response.id, first_line, &spans, galley_pos);
+        }
+        if let Some(text) = &self.watermark {
+            watermark::paint_watermark(ui, response.rect, text);
+        }
+        response
+    }
+
+    fn gutter_clicks(
+        &mut self,
+        ui: &Ui,
+        gutter_rect: egui::Rect,
+        id: egui::Id,
+        first_line: usize,
+        spans: &[egui::Rangef],
+        galley_pos: egui::Pos2,
+    ) {
+        if self.on_line_click.is_none() && self.permalink.is_none() {
+            return;
+        }
 
-                job.wrap.max_width = wrap_width;
-                ui.fonts(|f| f.layout_job(job))
-            }) as Box<dyn FnMut(&Ui, &dyn egui::TextBuffer, f32) -> Arc<Galley>>
+        let response = ui
+            .interact(gutter_rect, id.with("line_numbers"), egui::Sense::click())
+            .on_hover_cursor(egui::CursorIcon::PointingHand);
+        let line_at_pointer = || {
+            response
+                .interact_pointer_pos()
+                .and_then(|pos| gutter::line_at(spans, galley_pos, pos.y))
+                .map(|line| first_line + line)
         };
 
-        ui.add(
-            TextEdit::multiline(&mut self.code)
-                .font(font)
-                .desired_width(f32::INFINITY)
-                .interactive(false)
-                .code_editor()
-                .layouter(&mut layouter),
-        )
+        if let Some(line) = response.clicked().then(line_at_pointer).flatten() {
+            let range = match self.anchor_line {
+                Some(anchor) if ui.input(|i| i.modifiers.shift) => anchor.min(line)..anchor.max(line) + 1,
+                _ => {
+                    self.anchor_line = Some(line);
+                    line..line + 1
+                }
+            };
+            self.clicked_lines = Some(range.clone());
+            if let Some(callback) = &mut self.on_line_click {
+                callback(range);
+            }
+        }
+
+        let Some(permalink) = &mut self.permalink else {
+            return;
+        };
+        if let Some(line) = response.secondary_clicked().then(line_at_pointer).flatten() {
+            self.menu_lines = match &self.clicked_lines {
+                Some(lines) if lines.contains(&line) => Some(lines.clone()),
+                _ => Some(line..line + 1),
+            };
+        }
+        let mut copy = false;
+        response.context_menu(|ui| {
+            if ui.button("Copy permalink").clicked() {
+                copy = true;
+                ui.close();
+            }
+        });
+        if copy && let Some(lines) = self.menu_lines.take() {
+            ui.ctx().copy_text(permalink(lines));
+        }
     }
+}
 
+/// Paints a gradient over the bottom of `rect`, fading the code into the background.
+fn paint_fade(ui: &Ui, rect: egui::Rect) {
+    let height = (rect.height() / 2.0).min(40.0);
+    let fade = egui::Rect::from_min_max(egui::pos2(rect.left(), rect.bottom() - height), rect.max);
+    let color = ui.visuals().extreme_bg_color;
+
+    let mut mesh = egui::Mesh::default();
+    mesh.colored_vertex(fade.left_top(), Color32::TRANSPARENT);
+    mesh.colored_vertex(fade.right_top(), Color32::TRANSPARENT);
+    mesh.colored_vertex(fade.left_bottom(), color);
+    mesh.colored_vertex(fade.right_bottom(), color);
+    mesh.add_triangle(0, 1, 2);
+    mesh.add_triangle(1, 2, 3);
+    ui.painter().add(mesh);
+}
+
+/// Returns `None` when no line is longer than `max_chars`.
+fn truncate_long_lines(code: &str, max_chars: usize) -> Option<String> {
+    if !code.lines().any(|line| line.chars().count() > max_chars) {
+        return None;
+    }
+
+    let lines: Vec<String> = code
+        .lines()
+        .map(|line| match line.char_indices().nth(max_chars) {
+            Some((end, _)) => format!("{}…", &line[..end]),
+            None => line.to_owned(),
+        })
+        .collect();
+    Some(lines.join("\n"))
 }
 
 impl Default for CodeViewer {
     fn default() -> Self {
-        let ps = SyntaxSet::load_defaults_newlines();
-        let ts = ThemeSet::load_defaults();
-        let theme = Arc::new(ts.themes["base16-ocean.dark"].clone());
-        let syntax = ps.find_syntax_by_extension("rs").unwrap();
+        Self::new("rs", "base16-ocean.dark")
+    }
+}
 
-        Self {
-            code: "".into(),
-            syntax_set: ps.clone(),
-            theme,
-            syntax: Box::leak(Box::new(syntax.clone())), // static lifetime workaround
-            highlighter: None,
-        }
+/// `ui.add(&mut viewer)`, like [`CodeViewer::ui`], so the viewer works with `add_sized`, `add_enabled` and the layouts.
+impl egui::Widget for &mut CodeViewer {
+    fn ui(self, ui: &mut Ui) -> egui::Response {
+        CodeViewer::ui(self, ui)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::tests::key_press;
+
+    #[test]
+    fn clamps_the_focus_step() {
+        let presentation = Presentation::new(vec![0..1, 2..4, 5..6]);
+        let mut viewer = CodeViewer::new("rs", "base16-ocean.dark").presentation(presentation);
+        assert_eq!(viewer.focused_lines(), Some(0..1));
+        viewer.set_focus_step(7);
+        assert_eq!(viewer.focus_step(), 2);
+        assert_eq!(viewer.focused_lines(), Some(5..6));
+
+        viewer.set_presentation(Some(Presentation::new(Vec::new())));
+        viewer.set_focus_step(3);
+        assert_eq!(viewer.focus_step(), 0);
+        assert_eq!(viewer.focused_lines(), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn steps_the_focus_up_to_the_ends() {
+        let mut viewer = CodeViewer::new("rs", "base16-ocean.dark").presentation(Presentation::new(vec![0..1, 2..4]));
+        assert!(!viewer.previous_focus());
+        assert!(viewer.next_focus());
+        assert_eq!(viewer.focused_lines(), Some(2..4));
+        assert!(!viewer.next_focus());
+        assert_eq!(viewer.focus_step(), 1);
+        assert!(viewer.previous_focus());
+        assert_eq!(viewer.focus_step(), 0);
+
+        let mut viewer = CodeViewer::new("rs", "base16-ocean.dark");
+        assert!(!viewer.next_focus());
+        assert_eq!(viewer.focused_lines(), None);
+    }
+
+    #[test]
+    fn presents_the_virtualized_code() {
+        let ctx = egui::Context::default();
+        let mut viewer = CodeViewer::new("rs", "base16-ocean.dark")
+            .virtualized(true)
+            .presentation(Presentation::new(vec![0..1, 1..2]));
+        viewer.code = "a\nb\nc".to_owned();
+        let right = key_press(egui::Key::ArrowRight);
+        let input = egui::RawInput { events: vec![right], ..Default::default() };
+        let _ = ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| viewer.ui(ui));
+        });
+        assert_eq!(viewer.focus_step(), 1);
+    }
+}
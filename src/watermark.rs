@@ -0,0 +1,29 @@
+use egui::{Align2, FontId, Rect, Ui, Vec2, epaint::TextShape};
+
+/// Largest font size of the watermark, short texts are not blown up over the whole widget.
+const MAX_FONT_SIZE: f32 = 48.0;
+
+/// Paints `text` dimmed along the rising diagonal of the visible part of `rect`.
+pub(crate) fn paint_watermark(ui: &Ui, rect: Rect, text: &str) {
+    let rect = rect.intersect(ui.clip_rect());
+    if !rect.is_positive() || text.is_empty() {
+        return;
+    }
+
+    let diagonal = rect.size().length();
+    let color = ui.visuals().text_color().gamma_multiply(0.12);
+    let measure = ui.fonts(|f| f.layout_no_wrap(text.to_owned(), FontId::proportional(MAX_FONT_SIZE), color));
+    // 80% of the diagonal at most
+    let size = (MAX_FONT_SIZE * 0.8 * diagonal / measure.size().x).min(MAX_FONT_SIZE);
+    let galley = ui.fonts(|f| f.layout_no_wrap(text.to_owned(), FontId::proportional(size), color));
+
+    let angle = -rect.height().atan2(rect.width());
+    // the text is rotated around its top left corner, shift it so its center is at the center of the rect
+    let half = galley.size() / 2.0;
+    let rotated_half = Vec2::new(
+        half.x * angle.cos() - half.y * angle.sin(),
+        half.x * angle.sin() + half.y * angle.cos(),
+    );
+    let pos = Align2::CENTER_CENTER.pos_in_rect(&rect) - rotated_half;
+    ui.painter_at(rect).add(TextShape::new(pos, galley, color).with_angle(angle));
+}
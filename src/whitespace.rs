@@ -0,0 +1,46 @@
+use egui::{Align2, Color32, FontId, Galley, Pos2, Ui};
+
+use crate::editor::Whitespace;
+use crate::geometry;
+
+/// Whether the whitespace char at the byte `offset` of `text` is shown with the `mode`.
+fn is_shown(text: &str, offset: usize, mode: Whitespace) -> bool {
+    match mode {
+        Whitespace::None => false,
+        Whitespace::All => true,
+        Whitespace::Trailing => {
+            let rest = &text[offset..];
+            let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+            line.trim_end_matches([' ', '\t', '\r']).is_empty()
+        }
+    }
+}
+
+/// Paints a `·` over the visible spaces and a `→` over the visible tabs of the galley of `text`.
+pub(crate) fn paint(ui: &Ui, galley: &Galley, galley_pos: Pos2, font: &FontId, mode: Whitespace, color: Color32) {
+    if mode == Whitespace::None {
+        return;
+    }
+    let text = galley.text();
+    let clip = ui.clip_rect();
+    let first = galley.cursor_from_pos(clip.min - galley_pos).index;
+    let last = galley.cursor_from_pos(clip.max - galley_pos).index;
+    let painter = ui.painter();
+    for (index, (offset, c)) in text.char_indices().enumerate() {
+        if index > last {
+            break;
+        }
+        let (marker, align) = match c {
+            ' ' => ("·", Align2::CENTER_CENTER),
+            '\t' => ("→", Align2::LEFT_CENTER),
+            _ => continue,
+        };
+        if index < first || !is_shown(text, offset, mode) {
+            continue;
+        }
+        for rect in geometry::char_range_rects(galley, galley_pos, index..index + 1) {
+            let pos = if align == Align2::LEFT_CENTER { rect.left_center() } else { rect.center() };
+            painter.text(pos, align, marker, font.clone(), color);
+        }
+    }
+}